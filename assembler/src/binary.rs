@@ -0,0 +1,115 @@
+use core::program::binary::{BinaryProgram, BINARY_PROGRAM_MAGIC, BINARY_PROGRAM_VERSION};
+use std::io::{Result, Write};
+
+// Serialize a program in the layout documented in `core::program::binary`.
+pub fn write_binary_program<W: Write>(program: &BinaryProgram, out: &mut W) -> Result<()> {
+    out.write_all(&BINARY_PROGRAM_MAGIC)?;
+    out.write_all(&BINARY_PROGRAM_VERSION.to_le_bytes())?;
+    out.write_all(&0u16.to_le_bytes())?;
+
+    for len in [
+        program.code.len() as u64,
+        program.data_addr,
+        program.data.len() as u64,
+        program.symbols.len() as u64,
+        program.line_info.len() as u64,
+    ] {
+        out.write_all(&len.to_le_bytes())?;
+    }
+
+    for word in program.code.iter().chain(program.data.iter()) {
+        out.write_all(&word.to_le_bytes())?;
+    }
+
+    for (name, addr) in &program.symbols {
+        out.write_all(&(name.len() as u32).to_le_bytes())?;
+        out.write_all(name.as_bytes())?;
+        out.write_all(&addr.to_le_bytes())?;
+    }
+
+    for (pc, line) in &program.line_info {
+        out.write_all(&pc.to_le_bytes())?;
+        out.write_all(&line.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use crate::binary::write_binary_program;
+    use crate::encode::Encoder;
+    use core::program::binary::{BinaryProgram, BinaryProgramError};
+    use core::program::PROPHET_REGION_START;
+
+    #[test]
+    fn binary_program_round_trip() {
+        let asm_codes = "mov r0 8
+         mov r3 0
+         .LBL_0_0:
+         EQ r0 r3
+         cjmp .LBL_0_1
+         // count down
+         not r4 0
+         add r0 r0 r4
+         jmp .LBL_0_0
+         .LBL_0_1:
+         end";
+
+        let mut encoder: Encoder = Default::default();
+        let asm_codes: Vec<String> = asm_codes.split('\n').map(|e| e.to_string()).collect();
+        let program = encoder.assemble_binary(asm_codes);
+
+        assert_eq!(
+            program.symbols,
+            vec![(".LBL_0_0".to_string(), 4), (".LBL_0_1".to_string(), 12)]
+        );
+        // pc of `EQ r0 r3` and `not r4 0` with their source lines
+        assert!(program.line_info.contains(&(4, 4)));
+        assert!(program.line_info.contains(&(7, 7)));
+
+        let mut bytes = Vec::new();
+        write_binary_program(&program, &mut bytes).unwrap();
+        let read_program = BinaryProgram::from_bytes(&bytes).unwrap();
+        assert_eq!(read_program, program);
+        assert_eq!(
+            read_program.instructions()[0],
+            "0x4000000840000000".to_string()
+        );
+
+        assert_eq!(
+            BinaryProgram::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(BinaryProgramError::Truncated)
+        );
+        assert_eq!(
+            BinaryProgram::from_bytes(b"0x4000000840000000"),
+            Err(BinaryProgramError::BadMagic)
+        );
+
+        let program = BinaryProgram {
+            data_addr: u64::MAX,
+            data: vec![1],
+            ..Default::default()
+        };
+        assert_eq!(
+            program.static_memory(),
+            Err(BinaryProgramError::DataAddressOverflow)
+        );
+        let mut bytes = Vec::new();
+        write_binary_program(&program, &mut bytes).unwrap();
+        assert_eq!(
+            BinaryProgram::from_bytes(&bytes),
+            Err(BinaryProgramError::DataAddressOverflow)
+        );
+        // The static data ends below the prophet region.
+        let program = BinaryProgram {
+            data_addr: PROPHET_REGION_START - 1,
+            data: vec![1, 2],
+            ..Default::default()
+        };
+        assert_eq!(
+            program.static_memory(),
+            Err(BinaryProgramError::DataAddressOverflow)
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use core::program::binary::BinaryProgram;
+use core::program::decoder::{decode_instruction_word, decode_opcode, DecodeError};
+use core::program::instruction::{Opcode, IMM_INSTRUCTION_LEN};
+
+// Readable listing of a program: one line per instruction with its address,
+// raw words and assembly text, preceded by the labels of the symbol table.
+// Immediates which are a jump target or a static data address are followed
+// by the matching label.
+pub fn disassemble(program: &BinaryProgram) -> Result<Vec<String>, DecodeError> {
+    let labels_at = |addr: u64| {
+        program
+            .symbols
+            .iter()
+            .filter(move |(_, symbol_addr)| *symbol_addr == addr)
+            .map(|(label, _)| label.as_str())
+    };
+    let data_end = program
+        .data_end()
+        .map_err(|_| DecodeError::DataAddressOverflow(program.data_addr))?;
+    let is_data_addr = |addr: u64| addr >= program.data_addr && addr < data_end;
+
+    let mut lines = vec![".text".to_string()];
+    let mut pc = 0;
+    while pc < program.code.len() {
+        let word = program.code[pc];
+        let imm = program.code.get(pc + 1).copied();
+        let (text, step) = decode_instruction_word(word, imm)?;
+        for label in labels_at(pc as u64).filter(|_| !is_data_addr(pc as u64)) {
+            lines.push(format!("{label}:"));
+        }
+
+        let mut line = if step == IMM_INSTRUCTION_LEN {
+            let imm = imm.unwrap_or_default();
+            format!("0x{pc:04x}  {word:016x} {imm:016x}  {text}")
+        } else {
+            format!("0x{pc:04x}  {word:016x} {:16}  {text}", "")
+        };
+        if step == IMM_INSTRUCTION_LEN {
+            let imm = imm.unwrap_or_default();
+            let is_target = matches!(
+                decode_opcode(word)?,
+                Opcode::JMP | Opcode::CJMP | Opcode::CALL
+            );
+            if is_target || is_data_addr(imm) {
+                if let Some(label) = labels_at(imm).next() {
+                    line += &format!("  // {label}");
+                }
+            }
+        }
+        lines.push(line);
+        pc += step as usize;
+    }
+
+    if !program.data.is_empty() {
+        lines.push(format!(".data 0x{:x}", program.data_addr));
+        for (offset, value) in program.data.iter().enumerate() {
+            let addr = program.data_addr + offset as u64;
+            for label in labels_at(addr) {
+                lines.push(format!("{label}:"));
+            }
+            lines.push(format!("0x{addr:04x}  {value:016x}  {value}"));
+        }
+    }
+    Ok(lines)
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use crate::disasm::disassemble;
+    use crate::encode::Encoder;
+    use core::program::binary::BinaryProgram;
+    use core::program::decoder::DecodeError;
+
+    #[test]
+    fn disassemble_test() {
+        let asm_codes = ".data 0x100
+                          values:
+                            7 9
+                        .text
+                        main:
+                          mov r1 values
+                          mload r0 r1
+                          call square
+                          end
+                        square:
+                          mul r0 r0 r0
+                          ret";
+
+        let mut encoder: Encoder = Default::default();
+        let asm_codes: Vec<String> = asm_codes.split('\n').map(|e| e.to_string()).collect();
+        let program = encoder.assemble_binary(asm_codes);
+        let lines = disassemble(&program).unwrap();
+
+        assert_eq!(
+            lines,
+            vec![
+                ".text",
+                "main:",
+                "0x0000  4000001040000000 0000000000000100  mov r1 256  // values",
+                "0x0002  0000200802000000                   mload r0 r1",
+                "0x0003  4000000008000000 0000000000000006  call 6  // square",
+                "0x0005  0000000000800000                   end",
+                "square:",
+                "0x0006  0020100a00000000                   mul r0 r0 r0",
+                "0x0007  0000000004000000                   ret",
+                ".data 0x100",
+                "values:",
+                "0x0100  0000000000000007  7",
+                "0x0101  0000000000000009  9",
+            ]
+        );
+
+        // imm flag set on the last word
+        let program = BinaryProgram {
+            code: vec![0x4000000840000000],
+            ..Default::default()
+        };
+        assert_eq!(
+            disassemble(&program),
+            Err(DecodeError::MissingImmediate(0x4000000840000000))
+        );
+
+        let program = BinaryProgram {
+            data_addr: u64::MAX,
+            data: vec![1],
+            ..Default::default()
+        };
+        assert_eq!(
+            disassemble(&program),
+            Err(DecodeError::DataAddressOverflow(u64::MAX))
+        );
+    }
+}
@@ -1,9 +1,14 @@
 use crate::error::AssemblerError;
+use core::program::binary::BinaryProgram;
 use core::program::instruction::Opcode;
+use core::program::instruction::{
+    PROPHET_ADD_U32, PROPHET_DIVMOD, PROPHET_INV, PROPHET_MUL_U32, PROPHET_SQRT, PROPHET_SUB_U32,
+};
 use core::program::instruction::{
     IMM_FLAG_FIELD_BIT_POSITION, IMM_INSTRUCTION_LEN, NO_IMM_INSTRUCTION_LEN,
     REG0_FIELD_BIT_POSITION, REG1_FIELD_BIT_POSITION, REG2_FIELD_BIT_POSITION,
 };
+use core::program::static_data_end;
 use log::debug;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
@@ -16,11 +21,19 @@ pub enum ImmediateFlag {
     Used,
 }
 
+// memory address of the `.data` section when the directive has no address
+pub const DEFAULT_DATA_ADDR: u64 = 0x10000000;
+
 #[derive(Debug, Default)]
 pub struct Encoder {
     pub labels: HashMap<String, u64>,
     pub asm_code: Vec<String>,
+    // source line of each `asm_code` item, kept in step by `relocate`
+    pub asm_lines: Vec<u64>,
     pub pc: u64,
+    // static data words loaded from memory address `data_addr`
+    pub data_addr: u64,
+    pub data: Vec<u64>,
 }
 
 impl Encoder {
@@ -68,7 +81,7 @@ impl Encoder {
         let mut instuction = Vec::new();
 
         match opcode.as_str() {
-            "mov" | "assert" | "eq" | "neq" | "not" | "gte" | "mload" => {
+            "mov" | "assert" | "eq" | "neq" | "not" | "gte" | "mload" | "prophet" => {
                 debug!("opcode: mov");
                 assert!(
                     ops.len() == 3,
@@ -113,10 +126,14 @@ impl Encoder {
                         raw_instruction |=
                             1 << Opcode::MLOAD as u8 | 1 << (dst_index + REG0_FIELD_BIT_POSITION)
                     }
+                    "prophet" => {
+                        raw_instruction |=
+                            1 << Opcode::PROPHET as u8 | 1 << (dst_index + REG0_FIELD_BIT_POSITION)
+                    }
                     _ => panic!("not match opcode:{}", opcode),
                 }
             }
-            "jmp" | "cjmp" | "call" | "range" => {
+            "jmp" | "cjmp" | "call" | "range" | "ecdsa" => {
                 debug!("opcode: cjmp");
                 assert!(
                     ops.len() == 2,
@@ -142,6 +159,9 @@ impl Encoder {
                     "range" => {
                         raw_instruction |= 1 << Opcode::RC as u8;
                     }
+                    "ecdsa" => {
+                        raw_instruction |= 1 << Opcode::ECDSA as u8;
+                    }
                     _ => panic!("not match opcode:{}", opcode),
                 }
             }
@@ -197,8 +217,8 @@ impl Encoder {
                 assert!(ops.len() == 1, "ret params len is 0");
                 raw_instruction |= 1 << Opcode::RET as u8;
             }
-            "mstore" => {
-                debug!("opcode: mstore");
+            "mstore" | "poseidon" | "keccak" => {
+                debug!("opcode: {}", opcode.as_str());
                 assert!(
                     ops.len() == 3,
                     "{}",
@@ -213,8 +233,13 @@ impl Encoder {
                 } else {
                     raw_instruction |= 1 << (op1_value.1 + REG1_FIELD_BIT_POSITION);
                 }
+                let opcode = match opcode.as_str() {
+                    "mstore" => Opcode::MSTORE,
+                    "keccak" => Opcode::KECCAK,
+                    _ => Opcode::PSDN,
+                };
                 raw_instruction |=
-                    1 << Opcode::MSTORE as u8 | 1 << (op2_index + REG2_FIELD_BIT_POSITION);
+                    1 << opcode as u8 | 1 << (op2_index + REG2_FIELD_BIT_POSITION);
             }
             "end" => {
                 debug!("opcode: end");
@@ -232,7 +257,7 @@ impl Encoder {
         let opcode = ops.first().unwrap().to_lowercase();
 
         match opcode.as_str() {
-            "mov" | "assert" | "eq" | "neq" | "not" | "gte" | "mload" => {
+            "mov" | "assert" | "eq" | "neq" | "not" | "gte" | "mload" | "prophet" => {
                 debug!("opcode: mov");
                 assert!(
                     ops.len() == 3,
@@ -244,7 +269,7 @@ impl Encoder {
                     return Ok(IMM_INSTRUCTION_LEN);
                 }
             }
-            "jmp" | "cjmp" | "call" | "range" => {
+            "jmp" | "cjmp" | "call" | "range" | "ecdsa" => {
                 debug!("opcode: cjmp");
                 assert!(
                     ops.len() == 2,
@@ -270,8 +295,8 @@ impl Encoder {
                     return Ok(IMM_INSTRUCTION_LEN);
                 }
             }
-            "mstore" => {
-                debug!("opcode: mstore");
+            "mstore" | "poseidon" | "keccak" => {
+                debug!("opcode: {}", opcode.as_str());
                 assert!(
                     ops.len() == 3,
                     "{}",
@@ -288,6 +313,203 @@ impl Encoder {
         Ok(NO_IMM_INSTRUCTION_LEN)
     }
 
+    pub fn parse_data_word(&self, word: &str) -> Result<u64, AssemblerError> {
+        let res = match word.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None => word.parse(),
+        };
+        res.map_err(|_| AssemblerError::ParseIntError)
+    }
+
+    // Address following the static data words.
+    fn data_end(&self) -> Result<u64, AssemblerError> {
+        static_data_end(self.data_addr, self.data.len() as u64)
+            .ok_or(AssemblerError::DataAddressOverflow)
+    }
+
+    // Move the `.data [addr]` ... `.text` sections out of `asm_code`, their
+    // labels are data addresses usable as immediates. The data words are
+    // loaded at one address, so a later `.data` section continues the
+    // previous one and its address, if any, must be where that one ends.
+    pub fn extract_data(&mut self) -> Result<(), AssemblerError> {
+        let mut seen_data = false;
+        let mut in_data = false;
+        let mut index = 0;
+        while index < self.asm_code.len() {
+            let item = self.asm_code[index].trim().to_string();
+            if let Some(addr) = item.strip_prefix(".data") {
+                let addr = addr.trim();
+                if !seen_data {
+                    self.data_addr = if addr.is_empty() {
+                        DEFAULT_DATA_ADDR
+                    } else {
+                        self.parse_data_word(addr)?
+                    };
+                } else if !addr.is_empty() {
+                    let addr = self.parse_data_word(addr)?;
+                    if addr != self.data_end()? {
+                        return Err(AssemblerError::DataSectionNotContiguous(addr));
+                    }
+                }
+                seen_data = true;
+                in_data = true;
+            } else if item == ".text" {
+                in_data = false;
+            } else if in_data && !item.is_empty() && !item.starts_with("//") {
+                if let Some(label) = item.strip_suffix(':') {
+                    self.labels.insert(label.to_string(), self.data_end()?);
+                } else {
+                    for word in item.split(|c: char| c == ',' || c.is_whitespace()) {
+                        if !word.is_empty() {
+                            let value = self.parse_data_word(word)?;
+                            self.data.push(value);
+                            self.data_end()?;
+                        }
+                    }
+                }
+            } else {
+                index += 1;
+                continue;
+            }
+            self.asm_code.remove(index);
+            self.asm_lines.remove(index);
+        }
+        Ok(())
+    }
+
+    // Expand the prophet macros. Their inputs are r1 and r2 as for a call, r6
+    // and r7 are clobbered. The outputs of the prophet are checked by the
+    // expanded instructions, a wrong answer makes the execution fail.
+    pub fn expand_prophet_macro(&self, raw_inst: &str) -> Option<Vec<String>> {
+        let ops: Vec<_> = raw_inst.trim().split(' ').collect();
+        let expansion = match ops[0] {
+            // rd = 1 / r1, checked as r1 * rd = 1
+            "inv" => {
+                assert!(ops.len() == 2, "inv params len is 1");
+                vec![
+                    format!("prophet r6 {PROPHET_INV}"),
+                    "mload r6 r6".to_string(),
+                    "mul r7 r1 r6".to_string(),
+                    "assert r7 1".to_string(),
+                    format!("mov {} r6", ops[1]),
+                ]
+            }
+            // rd = sqrt(r1), checked as rd * rd = r1
+            "sqrt" => {
+                assert!(ops.len() == 2, "sqrt params len is 1");
+                vec![
+                    format!("prophet r6 {PROPHET_SQRT}"),
+                    "mload r6 r6".to_string(),
+                    "mul r7 r6 r6".to_string(),
+                    "assert r7 r1".to_string(),
+                    format!("mov {} r6", ops[1]),
+                ]
+            }
+            // rq, rr = r1 / r2, r1 % r2 for u32 r1 and r2. rq and rr are range
+            // checked, rr < r2 and r1 = rq * r2 + rr, which is below p so it
+            // does not wrap.
+            "divmod" => {
+                assert!(ops.len() == 3, "divmod params len is 2");
+                let (rq, rr) = (ops[1], ops[2]);
+                check_outputs("divmod", &[rq, rr]);
+                let mut expansion = divmod_checked(rq);
+                expansion.extend([format!("mov {rr} r7"), format!("mload {rq} r6")]);
+                expansion
+            }
+            // rd = r1 / r2 for u32 r1 and r2, checked as divmod
+            "div_u32" => {
+                assert!(ops.len() == 2, "div_u32 params len is 1");
+                check_outputs("div_u32", &[ops[1]]);
+                let mut expansion = divmod_checked(ops[1]);
+                expansion.push(format!("mload {} r6", ops[1]));
+                expansion
+            }
+            // rd = r1 % r2 for u32 r1 and r2, checked as divmod
+            "mod_u32" => {
+                assert!(ops.len() == 2, "mod_u32 params len is 1");
+                check_outputs("mod_u32", &[ops[1]]);
+                let mut expansion = divmod_checked(ops[1]);
+                expansion.push(format!("mov {} r7", ops[1]));
+                expansion
+            }
+            // rd, rc = (r1 + r2) % 2^32, (r1 + r2) / 2^32 for u32 r1 and r2.
+            // rd is range checked, rc is 0 or 1 and r1 + r2 = rd + rc * 2^32.
+            "add_u32" => {
+                assert!(ops.len() == 3, "add_u32 params len is 2");
+                let (rd, rc) = (ops[1], ops[2]);
+                check_outputs("add_u32", &[rd, rc]);
+                let mut expansion = vec![
+                    format!("prophet r6 {PROPHET_ADD_U32}"),
+                    "range r1".to_string(),
+                    "range r2".to_string(),
+                    // rc holds r1 + r2 - rd until the end
+                    format!("add {rc} r1 r2"),
+                    format!("mload {rd} r6"),
+                    format!("range {rd}"),
+                    format!("not r7 {rd}"),
+                    format!("add {rc} {rc} r7"),
+                    format!("add {rc} {rc} 1"),
+                ];
+                expansion.extend(carry_checked(rc));
+                expansion
+            }
+            // rd, rb = (r1 - r2) % 2^32, r1 < r2 for u32 r1 and r2. rd is range
+            // checked, rb is 0 or 1 and r1 - r2 = rd - rb * 2^32.
+            "sub_u32" => {
+                assert!(ops.len() == 3, "sub_u32 params len is 2");
+                let (rd, rb) = (ops[1], ops[2]);
+                check_outputs("sub_u32", &[rd, rb]);
+                let mut expansion = vec![
+                    format!("prophet r6 {PROPHET_SUB_U32}"),
+                    "range r1".to_string(),
+                    "range r2".to_string(),
+                    // rb holds rd + r2 - r1 until the end
+                    "not r7 r1".to_string(),
+                    format!("add {rb} r7 r2"),
+                    format!("mload {rd} r6"),
+                    format!("range {rd}"),
+                    format!("add {rb} {rb} {rd}"),
+                    format!("add {rb} {rb} 1"),
+                ];
+                expansion.extend(carry_checked(rb));
+                expansion
+            }
+            // rd, rh = low and high u32 words of r1 * r2 for u32 r1 and r2. Both
+            // are range checked, rh < 2^32 - 1 and r1 * r2 = rd + rh * 2^32,
+            // which is below p so it does not wrap.
+            "mul_u32" => {
+                assert!(ops.len() == 3, "mul_u32 params len is 2");
+                let (rd, rh) = (ops[1], ops[2]);
+                check_outputs("mul_u32", &[rd, rh]);
+                vec![
+                    format!("prophet r6 {PROPHET_MUL_U32}"),
+                    "range r1".to_string(),
+                    "range r2".to_string(),
+                    // rh holds r1 * r2 until the end
+                    format!("mul {rh} r1 r2"),
+                    format!("mload {rd} r6"),
+                    format!("range {rd}"),
+                    // the high word is at r6 + 1
+                    "add r7 r6 1".to_string(),
+                    "mload r7 r7".to_string(),
+                    "range r7".to_string(),
+                    "not r7 r7".to_string(),
+                    "add r7 r7 0xffffffff".to_string(),
+                    "range r7".to_string(),
+                    "add r7 r6 1".to_string(),
+                    "mload r7 r7".to_string(),
+                    "mul r7 r7 0x100000000".to_string(),
+                    format!("add r7 r7 {rd}"),
+                    format!("assert r7 {rh}"),
+                    "add r7 r6 1".to_string(),
+                    format!("mload {rh} r7"),
+                ]
+            }
+            _ => return None,
+        };
+        Some(expansion)
+    }
+
     pub fn relocate(&mut self) {
         let init_asm_len = self.asm_code.len();
         let mut cur_asm_len = init_asm_len;
@@ -303,12 +525,21 @@ impl Encoder {
                 label.remove(label.len() - 1);
                 self.labels.insert(label, self.pc);
                 self.asm_code.remove(index);
+                self.asm_lines.remove(index);
                 cur_asm_len -= 1;
                 continue;
             } else if  item.contains("//"){
                 self.asm_code.remove(index);
+                self.asm_lines.remove(index);
                 cur_asm_len -= 1;
                 continue;
+            } else if let Some(expansion) = self.expand_prophet_macro(item) {
+                let line = self.asm_lines[index];
+                let len = expansion.len();
+                self.asm_code.splice(index..index + 1, expansion);
+                self.asm_lines.splice(index..index + 1, vec![line; len]);
+                cur_asm_len += len - 1;
+                continue;
             } else if item.contains("[") {
                 // not r5 3
                 // add r5 r5 1
@@ -353,6 +584,8 @@ impl Encoder {
                 cur_asm_len += 1;
                 self.asm_code.remove(index);
                 cur_asm_len -= 1;
+                let line = self.asm_lines[index];
+                self.asm_lines.splice(index..index + 1, [line; 4]);
                 continue;
             }
             let len = self.get_inst_len(&item).unwrap();
@@ -364,7 +597,9 @@ impl Encoder {
     pub fn assemble_link(&mut self, asm_codes: Vec<String>) -> Vec<String> {
         let mut raw_insts = Vec::new();
 
+        self.asm_lines = (1..=asm_codes.len() as u64).collect();
         self.asm_code = asm_codes;
+        self.extract_data().unwrap();
         self.relocate();
         for item in &self.asm_code {
             println!("{}", item);
@@ -375,11 +610,95 @@ impl Encoder {
         }
         raw_insts
     }
+
+    pub fn assemble_binary(&mut self, asm_codes: Vec<String>) -> BinaryProgram {
+        let raw_insts = self.assemble_link(asm_codes);
+
+        let mut line_info = Vec::new();
+        let mut pc = 0;
+        for (raw_code, line) in self.asm_code.iter().zip(self.asm_lines.iter()) {
+            line_info.push((pc, *line));
+            pc += self.get_inst_len(raw_code).unwrap();
+        }
+
+        let mut symbols: Vec<(String, u64)> = self
+            .labels
+            .iter()
+            .map(|(label, addr)| (label.clone(), *addr))
+            .collect();
+        symbols.sort_by(|a, b| (a.1, &a.0).cmp(&(b.1, &b.0)));
+
+        BinaryProgram {
+            code: raw_insts
+                .iter()
+                .map(|inst| u64::from_str_radix(inst.trim_start_matches("0x"), 16).unwrap())
+                .collect(),
+            data_addr: self.data_addr,
+            data: self.data.clone(),
+            symbols,
+            line_info,
+        }
+    }
+}
+
+// The outputs of a macro are written after its checks, they must not be the
+// scratch registers or each other.
+fn check_outputs(name: &str, outputs: &[&str]) {
+    assert!(
+        outputs.iter().all(|r| *r != "r6" && *r != "r7")
+            && (outputs.len() < 2 || outputs[0] != outputs[1]),
+        "{name} outputs must be different and not r6 or r7"
+    );
+}
+
+// Checks the quotient and remainder of PROPHET_DIVMOD at r6, leaves the
+// remainder in r7 and clobbers rq.
+fn divmod_checked(rq: &str) -> Vec<String> {
+    vec![
+        format!("prophet r6 {PROPHET_DIVMOD}"),
+        "range r1".to_string(),
+        "range r2".to_string(),
+        // the remainder is at r6 + 1
+        "add r7 r6 1".to_string(),
+        "mload r7 r7".to_string(),
+        "range r7".to_string(),
+        "not r7 r7".to_string(),
+        "add r7 r7 r2".to_string(),
+        "range r7".to_string(),
+        // rq holds r1 - quotient * r2 until the end
+        "mload r7 r6".to_string(),
+        "range r7".to_string(),
+        "mul r7 r7 r2".to_string(),
+        "not r7 r7".to_string(),
+        format!("add {rq} r7 r1"),
+        format!("add {rq} {rq} 1"),
+        "add r7 r6 1".to_string(),
+        "mload r7 r7".to_string(),
+        format!("assert r7 {rq}"),
+    ]
+}
+
+// Checks that the word at r6 + 1 is 0 or 1 and its multiple of 2^32 equals
+// rc, then sets rc to it.
+fn carry_checked(rc: &str) -> Vec<String> {
+    vec![
+        "add r7 r6 1".to_string(),
+        "mload r7 r7".to_string(),
+        "mul r7 r7 0x100000000".to_string(),
+        format!("assert r7 {rc}"),
+        "add r7 r6 1".to_string(),
+        "mload r7 r7".to_string(),
+        format!("mul {rc} r7 r7"),
+        format!("assert {rc} r7"),
+        format!("mov {rc} r7"),
+    ]
 }
 
 #[allow(unused_imports)]
 mod tests {
-    use crate::encode::Encoder;
+    use crate::encode::{Encoder, DEFAULT_DATA_ADDR};
+    use crate::error::AssemblerError;
+    use core::program::PROPHET_REGION_START;
     use log::{debug, error, LevelFilter};
     #[test]
     fn encode_test() {
@@ -797,4 +1116,164 @@ mod tests {
             println!("{}", item);
         }
     }
+
+    #[test]
+    fn data_section() {
+        let asm_codes = ".data
+                          values:
+                            7 0x9
+                          // ignored
+                          last:
+                            11
+                        .text
+                        main:
+                          mov r1 values
+                          mload r0 r1
+                          add r1 r1 1
+                          mload r2 r1
+                          mload r4 last
+                          add r3 r0 r2
+                          end";
+
+        let mut encoder: Encoder = Default::default();
+        let asm_codes: Vec<String> = asm_codes.split('\n').map(|e| e.to_string()).collect();
+        let program = encoder.assemble_binary(asm_codes);
+        for item in program.instructions() {
+            println!("{}", item);
+        }
+
+        assert_eq!(program.data_addr, DEFAULT_DATA_ADDR);
+        assert_eq!(program.data, vec![7, 9, 11]);
+        assert_eq!(encoder.labels["values"], DEFAULT_DATA_ADDR);
+        assert_eq!(encoder.labels["last"], DEFAULT_DATA_ADDR + 2);
+        assert_eq!(encoder.labels["main"], 0);
+        // data labels are immediates
+        assert_eq!(program.code[1], DEFAULT_DATA_ADDR);
+        assert_eq!(program.code[7], DEFAULT_DATA_ADDR + 2);
+        // source lines of the text section are kept
+        assert_eq!(program.line_info[0], (0, 9));
+
+        let mut encoder: Encoder = Default::default();
+        let asm_codes = vec![
+            ".data 0x20".to_string(),
+            "1".to_string(),
+            ".text".to_string(),
+        ];
+        encoder.assemble_link(asm_codes);
+        assert_eq!(encoder.data_addr, 0x20);
+        assert_eq!(encoder.data, vec![1]);
+
+        // a later section continues the data words
+        let mut encoder: Encoder = Default::default();
+        let asm_codes = vec![
+            ".data 0x20",
+            "1 2",
+            ".text",
+            "end",
+            ".data",
+            "a:",
+            "3",
+            ".data 0x23",
+            "b:",
+            "4",
+        ];
+        encoder.assemble_link(asm_codes.into_iter().map(String::from).collect());
+        assert_eq!(encoder.data_addr, 0x20);
+        assert_eq!(encoder.data, vec![1, 2, 3, 4]);
+        assert_eq!(encoder.labels["a"], 0x22);
+        assert_eq!(encoder.labels["b"], 0x23);
+
+        let mut encoder: Encoder = Default::default();
+        encoder.asm_code = [".data 0x20", "1", ".data 0x30", "2"]
+            .map(String::from)
+            .to_vec();
+        encoder.asm_lines = vec![1, 2, 3, 4];
+        assert!(matches!(
+            encoder.extract_data(),
+            Err(AssemblerError::DataSectionNotContiguous(0x30))
+        ));
+
+        let mut encoder: Encoder = Default::default();
+        encoder.asm_code = [".data 0xffffffffffffffff", "1", "end:"]
+            .map(String::from)
+            .to_vec();
+        encoder.asm_lines = vec![1, 2, 3];
+        assert!(matches!(
+            encoder.extract_data(),
+            Err(AssemblerError::DataAddressOverflow)
+        ));
+
+        // The data words end below the prophet region.
+        let mut encoder: Encoder = Default::default();
+        encoder.asm_code = vec![
+            format!(".data {:#x}", PROPHET_REGION_START),
+            "1".to_string(),
+        ];
+        encoder.asm_lines = vec![1, 2];
+        assert!(matches!(
+            encoder.extract_data(),
+            Err(AssemblerError::DataAddressOverflow)
+        ));
+    }
+
+    #[test]
+    fn prophet_macros() {
+        let asm_codes = "main:
+                           mov r1 3
+                           inv r0
+                           mov r1 9
+                           sqrt r3
+                           mov r1 100
+                           mov r2 7
+                           divmod r4 r5
+                           end";
+
+        let mut encoder: Encoder = Default::default();
+        let asm_codes: Vec<String> = asm_codes.split('\n').map(|e| e.to_string()).collect();
+        let program = encoder.assemble_binary(asm_codes);
+
+        // 5 + 5 + 20 instructions, the source line is kept for each
+        assert_eq!(encoder.asm_code.len(), 35);
+        assert_eq!(encoder.asm_code[1].trim(), "prophet r6 1");
+        assert_eq!(encoder.asm_code[5].trim(), "mov r0 r6");
+        assert_eq!(encoder.asm_code[34].trim(), "end");
+        assert_eq!(program.line_info[1].1, 3);
+        assert_eq!(program.line_info[33].1, 8);
+        // prophet r6 1
+        assert_eq!(program.code[2..4], [0x4000020000001000, 1]);
+    }
+
+    #[test]
+    fn u32_macros() {
+        let asm_codes = "main:
+                           mov r1 0xffffffff
+                           mov r2 7
+                           add_u32 r3 r4
+                           sub_u32 r5 r0
+                           mstore 0x100 r3
+                           mstore 0x101 r4
+                           mstore 0x102 r5
+                           mstore 0x103 r0
+                           mul_u32 r3 r4
+                           div_u32 r5
+                           mod_u32 r0
+                           end";
+
+        let mut encoder: Encoder = Default::default();
+        let asm_codes: Vec<String> = asm_codes.split('\n').map(|e| e.to_string()).collect();
+        let program = encoder.assemble_binary(asm_codes);
+
+        // 18 + 18 + 19 + 19 + 19 instructions for the macros
+        assert_eq!(encoder.asm_code.len(), 100);
+        assert_eq!(encoder.asm_code[2].trim(), "prophet r6 4");
+        assert_eq!(encoder.asm_code[19].trim(), "mov r4 r7");
+        assert_eq!(encoder.asm_code[20].trim(), "prophet r6 5");
+        assert_eq!(encoder.asm_code[42].trim(), "prophet r6 6");
+        assert_eq!(encoder.asm_code[79].trim(), "mload r5 r6");
+        assert_eq!(encoder.asm_code[98].trim(), "mov r0 r7");
+        assert_eq!(program.line_info[2].1, 4);
+        assert_eq!(program.line_info[98].1, 12);
+        // mul r7 r7 0x100000000
+        assert_eq!(program.code[18..20], [0x5000040200000000, 1 << 32]);
+    }
 }
@@ -4,4 +4,8 @@ pub enum AssemblerError {
     ParseIntError,
     /// parse integer to opcode fail
     ParseOpcodeError,
+    /// later `.data` section at an address not following the previous one
+    DataSectionNotContiguous(u64),
+    /// static data words or labels in the prophet memory region
+    DataAddressOverflow,
 }
@@ -0,0 +1,39 @@
+use crate::encode::Encoder;
+
+// Assembles the asm source of a test program for the executor and circuits
+// tests.
+pub fn assemble(asm_codes: &str) -> Vec<String> {
+    let mut encoder: Encoder = Default::default();
+    encoder.assemble_link(asm_codes.lines().map(|e| e.to_string()).collect())
+}
+
+// The asm source storing the words from addr up through r1, one mov and one
+// mstore per word.
+pub fn store_words(addr: u64, words: &[u64]) -> String {
+    words
+        .iter()
+        .zip(addr..)
+        .map(|(word, addr)| format!("mov r1 {word:#x}\nmstore {addr:#x} r1\n"))
+        .collect()
+}
+
+// The u32 macros on r1 = a and r2 = b, assembled for the executor and
+// circuits tests. The sums and differences are stored at 0x100..0x104, the
+// product, quotient and remainder are left in r3, r4, r5 and r0.
+pub fn u32_program(a: u64, b: u64) -> Vec<String> {
+    assemble(&format!(
+        "main:
+         mov r1 {a:#x}
+         mov r2 {b:#x}
+         add_u32 r3 r4
+         sub_u32 r5 r0
+         mstore 0x100 r3
+         mstore 0x101 r4
+         mstore 0x102 r5
+         mstore 0x103 r0
+         mul_u32 r3 r4
+         div_u32 r5
+         mod_u32 r0
+         end"
+    ))
+}
@@ -1,2 +1,6 @@
+pub mod binary;
+pub mod disasm;
 pub mod encode;
+#[cfg(any(test, feature = "fixtures"))]
+pub mod fixtures;
 mod error;
@@ -74,7 +74,7 @@ pub(crate) fn bench_fibo_loop(inst_size: u64) {
     let mut process = Process::new();
 
     let start = Instant::now();
-    process.execute(&mut program);
+    process.execute(&mut program).unwrap();
 
     let exec_time = start.elapsed();
     info!(
@@ -83,7 +83,7 @@ pub(crate) fn bench_fibo_loop(inst_size: u64) {
         program.trace.exec.len()
     );
     let mut all_stark = AllStark::default();
-    let (traces, public_values) = generate_traces(&program, &mut all_stark);
+    let (traces, public_values) = generate_traces(program, &mut all_stark).unwrap();
     let config = StarkConfig::standard_fast_config();
 
     let proof = prove_with_traces::<F, C, D>(
@@ -94,7 +94,7 @@ pub(crate) fn bench_fibo_loop(inst_size: u64) {
         &mut TimingTree::default(),
     )
     .unwrap();
-    verify_proof(all_stark, proof, &config).unwrap();
+    verify_proof(all_stark, proof, &config, None).unwrap();
 }
 
 fn fibo_loop_benchmark(c: &mut Criterion) {
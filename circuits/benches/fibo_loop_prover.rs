@@ -17,7 +17,7 @@ type F = <C as GenericConfig<D>>::F;
 
 pub(crate) fn bench_fibo_loop_prover(program: &Program) {
     let mut all_stark = AllStark::default();
-    let (traces, public_values) = generate_traces(&program, &mut all_stark);
+    let (traces, public_values) = generate_traces(program.clone(), &mut all_stark).unwrap();
     let config = StarkConfig::standard_fast_config();
 
     let proof = prove_with_traces::<F, C, D>(
@@ -77,7 +77,7 @@ fn fibo_loop_prover_benchmark(c: &mut Criterion) {
     }
 
     let mut process = Process::new();
-    process.execute(&mut program);
+    process.execute(&mut program).unwrap();
 
     let mut group = c.benchmark_group("fibo_loop_prover");
 
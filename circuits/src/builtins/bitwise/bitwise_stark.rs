@@ -1,11 +1,8 @@
 use crate::builtins::bitwise::columns::*;
-use anyhow::Result;
 use itertools::Itertools;
 //use crate::var::{StarkEvaluationTargets, StarkEvaluationVars};
 use crate::stark::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
 use crate::stark::cross_table_lookup::Column;
-use crate::stark::lookup::*;
-use crate::stark::permutation::*;
 use crate::stark::stark::Stark;
 use crate::stark::vars::{StarkEvaluationTargets, StarkEvaluationVars};
 use plonky2::field::extension::{Extendable, FieldExtension};
@@ -20,22 +17,11 @@ use std::marker::PhantomData;
 
 #[derive(Copy, Clone, Default)]
 pub struct BitwiseStark<F, const D: usize> {
-    compress_challenge: Option<F>,
     pub _phantom: PhantomData<F>,
 }
 
 impl<F: RichField, const D: usize> BitwiseStark<F, D> {
     const BASE: usize = 1 << 8;
-
-    pub fn set_compress_challenge(&mut self, challenge: F) -> Result<()> {
-        assert!(self.compress_challenge.is_none(), "already set?");
-        self.compress_challenge = Some(challenge);
-        Ok(())
-    }
-
-    pub fn get_compress_challenge(&self) -> Option<F> {
-        self.compress_challenge
-    }
 }
 
 impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for BitwiseStark<F, D> {
@@ -73,115 +59,21 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for BitwiseStark<
             reduce_with_powers(&res_limbs, P::Scalar::from_canonical_usize(Self::BASE));
         yield_constr.constraint(computed_sum - res);
 
-        // Constrain compress logic.
-        let beta = FE::from_basefield(self.get_compress_challenge().unwrap());
+        // tag is one of AND, OR, XOR
+        let tag = lv[TAG];
+        let two = P::ONES + P::ONES;
+        let three = two + P::ONES;
+        yield_constr.constraint(tag * (tag - P::ONES) * (tag - two));
+
+        // With x = op0_limb_i + op1_limb_i - 2 * and_limb_i:
+        // 2 * res_limb_i = (2 - tag * (tag - 1)) * and_limb_i + tag * (3 - tag) * x
+        let and_coeff = two - tag * (tag - P::ONES);
+        let x_coeff = tag * (three - tag);
         for i in 0..4 {
-            yield_constr.constraint(
-                lv[TAG]
-                    + lv[OP0_LIMBS.start + i] * beta
-                    + lv[OP1_LIMBS.start + i] * beta * beta
-                    + lv[RES_LIMBS.start + i] * beta * beta * beta
-                    - lv[COMPRESS_LIMBS.start + i],
-            );
+            let and = lv[AND_LIMBS.start + i];
+            let x = lv[OP0_LIMBS.start + i] + lv[OP1_LIMBS.start + i] - two * and;
+            yield_constr.constraint(two * lv[RES_LIMBS.start + i] - and_coeff * and - x_coeff * x);
         }
-
-        eval_lookups(
-            vars,
-            yield_constr,
-            OP0_LIMBS_PERMUTED.start,
-            FIX_RANGE_CHECK_U8_PERMUTED.start,
-        );
-        eval_lookups(
-            vars,
-            yield_constr,
-            OP0_LIMBS_PERMUTED.start + 1,
-            FIX_RANGE_CHECK_U8_PERMUTED.start + 1,
-        );
-        eval_lookups(
-            vars,
-            yield_constr,
-            OP0_LIMBS_PERMUTED.start + 2,
-            FIX_RANGE_CHECK_U8_PERMUTED.start + 2,
-        );
-        eval_lookups(
-            vars,
-            yield_constr,
-            OP0_LIMBS_PERMUTED.start + 3,
-            FIX_RANGE_CHECK_U8_PERMUTED.start + 3,
-        );
-        eval_lookups(
-            vars,
-            yield_constr,
-            OP1_LIMBS_PERMUTED.start,
-            FIX_RANGE_CHECK_U8_PERMUTED.start + 4,
-        );
-        eval_lookups(
-            vars,
-            yield_constr,
-            OP1_LIMBS_PERMUTED.start + 1,
-            FIX_RANGE_CHECK_U8_PERMUTED.start + 5,
-        );
-        eval_lookups(
-            vars,
-            yield_constr,
-            OP1_LIMBS_PERMUTED.start + 2,
-            FIX_RANGE_CHECK_U8_PERMUTED.start + 6,
-        );
-        eval_lookups(
-            vars,
-            yield_constr,
-            OP1_LIMBS_PERMUTED.start + 3,
-            FIX_RANGE_CHECK_U8_PERMUTED.start + 7,
-        );
-        eval_lookups(
-            vars,
-            yield_constr,
-            RES_LIMBS_PERMUTED.start,
-            FIX_RANGE_CHECK_U8_PERMUTED.start + 8,
-        );
-        eval_lookups(
-            vars,
-            yield_constr,
-            RES_LIMBS_PERMUTED.start + 1,
-            FIX_RANGE_CHECK_U8_PERMUTED.start + 9,
-        );
-        eval_lookups(
-            vars,
-            yield_constr,
-            RES_LIMBS_PERMUTED.start + 2,
-            FIX_RANGE_CHECK_U8_PERMUTED.start + 10,
-        );
-        eval_lookups(
-            vars,
-            yield_constr,
-            RES_LIMBS_PERMUTED.start + 3,
-            FIX_RANGE_CHECK_U8_PERMUTED.start + 11,
-        );
-
-        eval_lookups(
-            vars,
-            yield_constr,
-            COMPRESS_PERMUTED.start,
-            FIX_COMPRESS_PERMUTED.start,
-        );
-        eval_lookups(
-            vars,
-            yield_constr,
-            COMPRESS_PERMUTED.start + 1,
-            FIX_COMPRESS_PERMUTED.start + 1,
-        );
-        eval_lookups(
-            vars,
-            yield_constr,
-            COMPRESS_PERMUTED.start + 2,
-            FIX_COMPRESS_PERMUTED.start + 2,
-        );
-        eval_lookups(
-            vars,
-            yield_constr,
-            COMPRESS_PERMUTED.start + 3,
-            FIX_COMPRESS_PERMUTED.start + 3,
-        );
     }
 
     fn eval_ext_circuit(
@@ -215,197 +107,52 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for BitwiseStark<
         let res_sum_cs = builder.sub_extension(computed_sum, res);
         yield_constr.constraint(builder, res_sum_cs);
 
-        // Constrain compress logic.
-        let beta = builder.constant_extension(F::Extension::from_basefield(
-            self.get_compress_challenge().unwrap(),
-        ));
-        let beta_2 = builder.mul_extension(beta, beta);
-        let beta_3 = builder.mul_extension(beta_2, beta);
+        // tag is one of AND, OR, XOR
+        let tag = lv[TAG];
+        let one = builder.one_extension();
+        let two = builder.two_extension();
+        let three = builder.constant_extension(F::Extension::from_canonical_u64(3));
+        let tag_minus_one = builder.sub_extension(tag, one);
+        let tag_minus_two = builder.sub_extension(tag, two);
+        let tag_cs = builder.mul_many_extension([tag, tag_minus_one, tag_minus_two]);
+        yield_constr.constraint(builder, tag_cs);
+
+        // With x = op0_limb_i + op1_limb_i - 2 * and_limb_i:
+        // 2 * res_limb_i = (2 - tag * (tag - 1)) * and_limb_i + tag * (3 - tag) * x
+        let tag_and = builder.mul_extension(tag, tag_minus_one);
+        let and_coeff = builder.sub_extension(two, tag_and);
+        let three_minus_tag = builder.sub_extension(three, tag);
+        let x_coeff = builder.mul_extension(tag, three_minus_tag);
         for i in 0..4 {
-            let op0_cs = builder.mul_extension(lv[OP0_LIMBS.start + i], beta);
-            let op1_cs = builder.mul_extension(lv[OP1_LIMBS.start + i], beta_2);
-            let res_cs = builder.mul_extension(lv[RES_LIMBS.start + i], beta_3);
-            let cs = builder.add_many_extension([lv[TAG], op0_cs, op1_cs, res_cs]);
-            let cs = builder.sub_extension(cs, lv[COMPRESS_LIMBS.start + i]);
-            yield_constr.constraint(builder, cs);
+            let and = lv[AND_LIMBS.start + i];
+            let limbs_sum = builder.add_extension(lv[OP0_LIMBS.start + i], lv[OP1_LIMBS.start + i]);
+            let double_and = builder.mul_extension(two, and);
+            let x = builder.sub_extension(limbs_sum, double_and);
+            let computed = builder.mul_extension(and_coeff, and);
+            let computed = builder.mul_add_extension(x_coeff, x, computed);
+            let double_res = builder.mul_extension(two, lv[RES_LIMBS.start + i]);
+            let res_limb_cs = builder.sub_extension(double_res, computed);
+            yield_constr.constraint(builder, res_limb_cs);
         }
-
-        eval_lookups_circuit(
-            builder,
-            vars,
-            yield_constr,
-            OP0_LIMBS_PERMUTED.start,
-            FIX_RANGE_CHECK_U8_PERMUTED.start,
-        );
-        eval_lookups_circuit(
-            builder,
-            vars,
-            yield_constr,
-            OP0_LIMBS_PERMUTED.start + 1,
-            FIX_RANGE_CHECK_U8_PERMUTED.start + 1,
-        );
-        eval_lookups_circuit(
-            builder,
-            vars,
-            yield_constr,
-            OP0_LIMBS_PERMUTED.start + 2,
-            FIX_RANGE_CHECK_U8_PERMUTED.start + 2,
-        );
-        eval_lookups_circuit(
-            builder,
-            vars,
-            yield_constr,
-            OP0_LIMBS_PERMUTED.start + 3,
-            FIX_RANGE_CHECK_U8_PERMUTED.start + 3,
-        );
-        eval_lookups_circuit(
-            builder,
-            vars,
-            yield_constr,
-            OP1_LIMBS_PERMUTED.start,
-            FIX_RANGE_CHECK_U8_PERMUTED.start + 4,
-        );
-        eval_lookups_circuit(
-            builder,
-            vars,
-            yield_constr,
-            OP1_LIMBS_PERMUTED.start + 1,
-            FIX_RANGE_CHECK_U8_PERMUTED.start + 5,
-        );
-        eval_lookups_circuit(
-            builder,
-            vars,
-            yield_constr,
-            OP1_LIMBS_PERMUTED.start + 2,
-            FIX_RANGE_CHECK_U8_PERMUTED.start + 6,
-        );
-        eval_lookups_circuit(
-            builder,
-            vars,
-            yield_constr,
-            OP1_LIMBS_PERMUTED.start + 3,
-            FIX_RANGE_CHECK_U8_PERMUTED.start + 7,
-        );
-        eval_lookups_circuit(
-            builder,
-            vars,
-            yield_constr,
-            RES_LIMBS_PERMUTED.start,
-            FIX_RANGE_CHECK_U8_PERMUTED.start + 8,
-        );
-        eval_lookups_circuit(
-            builder,
-            vars,
-            yield_constr,
-            RES_LIMBS_PERMUTED.start + 1,
-            FIX_RANGE_CHECK_U8_PERMUTED.start + 9,
-        );
-        eval_lookups_circuit(
-            builder,
-            vars,
-            yield_constr,
-            RES_LIMBS_PERMUTED.start + 2,
-            FIX_RANGE_CHECK_U8_PERMUTED.start + 10,
-        );
-        eval_lookups_circuit(
-            builder,
-            vars,
-            yield_constr,
-            RES_LIMBS_PERMUTED.start + 3,
-            FIX_RANGE_CHECK_U8_PERMUTED.start + 11,
-        );
-
-        eval_lookups_circuit(
-            builder,
-            vars,
-            yield_constr,
-            COMPRESS_PERMUTED.start,
-            FIX_COMPRESS_PERMUTED.start,
-        );
-        eval_lookups_circuit(
-            builder,
-            vars,
-            yield_constr,
-            COMPRESS_PERMUTED.start + 1,
-            FIX_COMPRESS_PERMUTED.start + 1,
-        );
-        eval_lookups_circuit(
-            builder,
-            vars,
-            yield_constr,
-            COMPRESS_PERMUTED.start + 2,
-            FIX_COMPRESS_PERMUTED.start + 2,
-        );
-        eval_lookups_circuit(
-            builder,
-            vars,
-            yield_constr,
-            COMPRESS_PERMUTED.start + 3,
-            FIX_COMPRESS_PERMUTED.start + 3,
-        );
     }
 
     fn constraint_degree(&self) -> usize {
         3
     }
-
-    fn permutation_pairs(&self) -> Vec<PermutationPair> {
-        vec![
-            PermutationPair::singletons(COMPRESS_LIMBS.start, COMPRESS_PERMUTED.start),
-            PermutationPair::singletons(COMPRESS_LIMBS.start + 1, COMPRESS_PERMUTED.start + 1),
-            PermutationPair::singletons(COMPRESS_LIMBS.start + 2, COMPRESS_PERMUTED.start + 2),
-            PermutationPair::singletons(COMPRESS_LIMBS.start + 3, COMPRESS_PERMUTED.start + 3),
-            PermutationPair::singletons(FIX_COMPRESS, FIX_COMPRESS_PERMUTED.start),
-            PermutationPair::singletons(FIX_COMPRESS, FIX_COMPRESS_PERMUTED.start + 1),
-            PermutationPair::singletons(FIX_COMPRESS, FIX_COMPRESS_PERMUTED.start + 2),
-            PermutationPair::singletons(FIX_COMPRESS, FIX_COMPRESS_PERMUTED.start + 3),
-        ]
-    }
 }
 
-// Get the column info for Cross_Lookup<Cpu_table, Bitwise_table>
+// Get the column info for Cross_Lookup<Cpu_table, Bitwise_table>, the sponge
+// of the keccak builtin looks up the same columns.
 pub fn ctl_data_with_cpu<F: Field>() -> Vec<Column<F>> {
-    Column::singles([OP0, OP1, RES]).collect_vec()
+    Column::singles([TAG, OP0, OP1, RES]).collect_vec()
 }
 
 pub fn ctl_filter_with_cpu<F: Field>() -> Column<F> {
     Column::single(FILTER)
 }
 
-// Get the column info for Cross_Lookup<Rangecheck_Fixed_table, Bitwise_table>
-/*pub fn ctl_data_with_rangecheck_fixed<F: Field>() -> Vec<Column<F>> {
-    let mut res = Column::singles(OP0_LIMBS).collect_vec();
-    res.extend(Column::singles(OP1_LIMBS).collect_vec());
-    res.extend(Column::singles(RES_LIMBS).collect_vec());
-    res
-}
-pub fn ctl_filter_with_rangecheck_fixed<F: Field>() -> Column<F> {
-    Column::one()
-}
-// Get the column info for Cross_Lookup<Bitwise_Fixed_table, Bitwise_table>
-pub fn ctl_data_with_bitwise_fixed<F: Field>() -> Vec<Column<F>> {
-    let mut res =
-        Column::singles([OP0_LIMBS.start, OP1_LIMBS.start, RES_LIMBS.start]).collect_vec();
-    res.extend(
-        Column::singles([
-            OP0_LIMBS.start.add(1),
-            OP1_LIMBS.start.add(1),
-            RES_LIMBS.start.add(1),
-        ])
-        .collect_vec(),
-    );
-    res.extend(
-        Column::singles([
-            OP0_LIMBS.start.add(2),
-            OP1_LIMBS.start.add(2),
-            RES_LIMBS.start.add(2),
-        ])
-        .collect_vec(),
-    );
-    res.extend(Column::singles([OP0_LIMBS.end, OP1_LIMBS.end, RES_LIMBS.end]).collect_vec());
-    res.extend(Column::singles([TAG]));
-    res
+// Get the column info for Cross_Lookup<Bitwise_table, Bitwise_Fixed_table>, the
+// <op0, op1, and> tuple of limb `i`.
+pub fn ctl_data_with_bitwise_fixed<F: Field>(i: usize) -> Vec<Column<F>> {
+    Column::singles(bitwise_tuple(i)).collect_vec()
 }
-pub fn ctl_filter_with_bitwise_fixed<F: Field>() -> Column<F> {
-    Column::one()
-}*/
@@ -11,17 +11,19 @@ Constraints as follows:
     op0 = op0_limb_0 + 2^8 * op0_limb_1 + 2^16 * op0_limb_2 + 2^24 * op0_limb_3
     op1 = op1_limb_0 + 2^8 * op1_limb_1 + 2^16 * op1_limb_2 + 2^24 * op1_limb_3
     res = res_limb_0 + 2^8 * res_limb_1 + 2^16 * res_limb_2 + 2^24 * res_limb_3
-2. rangecheck for limbs
-    Lookup{<limbs>; fix_vector}
-3. check AND logic per 3 limbs
-    Lookup{<tag, op0_limb_0, op1_limb_0, res_limb_0>,...; fix_COMBINED_table}
+2. check AND logic per 3 limbs, which also rangechecks the limbs
+    Cross_Lookup{<op0_limb_i, op1_limb_i, and_limb_i>; BITWISE_FIXED_TABLE}
+3. res limbs from the and limbs, with x = op0_limb_i + op1_limb_i - 2 * and_limb_i
+    AND: res_limb_i = and_limb_i
+    OR:  res_limb_i = and_limb_i + x
+    XOR: res_limb_i = x
 4. Cross_Lookup
     Cross_Lookup{SUB_TRACE: <OP0,OP1,OP2>; MAIN_TRACE: <SEL_AND*(OP0,OP1,DST)>}
 */
 //Identify different LOOKUP TABLE
-// 1 => AND TABLE
-// 2 => OR  TABLE
-// 3 => XOR TABLE
+// 0 => AND TABLE
+// 1 => OR  TABLE
+// 2 => XOR TABLE
 pub(crate) const FILTER: usize = 0;
 pub(crate) const TAG: usize = FILTER + 1;
 
@@ -44,33 +46,16 @@ pub(crate) const RES_LIMBS: Range<usize> = OP1_LIMBS.end..OP1_LIMBS.end + 4;
 //pub(crate) const RES_LIMB_2: usize  = RES_LIMB_1 + 1;
 //pub(crate) const RES_LIMB_3: usize  = RES_LIMB_2 + 1;
 
-pub(crate) const OP0_LIMBS_PERMUTED: Range<usize> = RES_LIMBS.end..RES_LIMBS.end + 4; //16
-pub(crate) const OP1_LIMBS_PERMUTED: Range<usize> =
-    OP0_LIMBS_PERMUTED.end..OP0_LIMBS_PERMUTED.end + 4; //20
-pub(crate) const RES_LIMBS_PERMUTED: Range<usize> =
-    OP1_LIMBS_PERMUTED.end..OP1_LIMBS_PERMUTED.end + 4; //24
+// op0_limb_i & op1_limb_i, looked up in the fixed bitwise table.
+pub(crate) const AND_LIMBS: Range<usize> = RES_LIMBS.end..RES_LIMBS.end + 4;
 
-pub(crate) const COMPRESS_LIMBS: Range<usize> = RES_LIMBS_PERMUTED.end..RES_LIMBS_PERMUTED.end + 4; //28
-pub(crate) const COMPRESS_PERMUTED: Range<usize> = COMPRESS_LIMBS.end..COMPRESS_LIMBS.end + 4; //32
+pub(crate) const COL_NUM_BITWISE: usize = AND_LIMBS.end; //20
 
-// [0...2^8-1]
-pub(crate) const FIX_RANGE_CHECK_U8: usize = COMPRESS_PERMUTED.end; //36
-pub(crate) const FIX_RANGE_CHECK_U8_PERMUTED: Range<usize> =
-    FIX_RANGE_CHECK_U8 + 1..FIX_RANGE_CHECK_U8 + 13; //37~48
-                                                     // 1 => AND TABLE
-                                                     // 2 => OR  TABLE
-                                                     // 3 => XOR TABLE
-pub(crate) const FIX_TAG: usize = FIX_RANGE_CHECK_U8_PERMUTED.end; //49
-pub(crate) const FIX_BITWSIE_OP0: usize = FIX_TAG + 1; //50
-pub(crate) const FIX_BITWSIE_OP1: usize = FIX_BITWSIE_OP0 + 1; //51
-pub(crate) const FIX_BITWSIE_RES: usize = FIX_BITWSIE_OP1 + 1; //52
-
-pub(crate) const FIX_COMPRESS: usize = FIX_BITWSIE_RES + 1; //53
-pub(crate) const FIX_COMPRESS_PERMUTED: Range<usize> = FIX_COMPRESS + 1..FIX_COMPRESS + 5; //54~57
-
-pub(crate) const COL_NUM_BITWISE: usize = FIX_COMPRESS_PERMUTED.end; //58
-
-pub(crate) const RANGE_CHECK_U8_SIZE: usize = 1 << 8;
-//pub(crate) const BITWISE_U8_SIZE_PER: usize = (1 << 15) + (1 << 7);
-pub(crate) const BITWISE_U8_SIZE_PER: usize = 1 << 16;
-pub(crate) const BITWISE_U8_SIZE: usize = 3 * BITWISE_U8_SIZE_PER;
+// The <op0, op1, and> tuple of limb `i`.
+pub(crate) fn bitwise_tuple(i: usize) -> [usize; 3] {
+    [
+        OP0_LIMBS.start + i,
+        OP1_LIMBS.start + i,
+        AND_LIMBS.start + i,
+    ]
+}
@@ -0,0 +1,169 @@
+/* Ecdsa Table, one relation over secp256k1 per row:
++-----------+-------+---------+------------------------------------+-----+---------------+
+| row types | phase | counters| registers: 13 * 16 limbs           | new | quot, carries |
++-----------+-------+---------+------------------------------------+-----+---------------+
+
+256 bit values are 16 limbs of 16 bits, least significant first. Every limb
+written by a row goes through the `new` column and is range checked by the
+fixed u16 table, so are the quotient and carry limbs.
+
+A row of type `t` checks the relation
+    A1 * B1 + A2 * B2 + LIN = Q * M
+of `ecdsa_stark::{A1, B1, A2, B2, LIN}`, where M is p, n or 0 by row type. Q
+can be negative, the `quot` column holds Q + 2^259. The product of polynomials
+in 2^16 is checked two coefficients at a time, each group of 32 bits carries
+`lo + 2^16 * hi - 2^31` to the next one.
+
+A verification of `ecdsa addr` is the sequence
+    LOAD_QX CHK_QX LOAD_QY CHK_QY SQR_QX ON_CURVE     Q is a curve point
+    LOAD_R CHK_R INV_R LOAD_S CHK_S INV_S             r and s are in [1, n)
+    LOAD_Z U1 U2                                      u1 = z/s, u2 = r/s
+    ADD*4 SAVE_QK ADD*4 SAVE_GQK                      Q+K and Q+G+K
+    (DBL1 DBL2 DBL3 ADD*4)*256                        ladder from K
+    SET_C ADD*4 CHK_X FIN_EQ|FIN_NE                   subtract the offsets
+where ADD*4 is ADD1..ADD4, adding (AX, AY) to (X, Y). ADD1 proves AX != X so
+the chord formula is right. Each ladder step doubles (X, Y) and adds the point
+selected by the bits of u1 and u2 among K, G+K, Q+K and Q+G+K. The bits are
+read from the top limbs of U1 and U2, which are shifted up every 16 steps.
+`phase` says which row follows ADD4. The table ends with PAD rows.
+
+K is `LADDER_OFFSET` of the executor, whose discrete log nobody knows, and
+the ladder ends at u1 * G + u2 * Q + (2^257 - 1) * K.
+*/
+
+pub(crate) const N_LIMBS: usize = 16;
+pub(crate) const LIMB_BITS: usize = 16;
+pub(crate) const N_QUOT_LIMBS: usize = N_LIMBS + 1;
+pub(crate) const N_CARRIES: usize = N_LIMBS - 1;
+// Q + 2^259 is not negative.
+pub(crate) const QUOT_OFFSET_BITS: usize = 259;
+
+// row types
+pub(crate) const ROW_LOAD_QX: usize = 0;
+pub(crate) const ROW_CHK_QX: usize = ROW_LOAD_QX + 1;
+pub(crate) const ROW_LOAD_QY: usize = ROW_CHK_QX + 1;
+pub(crate) const ROW_CHK_QY: usize = ROW_LOAD_QY + 1;
+pub(crate) const ROW_SQR_QX: usize = ROW_CHK_QY + 1;
+pub(crate) const ROW_ON_CURVE: usize = ROW_SQR_QX + 1;
+pub(crate) const ROW_LOAD_R: usize = ROW_ON_CURVE + 1;
+pub(crate) const ROW_CHK_R: usize = ROW_LOAD_R + 1;
+pub(crate) const ROW_INV_R: usize = ROW_CHK_R + 1;
+pub(crate) const ROW_LOAD_S: usize = ROW_INV_R + 1;
+pub(crate) const ROW_CHK_S: usize = ROW_LOAD_S + 1;
+pub(crate) const ROW_INV_S: usize = ROW_CHK_S + 1;
+pub(crate) const ROW_LOAD_Z: usize = ROW_INV_S + 1;
+pub(crate) const ROW_U1: usize = ROW_LOAD_Z + 1;
+pub(crate) const ROW_U2: usize = ROW_U1 + 1;
+pub(crate) const ROW_ADD1: usize = ROW_U2 + 1;
+pub(crate) const ROW_ADD2: usize = ROW_ADD1 + 1;
+pub(crate) const ROW_ADD3: usize = ROW_ADD2 + 1;
+pub(crate) const ROW_ADD4: usize = ROW_ADD3 + 1;
+pub(crate) const ROW_SAVE_QK: usize = ROW_ADD4 + 1;
+pub(crate) const ROW_SAVE_GQK: usize = ROW_SAVE_QK + 1;
+pub(crate) const ROW_DBL1: usize = ROW_SAVE_GQK + 1;
+pub(crate) const ROW_DBL2: usize = ROW_DBL1 + 1;
+pub(crate) const ROW_DBL3: usize = ROW_DBL2 + 1;
+pub(crate) const ROW_SET_C: usize = ROW_DBL3 + 1;
+pub(crate) const ROW_CHK_X: usize = ROW_SET_C + 1;
+pub(crate) const ROW_FIN_EQ: usize = ROW_CHK_X + 1;
+pub(crate) const ROW_FIN_NE: usize = ROW_FIN_EQ + 1;
+pub(crate) const ROW_PAD: usize = ROW_FIN_NE + 1;
+pub(crate) const NUM_ROW_TYPES: usize = ROW_PAD + 1;
+
+// phases, the successor of ADD4
+pub(crate) const PHASE_QK: usize = 0;
+pub(crate) const PHASE_GQK: usize = 1;
+pub(crate) const PHASE_LADDER: usize = 2;
+pub(crate) const PHASE_FINAL: usize = 3;
+pub(crate) const NUM_PHASES: usize = 4;
+
+// registers
+pub(crate) const REG_X: usize = 0;
+pub(crate) const REG_Y: usize = REG_X + 1;
+pub(crate) const REG_L: usize = REG_Y + 1;
+pub(crate) const REG_T: usize = REG_L + 1;
+pub(crate) const REG_AX: usize = REG_T + 1;
+pub(crate) const REG_AY: usize = REG_AX + 1;
+pub(crate) const REG_QKX: usize = REG_AY + 1;
+pub(crate) const REG_QKY: usize = REG_QKX + 1;
+pub(crate) const REG_GQKX: usize = REG_QKY + 1;
+pub(crate) const REG_GQKY: usize = REG_GQKX + 1;
+pub(crate) const REG_U1: usize = REG_GQKY + 1;
+pub(crate) const REG_U2: usize = REG_U1 + 1;
+pub(crate) const REG_R: usize = REG_U2 + 1;
+pub(crate) const NUM_REGS: usize = REG_R + 1;
+
+// Rows of a verification, the ladder is 7 rows per step.
+pub(crate) const ROWS_PER_VERIFICATION: usize = 15 + 2 * 5 + 256 * 7 + 7;
+
+pub(crate) const SEL_START: usize = 0;
+pub(crate) const PHASE_START: usize = SEL_START + NUM_ROW_TYPES;
+// one-hot index of the point added by a ladder step, `b1 + 2 * b2` for the
+// bits b1 of u1 and b2 of u2
+pub(crate) const FSEL_START: usize = PHASE_START + NUM_PHASES;
+// the bits of the current limb of u1 and u2 read so far
+pub(crate) const ACC1: usize = FSEL_START + 4;
+pub(crate) const ACC2: usize = ACC1 + 1;
+// position of the bit in its limb, 0 is the last one
+pub(crate) const BIT: usize = ACC2 + 1;
+pub(crate) const BIT_INV: usize = BIT + 1;
+pub(crate) const IS_LIMB_END: usize = BIT_INV + 1;
+// index of the current limb, 0 is the last one
+pub(crate) const LIMB: usize = IS_LIMB_END + 1;
+pub(crate) const LIMB_INV: usize = LIMB + 1;
+pub(crate) const IS_LAST_LIMB: usize = LIMB_INV + 1;
+pub(crate) const IS_LAST_STEP: usize = IS_LAST_LIMB + 1;
+pub(crate) const CLK: usize = IS_LAST_STEP + 1;
+pub(crate) const ADDR: usize = CLK + 1;
+pub(crate) const REG_START: usize = ADDR + 1;
+pub(crate) const NEW_START: usize = REG_START + NUM_REGS * N_LIMBS;
+pub(crate) const QUOT_START: usize = NEW_START + N_LIMBS;
+pub(crate) const CARRY_LO_START: usize = QUOT_START + N_QUOT_LIMBS;
+pub(crate) const CARRY_HI_START: usize = CARRY_LO_START + N_CARRIES;
+
+pub(crate) const COL_NUM_ECDSA: usize = CARRY_HI_START + N_CARRIES; // 319
+
+pub(crate) fn col_sel(row_type: usize) -> usize {
+    debug_assert!(row_type < NUM_ROW_TYPES);
+    SEL_START + row_type
+}
+
+pub(crate) fn col_phase(phase: usize) -> usize {
+    debug_assert!(phase < NUM_PHASES);
+    PHASE_START + phase
+}
+
+pub(crate) fn col_fsel(index: usize) -> usize {
+    debug_assert!(index < 4);
+    FSEL_START + index
+}
+
+pub(crate) fn col_reg(reg: usize, limb: usize) -> usize {
+    debug_assert!(reg < NUM_REGS && limb < N_LIMBS);
+    REG_START + reg * N_LIMBS + limb
+}
+
+pub(crate) fn col_new(limb: usize) -> usize {
+    debug_assert!(limb < N_LIMBS);
+    NEW_START + limb
+}
+
+pub(crate) fn col_quot(limb: usize) -> usize {
+    debug_assert!(limb < N_QUOT_LIMBS);
+    QUOT_START + limb
+}
+
+pub(crate) fn col_carry_lo(i: usize) -> usize {
+    debug_assert!(i < N_CARRIES);
+    CARRY_LO_START + i
+}
+
+pub(crate) fn col_carry_hi(i: usize) -> usize {
+    debug_assert!(i < N_CARRIES);
+    CARRY_HI_START + i
+}
+
+// The u16 columns, looked up in the fixed rangecheck table.
+pub(crate) fn range_checked_columns() -> impl Iterator<Item = usize> {
+    NEW_START..COL_NUM_ECDSA
+}
@@ -0,0 +1,1055 @@
+use crate::builtins::ecdsa::columns::*;
+use core::program::instruction::Opcode;
+use core::trace::trace::ECDSA_WORD_NUM;
+use executor::coprocessor::ecdsa::{
+    BASE_ORDER, CURVE_B, GENERATOR_PLUS_OFFSET, LADDER_CORRECTION, LADDER_OFFSET, SCALAR_ORDER,
+};
+use itertools::Itertools;
+
+use crate::stark::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+use crate::stark::cross_table_lookup::Column;
+use crate::stark::stark::Stark;
+use crate::stark::vars::{StarkEvaluationTargets, StarkEvaluationVars};
+use plonky2::field::extension::{Extendable, FieldExtension};
+use plonky2::field::packed::PackedField;
+use plonky2::field::types::Field;
+use plonky2::hash::hash_types::RichField;
+use plonky2::iop::ext_target::ExtensionTarget;
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use std::marker::PhantomData;
+
+#[derive(Copy, Clone, Default)]
+pub struct EcdsaStark<F, const D: usize> {
+    pub _phantom: PhantomData<F>,
+}
+
+// The 16 limbs of a row: a register or the value written by the row.
+#[derive(Clone, Copy)]
+pub(crate) enum Limbs {
+    Reg(usize),
+    New,
+}
+
+// `sum(coef * limbs)` in the rows of type `rows`, 0 in the other rows.
+pub(crate) struct Operand {
+    rows: &'static [usize],
+    terms: &'static [(i64, Limbs)],
+}
+
+use Limbs::{New, Reg};
+
+pub(crate) const A1: &[Operand] = &[
+    Operand {
+        rows: &[ROW_ADD1, ROW_ADD2],
+        terms: &[(1, Reg(REG_AX)), (-1, Reg(REG_X))],
+    },
+    Operand {
+        rows: &[ROW_ADD3, ROW_ADD4, ROW_DBL2, ROW_DBL3, ROW_INV_S],
+        terms: &[(1, Reg(REG_L))],
+    },
+    Operand {
+        rows: &[ROW_DBL1],
+        terms: &[(2, Reg(REG_Y))],
+    },
+    Operand {
+        rows: &[ROW_SQR_QX],
+        terms: &[(1, Reg(REG_AX))],
+    },
+    Operand {
+        rows: &[ROW_ON_CURVE],
+        terms: &[(1, Reg(REG_AY))],
+    },
+    Operand {
+        rows: &[ROW_INV_R, ROW_U2],
+        terms: &[(1, Reg(REG_R))],
+    },
+    Operand {
+        rows: &[ROW_U1],
+        terms: &[(1, Reg(REG_X))],
+    },
+    Operand {
+        rows: &[ROW_FIN_NE],
+        terms: &[(1, Reg(REG_X)), (-1, Reg(REG_R))],
+    },
+];
+
+pub(crate) const B1: &[Operand] = &[
+    Operand {
+        rows: &[
+            ROW_ADD1, ROW_ADD2, ROW_DBL1, ROW_INV_R, ROW_INV_S, ROW_FIN_NE,
+        ],
+        terms: &[(1, New)],
+    },
+    Operand {
+        rows: &[ROW_ADD3, ROW_DBL2],
+        terms: &[(1, Reg(REG_L))],
+    },
+    Operand {
+        rows: &[ROW_ADD4, ROW_DBL3],
+        terms: &[(1, Reg(REG_X)), (-1, Reg(REG_T))],
+    },
+    Operand {
+        rows: &[ROW_SQR_QX],
+        terms: &[(1, Reg(REG_AX))],
+    },
+    Operand {
+        rows: &[ROW_ON_CURVE],
+        terms: &[(1, Reg(REG_AY))],
+    },
+    Operand {
+        rows: &[ROW_U1, ROW_U2],
+        terms: &[(1, Reg(REG_T))],
+    },
+];
+
+pub(crate) const A2: &[Operand] = &[
+    Operand {
+        rows: &[ROW_DBL1],
+        terms: &[(-3, Reg(REG_X))],
+    },
+    Operand {
+        rows: &[ROW_ON_CURVE],
+        terms: &[(-1, Reg(REG_T))],
+    },
+];
+
+pub(crate) const B2: &[Operand] = &[
+    Operand {
+        rows: &[ROW_DBL1],
+        terms: &[(1, Reg(REG_X))],
+    },
+    Operand {
+        rows: &[ROW_ON_CURVE],
+        terms: &[(1, Reg(REG_AX))],
+    },
+];
+
+pub(crate) const LIN: &[Operand] = &[
+    Operand {
+        rows: &[ROW_ADD2],
+        terms: &[(1, Reg(REG_Y)), (-1, Reg(REG_AY))],
+    },
+    Operand {
+        rows: &[ROW_ADD3],
+        terms: &[(-1, Reg(REG_X)), (-1, Reg(REG_AX)), (-1, New)],
+    },
+    Operand {
+        rows: &[ROW_ADD4, ROW_DBL3],
+        terms: &[(-1, Reg(REG_Y)), (-1, New)],
+    },
+    Operand {
+        rows: &[ROW_DBL2],
+        terms: &[(-2, Reg(REG_X)), (-1, New)],
+    },
+    Operand {
+        rows: &[ROW_SQR_QX, ROW_U1, ROW_U2],
+        terms: &[(-1, New)],
+    },
+    Operand {
+        rows: &[ROW_FIN_EQ],
+        terms: &[(1, Reg(REG_X)), (-1, Reg(REG_R))],
+    },
+    Operand {
+        rows: &[ROW_CHK_QX],
+        terms: &[(1, New), (1, Reg(REG_AX))],
+    },
+    Operand {
+        rows: &[ROW_CHK_QY],
+        terms: &[(1, New), (1, Reg(REG_AY))],
+    },
+    Operand {
+        rows: &[ROW_CHK_R],
+        terms: &[(1, New), (1, Reg(REG_R))],
+    },
+    Operand {
+        rows: &[ROW_CHK_S],
+        terms: &[(1, New), (1, Reg(REG_L))],
+    },
+    Operand {
+        rows: &[ROW_CHK_X],
+        terms: &[(1, New), (1, Reg(REG_X))],
+    },
+];
+
+// Constants subtracted from LIN. A CHK row proves a value is at most p - 1
+// or n - 1, since `new` is not negative.
+pub(crate) const LIN_CONSTANTS: &[(&[usize], [u64; 4])] = &[
+    (&[ROW_ADD1, ROW_INV_R, ROW_INV_S, ROW_FIN_NE], [1, 0, 0, 0]),
+    (&[ROW_ON_CURVE], [CURVE_B, 0, 0, 0]),
+    (
+        &[ROW_CHK_QX, ROW_CHK_QY, ROW_CHK_X],
+        [
+            BASE_ORDER[0] - 1,
+            BASE_ORDER[1],
+            BASE_ORDER[2],
+            BASE_ORDER[3],
+        ],
+    ),
+    (
+        &[ROW_CHK_R, ROW_CHK_S],
+        [
+            SCALAR_ORDER[0] - 1,
+            SCALAR_ORDER[1],
+            SCALAR_ORDER[2],
+            SCALAR_ORDER[3],
+        ],
+    ),
+];
+
+// Rows of a relation modulo p and modulo n, the others are exact.
+pub(crate) const BASE_ROWS: &[usize] = &[
+    ROW_SQR_QX,
+    ROW_ON_CURVE,
+    ROW_ADD1,
+    ROW_ADD2,
+    ROW_ADD3,
+    ROW_ADD4,
+    ROW_DBL1,
+    ROW_DBL2,
+    ROW_DBL3,
+];
+pub(crate) const SCALAR_ROWS: &[usize] =
+    &[ROW_INV_R, ROW_INV_S, ROW_U1, ROW_U2, ROW_FIN_EQ, ROW_FIN_NE];
+
+// The value a row writes to a register of the next row.
+#[derive(Clone, Copy)]
+pub(crate) enum Source {
+    New,
+    Reg(usize),
+    Const([u64; 4]),
+    // x or y of the point selected by `fsel` among K, G+K, Q+K and Q+G+K
+    LadderPoint { y: bool },
+    // the register shifted up a limb at the end of a limb
+    ShiftUp,
+}
+
+// (row type, register, value), registers are kept by the other rows.
+pub(crate) const REGISTER_WRITES: &[(usize, usize, Source)] = &[
+    (ROW_LOAD_QX, REG_AX, Source::New),
+    (ROW_LOAD_QY, REG_AY, Source::New),
+    (ROW_SQR_QX, REG_T, Source::New),
+    (ROW_LOAD_R, REG_R, Source::New),
+    (ROW_LOAD_S, REG_L, Source::New),
+    (ROW_INV_S, REG_T, Source::New),
+    (ROW_LOAD_Z, REG_X, Source::New),
+    (ROW_U1, REG_U1, Source::New),
+    (ROW_U2, REG_U2, Source::New),
+    (ROW_U2, REG_X, Source::Const(LADDER_OFFSET.x.0)),
+    (ROW_U2, REG_Y, Source::Const(LADDER_OFFSET.y.0)),
+    (ROW_ADD2, REG_L, Source::New),
+    (ROW_ADD3, REG_T, Source::New),
+    (ROW_ADD4, REG_X, Source::Reg(REG_T)),
+    (ROW_ADD4, REG_Y, Source::New),
+    (ROW_SAVE_QK, REG_QKX, Source::Reg(REG_X)),
+    (ROW_SAVE_QK, REG_QKY, Source::Reg(REG_Y)),
+    (ROW_SAVE_QK, REG_X, Source::Const(GENERATOR_PLUS_OFFSET.x.0)),
+    (ROW_SAVE_QK, REG_Y, Source::Const(GENERATOR_PLUS_OFFSET.y.0)),
+    (ROW_SAVE_GQK, REG_GQKX, Source::Reg(REG_X)),
+    (ROW_SAVE_GQK, REG_GQKY, Source::Reg(REG_Y)),
+    (ROW_SAVE_GQK, REG_X, Source::Const(LADDER_OFFSET.x.0)),
+    (ROW_SAVE_GQK, REG_Y, Source::Const(LADDER_OFFSET.y.0)),
+    (ROW_DBL1, REG_L, Source::New),
+    (ROW_DBL1, REG_AX, Source::LadderPoint { y: false }),
+    (ROW_DBL1, REG_AY, Source::LadderPoint { y: true }),
+    (ROW_DBL1, REG_U1, Source::ShiftUp),
+    (ROW_DBL1, REG_U2, Source::ShiftUp),
+    (ROW_DBL2, REG_T, Source::New),
+    (ROW_DBL3, REG_X, Source::Reg(REG_T)),
+    (ROW_DBL3, REG_Y, Source::New),
+    (ROW_SET_C, REG_AX, Source::Const(LADDER_CORRECTION.x.0)),
+    (ROW_SET_C, REG_AY, Source::Const(LADDER_CORRECTION.y.0)),
+];
+
+// The row types with a single successor.
+const SUCCESSORS: &[(usize, usize)] = &[
+    (ROW_LOAD_QX, ROW_CHK_QX),
+    (ROW_CHK_QX, ROW_LOAD_QY),
+    (ROW_LOAD_QY, ROW_CHK_QY),
+    (ROW_CHK_QY, ROW_SQR_QX),
+    (ROW_SQR_QX, ROW_ON_CURVE),
+    (ROW_ON_CURVE, ROW_LOAD_R),
+    (ROW_LOAD_R, ROW_CHK_R),
+    (ROW_CHK_R, ROW_INV_R),
+    (ROW_INV_R, ROW_LOAD_S),
+    (ROW_LOAD_S, ROW_CHK_S),
+    (ROW_CHK_S, ROW_INV_S),
+    (ROW_INV_S, ROW_LOAD_Z),
+    (ROW_LOAD_Z, ROW_U1),
+    (ROW_U1, ROW_U2),
+    (ROW_U2, ROW_ADD1),
+    (ROW_ADD1, ROW_ADD2),
+    (ROW_ADD2, ROW_ADD3),
+    (ROW_ADD3, ROW_ADD4),
+    (ROW_SAVE_QK, ROW_ADD1),
+    (ROW_SAVE_GQK, ROW_DBL1),
+    (ROW_DBL1, ROW_DBL2),
+    (ROW_DBL2, ROW_DBL3),
+    (ROW_DBL3, ROW_ADD1),
+    (ROW_SET_C, ROW_ADD1),
+];
+
+// The phases changed by a row type, for the next row.
+const PHASE_CHANGES: &[(usize, usize)] = &[
+    (ROW_SAVE_QK, PHASE_GQK),
+    (ROW_SAVE_GQK, PHASE_LADDER),
+    (ROW_SET_C, PHASE_FINAL),
+];
+
+// The successor of ADD4 outside the ladder.
+const ADD4_SUCCESSORS: &[(usize, usize)] = &[
+    (PHASE_QK, ROW_SAVE_QK),
+    (PHASE_GQK, ROW_SAVE_GQK),
+    (PHASE_FINAL, ROW_CHK_X),
+];
+
+// LOAD rows and the index of the value they read, in memory order.
+pub(crate) const LOADS: [(usize, usize); 5] = [
+    (ROW_LOAD_Z, 0),
+    (ROW_LOAD_QX, 1),
+    (ROW_LOAD_QY, 2),
+    (ROW_LOAD_R, 3),
+    (ROW_LOAD_S, 4),
+];
+
+// Limb `i` of a 256 bit value.
+pub(crate) fn limb(value: &[u64; 4], i: usize) -> u64 {
+    (value[i / 4] >> (LIMB_BITS * (i % 4))) & 0xffff
+}
+
+// 2^259 = QUOT_OFFSET_LIMB * (2^16)^16
+const QUOT_OFFSET_LIMB: u64 = 1 << (QUOT_OFFSET_BITS - LIMB_BITS * N_LIMBS);
+
+fn coef<F: Field>(c: i64) -> F {
+    if c < 0 {
+        -F::from_canonical_u64(c.unsigned_abs())
+    } else {
+        F::from_canonical_u64(c as u64)
+    }
+}
+
+// The coefficients of A1 * B1 + A2 * B2 + LIN + 2^259 * M - quot * M in base
+// 2^16, they are the limbs of 0 once carried.
+pub(crate) fn relation_coefficients<P: PackedField>(lv: &[P; COL_NUM_ECDSA]) -> [P; 2 * N_LIMBS] {
+    let sel_sum = |rows: &[usize]| rows.iter().map(|&t| lv[col_sel(t)]).sum::<P>();
+    let operand = |ops: &[Operand]| {
+        let mut res = [P::ZEROS; N_LIMBS];
+        for op in ops {
+            let s = sel_sum(op.rows);
+            for &(c, limbs) in op.terms {
+                let c = coef::<P::Scalar>(c);
+                for (i, x) in res.iter_mut().enumerate() {
+                    let v = match limbs {
+                        Limbs::Reg(r) => lv[col_reg(r, i)],
+                        Limbs::New => lv[col_new(i)],
+                    };
+                    *x += s * v * c;
+                }
+            }
+        }
+        res
+    };
+    let (a1, b1, a2, b2, mut lin) = (
+        operand(A1),
+        operand(B1),
+        operand(A2),
+        operand(B2),
+        operand(LIN),
+    );
+    for (rows, value) in LIN_CONSTANTS {
+        let s = sel_sum(rows);
+        for (i, x) in lin.iter_mut().enumerate() {
+            *x -= s * P::Scalar::from_canonical_u64(limb(value, i));
+        }
+    }
+    let (s_base, s_scalar) = (sel_sum(BASE_ROWS), sel_sum(SCALAR_ROWS));
+    let m: [P; N_LIMBS] = std::array::from_fn(|i| {
+        s_base * P::Scalar::from_canonical_u64(limb(&BASE_ORDER, i))
+            + s_scalar * P::Scalar::from_canonical_u64(limb(&SCALAR_ORDER, i))
+    });
+
+    let mut e = [P::ZEROS; 2 * N_LIMBS];
+    for i in 0..N_LIMBS {
+        for j in 0..N_LIMBS {
+            e[i + j] += a1[i] * b1[j] + a2[i] * b2[j];
+        }
+        e[i] += lin[i];
+        e[i + N_LIMBS] += m[i] * P::Scalar::from_canonical_u64(QUOT_OFFSET_LIMB);
+    }
+    for i in 0..N_QUOT_LIMBS {
+        for j in 0..N_LIMBS {
+            e[i + j] -= lv[col_quot(i)] * m[j];
+        }
+    }
+    e
+}
+
+fn relation_coefficients_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    lv: &[ExtensionTarget<D>; COL_NUM_ECDSA],
+) -> [ExtensionTarget<D>; 2 * N_LIMBS] {
+    let zero = builder.zero_extension();
+    let one = builder.one_extension();
+    let sel_sum = |builder: &mut CircuitBuilder<F, D>, rows: &[usize]| {
+        let sels = rows.iter().map(|&t| lv[col_sel(t)]).collect_vec();
+        builder.add_many_extension(sels)
+    };
+    let operand = |builder: &mut CircuitBuilder<F, D>, ops: &[Operand]| {
+        let mut res = [zero; N_LIMBS];
+        for op in ops {
+            let s = sel_sum(builder, op.rows);
+            for &(c, limbs) in op.terms {
+                let c = coef::<F>(c);
+                for (i, x) in res.iter_mut().enumerate() {
+                    let v = match limbs {
+                        Limbs::Reg(r) => lv[col_reg(r, i)],
+                        Limbs::New => lv[col_new(i)],
+                    };
+                    let sv = builder.mul_extension(s, v);
+                    *x = builder.arithmetic_extension(c, F::ONE, sv, one, *x);
+                }
+            }
+        }
+        res
+    };
+    let a1 = operand(builder, A1);
+    let b1 = operand(builder, B1);
+    let a2 = operand(builder, A2);
+    let b2 = operand(builder, B2);
+    let mut lin = operand(builder, LIN);
+    for (rows, value) in LIN_CONSTANTS {
+        let s = sel_sum(builder, rows);
+        for (i, x) in lin.iter_mut().enumerate() {
+            let c = F::from_canonical_u64(limb(value, i));
+            *x = builder.arithmetic_extension(-c, F::ONE, s, one, *x);
+        }
+    }
+    let s_base = sel_sum(builder, BASE_ROWS);
+    let s_scalar = sel_sum(builder, SCALAR_ROWS);
+    let m: [ExtensionTarget<D>; N_LIMBS] = std::array::from_fn(|i| {
+        let base = builder.mul_const_extension(F::from_canonical_u64(limb(&BASE_ORDER, i)), s_base);
+        let scalar = F::from_canonical_u64(limb(&SCALAR_ORDER, i));
+        builder.arithmetic_extension(scalar, F::ONE, s_scalar, one, base)
+    });
+
+    let mut e = [zero; 2 * N_LIMBS];
+    for i in 0..N_LIMBS {
+        for j in 0..N_LIMBS {
+            e[i + j] = builder.mul_add_extension(a1[i], b1[j], e[i + j]);
+            e[i + j] = builder.mul_add_extension(a2[i], b2[j], e[i + j]);
+        }
+        e[i] = builder.add_extension(e[i], lin[i]);
+        e[i + N_LIMBS] = builder.arithmetic_extension(
+            F::from_canonical_u64(QUOT_OFFSET_LIMB),
+            F::ONE,
+            m[i],
+            one,
+            e[i + N_LIMBS],
+        );
+    }
+    for i in 0..N_QUOT_LIMBS {
+        for j in 0..N_LIMBS {
+            e[i + j] =
+                builder.arithmetic_extension(F::NEG_ONE, F::ONE, lv[col_quot(i)], m[j], e[i + j]);
+        }
+    }
+    e
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for EcdsaStark<F, D> {
+    const COLUMNS: usize = COL_NUM_ECDSA;
+
+    fn eval_packed_generic<FE, P, const D2: usize>(
+        &self,
+        vars: StarkEvaluationVars<FE, P, { COL_NUM_ECDSA }>,
+        yield_constr: &mut ConstraintConsumer<P>,
+    ) where
+        FE: FieldExtension<D2, BaseField = F>,
+        P: PackedField<Scalar = FE>,
+    {
+        let lv = vars.local_values;
+        let nv = vars.next_values;
+        let one = P::ONES;
+        let sel = |t: usize| lv[col_sel(t)];
+        let next_sel = |t: usize| nv[col_sel(t)];
+        let phase = |p: usize| lv[col_phase(p)];
+        let constant = |v: u64| P::from(FE::from_canonical_u64(v));
+
+        // 1. Row types are one-hot and follow each other as in the columns doc.
+        let mut sum_sel = P::ZEROS;
+        for t in 0..NUM_ROW_TYPES {
+            yield_constr.constraint(sel(t) * (one - sel(t)));
+            sum_sel += sel(t);
+        }
+        yield_constr.constraint(one - sum_sel);
+        yield_constr.constraint_first_row(one - sel(ROW_LOAD_QX) - sel(ROW_PAD));
+        yield_constr.constraint_last_row(one - sel(ROW_PAD));
+        for &(t, next) in SUCCESSORS {
+            yield_constr.constraint_transition(sel(t) * (one - next_sel(next)));
+        }
+        let fin = sel(ROW_FIN_EQ) + sel(ROW_FIN_NE);
+        yield_constr.constraint_transition(fin * (one - next_sel(ROW_LOAD_QX) - next_sel(ROW_PAD)));
+        yield_constr.constraint_transition(
+            sel(ROW_CHK_X) * (one - next_sel(ROW_FIN_EQ) - next_sel(ROW_FIN_NE)),
+        );
+        yield_constr.constraint_transition(sel(ROW_PAD) * (one - next_sel(ROW_PAD)));
+
+        // The rows of a verification have the same clk and addr.
+        let in_verification = one - fin - sel(ROW_PAD);
+        yield_constr.constraint_transition(in_verification * (nv[CLK] - lv[CLK]));
+        yield_constr.constraint_transition(in_verification * (nv[ADDR] - lv[ADDR]));
+
+        // 2. Phase, one-hot and only changed by SAVE_QK, SAVE_GQK and SET_C.
+        let mut sum_phase = P::ZEROS;
+        for p in 0..NUM_PHASES {
+            yield_constr.constraint(phase(p) * (one - phase(p)));
+            sum_phase += phase(p);
+        }
+        yield_constr.constraint(one - sum_phase);
+        yield_constr.constraint(sel(ROW_LOAD_QX) * (one - phase(PHASE_QK)));
+        let phase_kept = PHASE_CHANGES
+            .iter()
+            .fold(in_verification, |acc, &(t, _)| acc - sel(t));
+        for p in 0..NUM_PHASES {
+            yield_constr.constraint_transition(phase_kept * (nv[col_phase(p)] - phase(p)));
+        }
+        for &(t, p) in PHASE_CHANGES {
+            yield_constr.constraint_transition(sel(t) * (one - nv[col_phase(p)]));
+        }
+        let add4 = sel(ROW_ADD4);
+        let last_step = lv[IS_LAST_STEP];
+        for &(p, next) in ADD4_SUCCESSORS {
+            yield_constr.constraint_transition(add4 * phase(p) * (one - next_sel(next)));
+        }
+        let add4_ladder = add4 * phase(PHASE_LADDER);
+        yield_constr
+            .constraint_transition(add4_ladder * (one - last_step) * (one - next_sel(ROW_DBL1)));
+        yield_constr.constraint_transition(add4_ladder * last_step * (one - next_sel(ROW_SET_C)));
+
+        // 3. Ladder bits, read at DBL1 from the top limbs of U1 and U2.
+        let mut sum_fsel = P::ZEROS;
+        for k in 0..4 {
+            yield_constr.constraint(lv[col_fsel(k)] * (one - lv[col_fsel(k)]));
+            sum_fsel += lv[col_fsel(k)];
+        }
+        yield_constr.constraint(one - sum_fsel);
+        let b1 = lv[col_fsel(1)] + lv[col_fsel(3)];
+        let b2 = lv[col_fsel(2)] + lv[col_fsel(3)];
+        let dbl1 = sel(ROW_DBL1);
+        let is_limb_end = lv[IS_LIMB_END];
+        let is_last_limb = lv[IS_LAST_LIMB];
+        yield_constr.constraint(is_limb_end * (one - is_limb_end));
+        yield_constr.constraint(is_last_limb * (one - is_last_limb));
+        yield_constr.constraint(dbl1 * is_limb_end * lv[BIT]);
+        yield_constr.constraint(dbl1 * (one - is_limb_end) * (one - lv[BIT] * lv[BIT_INV]));
+        yield_constr.constraint(dbl1 * is_last_limb * lv[LIMB]);
+        yield_constr.constraint(dbl1 * (one - is_last_limb) * (one - lv[LIMB] * lv[LIMB_INV]));
+        yield_constr.constraint(dbl1 * (last_step - is_limb_end * is_last_limb));
+        for (acc, b, u) in [(ACC1, b1, REG_U1), (ACC2, b2, REG_U2)] {
+            let bits = lv[acc] + lv[acc] + b;
+            yield_constr.constraint(dbl1 * is_limb_end * (bits - lv[col_reg(u, N_LIMBS - 1)]));
+            yield_constr.constraint_transition(dbl1 * (one - is_limb_end) * (nv[acc] - bits));
+            yield_constr.constraint_transition(dbl1 * is_limb_end * nv[acc]);
+        }
+        yield_constr.constraint_transition(dbl1 * (one - is_limb_end) * (nv[BIT] - lv[BIT] + one));
+        yield_constr
+            .constraint_transition(dbl1 * is_limb_end * (nv[BIT] - constant(LIMB_BITS as u64 - 1)));
+        yield_constr.constraint_transition(dbl1 * is_limb_end * (nv[LIMB] - lv[LIMB] + one));
+        yield_constr.constraint_transition(dbl1 * (one - is_limb_end) * (nv[LIMB] - lv[LIMB]));
+        // SAVE_GQK starts the ladder from the top bit.
+        let save_gqk = sel(ROW_SAVE_GQK);
+        yield_constr.constraint_transition(save_gqk * (nv[BIT] - constant(LIMB_BITS as u64 - 1)));
+        yield_constr.constraint_transition(save_gqk * (nv[LIMB] - constant(N_LIMBS as u64 - 1)));
+        yield_constr.constraint_transition(save_gqk * nv[ACC1]);
+        yield_constr.constraint_transition(save_gqk * nv[ACC2]);
+        let counters_kept = in_verification - dbl1 - save_gqk;
+        for c in [ACC1, ACC2, BIT, LIMB] {
+            yield_constr.constraint_transition(counters_kept * (nv[c] - lv[c]));
+        }
+        yield_constr.constraint_transition(
+            in_verification * (one - next_sel(ROW_DBL1)) * (nv[IS_LAST_STEP] - last_step),
+        );
+
+        // 4. Registers.
+        for reg in 0..NUM_REGS {
+            for i in 0..N_LIMBS {
+                let current = lv[col_reg(reg, i)];
+                let mut written = P::ZEROS;
+                for &(t, _, source) in REGISTER_WRITES.iter().filter(|w| w.1 == reg) {
+                    let value = match source {
+                        Source::New => lv[col_new(i)],
+                        Source::Reg(r) => lv[col_reg(r, i)],
+                        Source::Const(v) => constant(limb(&v, i)),
+                        Source::LadderPoint { y } => {
+                            let (k, gk) = if y {
+                                (LADDER_OFFSET.y.0, GENERATOR_PLUS_OFFSET.y.0)
+                            } else {
+                                (LADDER_OFFSET.x.0, GENERATOR_PLUS_OFFSET.x.0)
+                            };
+                            let (qk, gqk) = if y {
+                                (REG_QKY, REG_GQKY)
+                            } else {
+                                (REG_QKX, REG_GQKX)
+                            };
+                            lv[col_fsel(0)] * constant(limb(&k, i))
+                                + lv[col_fsel(1)] * constant(limb(&gk, i))
+                                + lv[col_fsel(2)] * lv[col_reg(qk, i)]
+                                + lv[col_fsel(3)] * lv[col_reg(gqk, i)]
+                        }
+                        Source::ShiftUp => {
+                            let below = if i == 0 {
+                                P::ZEROS
+                            } else {
+                                lv[col_reg(reg, i - 1)]
+                            };
+                            is_limb_end * below + (one - is_limb_end) * current
+                        }
+                    };
+                    written += sel(t) * (value - current);
+                }
+                yield_constr.constraint_transition(
+                    in_verification * (nv[col_reg(reg, i)] - current) - written,
+                );
+            }
+        }
+
+        // 5. The relation of the row, carried 32 bits at a time.
+        let e = relation_coefficients(lv);
+        let shift = constant(1 << LIMB_BITS);
+        let carry_base = constant(1 << (2 * LIMB_BITS));
+        let carry_offset = constant(1 << (2 * LIMB_BITS - 1));
+        let mut carry_in = P::ZEROS;
+        for j in 0..N_LIMBS {
+            let group = e[2 * j] + e[2 * j + 1] * shift + carry_in;
+            if j < N_CARRIES {
+                let carry = lv[col_carry_lo(j)] + lv[col_carry_hi(j)] * shift - carry_offset;
+                yield_constr.constraint(group - carry * carry_base);
+                carry_in = carry;
+            } else {
+                yield_constr.constraint(group);
+            }
+        }
+    }
+
+    fn eval_ext_circuit(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        vars: StarkEvaluationTargets<D, { COL_NUM_ECDSA }>,
+        yield_constr: &mut RecursiveConstraintConsumer<F, D>,
+    ) {
+        let lv = vars.local_values;
+        let nv = vars.next_values;
+        let one = builder.one_extension();
+        let sel = |t: usize| lv[col_sel(t)];
+        let next_sel = |t: usize| nv[col_sel(t)];
+        let phase = |p: usize| lv[col_phase(p)];
+        let binary = |builder: &mut CircuitBuilder<F, D>,
+                      x: ExtensionTarget<D>,
+                      yield_constr: &mut RecursiveConstraintConsumer<F, D>| {
+            let cs = builder.arithmetic_extension(F::NEG_ONE, F::ONE, x, x, x);
+            yield_constr.constraint(builder, cs);
+        };
+
+        // 1. Row types are one-hot and follow each other as in the columns doc.
+        for t in 0..NUM_ROW_TYPES {
+            binary(builder, sel(t), yield_constr);
+        }
+        let sum_sel = builder.add_many_extension((0..NUM_ROW_TYPES).map(sel));
+        let sum_sel_cs = builder.sub_extension(one, sum_sel);
+        yield_constr.constraint(builder, sum_sel_cs);
+        let first = builder.add_extension(sel(ROW_LOAD_QX), sel(ROW_PAD));
+        let first_cs = builder.sub_extension(one, first);
+        yield_constr.constraint_first_row(builder, first_cs);
+        let last_cs = builder.sub_extension(one, sel(ROW_PAD));
+        yield_constr.constraint_last_row(builder, last_cs);
+        for &(t, next) in SUCCESSORS {
+            let cs =
+                builder.arithmetic_extension(F::NEG_ONE, F::ONE, sel(t), next_sel(next), sel(t));
+            yield_constr.constraint_transition(builder, cs);
+        }
+        let fin = builder.add_extension(sel(ROW_FIN_EQ), sel(ROW_FIN_NE));
+        let fin_next = builder.add_extension(next_sel(ROW_LOAD_QX), next_sel(ROW_PAD));
+        let cs = builder.arithmetic_extension(F::NEG_ONE, F::ONE, fin, fin_next, fin);
+        yield_constr.constraint_transition(builder, cs);
+        let chk_x_next = builder.add_extension(next_sel(ROW_FIN_EQ), next_sel(ROW_FIN_NE));
+        let cs = builder.arithmetic_extension(
+            F::NEG_ONE,
+            F::ONE,
+            sel(ROW_CHK_X),
+            chk_x_next,
+            sel(ROW_CHK_X),
+        );
+        yield_constr.constraint_transition(builder, cs);
+        let cs = builder.arithmetic_extension(
+            F::NEG_ONE,
+            F::ONE,
+            sel(ROW_PAD),
+            next_sel(ROW_PAD),
+            sel(ROW_PAD),
+        );
+        yield_constr.constraint_transition(builder, cs);
+
+        // The rows of a verification have the same clk and addr.
+        let fin_or_pad = builder.add_extension(fin, sel(ROW_PAD));
+        let in_verification = builder.sub_extension(one, fin_or_pad);
+        for c in [CLK, ADDR] {
+            let diff = builder.sub_extension(nv[c], lv[c]);
+            let cs = builder.mul_extension(in_verification, diff);
+            yield_constr.constraint_transition(builder, cs);
+        }
+
+        // 2. Phase, one-hot and only changed by SAVE_QK, SAVE_GQK and SET_C.
+        for p in 0..NUM_PHASES {
+            binary(builder, phase(p), yield_constr);
+        }
+        let sum_phase = builder.add_many_extension((0..NUM_PHASES).map(phase));
+        let sum_phase_cs = builder.sub_extension(one, sum_phase);
+        yield_constr.constraint(builder, sum_phase_cs);
+        let cs = builder.arithmetic_extension(
+            F::NEG_ONE,
+            F::ONE,
+            sel(ROW_LOAD_QX),
+            phase(PHASE_QK),
+            sel(ROW_LOAD_QX),
+        );
+        yield_constr.constraint(builder, cs);
+        let mut phase_kept = in_verification;
+        for &(t, _) in PHASE_CHANGES {
+            phase_kept = builder.sub_extension(phase_kept, sel(t));
+        }
+        for p in 0..NUM_PHASES {
+            let diff = builder.sub_extension(nv[col_phase(p)], phase(p));
+            let cs = builder.mul_extension(phase_kept, diff);
+            yield_constr.constraint_transition(builder, cs);
+        }
+        for &(t, p) in PHASE_CHANGES {
+            let cs =
+                builder.arithmetic_extension(F::NEG_ONE, F::ONE, sel(t), nv[col_phase(p)], sel(t));
+            yield_constr.constraint_transition(builder, cs);
+        }
+        let add4 = sel(ROW_ADD4);
+        let last_step = lv[IS_LAST_STEP];
+        for &(p, next) in ADD4_SUCCESSORS {
+            let add4_phase = builder.mul_extension(add4, phase(p));
+            let cs = builder.arithmetic_extension(
+                F::NEG_ONE,
+                F::ONE,
+                add4_phase,
+                next_sel(next),
+                add4_phase,
+            );
+            yield_constr.constraint_transition(builder, cs);
+        }
+        let add4_ladder = builder.mul_extension(add4, phase(PHASE_LADDER));
+        let not_last = builder.sub_extension(one, last_step);
+        let add4_not_last = builder.mul_extension(add4_ladder, not_last);
+        let cs = builder.arithmetic_extension(
+            F::NEG_ONE,
+            F::ONE,
+            add4_not_last,
+            next_sel(ROW_DBL1),
+            add4_not_last,
+        );
+        yield_constr.constraint_transition(builder, cs);
+        let add4_last = builder.mul_extension(add4_ladder, last_step);
+        let cs = builder.arithmetic_extension(
+            F::NEG_ONE,
+            F::ONE,
+            add4_last,
+            next_sel(ROW_SET_C),
+            add4_last,
+        );
+        yield_constr.constraint_transition(builder, cs);
+
+        // 3. Ladder bits, read at DBL1 from the top limbs of U1 and U2.
+        for k in 0..4 {
+            binary(builder, lv[col_fsel(k)], yield_constr);
+        }
+        let sum_fsel = builder.add_many_extension((0..4).map(|k| lv[col_fsel(k)]));
+        let sum_fsel_cs = builder.sub_extension(one, sum_fsel);
+        yield_constr.constraint(builder, sum_fsel_cs);
+        let b1 = builder.add_extension(lv[col_fsel(1)], lv[col_fsel(3)]);
+        let b2 = builder.add_extension(lv[col_fsel(2)], lv[col_fsel(3)]);
+        let dbl1 = sel(ROW_DBL1);
+        let is_limb_end = lv[IS_LIMB_END];
+        let is_last_limb = lv[IS_LAST_LIMB];
+        binary(builder, is_limb_end, yield_constr);
+        binary(builder, is_last_limb, yield_constr);
+        let dbl1_end = builder.mul_extension(dbl1, is_limb_end);
+        let dbl1_not_end = builder.sub_extension(dbl1, dbl1_end);
+        let cs = builder.mul_extension(dbl1_end, lv[BIT]);
+        yield_constr.constraint(builder, cs);
+        let bit_inv = builder.mul_extension(lv[BIT], lv[BIT_INV]);
+        let cs =
+            builder.arithmetic_extension(F::NEG_ONE, F::ONE, dbl1_not_end, bit_inv, dbl1_not_end);
+        yield_constr.constraint(builder, cs);
+        let dbl1_last_limb = builder.mul_extension(dbl1, is_last_limb);
+        let dbl1_not_last_limb = builder.sub_extension(dbl1, dbl1_last_limb);
+        let cs = builder.mul_extension(dbl1_last_limb, lv[LIMB]);
+        yield_constr.constraint(builder, cs);
+        let limb_inv = builder.mul_extension(lv[LIMB], lv[LIMB_INV]);
+        let cs = builder.arithmetic_extension(
+            F::NEG_ONE,
+            F::ONE,
+            dbl1_not_last_limb,
+            limb_inv,
+            dbl1_not_last_limb,
+        );
+        yield_constr.constraint(builder, cs);
+        let last = builder.mul_extension(is_limb_end, is_last_limb);
+        let last_diff = builder.sub_extension(last_step, last);
+        let cs = builder.mul_extension(dbl1, last_diff);
+        yield_constr.constraint(builder, cs);
+        for (acc, b, u) in [(ACC1, b1, REG_U1), (ACC2, b2, REG_U2)] {
+            let double = builder.add_extension(lv[acc], lv[acc]);
+            let bits = builder.add_extension(double, b);
+            let diff = builder.sub_extension(bits, lv[col_reg(u, N_LIMBS - 1)]);
+            let cs = builder.mul_extension(dbl1_end, diff);
+            yield_constr.constraint(builder, cs);
+            let diff = builder.sub_extension(nv[acc], bits);
+            let cs = builder.mul_extension(dbl1_not_end, diff);
+            yield_constr.constraint_transition(builder, cs);
+            let cs = builder.mul_extension(dbl1_end, nv[acc]);
+            yield_constr.constraint_transition(builder, cs);
+        }
+        let top_bit =
+            builder.constant_extension(F::Extension::from_canonical_u64(LIMB_BITS as u64 - 1));
+        let top_limb =
+            builder.constant_extension(F::Extension::from_canonical_u64(N_LIMBS as u64 - 1));
+        let bit_diff = builder.sub_extension(nv[BIT], lv[BIT]);
+        let bit_decr = builder.add_extension(bit_diff, one);
+        let cs = builder.mul_extension(dbl1_not_end, bit_decr);
+        yield_constr.constraint_transition(builder, cs);
+        let bit_reset = builder.sub_extension(nv[BIT], top_bit);
+        let cs = builder.mul_extension(dbl1_end, bit_reset);
+        yield_constr.constraint_transition(builder, cs);
+        let limb_diff = builder.sub_extension(nv[LIMB], lv[LIMB]);
+        let limb_decr = builder.add_extension(limb_diff, one);
+        let cs = builder.mul_extension(dbl1_end, limb_decr);
+        yield_constr.constraint_transition(builder, cs);
+        let cs = builder.mul_extension(dbl1_not_end, limb_diff);
+        yield_constr.constraint_transition(builder, cs);
+        // SAVE_GQK starts the ladder from the top bit.
+        let save_gqk = sel(ROW_SAVE_GQK);
+        let bit_start = builder.sub_extension(nv[BIT], top_bit);
+        let cs = builder.mul_extension(save_gqk, bit_start);
+        yield_constr.constraint_transition(builder, cs);
+        let limb_start = builder.sub_extension(nv[LIMB], top_limb);
+        let cs = builder.mul_extension(save_gqk, limb_start);
+        yield_constr.constraint_transition(builder, cs);
+        for acc in [ACC1, ACC2] {
+            let cs = builder.mul_extension(save_gqk, nv[acc]);
+            yield_constr.constraint_transition(builder, cs);
+        }
+        let counters_kept = builder.sub_extension(in_verification, dbl1);
+        let counters_kept = builder.sub_extension(counters_kept, save_gqk);
+        for c in [ACC1, ACC2, BIT, LIMB] {
+            let diff = builder.sub_extension(nv[c], lv[c]);
+            let cs = builder.mul_extension(counters_kept, diff);
+            yield_constr.constraint_transition(builder, cs);
+        }
+        let not_next_dbl1 = builder.sub_extension(one, next_sel(ROW_DBL1));
+        let last_kept = builder.mul_extension(in_verification, not_next_dbl1);
+        let last_diff = builder.sub_extension(nv[IS_LAST_STEP], last_step);
+        let cs = builder.mul_extension(last_kept, last_diff);
+        yield_constr.constraint_transition(builder, cs);
+
+        // 4. Registers.
+        let constant = |builder: &mut CircuitBuilder<F, D>, v: u64| {
+            builder.constant_extension(F::Extension::from_canonical_u64(v))
+        };
+        for reg in 0..NUM_REGS {
+            for i in 0..N_LIMBS {
+                let current = lv[col_reg(reg, i)];
+                let next_diff = builder.sub_extension(nv[col_reg(reg, i)], current);
+                let mut cs = builder.mul_extension(in_verification, next_diff);
+                for &(t, _, source) in REGISTER_WRITES.iter().filter(|w| w.1 == reg) {
+                    let value = match source {
+                        Source::New => lv[col_new(i)],
+                        Source::Reg(r) => lv[col_reg(r, i)],
+                        Source::Const(v) => constant(builder, limb(&v, i)),
+                        Source::LadderPoint { y } => {
+                            let (k, gk) = if y {
+                                (LADDER_OFFSET.y.0, GENERATOR_PLUS_OFFSET.y.0)
+                            } else {
+                                (LADDER_OFFSET.x.0, GENERATOR_PLUS_OFFSET.x.0)
+                            };
+                            let (qk, gqk) = if y {
+                                (REG_QKY, REG_GQKY)
+                            } else {
+                                (REG_QKX, REG_GQKX)
+                            };
+                            let value = builder.mul_const_extension(
+                                F::from_canonical_u64(limb(&k, i)),
+                                lv[col_fsel(0)],
+                            );
+                            let value = builder.arithmetic_extension(
+                                F::from_canonical_u64(limb(&gk, i)),
+                                F::ONE,
+                                lv[col_fsel(1)],
+                                one,
+                                value,
+                            );
+                            let value = builder.mul_add_extension(
+                                lv[col_fsel(2)],
+                                lv[col_reg(qk, i)],
+                                value,
+                            );
+                            builder.mul_add_extension(lv[col_fsel(3)], lv[col_reg(gqk, i)], value)
+                        }
+                        Source::ShiftUp => {
+                            let below = if i == 0 {
+                                builder.zero_extension()
+                            } else {
+                                lv[col_reg(reg, i - 1)]
+                            };
+                            // current + is_limb_end * (below - current)
+                            let diff = builder.sub_extension(below, current);
+                            builder.mul_add_extension(is_limb_end, diff, current)
+                        }
+                    };
+                    let diff = builder.sub_extension(value, current);
+                    let written = builder.mul_extension(sel(t), diff);
+                    cs = builder.sub_extension(cs, written);
+                }
+                yield_constr.constraint_transition(builder, cs);
+            }
+        }
+
+        // 5. The relation of the row, carried 32 bits at a time.
+        let e = relation_coefficients_circuit(builder, lv);
+        let shift = F::from_canonical_u64(1 << LIMB_BITS);
+        let carry_base = F::from_canonical_u64(1 << (2 * LIMB_BITS));
+        let carry_offset = constant(builder, 1 << (2 * LIMB_BITS - 1));
+        let mut carry_in = builder.zero_extension();
+        for j in 0..N_LIMBS {
+            let group = builder.arithmetic_extension(shift, F::ONE, e[2 * j + 1], one, e[2 * j]);
+            let group = builder.add_extension(group, carry_in);
+            if j < N_CARRIES {
+                let carry = builder.arithmetic_extension(
+                    shift,
+                    F::ONE,
+                    lv[col_carry_hi(j)],
+                    one,
+                    lv[col_carry_lo(j)],
+                );
+                let carry = builder.sub_extension(carry, carry_offset);
+                let cs = builder.arithmetic_extension(-carry_base, F::ONE, carry, one, group);
+                yield_constr.constraint(builder, cs);
+                carry_in = carry;
+            } else {
+                yield_constr.constraint(builder, group);
+            }
+        }
+    }
+
+    fn constraint_degree(&self) -> usize {
+        4
+    }
+}
+
+// Get the column info for Cross_Lookup<Cpu_table, Ecdsa_table>, the flag is
+// whether the verification ends with FIN_EQ.
+pub fn ctl_data_with_cpu<F: Field>() -> Vec<Column<F>> {
+    Column::singles([CLK, ADDR, col_sel(ROW_FIN_EQ)]).collect_vec()
+}
+
+pub fn ctl_filter_with_cpu<F: Field>() -> Column<F> {
+    Column::sum([col_sel(ROW_FIN_EQ), col_sel(ROW_FIN_NE)])
+}
+
+// (clk, op, addr, value) of the memory access reading the u32 word `i` of
+// a LOAD row, see `memory_stark::ctl_data`.
+pub fn ctl_data_with_memory<F: Field>(i: usize) -> Vec<Column<F>> {
+    debug_assert!(i < ECDSA_WORD_NUM);
+    let offsets = LOADS
+        .iter()
+        .filter(|(_, index)| *index > 0)
+        .map(|&(t, index)| (col_sel(t), F::from_canonical_usize(index * ECDSA_WORD_NUM)));
+    vec![
+        Column::single(CLK),
+        Column::constant(F::from_canonical_u64(1 << Opcode::ECDSA as u64)),
+        Column::linear_combination_with_constant(
+            std::iter::once((ADDR, F::ONE)).chain(offsets),
+            F::from_canonical_usize(i),
+        ),
+        Column::linear_combination([
+            (col_new(2 * i), F::ONE),
+            (col_new(2 * i + 1), F::from_canonical_u64(1 << LIMB_BITS)),
+        ]),
+    ]
+}
+
+pub fn ctl_filter_with_memory<F: Field>() -> Column<F> {
+    Column::sum(LOADS.map(|(t, _)| col_sel(t)))
+}
+
+// Get the column info for Cross_Lookup<Ecdsa_table, Rangecheck_Fixed_table>,
+// `col` is one of `range_checked_columns`.
+pub fn ctl_data_with_rangecheck_fixed<F: Field>(col: usize) -> Vec<Column<F>> {
+    vec![Column::single(col)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generation::builtin::generate_builtins_ecdsa_trace;
+    use core::trace::trace::Trace;
+    use executor::coprocessor::ecdsa::EcdsaInput;
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::field::secp256k1_scalar::Secp256K1Scalar;
+    use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+
+    #[test]
+    fn test_ecdsa_stark() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type S = EcdsaStark<F, D>;
+
+        let stark = S::default();
+
+        let z = [0x1234, 0x5678, 0x9abc, 0xdef0];
+        let d = Secp256K1Scalar::from_canonical_u64(0xdead_beef);
+        let k = Secp256K1Scalar::from_canonical_u64(0x1337);
+        let signed = EcdsaInput::sign(z, &d, &k).unwrap();
+        let mut tampered = signed;
+        tampered.z[0] += 1;
+
+        let mut trace = Trace::default();
+        for (i, input) in [signed, tampered].iter().enumerate() {
+            let flag = input.verify().unwrap();
+            assert_eq!(flag, i == 0);
+            trace.insert_ecdsa(
+                i as u32 + 1,
+                F::from_canonical_u64(0x100),
+                input.to_words(),
+                flag,
+            );
+        }
+        let trace_rows = generate_builtins_ecdsa_trace::<F>(&trace.builtin_ecdsa);
+        assert_eq!(trace_rows.len(), 4096);
+        let fin = |verification: usize| &trace_rows[(verification + 1) * ROWS_PER_VERIFICATION - 1];
+        assert_eq!(fin(0)[col_sel(ROW_FIN_EQ)], F::ONE);
+        assert_eq!(fin(1)[col_sel(ROW_FIN_NE)], F::ONE);
+        assert_eq!(trace_rows[4095][col_sel(ROW_PAD)], F::ONE);
+
+        for i in 0..trace_rows.len() {
+            let vars = StarkEvaluationVars {
+                local_values: &trace_rows[i],
+                next_values: &trace_rows[(i + 1) % trace_rows.len()],
+            };
+
+            let mut constraint_consumer = ConstraintConsumer::new(
+                vec![GoldilocksField(2), GoldilocksField(3), GoldilocksField(5)],
+                GoldilocksField::from_bool(i != trace_rows.len() - 1),
+                GoldilocksField::from_bool(i == 0),
+                GoldilocksField::from_bool(i == trace_rows.len() - 1),
+            );
+            stark.eval_packed_generic(vars, &mut constraint_consumer);
+
+            for &acc in &constraint_consumer.constraint_accs {
+                assert_eq!(acc, GoldilocksField::ZERO, "row {i}");
+            }
+        }
+    }
+}
@@ -0,0 +1,102 @@
+/* Keccak Table, one round of Keccak-f[1600] per row, 24 rows per permutation:
++----------+-------------+--------+-----------+------------+-------------+--------------+----------------+-------------+
+| step[24] | preimage[50]| a[50]  | c[5][64]  | c'[5][64]  | a'[25][64]  | a''[50]      | a''[0][0] bits | a'''[0][0]  |
++----------+-------------+--------+-----------+------------+-------------+--------------+----------------+-------------+
+
+The state is 25 lanes `x + 5 * y` of 64 bits, each lane is two u32 limbs,
+least significant first, as the words of `executor::coprocessor::keccak`.
+`step` is the one-hot round of the row, `a` the state at the start of the
+round and `preimage` the state at the start of the permutation.
+
+theta is checked on bits: c[x] is the xor of the column x of a, c'[x] is
+    c'[x][z] = c[x][z] ^ c[x - 1][z] ^ c[x + 1][z - 1]
+and a'[x][y] = a[x][y] ^ c[x] ^ c'[x]. The limbs of a are rebuilt from the
+bits of a', c and c', and the parity of the column x of a' is c'[x], which
+together make c the xor of the column.
+rho and pi only move bits, chi gives the limbs of a'' from the bits of a',
+iota xors the round constant into the bits of a''[0][0] as a'''[0][0].
+The next round starts from a'' with a'''[0][0] for the first lane.
+
+The output of a permutation is a'' and a'''[0][0] of its last row. Padding
+rows are all zero, which is a valid row of no round.
+*/
+
+pub(crate) const NUM_ROUNDS: usize = 24;
+pub(crate) const NUM_LANES: usize = 25;
+pub(crate) const LANE_BITS: usize = 64;
+pub(crate) const LIMB_BITS: usize = 32;
+// u32 limbs of the state
+pub(crate) const NUM_LIMBS: usize = 2 * NUM_LANES;
+
+pub(crate) const STEP_START: usize = 0;
+pub(crate) const PREIMAGE_START: usize = STEP_START + NUM_ROUNDS;
+pub(crate) const A_START: usize = PREIMAGE_START + NUM_LIMBS;
+pub(crate) const C_START: usize = A_START + NUM_LIMBS;
+pub(crate) const C_PRIME_START: usize = C_START + 5 * LANE_BITS;
+pub(crate) const A_PRIME_START: usize = C_PRIME_START + 5 * LANE_BITS;
+pub(crate) const A_PRIME_PRIME_START: usize = A_PRIME_START + NUM_LANES * LANE_BITS;
+pub(crate) const A_PRIME_PRIME_0_0_BITS_START: usize = A_PRIME_PRIME_START + NUM_LIMBS;
+pub(crate) const A_PRIME_PRIME_PRIME_0_0_START: usize = A_PRIME_PRIME_0_0_BITS_START + LANE_BITS;
+
+pub(crate) const COL_NUM_KECCAK: usize = A_PRIME_PRIME_PRIME_0_0_START + 2; // 2480
+
+pub(crate) fn col_step(round: usize) -> usize {
+    debug_assert!(round < NUM_ROUNDS);
+    STEP_START + round
+}
+
+// The limb `part` of lane (x, y).
+pub(crate) fn limb(x: usize, y: usize, part: usize) -> usize {
+    debug_assert!(x < 5 && y < 5 && part < 2);
+    2 * (x + 5 * y) + part
+}
+
+pub(crate) fn col_preimage(i: usize) -> usize {
+    debug_assert!(i < NUM_LIMBS);
+    PREIMAGE_START + i
+}
+
+pub(crate) fn col_a(i: usize) -> usize {
+    debug_assert!(i < NUM_LIMBS);
+    A_START + i
+}
+
+pub(crate) fn col_c(x: usize, z: usize) -> usize {
+    debug_assert!(x < 5 && z < LANE_BITS);
+    C_START + x * LANE_BITS + z
+}
+
+pub(crate) fn col_c_prime(x: usize, z: usize) -> usize {
+    debug_assert!(x < 5 && z < LANE_BITS);
+    C_PRIME_START + x * LANE_BITS + z
+}
+
+pub(crate) fn col_a_prime(x: usize, y: usize, z: usize) -> usize {
+    debug_assert!(x < 5 && y < 5 && z < LANE_BITS);
+    A_PRIME_START + (x + 5 * y) * LANE_BITS + z
+}
+
+pub(crate) fn col_a_prime_prime(i: usize) -> usize {
+    debug_assert!(i < NUM_LIMBS);
+    A_PRIME_PRIME_START + i
+}
+
+pub(crate) fn col_a_prime_prime_0_0_bit(z: usize) -> usize {
+    debug_assert!(z < LANE_BITS);
+    A_PRIME_PRIME_0_0_BITS_START + z
+}
+
+pub(crate) fn col_a_prime_prime_prime_0_0(part: usize) -> usize {
+    debug_assert!(part < 2);
+    A_PRIME_PRIME_PRIME_0_0_START + part
+}
+
+// Limb `i` of the state at the end of the round.
+pub(crate) fn col_output(i: usize) -> usize {
+    debug_assert!(i < NUM_LIMBS);
+    if i < 2 {
+        col_a_prime_prime_prime_0_0(i)
+    } else {
+        col_a_prime_prime(i)
+    }
+}
@@ -0,0 +1,413 @@
+use crate::builtins::keccak::columns::*;
+use executor::coprocessor::keccak::{ROTATIONS, ROUND_CONSTANTS};
+use itertools::Itertools;
+
+use crate::stark::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+use crate::stark::cross_table_lookup::Column;
+use crate::stark::stark::Stark;
+use crate::stark::vars::{StarkEvaluationTargets, StarkEvaluationVars};
+use plonky2::field::extension::{Extendable, FieldExtension};
+use plonky2::field::packed::PackedField;
+use plonky2::field::types::Field;
+use plonky2::hash::hash_types::RichField;
+use plonky2::iop::ext_target::ExtensionTarget;
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use std::marker::PhantomData;
+
+#[derive(Copy, Clone, Default)]
+pub struct KeccakStark<F, const D: usize> {
+    pub _phantom: PhantomData<F>,
+}
+
+// The column of bit z of lane (x, y) after rho and pi.
+pub(crate) fn col_b(x: usize, y: usize, z: usize) -> usize {
+    let a_x = (x + 3 * y) % 5;
+    let rotation = ROTATIONS[a_x][x] as usize;
+    col_a_prime(a_x, x, (z + LANE_BITS - rotation) % LANE_BITS)
+}
+
+// Bit z of the round constant of the row, zero on padding rows.
+fn rc_bit<P: PackedField>(lv: &[P], z: usize) -> P {
+    (0..NUM_ROUNDS)
+        .filter(|&r| ROUND_CONSTANTS[r] >> z & 1 == 1)
+        .map(|r| lv[col_step(r)])
+        .sum()
+}
+
+fn xor<P: PackedField>(a: P, b: P) -> P {
+    a + b - (a * b).doubles()
+}
+
+fn xor3<P: PackedField>(a: P, b: P, c: P) -> P {
+    xor(xor(a, b), c)
+}
+
+fn andn<P: PackedField>(a: P, b: P) -> P {
+    (P::ONES - a) * b
+}
+
+fn rc_bit_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    lv: &[ExtensionTarget<D>],
+    z: usize,
+) -> ExtensionTarget<D> {
+    let steps = (0..NUM_ROUNDS)
+        .filter(|&r| ROUND_CONSTANTS[r] >> z & 1 == 1)
+        .map(|r| lv[col_step(r)])
+        .collect_vec();
+    builder.add_many_extension(steps)
+}
+
+fn xor_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    a: ExtensionTarget<D>,
+    b: ExtensionTarget<D>,
+) -> ExtensionTarget<D> {
+    let sum = builder.add_extension(a, b);
+    builder.arithmetic_extension(-F::TWO, F::ONE, a, b, sum)
+}
+
+fn xor3_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    a: ExtensionTarget<D>,
+    b: ExtensionTarget<D>,
+    c: ExtensionTarget<D>,
+) -> ExtensionTarget<D> {
+    let ab = xor_circuit(builder, a, b);
+    xor_circuit(builder, ab, c)
+}
+
+fn andn_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    a: ExtensionTarget<D>,
+    b: ExtensionTarget<D>,
+) -> ExtensionTarget<D> {
+    builder.arithmetic_extension(F::NEG_ONE, F::ONE, a, b, b)
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for KeccakStark<F, D> {
+    const COLUMNS: usize = COL_NUM_KECCAK;
+
+    fn eval_packed_generic<FE, P, const D2: usize>(
+        &self,
+        vars: StarkEvaluationVars<FE, P, { COL_NUM_KECCAK }>,
+        yield_constr: &mut ConstraintConsumer<P>,
+    ) where
+        FE: FieldExtension<D2, BaseField = F>,
+        P: PackedField<Scalar = FE>,
+    {
+        let lv = vars.local_values;
+        let nv = vars.next_values;
+        let pow = |z: usize| FE::from_canonical_u64(1 << (z % LIMB_BITS));
+
+        // One round per row, the rounds of a permutation follow each other from
+        // the first one.
+        let mut step_sum = P::ZEROS;
+        for r in 0..NUM_ROUNDS {
+            let step = lv[col_step(r)];
+            yield_constr.constraint(step * (P::ONES - step));
+            step_sum += step;
+        }
+        yield_constr.constraint(step_sum * (P::ONES - step_sum));
+        for r in 1..NUM_ROUNDS {
+            yield_constr.constraint_first_row(lv[col_step(r)]);
+        }
+        for r in 0..NUM_ROUNDS - 1 {
+            yield_constr.constraint_transition(nv[col_step(r + 1)] - lv[col_step(r)]);
+        }
+
+        // The first round starts from the preimage, the next ones from the output
+        // of the previous round.
+        let step_0 = lv[col_step(0)];
+        let not_last_round = step_sum - lv[col_step(NUM_ROUNDS - 1)];
+        for i in 0..NUM_LIMBS {
+            yield_constr.constraint(step_0 * (lv[col_a(i)] - lv[col_preimage(i)]));
+            yield_constr.constraint_transition(
+                not_last_round * (nv[col_preimage(i)] - lv[col_preimage(i)]),
+            );
+            yield_constr.constraint_transition(not_last_round * (nv[col_a(i)] - lv[col_output(i)]));
+        }
+
+        // theta
+        for x in 0..5 {
+            for z in 0..LANE_BITS {
+                let c = lv[col_c(x, z)];
+                yield_constr.constraint(c * (P::ONES - c));
+                let c_left = lv[col_c((x + 4) % 5, z)];
+                let c_right = lv[col_c((x + 1) % 5, (z + LANE_BITS - 1) % LANE_BITS)];
+                yield_constr.constraint(lv[col_c_prime(x, z)] - xor3(c, c_left, c_right));
+
+                let sum: P = (0..5).map(|y| lv[col_a_prime(x, y, z)]).sum();
+                let diff = sum - lv[col_c_prime(x, z)];
+                yield_constr.constraint(
+                    diff * (diff - FE::from_canonical_u64(2)) * (diff - FE::from_canonical_u64(4)),
+                );
+            }
+        }
+        for x in 0..5 {
+            for y in 0..5 {
+                for z in 0..LANE_BITS {
+                    let bit = lv[col_a_prime(x, y, z)];
+                    yield_constr.constraint(bit * (P::ONES - bit));
+                }
+                for part in 0..2 {
+                    let computed: P = (part * LIMB_BITS..(part + 1) * LIMB_BITS)
+                        .map(|z| {
+                            xor3(
+                                lv[col_a_prime(x, y, z)],
+                                lv[col_c(x, z)],
+                                lv[col_c_prime(x, z)],
+                            ) * pow(z)
+                        })
+                        .sum();
+                    yield_constr.constraint(lv[col_a(limb(x, y, part))] - computed);
+                }
+            }
+        }
+
+        // rho and pi are in `col_b`, chi
+        let chi = |x: usize, y: usize, z: usize| {
+            xor(
+                lv[col_b(x, y, z)],
+                andn(lv[col_b((x + 1) % 5, y, z)], lv[col_b((x + 2) % 5, y, z)]),
+            )
+        };
+        for x in 0..5 {
+            for y in 0..5 {
+                for part in 0..2 {
+                    let range = part * LIMB_BITS..(part + 1) * LIMB_BITS;
+                    let computed: P = if (x, y) == (0, 0) {
+                        for z in range.clone() {
+                            yield_constr
+                                .constraint(lv[col_a_prime_prime_0_0_bit(z)] - chi(x, y, z));
+                        }
+                        range
+                            .map(|z| lv[col_a_prime_prime_0_0_bit(z)] * pow(z))
+                            .sum()
+                    } else {
+                        range.map(|z| chi(x, y, z) * pow(z)).sum()
+                    };
+                    yield_constr.constraint(lv[col_a_prime_prime(limb(x, y, part))] - computed);
+                }
+            }
+        }
+
+        // iota
+        for part in 0..2 {
+            let computed: P = (part * LIMB_BITS..(part + 1) * LIMB_BITS)
+                .map(|z| xor(lv[col_a_prime_prime_0_0_bit(z)], rc_bit(lv, z)) * pow(z))
+                .sum();
+            yield_constr.constraint(lv[col_a_prime_prime_prime_0_0(part)] - computed);
+        }
+    }
+
+    fn eval_ext_circuit(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        vars: StarkEvaluationTargets<D, { COL_NUM_KECCAK }>,
+        yield_constr: &mut RecursiveConstraintConsumer<F, D>,
+    ) {
+        let lv = vars.local_values;
+        let nv = vars.next_values;
+        let one = builder.one_extension();
+        let pow = |z: usize| F::from_canonical_u64(1 << (z % LIMB_BITS));
+
+        let mut step_sum = builder.zero_extension();
+        for r in 0..NUM_ROUNDS {
+            let step = lv[col_step(r)];
+            let one_m_step = builder.sub_extension(one, step);
+            let step_cs = builder.mul_extension(step, one_m_step);
+            yield_constr.constraint(builder, step_cs);
+            step_sum = builder.add_extension(step_sum, step);
+        }
+        let one_m_step_sum = builder.sub_extension(one, step_sum);
+        let step_sum_cs = builder.mul_extension(step_sum, one_m_step_sum);
+        yield_constr.constraint(builder, step_sum_cs);
+        for r in 1..NUM_ROUNDS {
+            yield_constr.constraint_first_row(builder, lv[col_step(r)]);
+        }
+        for r in 0..NUM_ROUNDS - 1 {
+            let step_cs = builder.sub_extension(nv[col_step(r + 1)], lv[col_step(r)]);
+            yield_constr.constraint_transition(builder, step_cs);
+        }
+
+        let step_0 = lv[col_step(0)];
+        let not_last_round = builder.sub_extension(step_sum, lv[col_step(NUM_ROUNDS - 1)]);
+        for i in 0..NUM_LIMBS {
+            let start = builder.sub_extension(lv[col_a(i)], lv[col_preimage(i)]);
+            let start_cs = builder.mul_extension(step_0, start);
+            yield_constr.constraint(builder, start_cs);
+            let preimage = builder.sub_extension(nv[col_preimage(i)], lv[col_preimage(i)]);
+            let preimage_cs = builder.mul_extension(not_last_round, preimage);
+            yield_constr.constraint_transition(builder, preimage_cs);
+            let next = builder.sub_extension(nv[col_a(i)], lv[col_output(i)]);
+            let next_cs = builder.mul_extension(not_last_round, next);
+            yield_constr.constraint_transition(builder, next_cs);
+        }
+
+        // theta
+        for x in 0..5 {
+            for z in 0..LANE_BITS {
+                let c = lv[col_c(x, z)];
+                let one_m_c = builder.sub_extension(one, c);
+                let c_cs = builder.mul_extension(c, one_m_c);
+                yield_constr.constraint(builder, c_cs);
+                let c_left = lv[col_c((x + 4) % 5, z)];
+                let c_right = lv[col_c((x + 1) % 5, (z + LANE_BITS - 1) % LANE_BITS)];
+                let c_prime = xor3_circuit(builder, c, c_left, c_right);
+                let c_prime_cs = builder.sub_extension(lv[col_c_prime(x, z)], c_prime);
+                yield_constr.constraint(builder, c_prime_cs);
+
+                let sum = builder.add_many_extension((0..5).map(|y| lv[col_a_prime(x, y, z)]));
+                let diff = builder.sub_extension(sum, lv[col_c_prime(x, z)]);
+                let diff_m_2 = builder.add_const_extension(diff, -F::from_canonical_u64(2));
+                let diff_m_4 = builder.add_const_extension(diff, -F::from_canonical_u64(4));
+                let diff_cs = builder.mul_many_extension([diff, diff_m_2, diff_m_4]);
+                yield_constr.constraint(builder, diff_cs);
+            }
+        }
+        for x in 0..5 {
+            for y in 0..5 {
+                for z in 0..LANE_BITS {
+                    let bit = lv[col_a_prime(x, y, z)];
+                    let one_m_bit = builder.sub_extension(one, bit);
+                    let bit_cs = builder.mul_extension(bit, one_m_bit);
+                    yield_constr.constraint(builder, bit_cs);
+                }
+                for part in 0..2 {
+                    let mut computed = builder.zero_extension();
+                    for z in part * LIMB_BITS..(part + 1) * LIMB_BITS {
+                        let bit = xor3_circuit(
+                            builder,
+                            lv[col_a_prime(x, y, z)],
+                            lv[col_c(x, z)],
+                            lv[col_c_prime(x, z)],
+                        );
+                        computed = builder.mul_const_add_extension(pow(z), bit, computed);
+                    }
+                    let a_cs = builder.sub_extension(lv[col_a(limb(x, y, part))], computed);
+                    yield_constr.constraint(builder, a_cs);
+                }
+            }
+        }
+
+        // chi
+        let chi = |builder: &mut CircuitBuilder<F, D>, x: usize, y: usize, z: usize| {
+            let andn = andn_circuit(
+                builder,
+                lv[col_b((x + 1) % 5, y, z)],
+                lv[col_b((x + 2) % 5, y, z)],
+            );
+            xor_circuit(builder, lv[col_b(x, y, z)], andn)
+        };
+        for x in 0..5 {
+            for y in 0..5 {
+                for part in 0..2 {
+                    let mut computed = builder.zero_extension();
+                    for z in part * LIMB_BITS..(part + 1) * LIMB_BITS {
+                        let bit = if (x, y) == (0, 0) {
+                            let bit = lv[col_a_prime_prime_0_0_bit(z)];
+                            let chi_bit = chi(builder, x, y, z);
+                            let bit_cs = builder.sub_extension(bit, chi_bit);
+                            yield_constr.constraint(builder, bit_cs);
+                            bit
+                        } else {
+                            chi(builder, x, y, z)
+                        };
+                        computed = builder.mul_const_add_extension(pow(z), bit, computed);
+                    }
+                    let a_cs =
+                        builder.sub_extension(lv[col_a_prime_prime(limb(x, y, part))], computed);
+                    yield_constr.constraint(builder, a_cs);
+                }
+            }
+        }
+
+        // iota
+        for part in 0..2 {
+            let mut computed = builder.zero_extension();
+            for z in part * LIMB_BITS..(part + 1) * LIMB_BITS {
+                let rc = rc_bit_circuit(builder, lv, z);
+                let bit = xor_circuit(builder, lv[col_a_prime_prime_0_0_bit(z)], rc);
+                computed = builder.mul_const_add_extension(pow(z), bit, computed);
+            }
+            let iota_cs = builder.sub_extension(lv[col_a_prime_prime_prime_0_0(part)], computed);
+            yield_constr.constraint(builder, iota_cs);
+        }
+    }
+
+    fn constraint_degree(&self) -> usize {
+        3
+    }
+}
+
+// The state before and after the permutation on the last round of a
+// permutation, for Cross_Lookup<KeccakSponge_table, Keccak_table>.
+pub fn ctl_data_with_sponge<F: Field>() -> Vec<Column<F>> {
+    Column::singles(
+        (0..NUM_LIMBS)
+            .map(col_preimage)
+            .chain((0..NUM_LIMBS).map(col_output)),
+    )
+    .collect_vec()
+}
+
+pub fn ctl_filter_with_sponge<F: Field>() -> Column<F> {
+    Column::single(col_step(NUM_ROUNDS - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generation::builtin::generate_keccak_trace;
+    use executor::coprocessor::keccak::{keccak_f, lanes_to_words, words_to_lanes};
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+
+    #[test]
+    fn test_keccak_stark() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type S = KeccakStark<F, D>;
+
+        let stark = S::default();
+
+        let inputs = [
+            [0; NUM_LIMBS],
+            std::array::from_fn(|i| (i as u32 + 1).wrapping_mul(0x9e37_79b9)),
+        ];
+        let trace_rows = generate_keccak_trace::<F>(&inputs);
+        assert_eq!(trace_rows.len(), 64);
+        for (i, input) in inputs.iter().enumerate() {
+            let mut lanes = words_to_lanes(input);
+            keccak_f(&mut lanes);
+            let last = &trace_rows[(i + 1) * NUM_ROUNDS - 1];
+            assert_eq!(last[col_step(NUM_ROUNDS - 1)], F::ONE);
+            for (j, word) in lanes_to_words(&lanes).iter().enumerate() {
+                assert_eq!(last[col_output(j)], F::from_canonical_u32(*word));
+            }
+        }
+
+        let last = trace_rows.len() - 1;
+        for i in 0..trace_rows.len() {
+            let vars = StarkEvaluationVars {
+                local_values: &trace_rows[i],
+                next_values: &trace_rows[(i + 1) % trace_rows.len()],
+            };
+
+            let flag = |b: bool| if b { F::ONE } else { F::ZERO };
+            let mut constraint_consumer = ConstraintConsumer::new(
+                vec![GoldilocksField(2), GoldilocksField(3), GoldilocksField(5)],
+                flag(i != last),
+                flag(i == 0),
+                flag(i == last),
+            );
+            stark.eval_packed_generic(vars, &mut constraint_consumer);
+
+            for &acc in &constraint_consumer.constraint_accs {
+                assert_eq!(acc, GoldilocksField::ZERO);
+            }
+        }
+    }
+}
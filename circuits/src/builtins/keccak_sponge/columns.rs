@@ -0,0 +1,68 @@
+pub(crate) use executor::coprocessor::keccak::{RATE_BYTES, RATE_WORDS, STATE_WORDS};
+
+/* Keccak Sponge Table, one absorbed block per row:
++---------+----------+----------+-----+------------+-------------+-----+----------------+
+| is_full | is_final | is_first | clk | input_addr | output_addr | len | absorbed_words |
++---------+----------+----------+-----+------------+-------------+-----+----------------+
++--------------------+-----------+--------------------+------------+-------------------+-----------+
+| is_final_len[136]  | block[34] | original_state[50] | xored[34]  | updated_state[50] | tail_word |
++--------------------+-----------+--------------------+------------+-------------------+-----------+
+
+A keccak instruction reads the length in bytes at `input_addr` and the
+bytes packed in u32 words, little endian, from `input_addr + 1`. Its rows
+are the full blocks of 136 bytes and a final block with the rest of the
+input and the padding, `absorbed_words` words are read before the block.
+`is_final_len` is the one-hot length in bytes of the input in the final
+block, `block` the words read from memory, zero past the input, and
+`tail_word` the word of the final block which is only partly input, zero
+if there is none.
+
+The rate of `original_state` is xored with the padded block into `xored`
+by the bitwise table, the keccak table permutes `xored` and the capacity of
+`original_state` into `updated_state`, which is the `original_state` of the
+next block. The digest is the first 8 words of the last `updated_state`.
+
+Padding rows are all zero.
+*/
+
+pub(crate) const IS_FULL: usize = 0;
+pub(crate) const IS_FINAL: usize = IS_FULL + 1;
+pub(crate) const IS_FIRST: usize = IS_FINAL + 1;
+pub(crate) const CLK: usize = IS_FIRST + 1;
+pub(crate) const INPUT_ADDR: usize = CLK + 1;
+pub(crate) const OUTPUT_ADDR: usize = INPUT_ADDR + 1;
+pub(crate) const LEN: usize = OUTPUT_ADDR + 1;
+pub(crate) const ABSORBED_WORDS: usize = LEN + 1;
+pub(crate) const IS_FINAL_LEN_START: usize = ABSORBED_WORDS + 1;
+pub(crate) const BLOCK_START: usize = IS_FINAL_LEN_START + RATE_BYTES;
+pub(crate) const ORIGINAL_STATE_START: usize = BLOCK_START + RATE_WORDS;
+pub(crate) const XORED_START: usize = ORIGINAL_STATE_START + STATE_WORDS;
+pub(crate) const UPDATED_STATE_START: usize = XORED_START + RATE_WORDS;
+pub(crate) const TAIL_WORD: usize = UPDATED_STATE_START + STATE_WORDS;
+
+pub(crate) const COL_NUM_KECCAK_SPONGE: usize = TAIL_WORD + 1; // 313
+
+pub(crate) fn col_is_final_len(i: usize) -> usize {
+    debug_assert!(i < RATE_BYTES);
+    IS_FINAL_LEN_START + i
+}
+
+pub(crate) fn col_block(j: usize) -> usize {
+    debug_assert!(j < RATE_WORDS);
+    BLOCK_START + j
+}
+
+pub(crate) fn col_original_state(i: usize) -> usize {
+    debug_assert!(i < STATE_WORDS);
+    ORIGINAL_STATE_START + i
+}
+
+pub(crate) fn col_xored(j: usize) -> usize {
+    debug_assert!(j < RATE_WORDS);
+    XORED_START + j
+}
+
+pub(crate) fn col_updated_state(i: usize) -> usize {
+    debug_assert!(i < STATE_WORDS);
+    UPDATED_STATE_START + i
+}
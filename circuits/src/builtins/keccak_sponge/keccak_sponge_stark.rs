@@ -0,0 +1,404 @@
+use crate::builtins::keccak_sponge::columns::*;
+use core::program::instruction::Opcode;
+use core::trace::trace::{BitwiseOperation, KECCAK_OUTPUT_NUM};
+use itertools::Itertools;
+
+use crate::stark::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+use crate::stark::cross_table_lookup::Column;
+use crate::stark::stark::Stark;
+use crate::stark::vars::{StarkEvaluationTargets, StarkEvaluationVars};
+use plonky2::field::extension::{Extendable, FieldExtension};
+use plonky2::field::packed::PackedField;
+use plonky2::field::types::Field;
+use plonky2::hash::hash_types::RichField;
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use std::marker::PhantomData;
+
+#[derive(Copy, Clone, Default)]
+pub struct KeccakSpongeStark<F, const D: usize> {
+    pub _phantom: PhantomData<F>,
+}
+
+// The lengths of a final block which reads its word `j`, a full block reads
+// every word.
+fn read_bytes(j: usize) -> std::ops::Range<usize> {
+    4 * j + 1..RATE_BYTES
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for KeccakSpongeStark<F, D> {
+    const COLUMNS: usize = COL_NUM_KECCAK_SPONGE;
+
+    fn eval_packed_generic<FE, P, const D2: usize>(
+        &self,
+        vars: StarkEvaluationVars<FE, P, { COL_NUM_KECCAK_SPONGE }>,
+        yield_constr: &mut ConstraintConsumer<P>,
+    ) where
+        FE: FieldExtension<D2, BaseField = F>,
+        P: PackedField<Scalar = FE>,
+    {
+        let lv = vars.local_values;
+        let nv = vars.next_values;
+
+        let is_full = lv[IS_FULL];
+        let is_final = lv[IS_FINAL];
+        let is_first = lv[IS_FIRST];
+        let is_real = is_full + is_final;
+        for flag in [is_full, is_final, is_first, is_real] {
+            yield_constr.constraint(flag * (P::ONES - flag));
+        }
+        yield_constr.constraint(is_first * (P::ONES - is_real));
+
+        // A hash starts on the first row or after a final block, full blocks are
+        // followed by a block of the same hash and padding rows by padding rows.
+        let next_is_real = nv[IS_FULL] + nv[IS_FINAL];
+        yield_constr.constraint_first_row(is_first - is_real);
+        yield_constr.constraint_transition(nv[IS_FIRST] - is_final * next_is_real);
+        yield_constr.constraint_transition(is_full * (P::ONES - next_is_real));
+        yield_constr.constraint_transition((P::ONES - is_real) * next_is_real);
+        yield_constr.constraint_last_row(is_full);
+
+        // The sponge starts from the zero state.
+        yield_constr.constraint(is_first * lv[ABSORBED_WORDS]);
+        for i in 0..STATE_WORDS {
+            yield_constr.constraint(is_first * lv[col_original_state(i)]);
+        }
+
+        // A full block passes its instruction and state to the next block.
+        for col in [CLK, INPUT_ADDR, OUTPUT_ADDR, LEN] {
+            yield_constr.constraint_transition(is_full * (nv[col] - lv[col]));
+        }
+        yield_constr.constraint_transition(
+            is_full
+                * (nv[ABSORBED_WORDS] - lv[ABSORBED_WORDS] - FE::from_canonical_usize(RATE_WORDS)),
+        );
+        for i in 0..STATE_WORDS {
+            yield_constr.constraint_transition(
+                is_full * (nv[col_original_state(i)] - lv[col_updated_state(i)]),
+            );
+        }
+
+        // The final block has the rest of the input, less than 136 bytes.
+        let mut final_len_sum = P::ZEROS;
+        let mut final_len = P::ZEROS;
+        for i in 0..RATE_BYTES {
+            let is_final_len = lv[col_is_final_len(i)];
+            yield_constr.constraint(is_final_len * (P::ONES - is_final_len));
+            final_len_sum += is_final_len;
+            final_len += is_final_len * FE::from_canonical_usize(i);
+        }
+        yield_constr.constraint(final_len_sum - is_final);
+        yield_constr.constraint(
+            is_final * (lv[LEN] - lv[ABSORBED_WORDS] * FE::from_canonical_u64(4)) - final_len,
+        );
+
+        // Words past the input are zero, the tail word is the word of the last
+        // bytes of a final block which does not end on a word.
+        let mut tail_word = P::ZEROS;
+        for j in 0..RATE_WORDS {
+            let read: P = is_full + read_bytes(j).map(|i| lv[col_is_final_len(i)]).sum::<P>();
+            yield_constr.constraint((P::ONES - read) * lv[col_block(j)]);
+            for i in 4 * j..4 * j + 4 {
+                tail_word += lv[col_is_final_len(i)] * lv[col_block(j)];
+            }
+        }
+        yield_constr.constraint(lv[TAIL_WORD] - tail_word);
+    }
+
+    fn eval_ext_circuit(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        vars: StarkEvaluationTargets<D, { COL_NUM_KECCAK_SPONGE }>,
+        yield_constr: &mut RecursiveConstraintConsumer<F, D>,
+    ) {
+        let lv = vars.local_values;
+        let nv = vars.next_values;
+        let one = builder.one_extension();
+
+        let is_full = lv[IS_FULL];
+        let is_final = lv[IS_FINAL];
+        let is_first = lv[IS_FIRST];
+        let is_real = builder.add_extension(is_full, is_final);
+        for flag in [is_full, is_final, is_first, is_real] {
+            let one_m_flag = builder.sub_extension(one, flag);
+            let flag_cs = builder.mul_extension(flag, one_m_flag);
+            yield_constr.constraint(builder, flag_cs);
+        }
+        let one_m_is_real = builder.sub_extension(one, is_real);
+        let first_cs = builder.mul_extension(is_first, one_m_is_real);
+        yield_constr.constraint(builder, first_cs);
+
+        let next_is_real = builder.add_extension(nv[IS_FULL], nv[IS_FINAL]);
+        let first_row_cs = builder.sub_extension(is_first, is_real);
+        yield_constr.constraint_first_row(builder, first_row_cs);
+        let next_first = builder.mul_extension(is_final, next_is_real);
+        let next_first_cs = builder.sub_extension(nv[IS_FIRST], next_first);
+        yield_constr.constraint_transition(builder, next_first_cs);
+        let one_m_next_is_real = builder.sub_extension(one, next_is_real);
+        let full_next_cs = builder.mul_extension(is_full, one_m_next_is_real);
+        yield_constr.constraint_transition(builder, full_next_cs);
+        let padding_next_cs = builder.mul_extension(one_m_is_real, next_is_real);
+        yield_constr.constraint_transition(builder, padding_next_cs);
+        yield_constr.constraint_last_row(builder, is_full);
+
+        let absorbed_cs = builder.mul_extension(is_first, lv[ABSORBED_WORDS]);
+        yield_constr.constraint(builder, absorbed_cs);
+        for i in 0..STATE_WORDS {
+            let state_cs = builder.mul_extension(is_first, lv[col_original_state(i)]);
+            yield_constr.constraint(builder, state_cs);
+        }
+
+        for col in [CLK, INPUT_ADDR, OUTPUT_ADDR, LEN] {
+            let diff = builder.sub_extension(nv[col], lv[col]);
+            let copy_cs = builder.mul_extension(is_full, diff);
+            yield_constr.constraint_transition(builder, copy_cs);
+        }
+        let absorbed = builder.sub_extension(nv[ABSORBED_WORDS], lv[ABSORBED_WORDS]);
+        let absorbed = builder.add_const_extension(absorbed, -F::from_canonical_usize(RATE_WORDS));
+        let absorbed_cs = builder.mul_extension(is_full, absorbed);
+        yield_constr.constraint_transition(builder, absorbed_cs);
+        for i in 0..STATE_WORDS {
+            let diff = builder.sub_extension(nv[col_original_state(i)], lv[col_updated_state(i)]);
+            let state_cs = builder.mul_extension(is_full, diff);
+            yield_constr.constraint_transition(builder, state_cs);
+        }
+
+        let mut final_len_sum = builder.zero_extension();
+        let mut final_len = builder.zero_extension();
+        for i in 0..RATE_BYTES {
+            let is_final_len = lv[col_is_final_len(i)];
+            let one_m_is_final_len = builder.sub_extension(one, is_final_len);
+            let is_final_len_cs = builder.mul_extension(is_final_len, one_m_is_final_len);
+            yield_constr.constraint(builder, is_final_len_cs);
+            final_len_sum = builder.add_extension(final_len_sum, is_final_len);
+            final_len = builder.mul_const_add_extension(
+                F::from_canonical_usize(i),
+                is_final_len,
+                final_len,
+            );
+        }
+        let final_len_sum_cs = builder.sub_extension(final_len_sum, is_final);
+        yield_constr.constraint(builder, final_len_sum_cs);
+        let rest = builder.mul_const_extension(F::from_canonical_u64(4), lv[ABSORBED_WORDS]);
+        let rest = builder.sub_extension(lv[LEN], rest);
+        let final_len_cs = builder.mul_sub_extension(is_final, rest, final_len);
+        yield_constr.constraint(builder, final_len_cs);
+
+        let mut tail_word = builder.zero_extension();
+        for j in 0..RATE_WORDS {
+            let read_final =
+                builder.add_many_extension(read_bytes(j).map(|i| lv[col_is_final_len(i)]));
+            let read = builder.add_extension(is_full, read_final);
+            let block_cs = builder.arithmetic_extension(
+                F::NEG_ONE,
+                F::ONE,
+                read,
+                lv[col_block(j)],
+                lv[col_block(j)],
+            );
+            yield_constr.constraint(builder, block_cs);
+            for i in 4 * j..4 * j + 4 {
+                tail_word =
+                    builder.mul_add_extension(lv[col_is_final_len(i)], lv[col_block(j)], tail_word);
+            }
+        }
+        let tail_word_cs = builder.sub_extension(lv[TAIL_WORD], tail_word);
+        yield_constr.constraint(builder, tail_word_cs);
+    }
+
+    fn constraint_degree(&self) -> usize {
+        3
+    }
+}
+
+fn is_real<F: Field>() -> Column<F> {
+    Column::sum([IS_FULL, IS_FINAL])
+}
+
+// Get the column info for Cross_Lookup<Cpu_table, KeccakSponge_table>, the
+// final block of every hash.
+pub fn ctl_data_with_cpu<F: Field>() -> Vec<Column<F>> {
+    Column::singles([CLK, INPUT_ADDR, OUTPUT_ADDR]).collect_vec()
+}
+
+pub fn ctl_filter_with_cpu<F: Field>() -> Column<F> {
+    Column::single(IS_FINAL)
+}
+
+fn ctl_data_with_memory<F: Field>(addr: Column<F>, value: usize) -> Vec<Column<F>> {
+    vec![
+        Column::single(CLK),
+        Column::constant(F::from_canonical_u64(1 << Opcode::KECCAK as u64)),
+        addr,
+        Column::single(value),
+    ]
+}
+
+// (clk, op, addr, value) of the memory access reading the length of the input,
+// see `memory_stark::ctl_data`.
+pub fn ctl_data_with_memory_len<F: Field>() -> Vec<Column<F>> {
+    ctl_data_with_memory(Column::single(INPUT_ADDR), LEN)
+}
+
+pub fn ctl_filter_with_memory_len<F: Field>() -> Column<F> {
+    Column::single(IS_FIRST)
+}
+
+// (clk, op, addr, value) of the memory access reading the word `j` of a block.
+pub fn ctl_data_with_memory_block<F: Field>(j: usize) -> Vec<Column<F>> {
+    let addr = Column::linear_combination_with_constant(
+        [(INPUT_ADDR, F::ONE), (ABSORBED_WORDS, F::ONE)],
+        F::from_canonical_usize(j + 1),
+    );
+    ctl_data_with_memory(addr, col_block(j))
+}
+
+pub fn ctl_filter_with_memory_block<F: Field>(j: usize) -> Column<F> {
+    Column::sum(
+        [IS_FULL]
+            .into_iter()
+            .chain(read_bytes(j).map(col_is_final_len)),
+    )
+}
+
+// (clk, op, addr, value) of the memory access writing the word `i` of the
+// digest.
+pub fn ctl_data_with_memory_output<F: Field>(i: usize) -> Vec<Column<F>> {
+    debug_assert!(i < KECCAK_OUTPUT_NUM);
+    let addr = Column::linear_combination_with_constant(
+        [(OUTPUT_ADDR, F::ONE)],
+        F::from_canonical_usize(i),
+    );
+    ctl_data_with_memory(addr, col_updated_state(i))
+}
+
+pub fn ctl_filter_with_memory_output<F: Field>() -> Column<F> {
+    Column::single(IS_FINAL)
+}
+
+// (tag, op0, op1, res) of the bitwise row xoring the padded word `j` into the
+// state, see `bitwise_stark::ctl_data_with_cpu`. The padding adds a one bit
+// after the input of the final block and the last bit of the block.
+pub fn ctl_data_with_bitwise_xor<F: Field>(j: usize) -> Vec<Column<F>> {
+    let mut padded = vec![(col_block(j), F::ONE)];
+    padded.extend((4 * j..4 * j + 4).map(|i| {
+        (
+            col_is_final_len(i),
+            F::from_canonical_u64(1 << (8 * (i % 4))),
+        )
+    }));
+    if j == RATE_WORDS - 1 {
+        padded.push((IS_FINAL, F::from_canonical_u64(0x80 << 24)));
+    }
+    vec![
+        Column::constant(F::from_canonical_u32(BitwiseOperation::Xor as u32)),
+        Column::single(col_original_state(j)),
+        Column::linear_combination(padded),
+        Column::single(col_xored(j)),
+    ]
+}
+
+pub fn ctl_filter_with_bitwise_xor<F: Field>() -> Column<F> {
+    is_real()
+}
+
+// (tag, op0, op1, res) of the bitwise row checking that the tail word has no
+// byte past the input, `tail_word & mask == tail_word`.
+pub fn ctl_data_with_bitwise_tail<F: Field>() -> Vec<Column<F>> {
+    let mask = (0..RATE_BYTES).filter(|i| i % 4 != 0).map(|i| {
+        (
+            col_is_final_len(i),
+            F::from_canonical_u64((1 << (8 * (i % 4))) - 1),
+        )
+    });
+    vec![
+        Column::constant(F::from_canonical_u32(BitwiseOperation::And as u32)),
+        Column::single(TAIL_WORD),
+        Column::linear_combination(mask),
+        Column::single(TAIL_WORD),
+    ]
+}
+
+pub fn ctl_filter_with_bitwise_tail<F: Field>() -> Column<F> {
+    Column::single(IS_FINAL)
+}
+
+// The state before and after the permutation of a block, for
+// Cross_Lookup<KeccakSponge_table, Keccak_table>.
+pub fn ctl_data_with_keccak<F: Field>() -> Vec<Column<F>> {
+    Column::singles(
+        (0..RATE_WORDS)
+            .map(col_xored)
+            .chain((RATE_WORDS..STATE_WORDS).map(col_original_state))
+            .chain((0..STATE_WORDS).map(col_updated_state)),
+    )
+    .collect_vec()
+}
+
+pub fn ctl_filter_with_keccak<F: Field>() -> Column<F> {
+    is_real()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generation::builtin::generate_builtins_keccak_sponge_trace;
+    use core::trace::trace::KeccakRow;
+    use executor::coprocessor::keccak::{absorb, bytes_to_words, digest};
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+
+    #[test]
+    fn test_keccak_sponge_stark() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type S = KeccakSpongeStark<F, D>;
+
+        let stark = S::default();
+
+        // A hash of two full blocks and a final block with a tail word, and a
+        // hash of the empty input.
+        let cells = [(0x100, 2 * RATE_BYTES + 7), (0x400, 0)].map(|(input_addr, len)| {
+            let bytes = (0..len).map(|i| i as u8).collect_vec();
+            let words = bytes_to_words(&bytes);
+            KeccakRow {
+                clk: F::from_canonical_usize(input_addr),
+                input_addr: F::from_canonical_usize(input_addr),
+                output_addr: F::from_canonical_usize(input_addr + 0x100),
+                len: F::from_canonical_usize(len),
+                input: words.iter().map(|w| F::from_canonical_u32(*w)).collect(),
+                output: digest(&absorb(len, &words)).map(F::from_canonical_u32),
+            }
+        });
+        let trace_rows = generate_builtins_keccak_sponge_trace::<F>(&cells);
+        assert_eq!(trace_rows.len(), 4);
+        assert_eq!(trace_rows[2][IS_FINAL], F::ONE);
+        assert_eq!(trace_rows[2][col_is_final_len(7)], F::ONE);
+        assert_eq!(trace_rows[2][TAIL_WORD], F::from_canonical_u32(0x0016_1514));
+        assert_eq!(trace_rows[3][IS_FIRST], F::ONE);
+        for (i, value) in cells[1].output.iter().enumerate() {
+            assert_eq!(trace_rows[3][col_updated_state(i)], *value);
+        }
+
+        let last = trace_rows.len() - 1;
+        for i in 0..trace_rows.len() {
+            let vars = StarkEvaluationVars {
+                local_values: &trace_rows[i],
+                next_values: &trace_rows[(i + 1) % trace_rows.len()],
+            };
+
+            let flag = |b: bool| if b { F::ONE } else { F::ZERO };
+            let mut constraint_consumer = ConstraintConsumer::new(
+                vec![GoldilocksField(2), GoldilocksField(3), GoldilocksField(5)],
+                flag(i != last),
+                flag(i == 0),
+                flag(i == last),
+            );
+            stark.eval_packed_generic(vars, &mut constraint_consumer);
+
+            for &acc in &constraint_consumer.constraint_accs {
+                assert_eq!(acc, GoldilocksField::ZERO);
+            }
+        }
+    }
+}
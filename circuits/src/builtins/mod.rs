@@ -1,4 +1,8 @@
 pub mod bitwise;
 pub mod cmp;
+pub mod ecdsa;
+pub mod keccak;
+pub mod keccak_sponge;
+pub mod poseidon;
 pub mod rangecheck;
 pub mod rangecheck_small_limbs;
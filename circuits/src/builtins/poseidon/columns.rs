@@ -0,0 +1,68 @@
+use plonky2::hash::hashing::SPONGE_WIDTH;
+use plonky2::hash::poseidon::{HALF_N_FULL_ROUNDS, N_PARTIAL_ROUNDS};
+
+/* Poseidon Table, one permutation per row:
++--------+----------------+-----+------------+-------------+----------+-----------------------+----------------------+-----------+
+| filter | filter_program | clk | input_addr | output_addr | input[8] | full rounds sbox      | partial rounds sbox  | output[4] |
+|        |                |     |            |             |          | (cube[12], out[12])*8 | (cube, out)*22       |           |
++--------+----------------+-----+------------+-------------+----------+-----------------------+----------------------+-----------+
+
+The state starts from the inputs followed by 4 zero capacity lanes. Every
+round adds the round constants, applies the x^7 s-box and the MDS matrix, as
+`Poseidon::poseidon_naive`. Full rounds apply the s-box to every lane and
+partial rounds only to the first one. The s-box input x is linear in the
+previous columns, it is split in two degree 3 constraints:
+    cube = x^3, out = x * cube^2
+The output is the first 4 lanes of the final state.
+
+filter marks the permutations of the poseidon instructions, looked up by the
+cpu and memory tables, and filter_program the links of the program hash
+chains of the program tables.
+
+Padding rows are permutations of zero inputs with both filters 0.
+*/
+
+pub(crate) const NUM_INPUTS: usize = 8;
+pub(crate) const NUM_OUTPUTS: usize = 4;
+pub(crate) const N_FULL_ROUNDS: usize = 2 * HALF_N_FULL_ROUNDS;
+
+pub(crate) const FILTER: usize = 0;
+pub(crate) const FILTER_PROGRAM: usize = FILTER + 1;
+pub(crate) const CLK: usize = FILTER_PROGRAM + 1;
+pub(crate) const INPUT_ADDR: usize = CLK + 1;
+pub(crate) const OUTPUT_ADDR: usize = INPUT_ADDR + 1;
+pub(crate) const INPUT_START: usize = OUTPUT_ADDR + 1;
+pub(crate) const FULL_SBOX_START: usize = INPUT_START + NUM_INPUTS;
+pub(crate) const PARTIAL_SBOX_START: usize = FULL_SBOX_START + N_FULL_ROUNDS * 2 * SPONGE_WIDTH;
+pub(crate) const OUTPUT_START: usize = PARTIAL_SBOX_START + N_PARTIAL_ROUNDS * 2;
+
+pub(crate) const COL_NUM_POSEIDON: usize = OUTPUT_START + NUM_OUTPUTS; // 253
+
+pub(crate) fn col_input(i: usize) -> usize {
+    debug_assert!(i < NUM_INPUTS);
+    INPUT_START + i
+}
+
+// `round` is the index among the 8 full rounds.
+pub(crate) fn col_full_sbox_cube(round: usize, lane: usize) -> usize {
+    debug_assert!(round < N_FULL_ROUNDS && lane < SPONGE_WIDTH);
+    FULL_SBOX_START + round * 2 * SPONGE_WIDTH + lane
+}
+
+pub(crate) fn col_full_sbox_out(round: usize, lane: usize) -> usize {
+    col_full_sbox_cube(round, lane) + SPONGE_WIDTH
+}
+
+pub(crate) fn col_partial_sbox_cube(round: usize) -> usize {
+    debug_assert!(round < N_PARTIAL_ROUNDS);
+    PARTIAL_SBOX_START + 2 * round
+}
+
+pub(crate) fn col_partial_sbox_out(round: usize) -> usize {
+    col_partial_sbox_cube(round) + 1
+}
+
+pub(crate) fn col_output(i: usize) -> usize {
+    debug_assert!(i < NUM_OUTPUTS);
+    OUTPUT_START + i
+}
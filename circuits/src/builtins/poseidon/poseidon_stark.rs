@@ -0,0 +1,323 @@
+use crate::builtins::poseidon::columns::*;
+use core::program::instruction::Opcode;
+use itertools::Itertools;
+
+use crate::stark::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+use crate::stark::cross_table_lookup::Column;
+use crate::stark::stark::Stark;
+use crate::stark::vars::{StarkEvaluationTargets, StarkEvaluationVars};
+use plonky2::field::extension::{Extendable, FieldExtension};
+use plonky2::field::packed::PackedField;
+use plonky2::field::types::Field;
+use plonky2::hash::hash_types::RichField;
+use plonky2::hash::hashing::SPONGE_WIDTH;
+use plonky2::hash::poseidon::{ALL_ROUND_CONSTANTS, HALF_N_FULL_ROUNDS, N_PARTIAL_ROUNDS};
+use plonky2::iop::ext_target::ExtensionTarget;
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use std::marker::PhantomData;
+
+#[derive(Copy, Clone, Default)]
+pub struct PoseidonStark<F, const D: usize> {
+    pub _phantom: PhantomData<F>,
+}
+
+fn constant_layer<F, FE, P, const D2: usize>(state: &mut [P; SPONGE_WIDTH], round_ctr: usize)
+where
+    F: RichField,
+    FE: FieldExtension<D2, BaseField = F>,
+    P: PackedField<Scalar = FE>,
+{
+    for (i, lane) in state.iter_mut().enumerate() {
+        *lane += FE::from_canonical_u64(ALL_ROUND_CONSTANTS[i + SPONGE_WIDTH * round_ctr]);
+    }
+}
+
+fn mds_layer<F, FE, P, const D2: usize>(state: &[P; SPONGE_WIDTH]) -> [P; SPONGE_WIDTH]
+where
+    F: RichField,
+    FE: FieldExtension<D2, BaseField = F>,
+    P: PackedField<Scalar = FE>,
+{
+    let mut result = [P::ZEROS; SPONGE_WIDTH];
+    for (r, res) in result.iter_mut().enumerate() {
+        for i in 0..SPONGE_WIDTH {
+            *res += state[(i + r) % SPONGE_WIDTH] * FE::from_canonical_u64(F::MDS_MATRIX_CIRC[i]);
+        }
+        *res += state[r] * FE::from_canonical_u64(F::MDS_MATRIX_DIAG[r]);
+    }
+    result
+}
+
+// Returns the s-box output column once `cube` and `out` are constrained.
+fn eval_sbox<P: PackedField>(x: P, cube: P, out: P, yield_constr: &mut ConstraintConsumer<P>) -> P {
+    yield_constr.constraint(cube - x * x * x);
+    yield_constr.constraint(out - x * cube * cube);
+    out
+}
+
+fn eval_sbox_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    x: ExtensionTarget<D>,
+    cube: ExtensionTarget<D>,
+    out: ExtensionTarget<D>,
+    yield_constr: &mut RecursiveConstraintConsumer<F, D>,
+) -> ExtensionTarget<D> {
+    let x_cube = builder.cube_extension(x);
+    let cube_cs = builder.sub_extension(cube, x_cube);
+    yield_constr.constraint(builder, cube_cs);
+    let x_7 = builder.mul_many_extension([x, cube, cube]);
+    let out_cs = builder.sub_extension(out, x_7);
+    yield_constr.constraint(builder, out_cs);
+    out
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for PoseidonStark<F, D> {
+    const COLUMNS: usize = COL_NUM_POSEIDON;
+
+    fn eval_packed_generic<FE, P, const D2: usize>(
+        &self,
+        vars: StarkEvaluationVars<FE, P, { COL_NUM_POSEIDON }>,
+        yield_constr: &mut ConstraintConsumer<P>,
+    ) where
+        FE: FieldExtension<D2, BaseField = F>,
+        P: PackedField<Scalar = FE>,
+    {
+        let lv = vars.local_values;
+        for filter in [lv[FILTER], lv[FILTER_PROGRAM]] {
+            yield_constr.constraint(filter * (P::ONES - filter));
+        }
+
+        let mut state = [P::ZEROS; SPONGE_WIDTH];
+        for (i, lane) in state[..NUM_INPUTS].iter_mut().enumerate() {
+            *lane = lv[col_input(i)];
+        }
+
+        let mut round_ctr = 0;
+        let full_rounds = |state: &mut [P; SPONGE_WIDTH],
+                           round_ctr: &mut usize,
+                           rounds: std::ops::Range<usize>,
+                           yield_constr: &mut ConstraintConsumer<P>| {
+            for round in rounds {
+                constant_layer(state, *round_ctr);
+                for (lane, x) in state.iter_mut().enumerate() {
+                    *x = eval_sbox(
+                        *x,
+                        lv[col_full_sbox_cube(round, lane)],
+                        lv[col_full_sbox_out(round, lane)],
+                        yield_constr,
+                    );
+                }
+                *state = mds_layer(state);
+                *round_ctr += 1;
+            }
+        };
+
+        full_rounds(
+            &mut state,
+            &mut round_ctr,
+            0..HALF_N_FULL_ROUNDS,
+            yield_constr,
+        );
+        for round in 0..N_PARTIAL_ROUNDS {
+            constant_layer(&mut state, round_ctr);
+            state[0] = eval_sbox(
+                state[0],
+                lv[col_partial_sbox_cube(round)],
+                lv[col_partial_sbox_out(round)],
+                yield_constr,
+            );
+            state = mds_layer(&state);
+            round_ctr += 1;
+        }
+        full_rounds(
+            &mut state,
+            &mut round_ctr,
+            HALF_N_FULL_ROUNDS..N_FULL_ROUNDS,
+            yield_constr,
+        );
+
+        for (i, lane) in state[..NUM_OUTPUTS].iter().enumerate() {
+            yield_constr.constraint(lv[col_output(i)] - *lane);
+        }
+    }
+
+    fn eval_ext_circuit(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        vars: StarkEvaluationTargets<D, { COL_NUM_POSEIDON }>,
+        yield_constr: &mut RecursiveConstraintConsumer<F, D>,
+    ) {
+        let lv = vars.local_values;
+        let one = builder.one_extension();
+        for filter in [lv[FILTER], lv[FILTER_PROGRAM]] {
+            let one_m_filter = builder.sub_extension(one, filter);
+            let filter_cs = builder.mul_extension(filter, one_m_filter);
+            yield_constr.constraint(builder, filter_cs);
+        }
+
+        let mut state = [builder.zero_extension(); SPONGE_WIDTH];
+        for (i, lane) in state[..NUM_INPUTS].iter_mut().enumerate() {
+            *lane = lv[col_input(i)];
+        }
+
+        let mut round_ctr = 0;
+        let full_rounds =
+            |builder: &mut CircuitBuilder<F, D>,
+             state: &mut [ExtensionTarget<D>; SPONGE_WIDTH],
+             round_ctr: &mut usize,
+             rounds: std::ops::Range<usize>,
+             yield_constr: &mut RecursiveConstraintConsumer<F, D>| {
+                for round in rounds {
+                    F::constant_layer_circuit(builder, state, *round_ctr);
+                    for (lane, x) in state.iter_mut().enumerate() {
+                        *x = eval_sbox_circuit(
+                            builder,
+                            *x,
+                            lv[col_full_sbox_cube(round, lane)],
+                            lv[col_full_sbox_out(round, lane)],
+                            yield_constr,
+                        );
+                    }
+                    *state = F::mds_layer_circuit(builder, state);
+                    *round_ctr += 1;
+                }
+            };
+
+        full_rounds(
+            builder,
+            &mut state,
+            &mut round_ctr,
+            0..HALF_N_FULL_ROUNDS,
+            yield_constr,
+        );
+        for round in 0..N_PARTIAL_ROUNDS {
+            F::constant_layer_circuit(builder, &mut state, round_ctr);
+            state[0] = eval_sbox_circuit(
+                builder,
+                state[0],
+                lv[col_partial_sbox_cube(round)],
+                lv[col_partial_sbox_out(round)],
+                yield_constr,
+            );
+            state = F::mds_layer_circuit(builder, &state);
+            round_ctr += 1;
+        }
+        full_rounds(
+            builder,
+            &mut state,
+            &mut round_ctr,
+            HALF_N_FULL_ROUNDS..N_FULL_ROUNDS,
+            yield_constr,
+        );
+
+        for (i, lane) in state[..NUM_OUTPUTS].iter().enumerate() {
+            let output_cs = builder.sub_extension(lv[col_output(i)], *lane);
+            yield_constr.constraint(builder, output_cs);
+        }
+    }
+
+    fn constraint_degree(&self) -> usize {
+        3
+    }
+}
+
+// Get the column info for Cross_Lookup<Cpu_table, Poseidon_table>
+pub fn ctl_data_with_cpu<F: Field>() -> Vec<Column<F>> {
+    Column::singles([CLK, INPUT_ADDR, OUTPUT_ADDR]).collect_vec()
+}
+
+pub fn ctl_filter_with_cpu<F: Field>() -> Column<F> {
+    Column::single(FILTER)
+}
+
+// (clk, op, addr, value) of the memory access reading the input `i`, see
+// `memory_stark::ctl_data`.
+pub fn ctl_data_with_memory_input<F: Field>(i: usize) -> Vec<Column<F>> {
+    ctl_data_with_memory(INPUT_ADDR, i, col_input(i))
+}
+
+// (clk, op, addr, value) of the memory access writing the output `i`.
+pub fn ctl_data_with_memory_output<F: Field>(i: usize) -> Vec<Column<F>> {
+    ctl_data_with_memory(OUTPUT_ADDR, i, col_output(i))
+}
+
+fn ctl_data_with_memory<F: Field>(addr: usize, offset: usize, value: usize) -> Vec<Column<F>> {
+    vec![
+        Column::single(CLK),
+        Column::constant(F::from_canonical_u64(1 << Opcode::PSDN as u64)),
+        Column::linear_combination_with_constant([(addr, F::ONE)], F::from_canonical_usize(offset)),
+        Column::single(value),
+    ]
+}
+
+pub fn ctl_filter_with_memory<F: Field>() -> Column<F> {
+    Column::single(FILTER)
+}
+
+// Get the column info for Cross_Lookup<Program_table, Poseidon_table>, the
+// inputs followed by the outputs.
+pub fn ctl_data_with_program<F: Field>() -> Vec<Column<F>> {
+    Column::singles((0..NUM_INPUTS).map(col_input))
+        .chain(Column::singles((0..NUM_OUTPUTS).map(col_output)))
+        .collect_vec()
+}
+
+pub fn ctl_filter_with_program<F: Field>() -> Column<F> {
+    Column::single(FILTER_PROGRAM)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generation::builtin::generate_builtins_poseidon_trace;
+    use core::trace::trace::PoseidonRow;
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::hash::poseidon::PoseidonHash;
+    use plonky2::plonk::config::{GenericConfig, Hasher, PoseidonGoldilocksConfig};
+
+    #[test]
+    fn test_poseidon_stark() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type S = PoseidonStark<F, D>;
+
+        let stark = S::default();
+
+        let input = F::rand_arr();
+        let output = PoseidonHash::hash_no_pad(&input).elements;
+        let cell = PoseidonRow {
+            clk: F::from_canonical_u64(5),
+            input_addr: F::from_canonical_u64(0x100),
+            output_addr: F::from_canonical_u64(0x200),
+            input,
+            output,
+        };
+        let trace_rows = generate_builtins_poseidon_trace::<F>(&[cell], &[]);
+        assert_eq!(trace_rows.len(), 2);
+        assert_eq!(trace_rows[0][FILTER], F::ONE);
+        assert_eq!(trace_rows[1][FILTER], F::ZERO);
+        for (i, value) in output.iter().enumerate() {
+            assert_eq!(trace_rows[0][col_output(i)], *value);
+        }
+
+        for i in 0..trace_rows.len() {
+            let vars = StarkEvaluationVars {
+                local_values: &trace_rows[i],
+                next_values: &trace_rows[(i + 1) % trace_rows.len()],
+            };
+
+            let mut constraint_consumer = ConstraintConsumer::new(
+                vec![GoldilocksField(2), GoldilocksField(3), GoldilocksField(5)],
+                GoldilocksField::ONE,
+                GoldilocksField::ONE,
+                GoldilocksField::ONE,
+            );
+            stark.eval_packed_generic(vars, &mut constraint_consumer);
+
+            for &acc in &constraint_consumer.constraint_accs {
+                assert_eq!(acc, GoldilocksField::ZERO);
+            }
+        }
+    }
+}
@@ -16,8 +16,8 @@
 Constraints as follows:
 1. Sumcheck relation
    val = limb_lo + 2^16* limb_hi
-2. Cross Lookup for limbs
-    Lookup {<limbs>; RC_FIXED_TABLE}
+2. Lookup for limbs
+    Cross_Lookup {<limb_lo>, <limb_hi>; RANGECHECK_FIXED_TABLE}
 */
 //Identify different Rangecheck TABLE
 // 0 => Main TABLE
@@ -31,13 +31,5 @@ pub(crate) const CMP_FILTER: usize = MEMORY_FILTER + 1;
 pub(crate) const VAL: usize = CMP_FILTER + 1;
 pub(crate) const LIMB_LO: usize = VAL + 1;
 pub(crate) const LIMB_HI: usize = LIMB_LO + 1;
-pub(crate) const LIMB_LO_PERMUTED: usize = LIMB_HI + 1;
-pub(crate) const LIMB_HI_PERMUTED: usize = LIMB_LO_PERMUTED + 1;
 
-pub(crate) const FIX_RANGE_CHECK_U16: usize = LIMB_HI_PERMUTED + 1;
-pub(crate) const FIX_RANGE_CHECK_U16_PERMUTED_LO: usize = FIX_RANGE_CHECK_U16 + 1;
-pub(crate) const FIX_RANGE_CHECK_U16_PERMUTED_HI: usize = FIX_RANGE_CHECK_U16_PERMUTED_LO + 1;
-
-pub(crate) const COL_NUM_RC: usize = FIX_RANGE_CHECK_U16_PERMUTED_HI + 1; //11
-
-pub(crate) const RANGE_CHECK_U16_SIZE: usize = 1 << 16; //4
+pub(crate) const COL_NUM_RC: usize = LIMB_HI + 1; //6
@@ -3,8 +3,6 @@ use itertools::Itertools;
 
 use crate::stark::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
 use crate::stark::cross_table_lookup::Column;
-use crate::stark::lookup::*;
-use crate::stark::permutation::*;
 use crate::stark::stark::Stark;
 use crate::stark::vars::{StarkEvaluationTargets, StarkEvaluationVars};
 use plonky2::field::extension::{Extendable, FieldExtension};
@@ -46,19 +44,6 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for RangeCheckSta
         let sum = limb_lo + limb_hi * base;
 
         yield_constr.constraint(val - sum);
-
-        eval_lookups(
-            vars,
-            yield_constr,
-            LIMB_LO_PERMUTED,
-            FIX_RANGE_CHECK_U16_PERMUTED_LO,
-        );
-        eval_lookups(
-            vars,
-            yield_constr,
-            LIMB_HI_PERMUTED,
-            FIX_RANGE_CHECK_U16_PERMUTED_HI,
-        );
     }
 
     fn eval_ext_circuit(
@@ -76,35 +61,11 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for RangeCheckSta
         let sum = builder.mul_add_extension(limb_hi, base, limb_lo);
         let val_sum_diff = builder.sub_extension(val, sum);
         yield_constr.constraint(builder, val_sum_diff);
-
-        eval_lookups_circuit(
-            builder,
-            vars,
-            yield_constr,
-            LIMB_LO_PERMUTED,
-            FIX_RANGE_CHECK_U16_PERMUTED_LO,
-        );
-        eval_lookups_circuit(
-            builder,
-            vars,
-            yield_constr,
-            LIMB_HI_PERMUTED,
-            FIX_RANGE_CHECK_U16_PERMUTED_HI,
-        );
     }
 
     fn constraint_degree(&self) -> usize {
         3
     }
-
-    fn permutation_pairs(&self) -> Vec<PermutationPair> {
-        vec![
-            PermutationPair::singletons(LIMB_LO, LIMB_LO_PERMUTED),
-            PermutationPair::singletons(LIMB_HI, LIMB_HI_PERMUTED),
-            PermutationPair::singletons(FIX_RANGE_CHECK_U16, FIX_RANGE_CHECK_U16_PERMUTED_LO),
-            PermutationPair::singletons(FIX_RANGE_CHECK_U16, FIX_RANGE_CHECK_U16_PERMUTED_HI),
-        ]
-    }
 }
 
 pub fn ctl_data_memory<F: Field>() -> Vec<Column<F>> {
@@ -133,11 +94,12 @@ pub fn ctl_filter_with_cpu<F: Field>() -> Column<F> {
     Column::single(CPU_FILTER)
 }
 
-// Get the column info for Cross_Lookup<Cpu_table, Bitwise_table>
-/*pub fn ctl_data_with_rangecheck_fixed<F: Field>() -> Vec<Column<F>> {
-    let mut res = Column::singles([LIMB_LO, LIMB_HI]).collect_vec();
-    res
+// Get the column info for Cross_Lookup<Rangecheck_table,
+// Rangecheck_Fixed_table>
+pub fn ctl_data_limb_lo_with_rangecheck_fixed<F: Field>() -> Vec<Column<F>> {
+    Column::singles([LIMB_LO]).collect_vec()
+}
+
+pub fn ctl_data_limb_hi_with_rangecheck_fixed<F: Field>() -> Vec<Column<F>> {
+    Column::singles([LIMB_HI]).collect_vec()
 }
-pub fn ctl_filter_with_rangecheck_fixed<F: Field>() -> Column<F> {
-    Column::one()
-}*/
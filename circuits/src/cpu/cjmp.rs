@@ -12,7 +12,7 @@ pub(crate) fn eval_packed_generic<P: PackedField>(
     yield_constr: &mut ConstraintConsumer<P>,
 ) {
     let two = P::ONES + P::ONES;
-    yield_constr.constraint(
+    yield_constr.constraint_transition(
         lv[COL_S_CJMP]
             * ((nv[COL_PC]
                 - lv[COL_FLAG] * lv[COL_OP1]
@@ -39,5 +39,5 @@ pub(crate) fn eval_ext_circuit<F: RichField + Extendable<D>, const D: usize>(
     let sub_n_flag = builder.add_extension(sub_cs, nv[COL_FLAG]);
     let cs = builder.mul_extension(lv[COL_S_CJMP], sub_n_flag);
 
-    yield_constr.constraint(builder, cs);
+    yield_constr.constraint_transition(builder, cs);
 }
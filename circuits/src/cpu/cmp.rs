@@ -20,7 +20,7 @@ pub(crate) fn eval_packed_generic<P: PackedField>(
 
     let eq_cs = is_eq * (flag * op_diff + (P::ONES - flag) * (P::ONES - diff_aux));
     let neq_cs = is_neq * ((P::ONES - flag) * op_diff + flag * (P::ONES - diff_aux));
-    yield_constr.constraint(eq_cs + neq_cs);
+    yield_constr.constraint_transition(eq_cs + neq_cs);
 }
 
 pub(crate) fn eval_ext_circuit<F: RichField + Extendable<D>, const D: usize>(
@@ -43,5 +43,5 @@ pub(crate) fn eval_ext_circuit<F: RichField + Extendable<D>, const D: usize>(
     let neq_cs = builder.mul_extension(lv[COL_S_NEQ], neq_cs);
 
     let cs = builder.add_extension(eq_cs, neq_cs);
-    yield_constr.constraint(builder, cs);
+    yield_constr.constraint_transition(builder, cs);
 }
@@ -80,13 +80,13 @@ pub(crate) const COL_S_MLOAD: usize = COL_S_RET + 1;
 pub(crate) const COL_S_MSTORE: usize = COL_S_MLOAD + 1;
 pub(crate) const COL_S_END: usize = COL_S_MSTORE + 1;
 
-// Selectors of Builtins related columns(9):
+// Selectors of Builtins related columns(11):
 // ┬───────┬───────┬───────┬───────┬───────┬───────┬───────┬────────────┬─────────┬
 // │  s_rc │ s_and │ s_or  │ s_xor │ s_not │ s_neq │ s_gte │ s_poseidon │
-// s_ecdsa │
+// s_ecdsa │ s_keccak │ s_prophet │
 // ┼───────┼───────┼───────┼───────┼───────┼───────┼───────┼────────────┼─────────┼
 // │   0   │   1   │   0   │   0   │   0   │   0   │   0   │      0     │    0
-// │
+// │     0    │     0     │
 // ┴───────┴───────┴───────┴───────┴───────┴───────┴───────┴────────────┴─────────┴
 pub(crate) const COL_S_RC: usize = COL_S_END + 1;
 pub(crate) const COL_S_AND: usize = COL_S_RC + 1;
@@ -97,17 +97,19 @@ pub(crate) const COL_S_NEQ: usize = COL_S_NOT + 1;
 pub(crate) const COL_S_GTE: usize = COL_S_NEQ + 1;
 pub(crate) const COL_S_PSDN: usize = COL_S_GTE + 1;
 pub(crate) const COL_S_ECDSA: usize = COL_S_PSDN + 1;
+pub(crate) const COL_S_KECCAK: usize = COL_S_ECDSA + 1;
+pub(crate) const COL_S_PROPHET: usize = COL_S_KECCAK + 1;
 
-// Program consistence relate columns(6):
-// ┬──────────┬────────┬──────────┬──────────┬─────────────┬──────────────┐
-// │ raw_inst │ raw_pc │ zip_raw  │ zip_exed │ per_zip_raw │ pre_zip_exed |
-// ┼──────────┼────────┼──────────┼──────────┼─────────────┼──────────────|
-// │     0    │    1   │     0    │     0    │       1     │       0      |
-// ┴──────────┴────────┴──────────┴──────────┴─────────────┴──────────────┘
-pub(crate) const COL_RAW_INST: usize = COL_S_ECDSA + 1;
+// Program consistence relate columns(5), raw_freq counts the executed
+// (pc, inst, imm) tuples looked up in each raw row:
+// ┬──────────┬────────┬─────────┬────────────┬──────────┐
+// │ raw_inst │ raw_pc │ raw_imm │ raw_filter │ raw_freq │
+// ┼──────────┼────────┼─────────┼────────────┼──────────┼
+// │     0    │    1   │    0    │      1     │     1    │
+// ┴──────────┴────────┴─────────┴────────────┴──────────┘
+pub(crate) const COL_RAW_INST: usize = COL_S_PROPHET + 1;
 pub(crate) const COL_RAW_PC: usize = COL_RAW_INST + 1;
-pub(crate) const COL_ZIP_RAW: usize = COL_RAW_PC + 1;
-pub(crate) const COL_ZIP_EXED: usize = COL_ZIP_RAW + 1;
-pub(crate) const COL_PER_ZIP_RAW: usize = COL_ZIP_EXED + 1;
-pub(crate) const COL_PER_ZIP_EXED: usize = COL_PER_ZIP_RAW + 1;
-pub(crate) const NUM_CPU_COLS: usize = COL_PER_ZIP_EXED + 1;
+pub(crate) const COL_RAW_IMM: usize = COL_RAW_PC + 1;
+pub(crate) const COL_RAW_FILTER: usize = COL_RAW_IMM + 1;
+pub(crate) const COL_RAW_FREQ: usize = COL_RAW_FILTER + 1;
+pub(crate) const NUM_CPU_COLS: usize = COL_RAW_FREQ + 1;
@@ -2,11 +2,11 @@ use {
     super::{columns::*, *},
     crate::stark::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer},
     crate::stark::cross_table_lookup::Column,
-    crate::stark::lookup::{eval_lookups, eval_lookups_circuit},
+    crate::stark::lookup::Lookup,
     crate::stark::stark::Stark,
     crate::stark::vars::{StarkEvaluationTargets, StarkEvaluationVars},
-    anyhow::Result,
     core::program::REGISTER_NUM,
+    core::trace::trace::BitwiseOperation,
     itertools::izip,
     itertools::Itertools,
     plonky2::field::extension::{Extendable, FieldExtension},
@@ -14,6 +14,7 @@ use {
     plonky2::field::types::Field,
     plonky2::hash::hash_types::RichField,
     plonky2::iop::ext_target::ExtensionTarget,
+    plonky2::iop::target::Target,
     plonky2::plonk::circuit_builder::CircuitBuilder,
     std::marker::PhantomData,
     std::ops::Range,
@@ -47,9 +48,12 @@ pub fn ctl_filter_cpu_mem_call_ret<F: Field>() -> Column<F> {
     Column::sum([COL_S_CALL, COL_S_RET])
 }
 
-// get the data source for bitwise in Cpu table
-pub fn ctl_data_with_bitwise<F: Field>() -> Vec<Column<F>> {
-    Column::singles([COL_OP0, COL_OP1, COL_DST]).collect_vec()
+// get the data source for bitwise in Cpu table, the operation tag followed by
+// op0, op1 and dst.
+pub fn ctl_data_with_bitwise<F: Field>(op: BitwiseOperation) -> Vec<Column<F>> {
+    let mut data = vec![Column::constant(F::from_canonical_u32(op as u32))];
+    data.extend(Column::singles([COL_OP0, COL_OP1, COL_DST]));
+    data
 }
 
 pub fn ctl_filter_with_bitwise_and<F: Field>() -> Column<F> {
@@ -82,36 +86,101 @@ pub fn ctl_filter_with_rangecheck<F: Field>() -> Column<F> {
     Column::single(COL_S_RC)
 }
 
-// get the data source for Rangecheck in Cpu table
+// get the data source for Poseidon in Cpu table, op0 is the input address and
+// op1 the output address.
+pub fn ctl_data_with_poseidon<F: Field>() -> Vec<Column<F>> {
+    Column::singles([COL_CLK, COL_OP0, COL_OP1]).collect_vec()
+}
+
+pub fn ctl_filter_with_poseidon<F: Field>() -> Column<F> {
+    Column::single(COL_S_PSDN)
+}
+
+// get the data source for Ecdsa in Cpu table, op1 is the input address and
+// aux0 the flag.
+pub fn ctl_data_with_ecdsa<F: Field>() -> Vec<Column<F>> {
+    Column::singles([COL_CLK, COL_OP1, COL_AUX0]).collect_vec()
+}
+
+pub fn ctl_filter_with_ecdsa<F: Field>() -> Column<F> {
+    Column::single(COL_S_ECDSA)
+}
+
+// get the data source for Keccak in Cpu table, op0 is the input address and
+// op1 the output address.
+pub fn ctl_data_with_keccak<F: Field>() -> Vec<Column<F>> {
+    Column::singles([COL_CLK, COL_OP0, COL_OP1]).collect_vec()
+}
+
+pub fn ctl_filter_with_keccak<F: Field>() -> Column<F> {
+    Column::single(COL_S_KECCAK)
+}
+
+// get the data source for Program in Cpu table
 pub fn ctl_data_with_program<F: Field>() -> Vec<Column<F>> {
-    Column::singles([COL_PC, COL_INST, COL_IMM_VAL]).collect_vec()
+    Column::singles([COL_RAW_PC, COL_RAW_INST, COL_RAW_IMM]).collect_vec()
 }
 
 pub fn ctl_filter_with_program<F: Field>() -> Column<F> {
-    Column::single(COL_INST)
+    Column::single(COL_RAW_FILTER)
+}
+
+/// State of the first and last rows of the cpu trace, they are public values.
+#[derive(Copy, Clone, Debug)]
+pub struct CpuPublicValues<T> {
+    pub initial_clk: T,
+    pub initial_pc: T,
+    pub initial_registers: [T; REGISTER_NUM],
+    pub final_clk: T,
+    pub final_pc: T,
+    pub final_registers: [T; REGISTER_NUM],
+    /// One when the last row is `END`, zero for a segment continued by the
+    /// next one.
+    pub is_final: T,
+}
+
+impl<T: Copy> CpuPublicValues<T> {
+    pub fn map<U, M: FnMut(T) -> U>(self, mut f: M) -> CpuPublicValues<U> {
+        CpuPublicValues {
+            initial_clk: f(self.initial_clk),
+            initial_pc: f(self.initial_pc),
+            initial_registers: self.initial_registers.map(&mut f),
+            final_clk: f(self.final_clk),
+            final_pc: f(self.final_pc),
+            final_registers: self.final_registers.map(&mut f),
+            is_final: f(self.is_final),
+        }
+    }
 }
 
 #[derive(Copy, Clone, Default)]
 pub struct CpuStark<F, const D: usize> {
-    compress_challenge: Option<F>,
+    public_values: Option<CpuPublicValues<F>>,
+    public_value_targets: Option<CpuPublicValues<Target>>,
     pub f: PhantomData<F>,
 }
 
 impl<F: RichField, const D: usize> CpuStark<F, D> {
-    pub const OPCODE_SHIFTS: Range<u32> = 14..35;
+    pub const OPCODE_SHIFTS: Range<u32> = 12..35;
     pub const OP1_IMM_SHIFT: u32 = 62;
     pub const OP0_SHIFT_START: u32 = 61;
     pub const OP1_SHIFT_START: u32 = 52;
     pub const DST_SHIFT_START: u32 = 43;
 
-    pub fn set_compress_challenge(&mut self, challenge: F) -> Result<()> {
-        assert!(self.compress_challenge.is_none(), "already set?");
-        self.compress_challenge = Some(challenge);
-        Ok(())
+    /// Set the state of the first row and last row, which are public values.
+    pub fn set_public_values(&mut self, public_values: CpuPublicValues<F>) {
+        self.public_values = Some(public_values);
+    }
+
+    pub fn get_public_values(&self) -> Option<CpuPublicValues<F>> {
+        self.public_values
     }
 
-    pub fn get_compress_challenge(&self) -> Option<F> {
-        self.compress_challenge
+    /// Set the first row and last row state as targets of a recursive
+    /// verifier, they take precedence over `public_values` in
+    /// `eval_ext_circuit`.
+    pub fn set_public_value_targets(&mut self, public_value_targets: CpuPublicValues<Target>) {
+        self.public_value_targets = Some(public_value_targets);
     }
 }
 
@@ -170,6 +239,8 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for CpuStark<F, D
             lv[COL_S_GTE],
             lv[COL_S_PSDN],
             lv[COL_S_ECDSA],
+            lv[COL_S_KECCAK],
+            lv[COL_S_PROPHET],
         ];
 
         op_selectors
@@ -215,16 +286,22 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for CpuStark<F, D
         }
 
         instruction += lv[COL_OPCODE];
-        yield_constr.constraint(lv[COL_INST] - instruction);
-
-        // We constrain raw inst and inst.
-        // First constrain compress consistency
-        let beta = FE::from_basefield(self.get_compress_challenge().unwrap());
-        yield_constr.constraint(lv[COL_RAW_INST] * beta + lv[COL_RAW_PC] - lv[COL_ZIP_RAW]);
-        yield_constr.constraint(lv[COL_INST] * beta + lv[COL_PC] - lv[COL_ZIP_EXED]);
-
-        // Then check raw inst and inst's lookup logic.
-        eval_lookups(vars, yield_constr, COL_PER_ZIP_EXED, COL_PER_ZIP_RAW);
+        // The last row of a segment ending before `END` is the first step of
+        // the next one, not executed, with no selector. See `Process::run`.
+        yield_constr.constraint_transition(lv[COL_INST] - instruction);
+
+        // We constrain raw inst and inst, every executed (pc, inst, imm) tuple is
+        // looked up in the raw program tuples, see `lookups`.
+
+        // Raw program rows are looked up in the program table, so they must come
+        // from the program. Rows not looked up (raw_filter = 0) repeat the
+        // previous raw row, hence every raw row is a program instruction.
+        yield_constr.constraint(lv[COL_RAW_FILTER] * (P::ONES - lv[COL_RAW_FILTER]));
+        yield_constr.constraint_first_row(P::ONES - lv[COL_RAW_FILTER]);
+        for col in [COL_RAW_PC, COL_RAW_INST, COL_RAW_IMM] {
+            yield_constr
+                .constraint_transition((P::ONES - nv[COL_RAW_FILTER]) * (nv[col] - lv[col]));
+        }
 
         // Only one register used for op0.
         let sum_s_op0: P = s_op0s.into_iter().sum();
@@ -251,28 +328,49 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for CpuStark<F, D
         let dst_sum: P = s_dsts.iter().zip(n_regs.iter()).map(|(s, r)| *s * *r).sum();
         yield_constr.constraint_transition(sum_s_dst * (lv[COL_DST] - dst_sum));
 
-        // Last row's 'next row' (AKA first row) regs should be all zeros.
-        n_regs
-            .iter()
-            .for_each(|nr| yield_constr.constraint_last_row(*nr));
+        // First row and last row state are public values.
+        let public_values = self.get_public_values().unwrap().map(FE::from_basefield);
+        yield_constr.constraint_first_row(lv[COL_CLK] - public_values.initial_clk);
+        yield_constr.constraint_first_row(lv[COL_PC] - public_values.initial_pc);
+        yield_constr.constraint_last_row(lv[COL_CLK] - public_values.final_clk);
+        yield_constr.constraint_last_row(lv[COL_PC] - public_values.final_pc);
+        for (r, initial_r, final_r) in izip!(
+            regs,
+            public_values.initial_registers,
+            public_values.final_registers
+        ) {
+            yield_constr.constraint_first_row(r - initial_r);
+            yield_constr.constraint_last_row(r - final_r);
+        }
 
         // When oprand exists, op1 is imm.
         yield_constr.constraint(lv[COL_OP1_IMM] * (lv[COL_OP1] - lv[COL_IMM_VAL]));
 
-        // Only one opcode selector enabled.
+        // Only one opcode selector enabled, but on the last row of a segment
+        // ending before `END`.
         let sum_s_op: P = op_selectors.into_iter().sum();
-        yield_constr.constraint(P::ONES - sum_s_op);
+        yield_constr.constraint_transition(P::ONES - sum_s_op);
+        yield_constr.constraint_last_row((P::ONES - sum_s_op) * public_values.is_final);
+        yield_constr.constraint_last_row((lv[COL_INST] - instruction) * public_values.is_final);
 
         // 2. Constrain state changing.
+        // The last row of a segment is the first row of the next one, so state
+        // changes are only constrained between rows.
         // clk
         // if instruction is end, we don't need to contrain clk.
-        yield_constr
-            .constraint((P::ONES - lv[COL_S_END]) * (nv[COL_CLK] - (lv[COL_CLK] + P::ONES)));
+        yield_constr.constraint_transition(
+            (P::ONES - lv[COL_S_END]) * (nv[COL_CLK] - (lv[COL_CLK] + P::ONES)),
+        );
 
         // flag
         yield_constr.constraint(lv[COL_FLAG] * (P::ONES - lv[COL_FLAG]));
-        let s_cmp = lv[COL_S_EQ] + lv[COL_S_NEQ] + lv[COL_S_GTE] + lv[COL_S_CJMP] + lv[COL_S_END];
-        yield_constr.constraint((P::ONES - s_cmp) * (nv[COL_FLAG] - lv[COL_FLAG]));
+        let s_cmp = lv[COL_S_EQ]
+            + lv[COL_S_NEQ]
+            + lv[COL_S_GTE]
+            + lv[COL_S_CJMP]
+            + lv[COL_S_END]
+            + lv[COL_S_ECDSA];
+        yield_constr.constraint_transition((P::ONES - s_cmp) * (nv[COL_FLAG] - lv[COL_FLAG]));
 
         // reg
         for (dst, l_r, n_r) in izip!(
@@ -300,7 +398,7 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for CpuStark<F, D
                 + lv[COL_FLAG] * lv[COL_OP1]);
         let pc_call = lv[COL_S_CALL] * lv[COL_OP1];
         let pc_ret = lv[COL_S_RET] * lv[COL_DST];
-        yield_constr.constraint(
+        yield_constr.constraint_transition(
             (P::ONES - lv[COL_S_END])
                 * (nv[COL_PC] - (pc_incr + pc_jmp + pc_cjmp + pc_call + pc_ret)),
         );
@@ -309,6 +407,7 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for CpuStark<F, D
         add::eval_packed_generic(lv, nv, yield_constr);
         mul::eval_packed_generic(lv, nv, yield_constr);
         cmp::eval_packed_generic(lv, nv, yield_constr);
+        ecdsa::eval_packed_generic(lv, nv, yield_constr);
         assert::eval_packed_generic(lv, nv, yield_constr);
         mov::eval_packed_generic(lv, nv, yield_constr);
         jmp::eval_packed_generic(lv, nv, yield_constr);
@@ -316,8 +415,9 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for CpuStark<F, D
         call::eval_packed_generic(lv, nv, yield_constr);
         ret::eval_packed_generic(lv, nv, yield_constr);
 
-        // Last row must be `END`
-        yield_constr.constraint_last_row(lv[COL_S_END] - P::ONES);
+        // Last row must be `END`, unless the execution continues in the next
+        // segment.
+        yield_constr.constraint_last_row((lv[COL_S_END] - P::ONES) * public_values.is_final);
 
         // Padding row must be `END`
         yield_constr.constraint_transition(lv[COL_S_END] * (nv[COL_S_END] - P::ONES));
@@ -382,6 +482,8 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for CpuStark<F, D
             lv[COL_S_GTE],
             lv[COL_S_PSDN],
             lv[COL_S_ECDSA],
+            lv[COL_S_KECCAK],
+            lv[COL_S_PROPHET],
         ];
         op_selectors.iter().for_each(|s| {
             let s_boolean = builder.sub_extension(one, *s);
@@ -399,9 +501,10 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for CpuStark<F, D
             .zip(opcode_shift.iter())
             .map(|(selector, shift)| builder.mul_extension(*selector, *shift))
             .collect::<Vec<_>>();
-        let opcodes_cs = opcodes
+        let opcode = opcodes
             .iter()
             .fold(zero, |acc, s| builder.add_extension(acc, *s));
+        let opcodes_cs = builder.sub_extension(lv[COL_OPCODE], opcode);
         yield_constr.constraint(builder, opcodes_cs);
 
         // Constrain instruction encoding.
@@ -442,30 +545,22 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for CpuStark<F, D
         }
 
         instruction = builder.add_extension(lv[COL_OPCODE], instruction);
+        // The last row of a segment ending before `END` is the first step of
+        // the next one, not executed, with no selector. See `Process::run`.
         let inst_cs = builder.sub_extension(lv[COL_INST], instruction);
-        yield_constr.constraint(builder, inst_cs);
-
-        // We constrain raw inst and inst.
-        // First constrain compress consistency
-        let beta = builder.constant_extension(F::Extension::from_basefield(
-            self.get_compress_challenge().unwrap(),
-        ));
-        let raw_cs = builder.mul_add_extension(lv[COL_RAW_INST], beta, lv[COL_RAW_PC]);
-        let raw_cs = builder.sub_extension(raw_cs, lv[COL_ZIP_RAW]);
-        yield_constr.constraint(builder, raw_cs);
-
-        let inst_cs = builder.mul_add_extension(lv[COL_INST], beta, lv[COL_PC]);
-        let inst_cs = builder.sub_extension(inst_cs, lv[COL_ZIP_EXED]);
-        yield_constr.constraint(builder, inst_cs);
-
-        // Then check raw inst and inst's lookup logic.
-        eval_lookups_circuit(
-            builder,
-            vars,
-            yield_constr,
-            COL_PER_ZIP_EXED,
-            COL_PER_ZIP_RAW,
-        );
+        yield_constr.constraint_transition(builder, inst_cs);
+
+        // Raw program rows consistency.
+        let raw_filter_boolean = builder.sub_extension(one, lv[COL_RAW_FILTER]);
+        let raw_filter_boolean_cs = builder.mul_extension(lv[COL_RAW_FILTER], raw_filter_boolean);
+        yield_constr.constraint(builder, raw_filter_boolean_cs);
+        yield_constr.constraint_first_row(builder, raw_filter_boolean);
+        let n_raw_filter_boolean = builder.sub_extension(one, nv[COL_RAW_FILTER]);
+        for col in [COL_RAW_PC, COL_RAW_INST, COL_RAW_IMM] {
+            let raw_diff = builder.sub_extension(nv[col], lv[col]);
+            let raw_cs = builder.mul_extension(n_raw_filter_boolean, raw_diff);
+            yield_constr.constraint_transition(builder, raw_cs);
+        }
 
         // Only one register used for op0.
         let sum_s_op0 = s_op0s
@@ -529,24 +624,51 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for CpuStark<F, D
             .fold(zero, |acc, s| builder.add_extension(acc, *s));
         let dst_sum_cs = builder.sub_extension(lv[COL_DST], dst_sum);
         let dst_sum_cs = builder.mul_extension(sum_s_dst, dst_sum_cs);
-        yield_constr.constraint(builder, dst_sum_cs);
-
-        // Last row's 'next row' (AKA first row) regs should be all zeros.
-        n_regs
-            .iter()
-            .for_each(|nr| yield_constr.constraint_last_row(builder, *nr));
+        yield_constr.constraint_transition(builder, dst_sum_cs);
+
+        // First row and last row state are public values.
+        let public_values = match self.public_value_targets {
+            Some(targets) => targets.map(|t| builder.convert_to_ext(t)),
+            None => self
+                .get_public_values()
+                .unwrap()
+                .map(|v| builder.constant_extension(F::Extension::from_basefield(v))),
+        };
+        let initial_clk_cs = builder.sub_extension(lv[COL_CLK], public_values.initial_clk);
+        yield_constr.constraint_first_row(builder, initial_clk_cs);
+        let initial_pc_cs = builder.sub_extension(lv[COL_PC], public_values.initial_pc);
+        yield_constr.constraint_first_row(builder, initial_pc_cs);
+        let final_clk_cs = builder.sub_extension(lv[COL_CLK], public_values.final_clk);
+        yield_constr.constraint_last_row(builder, final_clk_cs);
+        let final_pc_cs = builder.sub_extension(lv[COL_PC], public_values.final_pc);
+        yield_constr.constraint_last_row(builder, final_pc_cs);
+        for (r, initial_r, final_r) in izip!(
+            regs,
+            public_values.initial_registers,
+            public_values.final_registers
+        ) {
+            let initial_cs = builder.sub_extension(r, initial_r);
+            yield_constr.constraint_first_row(builder, initial_cs);
+            let final_cs = builder.sub_extension(r, final_r);
+            yield_constr.constraint_last_row(builder, final_cs);
+        }
 
         // When oprand exists, op1 is imm.
         let op1_imm_val_cs = builder.sub_extension(lv[COL_OP1], lv[COL_IMM_VAL]);
         let op1_imm_val_cs = builder.mul_extension(lv[COL_OP1_IMM], op1_imm_val_cs);
         yield_constr.constraint(builder, op1_imm_val_cs);
 
-        // Only one opcode selector enabled.
+        // Only one opcode selector enabled, but on the last row of a segment
+        // ending before `END`.
         let sum_s_op = op_selectors
             .iter()
             .fold(zero, |acc, s| builder.add_extension(acc, *s));
         let sum_s_op_cs = builder.sub_extension(one, sum_s_op);
-        yield_constr.constraint(builder, sum_s_op_cs);
+        yield_constr.constraint_transition(builder, sum_s_op_cs);
+        let last_sum_s_op_cs = builder.mul_extension(sum_s_op_cs, public_values.is_final);
+        yield_constr.constraint_last_row(builder, last_sum_s_op_cs);
+        let last_inst_cs = builder.mul_extension(inst_cs, public_values.is_final);
+        yield_constr.constraint_last_row(builder, last_inst_cs);
 
         // 2. Constrain state changing.
         // clk
@@ -555,7 +677,7 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for CpuStark<F, D
         let clk_cs = builder.sub_extension(nv[COL_CLK], clk_cs);
         let end_boolean = builder.sub_extension(one, lv[COL_S_END]);
         let clk_cs = builder.mul_extension(end_boolean, clk_cs);
-        yield_constr.constraint(builder, clk_cs);
+        yield_constr.constraint_transition(builder, clk_cs);
 
         // flag
         let flag_boolean = builder.sub_extension(one, lv[COL_FLAG]);
@@ -567,13 +689,14 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for CpuStark<F, D
             lv[COL_S_GTE],
             lv[COL_S_CJMP],
             lv[COL_S_END],
+            lv[COL_S_ECDSA],
         ]
         .iter()
         .fold(zero, |acc, s| builder.add_extension(acc, *s));
         let flag_diff = builder.sub_extension(nv[COL_FLAG], lv[COL_FLAG]);
         let s_cmp_boolean = builder.sub_extension(one, s_cmp);
         let flag_cs = builder.mul_extension(s_cmp_boolean, flag_diff);
-        yield_constr.constraint(builder, flag_cs);
+        yield_constr.constraint_transition(builder, flag_cs);
 
         // reg
         for (dst, l_r, n_r) in izip!(
@@ -595,7 +718,7 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for CpuStark<F, D
         let fp_diff = builder.sub_extension(n_regs[REGISTER_NUM - 1], regs[REGISTER_NUM - 1]);
         let fp_cs = builder.mul_many_extension([ret_boolean, fp_boolean, fp_diff]);
         let fp_cs = builder.add_extension(ret_cs, fp_cs);
-        yield_constr.constraint(builder, fp_cs);
+        yield_constr.constraint_transition(builder, fp_cs);
 
         // pc
         // if instruction is end, we don't need to constrain pc.
@@ -620,12 +743,13 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for CpuStark<F, D
             builder.add_many_extension([pc_incr_cs, pc_jmp, pc_cjmp_cs, pc_call, pc_ret]);
         let pc_diff = builder.sub_extension(nv[COL_PC], pc_part_cs);
         let pc_cs = builder.mul_extension(end_boolean, pc_diff);
-        yield_constr.constraint(builder, pc_cs);
+        yield_constr.constraint_transition(builder, pc_cs);
 
         // opcode
         add::eval_ext_circuit(builder, lv, nv, yield_constr);
         mul::eval_ext_circuit(builder, lv, nv, yield_constr);
         cmp::eval_ext_circuit(builder, lv, nv, yield_constr);
+        ecdsa::eval_ext_circuit(builder, lv, nv, yield_constr);
         assert::eval_ext_circuit(builder, lv, nv, yield_constr);
         mov::eval_ext_circuit(builder, lv, nv, yield_constr);
         jmp::eval_ext_circuit(builder, lv, nv, yield_constr);
@@ -633,24 +757,34 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for CpuStark<F, D
         call::eval_ext_circuit(builder, lv, nv, yield_constr);
         ret::eval_ext_circuit(builder, lv, nv, yield_constr);
 
-        // Last row must be `END`
+        // Last row must be `END`, unless the execution continues in the next
+        // segment.
         let last_end_cs = builder.sub_extension(lv[COL_S_END], one);
-        yield_constr.constraint(builder, last_end_cs);
+        let last_end_cs = builder.mul_extension(last_end_cs, public_values.is_final);
+        yield_constr.constraint_last_row(builder, last_end_cs);
 
         // Padding row must be `END`
         let next_end_boolean = builder.sub_extension(nv[COL_S_END], one);
         let next_end_cs = builder.mul_extension(lv[COL_S_END], next_end_boolean);
-        yield_constr.constraint(builder, next_end_cs);
+        yield_constr.constraint_transition(builder, next_end_cs);
     }
 
     fn constraint_degree(&self) -> usize {
         4
     }
+
+    fn lookups(&self) -> Vec<Lookup> {
+        vec![Lookup {
+            columns: vec![vec![COL_PC, COL_INST, COL_IMM_VAL]],
+            table_columns: vec![COL_RAW_PC, COL_RAW_INST, COL_RAW_IMM],
+            frequencies_column: COL_RAW_FREQ,
+        }]
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::generation::cpu::generate_cpu_trace;
+    use crate::generation::cpu::{cpu_public_values, generate_cpu_trace};
     use {
         super::*,
         core::program::Program,
@@ -681,11 +815,12 @@ mod tests {
         let mut process = Process::new();
         let _ = process.execute(&mut program);
 
-        let (cpu_rows, beta) =
-            generate_cpu_trace::<F>(&program.trace.exec, &program.trace.raw_binary_instructions);
+        let cpu_rows =
+            generate_cpu_trace::<F>(&program.trace.exec, &program.trace.raw_binary_instructions)
+                .unwrap();
 
         let mut stark = S::default();
-        stark.set_compress_challenge(beta).unwrap();
+        stark.set_public_values(cpu_public_values(&cpu_rows));
         let len = cpu_rows.len();
         let last = F::primitive_root_of_unity(log2_strict(len)).inverse();
         let subgroup =
@@ -0,0 +1,28 @@
+use super::columns::*;
+use crate::stark::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+use plonky2::{
+    field::{extension::Extendable, packed::PackedField},
+    hash::hash_types::RichField,
+    iop::ext_target::ExtensionTarget,
+    plonk::circuit_builder::CircuitBuilder,
+};
+
+// The flag of an ecdsa instruction is aux0, which the ecdsa table checks.
+pub(crate) fn eval_packed_generic<P: PackedField>(
+    lv: &[P; NUM_CPU_COLS],
+    nv: &[P; NUM_CPU_COLS],
+    yield_constr: &mut ConstraintConsumer<P>,
+) {
+    yield_constr.constraint_transition(lv[COL_S_ECDSA] * (nv[COL_FLAG] - lv[COL_AUX0]));
+}
+
+pub(crate) fn eval_ext_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    lv: &[ExtensionTarget<D>; NUM_CPU_COLS],
+    nv: &[ExtensionTarget<D>; NUM_CPU_COLS],
+    yield_constr: &mut RecursiveConstraintConsumer<F, D>,
+) {
+    let flag_diff = builder.sub_extension(nv[COL_FLAG], lv[COL_AUX0]);
+    let cs = builder.mul_extension(lv[COL_S_ECDSA], flag_diff);
+    yield_constr.constraint_transition(builder, cs);
+}
@@ -12,7 +12,7 @@ pub(crate) fn eval_packed_generic<P: PackedField>(
     nv: &[P; NUM_CPU_COLS],
     yield_constr: &mut ConstraintConsumer<P>,
 ) {
-    yield_constr.constraint(lv[COL_S_JMP] * (nv[COL_PC] - lv[COL_OP1]));
+    yield_constr.constraint_transition(lv[COL_S_JMP] * (nv[COL_PC] - lv[COL_OP1]));
 }
 
 pub(crate) fn eval_ext_circuit<F: RichField + Extendable<D>, const D: usize>(
@@ -23,5 +23,5 @@ pub(crate) fn eval_ext_circuit<F: RichField + Extendable<D>, const D: usize>(
 ) {
     let ret = builder.sub_extension(nv[COL_PC], lv[COL_OP1]);
     let cs = builder.mul_extension(lv[COL_S_JMP], ret);
-    yield_constr.constraint(builder, cs);
+    yield_constr.constraint_transition(builder, cs);
 }
@@ -5,6 +5,7 @@ mod cjmp;
 mod cmp;
 pub mod columns;
 pub mod cpu_stark;
+mod ecdsa;
 mod jmp;
 mod mov;
 mod mul;
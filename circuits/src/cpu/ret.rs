@@ -20,7 +20,7 @@ pub(crate) fn eval_packed_generic<P: PackedField>(
     let dst_cs = lv[COL_DST] - nv[COL_PC];
     let aux0_cs = lv[COL_AUX0] + P::ONES + P::ONES - fp;
 
-    yield_constr.constraint(lv[COL_S_RET] * (op0_cs + dst_cs + aux0_cs));
+    yield_constr.constraint_transition(lv[COL_S_RET] * (op0_cs + dst_cs + aux0_cs));
 }
 
 pub(crate) fn eval_ext_circuit<F: RichField + Extendable<D>, const D: usize>(
@@ -43,5 +43,5 @@ pub(crate) fn eval_ext_circuit<F: RichField + Extendable<D>, const D: usize>(
     let no_s_css = builder.add_many_extension([op0_cs, dst_cs, aux0_cs]);
     let cs = builder.mul_extension(lv[COL_S_RET], no_s_css);
 
-    yield_constr.constraint(builder, cs);
+    yield_constr.constraint_transition(builder, cs);
 }
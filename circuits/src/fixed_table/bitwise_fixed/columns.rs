@@ -1,56 +1,34 @@
-// // 2022-12-26: written by xb
+// 2022-12-26: written by xb
 
-// /* Rangecheck_Fixed_Table construction as follows:
-// +-----+-----------+-----------+-----------
-// | TAG |    OP0    |    OP0    |    OP0
-// +-----+-----------+-----------+-----------
-// +-----+-----------+-----------+-----------
-// |  1  | 000000000 | 000000000 | 000000000
-// +-----+-----------+-----------+-----------
-// +-----+-----------+-----------+-----------
-// |  1  | 000000000 | 000000001 | 000000000
-// +-----+-----------+-----------+-----------
-// +-----+-----------+-----------+---------
-// | ... | ......... | ......... | .........
-// +-----+-----------+-----------+---------
-// +-----+-----------+-----------+-----------
-// |  1  | 11111111  | 11111111  | 11111111  // 2^15 + 2^7 - 1
-// +-----+-----------+-----------+-----------
-// +-----+-----------+-----------+-----------
-// |  2  | 000000000 | 000000000 | 000000000 // 2^ 15 + 2^7
-// +-----+-----------+-----------+-----------
-// +-----+-----------+-----------+-----------
-// |  2  | 000000000 | 000000001 | 000000001
-// +-----+-----------+-----------+-----------
-// +-----+-----------+-----------+---------
-// | ... | ......... | ......... | .........
-// +-----+-----------+-----------+---------
-// +-----+-----------+-----------+-----------
-// |  2  | 11111111  | 11111111  | 11111111  // 2 * (2^15 + 2^7) - 1
-// +-----+-----------+-----------+-----------
-// +-----+-----------+-----------+-----------
-// |  3  | 000000000 | 000000000 | 000000000 // 2 * (2^15 + 2^7)
-// +-----+-----------+-----------+-----------
-// +-----+-----------+-----------+-----------
-// |  3  | 000000000 | 000000001 | 000000001
-// +-----+-----------+-----------+-----------
-// +-----+-----------+-----------+---------
-// | ... | ......... | ......... | .........
-// +-----+-----------+-----------+---------
-// +-----+-----------+-----------+-----------
-// |  3  | 11111111  | 11111110  | 00000001
-// +-----+-----------+-----------+-----------
-// +-----+-----------+-----------+-----------
-// |  3  | 11111111  | 11111111  | 00000000  // 3 * (2 ^ 15 + 2^7) - 1
-// +-----+-----------+-----------+-----------
-// */
-// // 1 : AND Operation
-// // 2 : OR  Operation
-// // 3 : XOR Operation
-// pub(crate) const TAG: usize = 0;
+use std::ops::Range;
 
-// pub(crate) const OP0: usize = TAG + 1;
-// pub(crate) const OP1: usize = OP0 + 1;
-// pub(crate) const RES: usize = OP1 + 1;
+/* Bitwise_Fixed_Table construction as follows:
++-----------+-----------+-----------+------+
+|    OP0    |    OP1    |  RES_AND  | FREQ |
++-----------+-----------+-----------+------+
++-----------+-----------+-----------+------+
+| 00000000  | 00000000  | 00000000  |  m0  |
++-----------+-----------+-----------+------+
++-----------+-----------+-----------+------+
+| 00000000  | 00000001  | 00000000  |  m1  |
++-----------+-----------+-----------+------+
++-----------+-----------+-----------+------+
+| ......... | ......... | ......... | .... |
++-----------+-----------+-----------+------+
++-----------+-----------+-----------+------+
+| 11111111  | 11111111  | 11111111  |  mn  |  // 2^16 - 1
++-----------+-----------+-----------+------+
+OP0 and OP1 are stored as bits. OR and XOR are derived from AND:
+    op0 | op1 = op0 + op1 - op0 & op1
+    op0 ^ op1 = op0 + op1 - 2 * (op0 & op1)
+OP0, OP1 and RES_AND are preprocessed columns, fixed by the verifier key.
+FREQ is how many times the row is looked up by the bitwise limbs.
+*/
+pub(crate) const OP0_BITS: Range<usize> = 0..8;
+pub(crate) const OP1_BITS: Range<usize> = OP0_BITS.end..OP0_BITS.end + 8;
+pub(crate) const RES_AND: usize = OP1_BITS.end; //16
+pub(crate) const FREQ: usize = RES_AND + 1; //17
 
-// pub(crate) const COL_NUM: usize = RES + 1; //4
+pub(crate) const COL_NUM: usize = FREQ + 1; //18
+
+pub(crate) const BITWISE_U8_SIZE: usize = 1 << 16;
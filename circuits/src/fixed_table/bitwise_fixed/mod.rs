@@ -1,2 +1,2 @@
-// pub mod bitwise_fixed_stark;
-// pub mod columns;
+pub mod bitwise_fixed_stark;
+pub mod columns;
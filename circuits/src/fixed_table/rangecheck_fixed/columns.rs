@@ -1,39 +1,27 @@
-// // 2022-12-26: written by xb
+// 2022-12-26: written by xb
 
-// /* Rangecheck_Fixed_Table construction as follows:
-// +-----+---------
-// | TAG | Value
-// +-----+---------
-// +-----+---------
-// |  1  | 0
-// +-----+---------
-// +-----+---------
-// |  1  | 1
-// +-----+---------
-// +-----+---------
-// | ... | ...
-// +-----+---------
-// +-----+---------
-// |  1  | 2^8 - 1
-// +-----+---------
-// +-----+---------
-// |  0  | 2^8
-// +-----+---------
-// +-----+---------
-// |  0  | 2^8 + 1
-// +-----+---------
-// +-----+---------
-// | ... | ...
-// +-----+---------
-// +-----+---------
-// |  0  | 2^16 - 1
-// +-----+---------
+/* Rangecheck_Fixed_Table construction as follows:
++---------+------+
+|   VAL   | FREQ |
++---------+------+
++---------+------+
+|    0    |  m0  |
++---------+------+
++---------+------+
+|    1    |  m1  |
++---------+------+
++---------+------+
+|   ...   | ...  |
++---------+------+
++---------+------+
+| 2^16-1  |  mn  |
++---------+------+
+VAL is a preprocessed column, fixed by the verifier key.
+FREQ is how many times VAL is looked up by the rangecheck limbs.
+*/
+pub(crate) const VAL: usize = 0;
+pub(crate) const FREQ: usize = VAL + 1;
 
-// 1 : [0, 2^8)
-// 1, 0 : [0, 2^16)
-// */
-// //pub(crate) const TAG: usize = 0;
+pub(crate) const COL_NUM: usize = FREQ + 1; //2
 
-// pub(crate) const VAL: usize = 0;
-
-// pub(crate) const COL_NUM: usize = VAL + 1; //4
+pub(crate) const RANGE_CHECK_U16_SIZE: usize = 1 << 16;
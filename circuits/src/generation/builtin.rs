@@ -1,15 +1,35 @@
-use core::trace::trace::{BitwiseCombinedRow, CmpRow, RangeCheckRow};
-
-use plonky2::field::types::PrimeField64;
+use core::trace::trace::{
+    BitwiseCombinedRow, CmpRow, EcdsaRow, KeccakRow, PoseidonRow, RangeCheckRow,
+};
+
+use executor::coprocessor::ecdsa::{
+    bit, canonical_words, AffinePoint, EcdsaInput, BASE_ORDER, GENERATOR_PLUS_OFFSET,
+    LADDER_OFFSET, LADDER_STEPS, SCALAR_ORDER,
+};
+use executor::coprocessor::keccak::{
+    absorb, input_words, keccak_round, lanes_to_words, tail_word, words_to_lanes, SpongeBlock,
+    ROUND_CONSTANTS,
+};
+use itertools::Itertools;
+use num::{BigInt, Integer, Signed, Zero};
+use plonky2::field::ops::Square;
+use plonky2::field::secp256k1_base::Secp256K1Base;
+use plonky2::field::secp256k1_scalar::Secp256K1Scalar;
+use plonky2::field::types::{Field, PrimeField64};
 use plonky2::hash::hash_types::RichField;
-use plonky2::iop::challenger::Challenger;
-use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
-use plonky2::util::transpose;
+use plonky2::hash::hashing::SPONGE_WIDTH;
+use plonky2::hash::poseidon::{HALF_N_FULL_ROUNDS, N_PARTIAL_ROUNDS};
 
 use crate::builtins::bitwise::columns as bitwise;
 use crate::builtins::cmp::columns as cmp;
+use crate::builtins::ecdsa::columns as ecdsa;
+use crate::builtins::ecdsa::ecdsa_stark::{
+    limb, relation_coefficients, Source, BASE_ROWS, REGISTER_WRITES, SCALAR_ROWS,
+};
+use crate::builtins::keccak::columns as keccak;
+use crate::builtins::keccak_sponge::columns as keccak_sponge;
+use crate::builtins::poseidon::columns as poseidon;
 use crate::builtins::rangecheck::columns as rangecheck;
-use crate::stark::lookup::permuted_cols;
 
 // add by xb 2023-1-5
 // case 1:
@@ -31,7 +51,7 @@ use crate::stark::lookup::permuted_cols;
 //      looked_table: <0,1,2,3,4,5,6,7,8,9,10,11,12,13,14,15>
 pub fn generate_builtins_bitwise_trace<F: RichField>(
     cells: &[BitwiseCombinedRow],
-) -> (Vec<[F; bitwise::COL_NUM_BITWISE]>, F) {
+) -> Vec<[F; bitwise::COL_NUM_BITWISE]> {
     let mut trace: Vec<[F; bitwise::COL_NUM_BITWISE]> = cells
         .iter()
         .map(|c| {
@@ -46,264 +66,38 @@ pub fn generate_builtins_bitwise_trace<F: RichField>(
             row[bitwise::OP0_LIMBS.start] = F::from_canonical_u64(c.op0_0.to_canonical_u64());
             row[bitwise::OP0_LIMBS.start + 1] = F::from_canonical_u64(c.op0_1.to_canonical_u64());
             row[bitwise::OP0_LIMBS.start + 2] = F::from_canonical_u64(c.op0_2.to_canonical_u64());
-            row[bitwise::OP0_LIMBS.end] = F::from_canonical_u64(c.op0_3.to_canonical_u64());
+            row[bitwise::OP0_LIMBS.start + 3] = F::from_canonical_u64(c.op0_3.to_canonical_u64());
 
             row[bitwise::OP1_LIMBS.start] = F::from_canonical_u64(c.op1_0.to_canonical_u64());
             row[bitwise::OP1_LIMBS.start + 1] = F::from_canonical_u64(c.op1_1.to_canonical_u64());
             row[bitwise::OP1_LIMBS.start + 2] = F::from_canonical_u64(c.op1_2.to_canonical_u64());
-            row[bitwise::OP1_LIMBS.end] = F::from_canonical_u64(c.op1_3.to_canonical_u64());
+            row[bitwise::OP1_LIMBS.start + 3] = F::from_canonical_u64(c.op1_3.to_canonical_u64());
 
             row[bitwise::RES_LIMBS.start] = F::from_canonical_u64(c.res_0.to_canonical_u64());
             row[bitwise::RES_LIMBS.start + 1] = F::from_canonical_u64(c.res_1.to_canonical_u64());
             row[bitwise::RES_LIMBS.start + 2] = F::from_canonical_u64(c.res_2.to_canonical_u64());
-            row[bitwise::RES_LIMBS.end] = F::from_canonical_u64(c.res_3.to_canonical_u64());
+            row[bitwise::RES_LIMBS.start + 3] = F::from_canonical_u64(c.res_3.to_canonical_u64());
+
+            let and_limbs = [
+                (c.op0_0, c.op1_0),
+                (c.op0_1, c.op1_1),
+                (c.op0_2, c.op1_2),
+                (c.op0_3, c.op1_3),
+            ];
+            for (col, (op0, op1)) in bitwise::AND_LIMBS.zip(and_limbs) {
+                row[col] = F::from_canonical_u64(op0.to_canonical_u64() & op1.to_canonical_u64());
+            }
 
             row
         })
         .collect();
 
-    if trace.is_empty() {
-        trace.extend([
-            [F::ZERO; bitwise::COL_NUM_BITWISE],
-            [F::ZERO; bitwise::COL_NUM_BITWISE],
-        ]);
-        (trace, F::default())
-    } else {
-        // Ensure the max rows number.
-        let trace_len = trace.len();
-        let max_trace_len = trace_len
-            .max(bitwise::RANGE_CHECK_U8_SIZE)
-            .max(bitwise::BITWISE_U8_SIZE);
-
-        let mut new_row_len = max_trace_len;
-
-        if !max_trace_len.is_power_of_two() {
-            new_row_len = max_trace_len.next_power_of_two();
-        }
-
-        // padding for exe trace
-        //if !max_trace_len.is_power_of_two() {
-        //let new_row_len = max_trace_len.next_power_of_two();
-        //let end_row = trace[trace_len - 1];
-        for _ in trace_len..new_row_len {
-            //let mut new_row = end_row;
-            //new_row[bitwise::FILTER] = F::ZERO;
-            trace.push([F::ZERO; bitwise::COL_NUM_BITWISE]);
-        }
-
-        // add fix bitwise info
-        // for 2^8 case, the row is 2^15 + 2^7
-        // fixed at 2023-1-16, for 2^8 case, row number is 2^16
-        let mut index = 0;
-        for op0 in 0..bitwise::RANGE_CHECK_U8_SIZE {
-            // add fix rangecheck info
-            trace[op0][bitwise::FIX_RANGE_CHECK_U8] = F::from_canonical_usize(op0);
-
-            //for op1 in op0..bitwise::RANGE_CHECK_U8_SIZE {
-            for op1 in 0..bitwise::RANGE_CHECK_U8_SIZE {
-                // exe the AND OPE ration
-                let res_and = op0 & op1;
-
-                trace[index][bitwise::FIX_BITWSIE_OP0] = F::from_canonical_usize(op0);
-                trace[index][bitwise::FIX_BITWSIE_OP1] = F::from_canonical_usize(op1);
-                trace[index][bitwise::FIX_BITWSIE_RES] = F::from_canonical_usize(res_and);
-                trace[index][bitwise::FIX_TAG] = F::from_canonical_usize(0);
-
-                let res_or = op0 | op1;
-
-                trace[bitwise::BITWISE_U8_SIZE_PER + index][bitwise::FIX_BITWSIE_OP0] =
-                    F::from_canonical_usize(op0);
-                trace[bitwise::BITWISE_U8_SIZE_PER + index][bitwise::FIX_BITWSIE_OP1] =
-                    F::from_canonical_usize(op1);
-                trace[bitwise::BITWISE_U8_SIZE_PER + index][bitwise::FIX_BITWSIE_RES] =
-                    F::from_canonical_usize(res_or);
-                trace[bitwise::BITWISE_U8_SIZE_PER + index][bitwise::FIX_TAG] =
-                    F::from_canonical_usize(1);
-
-                let res_xor = op0 ^ op1;
-
-                trace[bitwise::BITWISE_U8_SIZE_PER * 2 + index][bitwise::FIX_BITWSIE_OP0] =
-                    F::from_canonical_usize(op0);
-                trace[bitwise::BITWISE_U8_SIZE_PER * 2 + index][bitwise::FIX_BITWSIE_OP1] =
-                    F::from_canonical_usize(op1);
-                trace[bitwise::BITWISE_U8_SIZE_PER * 2 + index][bitwise::FIX_BITWSIE_RES] =
-                    F::from_canonical_usize(res_xor);
-                trace[bitwise::BITWISE_U8_SIZE_PER * 2 + index][bitwise::FIX_TAG] =
-                    F::from_canonical_usize(2);
-
-                index += 1;
-            }
-        }
+    // Pad trace to power of two, zero rows are a valid AND.
+    let trace_len = trace.len();
+    let new_row_len = trace_len.next_power_of_two().max(2);
+    trace.resize(new_row_len, [F::ZERO; bitwise::COL_NUM_BITWISE]);
 
-        // TODO: We should choose proper columns for oracle.
-        let mut challenger =
-            Challenger::<F, <PoseidonGoldilocksConfig as GenericConfig<2>>::Hasher>::new();
-        let mut op0_columns = vec![];
-        let mut op1_columns = vec![];
-        let mut res_columns = vec![];
-        trace.iter().for_each(|row| {
-            op0_columns.extend(row[bitwise::OP0_LIMBS].iter().clone());
-            op1_columns.extend(row[bitwise::OP1_LIMBS].iter().clone());
-            res_columns.extend(row[bitwise::RES_LIMBS].iter().clone());
-        });
-        challenger.observe_elements(&[op0_columns, op1_columns, res_columns].concat());
-        let beta = challenger.get_challenge();
-
-        for t in trace.iter_mut().take(max_trace_len) {
-            t[bitwise::COMPRESS_LIMBS.start] = t[bitwise::TAG]
-                + t[bitwise::OP0_LIMBS.start] * beta
-                + t[bitwise::OP1_LIMBS.start] * beta * beta
-                + t[bitwise::RES_LIMBS.start] * beta * beta * beta;
-
-            t[bitwise::COMPRESS_LIMBS.start + 1] = t[bitwise::TAG]
-                + t[bitwise::OP0_LIMBS.start + 1] * beta
-                + t[bitwise::OP1_LIMBS.start + 1] * beta * beta
-                + t[bitwise::RES_LIMBS.start + 1] * beta * beta * beta;
-
-            t[bitwise::COMPRESS_LIMBS.start + 2] = t[bitwise::TAG]
-                + t[bitwise::OP0_LIMBS.start + 2] * beta
-                + t[bitwise::OP1_LIMBS.start + 2] * beta * beta
-                + t[bitwise::RES_LIMBS.start + 2] * beta * beta * beta;
-
-            t[bitwise::COMPRESS_LIMBS.start + 3] = t[bitwise::TAG]
-                + t[bitwise::OP0_LIMBS.start + 3] * beta
-                + t[bitwise::OP1_LIMBS.start + 3] * beta * beta
-                + t[bitwise::RES_LIMBS.start + 3] * beta * beta * beta;
-
-            t[bitwise::FIX_COMPRESS] = t[bitwise::FIX_TAG]
-                + t[bitwise::FIX_BITWSIE_OP0] * beta
-                + t[bitwise::FIX_BITWSIE_OP1] * beta * beta
-                + t[bitwise::FIX_BITWSIE_RES] * beta * beta * beta;
-        }
-
-        // Transpose to column-major form.
-        let trace_row_vecs: Vec<_> = trace.into_iter().map(|row| row.to_vec()).collect();
-        let mut trace_col_vecs = transpose(&trace_row_vecs);
-
-        // add the permutation information
-        for i in 0..4 {
-            // permuted for rangecheck
-            let (permuted_inputs, permuted_table) = permuted_cols(
-                &trace_col_vecs[bitwise::OP0_LIMBS.start + i],
-                &trace_col_vecs[bitwise::FIX_RANGE_CHECK_U8],
-            );
-
-            trace_col_vecs[bitwise::OP0_LIMBS_PERMUTED.start + i] = permuted_inputs;
-            trace_col_vecs[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + i] = permuted_table;
-
-            let (permuted_inputs, permuted_table) = permuted_cols(
-                &trace_col_vecs[bitwise::OP1_LIMBS.start + i],
-                &trace_col_vecs[bitwise::FIX_RANGE_CHECK_U8],
-            );
-
-            trace_col_vecs[bitwise::OP1_LIMBS_PERMUTED.start + i] = permuted_inputs;
-            trace_col_vecs[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + 4 + i] = permuted_table;
-
-            let (permuted_inputs, permuted_table) = permuted_cols(
-                &trace_col_vecs[bitwise::RES_LIMBS.start + i],
-                &trace_col_vecs[bitwise::FIX_RANGE_CHECK_U8],
-            );
-
-            trace_col_vecs[bitwise::RES_LIMBS_PERMUTED.start + i] = permuted_inputs;
-            trace_col_vecs[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + 8 + i] = permuted_table;
-
-            // permutation for bitwise
-            let (permuted_inputs, permuted_table) = permuted_cols(
-                &trace_col_vecs[bitwise::COMPRESS_LIMBS.start + i],
-                &trace_col_vecs[bitwise::FIX_COMPRESS],
-            );
-
-            trace_col_vecs[bitwise::COMPRESS_PERMUTED.start + i] = permuted_inputs;
-            trace_col_vecs[bitwise::FIX_COMPRESS_PERMUTED.start + i] = permuted_table;
-        }
-
-        let final_trace = transpose(&trace_col_vecs);
-
-        let trace_row_vecs: Vec<_> = final_trace
-            .into_iter()
-            .map(|row| vec_to_ary_bitwise(row))
-            .collect();
-
-        (trace_row_vecs, beta)
-    }
-}
-
-pub fn vec_to_ary_bitwise<F: RichField>(input: Vec<F>) -> [F; bitwise::COL_NUM_BITWISE] {
-    let mut ary = [F::ZERO; bitwise::COL_NUM_BITWISE];
-
-    ary[bitwise::FILTER] = input[bitwise::FILTER];
-    ary[bitwise::TAG] = input[bitwise::TAG];
-    ary[bitwise::OP0] = input[bitwise::OP0];
-    ary[bitwise::OP1] = input[bitwise::OP1];
-    ary[bitwise::RES] = input[bitwise::RES];
-    ary[bitwise::OP0_LIMBS.start] = input[bitwise::OP0_LIMBS.start];
-    ary[bitwise::OP0_LIMBS.start + 1] = input[bitwise::OP0_LIMBS.start + 1];
-    ary[bitwise::OP0_LIMBS.start + 2] = input[bitwise::OP0_LIMBS.start + 2];
-    ary[bitwise::OP0_LIMBS.start + 3] = input[bitwise::OP0_LIMBS.start + 3];
-    ary[bitwise::OP1_LIMBS.start] = input[bitwise::OP1_LIMBS.start];
-    ary[bitwise::OP1_LIMBS.start + 1] = input[bitwise::OP1_LIMBS.start + 1];
-    ary[bitwise::OP1_LIMBS.start + 2] = input[bitwise::OP1_LIMBS.start + 2];
-    ary[bitwise::OP1_LIMBS.start + 3] = input[bitwise::OP1_LIMBS.start + 3];
-    ary[bitwise::RES_LIMBS.start] = input[bitwise::RES_LIMBS.start];
-    ary[bitwise::RES_LIMBS.start + 1] = input[bitwise::RES_LIMBS.start + 1];
-    ary[bitwise::RES_LIMBS.start + 2] = input[bitwise::RES_LIMBS.start + 2];
-    ary[bitwise::RES_LIMBS.start + 3] = input[bitwise::RES_LIMBS.start + 3];
-    ary[bitwise::OP0_LIMBS_PERMUTED.start] = input[bitwise::OP0_LIMBS_PERMUTED.start];
-    ary[bitwise::OP0_LIMBS_PERMUTED.start + 1] = input[bitwise::OP0_LIMBS_PERMUTED.start + 1];
-    ary[bitwise::OP0_LIMBS_PERMUTED.start + 2] = input[bitwise::OP0_LIMBS_PERMUTED.start + 2];
-    ary[bitwise::OP0_LIMBS_PERMUTED.start + 3] = input[bitwise::OP0_LIMBS_PERMUTED.start + 3];
-    ary[bitwise::OP1_LIMBS_PERMUTED.start] = input[bitwise::OP1_LIMBS_PERMUTED.start];
-    ary[bitwise::OP1_LIMBS_PERMUTED.start + 1] = input[bitwise::OP1_LIMBS_PERMUTED.start + 1];
-    ary[bitwise::OP1_LIMBS_PERMUTED.start + 2] = input[bitwise::OP1_LIMBS_PERMUTED.start + 2];
-    ary[bitwise::OP1_LIMBS_PERMUTED.start + 3] = input[bitwise::OP1_LIMBS_PERMUTED.start + 3];
-    ary[bitwise::RES_LIMBS_PERMUTED.start] = input[bitwise::RES_LIMBS_PERMUTED.start];
-    ary[bitwise::RES_LIMBS_PERMUTED.start + 1] = input[bitwise::RES_LIMBS_PERMUTED.start + 1];
-    ary[bitwise::RES_LIMBS_PERMUTED.start + 2] = input[bitwise::RES_LIMBS_PERMUTED.start + 2];
-    ary[bitwise::RES_LIMBS_PERMUTED.start + 3] = input[bitwise::RES_LIMBS_PERMUTED.start + 3];
-    ary[bitwise::COMPRESS_LIMBS.start] = input[bitwise::COMPRESS_LIMBS.start];
-    ary[bitwise::COMPRESS_LIMBS.start + 1] = input[bitwise::COMPRESS_LIMBS.start + 1];
-    ary[bitwise::COMPRESS_LIMBS.start + 2] = input[bitwise::COMPRESS_LIMBS.start + 2];
-    ary[bitwise::COMPRESS_LIMBS.start + 3] = input[bitwise::COMPRESS_LIMBS.start + 3];
-    ary[bitwise::COMPRESS_PERMUTED.start] = input[bitwise::COMPRESS_PERMUTED.start];
-    ary[bitwise::COMPRESS_PERMUTED.start + 1] = input[bitwise::COMPRESS_PERMUTED.start + 1];
-    ary[bitwise::COMPRESS_PERMUTED.start + 2] = input[bitwise::COMPRESS_PERMUTED.start + 2];
-    ary[bitwise::COMPRESS_PERMUTED.start + 3] = input[bitwise::COMPRESS_PERMUTED.start + 3];
-    ary[bitwise::FIX_RANGE_CHECK_U8] = input[bitwise::FIX_RANGE_CHECK_U8];
-    ary[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start] =
-        input[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start];
-    ary[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + 1] =
-        input[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + 1];
-    ary[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + 2] =
-        input[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + 2];
-    ary[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + 3] =
-        input[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + 3];
-    ary[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + 4] =
-        input[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + 4];
-    ary[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + 5] =
-        input[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + 5];
-    ary[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + 6] =
-        input[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + 6];
-    ary[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + 7] =
-        input[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + 7];
-    ary[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + 8] =
-        input[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + 8];
-    ary[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + 9] =
-        input[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + 9];
-    ary[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + 10] =
-        input[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + 10];
-    ary[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + 11] =
-        input[bitwise::FIX_RANGE_CHECK_U8_PERMUTED.start + 11];
-    ary[bitwise::FIX_TAG] = input[bitwise::FIX_TAG];
-    ary[bitwise::FIX_BITWSIE_OP0] = input[bitwise::FIX_BITWSIE_OP0];
-    ary[bitwise::FIX_BITWSIE_OP1] = input[bitwise::FIX_BITWSIE_OP1];
-    ary[bitwise::FIX_BITWSIE_RES] = input[bitwise::FIX_BITWSIE_RES];
-    ary[bitwise::FIX_COMPRESS] = input[bitwise::FIX_COMPRESS];
-    ary[bitwise::FIX_COMPRESS_PERMUTED.start] = input[bitwise::FIX_COMPRESS_PERMUTED.start];
-    ary[bitwise::FIX_COMPRESS_PERMUTED.start + 1] = input[bitwise::FIX_COMPRESS_PERMUTED.start + 1];
-    ary[bitwise::FIX_COMPRESS_PERMUTED.start + 2] = input[bitwise::FIX_COMPRESS_PERMUTED.start + 2];
-    ary[bitwise::FIX_COMPRESS_PERMUTED.start + 3] = input[bitwise::FIX_COMPRESS_PERMUTED.start + 3];
-
-    ary
+    trace
 }
 
 pub fn generate_builtins_cmp_trace<F: RichField>(cells: &[CmpRow]) -> Vec<[F; cmp::COL_NUM_CMP]> {
@@ -387,89 +181,555 @@ pub fn generate_builtins_rangecheck_trace<F: RichField>(
         })
         .collect();
 
-    if trace.is_empty() {
-        let ary = [F::ZERO; rangecheck::COL_NUM_RC];
+    // Pad trace to power of two.
+    let trace_len = trace.len();
+    let new_row_len = trace_len.next_power_of_two().max(2);
+    trace.resize(new_row_len, [F::ZERO; rangecheck::COL_NUM_RC]);
 
-        trace.push(ary);
-        trace.push(ary);
+    trace
+}
 
-        trace
-    } else {
-        // Pad trace to power of two.
-        // Ensure the max rows number.
-        let trace_len = trace.len();
-        let max_trace_len = trace_len.max(rangecheck::RANGE_CHECK_U16_SIZE);
+/// `program_inputs` are the inputs of the program hash chains, see
+/// `program::program_hash_inputs`.
+pub fn generate_builtins_poseidon_trace<F: RichField>(
+    cells: &[PoseidonRow],
+    program_inputs: &[[F; poseidon::NUM_INPUTS]],
+) -> Vec<[F; poseidon::COL_NUM_POSEIDON]> {
+    let mut trace: Vec<[F; poseidon::COL_NUM_POSEIDON]> = cells
+        .iter()
+        .map(|c| {
+            let input = c.input.map(|e| F::from_canonical_u64(e.to_canonical_u64()));
+            let mut row = generate_poseidon_row(input);
+            debug_assert!((0..poseidon::NUM_OUTPUTS).all(|i| {
+                row[poseidon::col_output(i)].to_canonical_u64() == c.output[i].to_canonical_u64()
+            }));
+
+            row[poseidon::FILTER] = F::ONE;
+            row[poseidon::CLK] = F::from_canonical_u64(c.clk.to_canonical_u64());
+            row[poseidon::INPUT_ADDR] = F::from_canonical_u64(c.input_addr.to_canonical_u64());
+            row[poseidon::OUTPUT_ADDR] = F::from_canonical_u64(c.output_addr.to_canonical_u64());
+            row
+        })
+        .collect();
+    trace.extend(program_inputs.iter().map(|input| {
+        let mut row = generate_poseidon_row(*input);
+        row[poseidon::FILTER_PROGRAM] = F::ONE;
+        row
+    }));
+
+    // Pad trace to power of two, the padding rows hash zero inputs.
+    let trace_len = trace.len();
+    let new_row_len = trace_len.next_power_of_two().max(2);
+    trace.resize(
+        new_row_len,
+        generate_poseidon_row([F::ZERO; poseidon::NUM_INPUTS]),
+    );
+
+    trace
+}
 
-        let mut new_row_len = max_trace_len;
+// The s-box columns of the permutation of `input`, in the order of
+// `Poseidon::poseidon_naive`.
+fn generate_poseidon_row<F: RichField>(
+    input: [F; poseidon::NUM_INPUTS],
+) -> [F; poseidon::COL_NUM_POSEIDON] {
+    let mut row = [F::ZERO; poseidon::COL_NUM_POSEIDON];
+    let mut state = [F::ZERO; SPONGE_WIDTH];
+    for (i, x) in input.into_iter().enumerate() {
+        row[poseidon::col_input(i)] = x;
+        state[i] = x;
+    }
 
-        if !max_trace_len.is_power_of_two() {
-            new_row_len = max_trace_len.next_power_of_two();
+    let sbox =
+        |row: &mut [F; poseidon::COL_NUM_POSEIDON], x: F, cube_col: usize, out_col: usize| {
+            let cube = x.cube();
+            row[cube_col] = cube;
+            row[out_col] = x * cube.square();
+            row[out_col]
+        };
+    let mut round_ctr = 0;
+    let full_rounds = |row: &mut [F; poseidon::COL_NUM_POSEIDON],
+                       state: &mut [F; SPONGE_WIDTH],
+                       round_ctr: &mut usize,
+                       rounds: std::ops::Range<usize>| {
+        for round in rounds {
+            F::constant_layer(state, *round_ctr);
+            for (lane, x) in state.iter_mut().enumerate() {
+                *x = sbox(
+                    row,
+                    *x,
+                    poseidon::col_full_sbox_cube(round, lane),
+                    poseidon::col_full_sbox_out(round, lane),
+                );
+            }
+            *state = F::mds_layer(state);
+            *round_ctr += 1;
         }
+    };
+
+    full_rounds(&mut row, &mut state, &mut round_ctr, 0..HALF_N_FULL_ROUNDS);
+    for round in 0..N_PARTIAL_ROUNDS {
+        F::constant_layer(&mut state, round_ctr);
+        state[0] = sbox(
+            &mut row,
+            state[0],
+            poseidon::col_partial_sbox_cube(round),
+            poseidon::col_partial_sbox_out(round),
+        );
+        state = F::mds_layer(&state);
+        round_ctr += 1;
+    }
+    full_rounds(
+        &mut row,
+        &mut state,
+        &mut round_ctr,
+        HALF_N_FULL_ROUNDS..poseidon::N_FULL_ROUNDS,
+    );
+
+    for i in 0..poseidon::NUM_OUTPUTS {
+        row[poseidon::col_output(i)] = state[i];
+    }
+    row
+}
 
-        // padding for exe trace
-        //if !max_trace_len.is_power_of_two() {
-        //let new_row_len = max_trace_len.next_power_of_two();
-        //let end_row = trace[trace_len - 1];
-        for _ in trace_len..new_row_len {
-            //let mut new_row = end_row;
-            //new_row[rangecheck::CPU_FILTER] = F::ZERO;
-            //new_row[rangecheck::MEMORY_FILTER] = F::ZERO;
-            //new_row[rangecheck::CMP_FILTER] = F::ZERO;
-            //trace.push(new_row);
-            trace.push([F::ZERO; rangecheck::COL_NUM_RC]);
+// The blocks absorbed by a keccak instruction.
+fn keccak_blocks(c: &KeccakRow) -> (usize, Vec<u32>, Vec<SpongeBlock>) {
+    let len = c.len.to_canonical_u64() as usize;
+    let words = input_words(len, &c.input).expect("the executor only records valid keccak inputs");
+    let blocks = absorb(len, &words);
+    (len, words, blocks)
+}
+
+pub fn generate_builtins_keccak_sponge_trace<F: RichField>(
+    cells: &[KeccakRow],
+) -> Vec<[F; keccak_sponge::COL_NUM_KECCAK_SPONGE]> {
+    let mut trace = Vec::new();
+    for c in cells {
+        let (len, words, blocks) = keccak_blocks(c);
+        for (i, block) in blocks.iter().enumerate() {
+            let mut row = [F::ZERO; keccak_sponge::COL_NUM_KECCAK_SPONGE];
+            let is_final = i + 1 == blocks.len();
+            row[keccak_sponge::IS_FULL] = F::from_bool(!is_final);
+            row[keccak_sponge::IS_FINAL] = F::from_bool(is_final);
+            row[keccak_sponge::IS_FIRST] = F::from_bool(i == 0);
+            row[keccak_sponge::CLK] = F::from_canonical_u64(c.clk.to_canonical_u64());
+            row[keccak_sponge::INPUT_ADDR] = F::from_canonical_u64(c.input_addr.to_canonical_u64());
+            row[keccak_sponge::OUTPUT_ADDR] =
+                F::from_canonical_u64(c.output_addr.to_canonical_u64());
+            row[keccak_sponge::LEN] = F::from_canonical_usize(len);
+            row[keccak_sponge::ABSORBED_WORDS] =
+                F::from_canonical_usize(i * keccak_sponge::RATE_WORDS);
+            if is_final {
+                row[keccak_sponge::col_is_final_len(len % keccak_sponge::RATE_BYTES)] = F::ONE;
+                row[keccak_sponge::TAIL_WORD] = F::from_canonical_u32(tail_word(len, &words));
+            }
+            for (j, word) in block.input.iter().enumerate() {
+                row[keccak_sponge::col_block(j)] = F::from_canonical_u32(*word);
+            }
+            for (j, word) in block.xored()[..keccak_sponge::RATE_WORDS]
+                .iter()
+                .enumerate()
+            {
+                row[keccak_sponge::col_xored(j)] = F::from_canonical_u32(*word);
+            }
+            for j in 0..keccak_sponge::STATE_WORDS {
+                row[keccak_sponge::col_original_state(j)] = F::from_canonical_u32(block.state[j]);
+                row[keccak_sponge::col_updated_state(j)] = F::from_canonical_u32(block.updated[j]);
+            }
+            trace.push(row);
         }
+        debug_assert!(blocks.last().unwrap().updated[..c.output.len()]
+            .iter()
+            .zip(c.output)
+            .all(|(word, output)| *word as u64 == output.to_canonical_u64()));
+    }
 
-        // Transpose to column-major form.
-        let trace_row_vecs: Vec<_> = trace.into_iter().map(|row| row.to_vec()).collect();
-        let mut trace_col_vecs = transpose(&trace_row_vecs);
+    // Pad trace to power of two, the padding rows are zero.
+    let new_row_len = trace.len().next_power_of_two().max(2);
+    trace.resize(new_row_len, [F::ZERO; keccak_sponge::COL_NUM_KECCAK_SPONGE]);
 
-        // add fix rangecheck info
-        trace_col_vecs[rangecheck::FIX_RANGE_CHECK_U16] = (0..rangecheck::RANGE_CHECK_U16_SIZE)
-            .map(|i| F::from_canonical_usize(i))
-            .collect();
+    trace
+}
+
+// The permutations of the blocks absorbed by the keccak instructions.
+pub fn generate_builtins_keccak_trace<F: RichField>(
+    cells: &[KeccakRow],
+) -> Vec<[F; keccak::COL_NUM_KECCAK]> {
+    let inputs = cells
+        .iter()
+        .flat_map(|c| keccak_blocks(c).2)
+        .map(|block| block.xored())
+        .collect_vec();
+    generate_keccak_trace(&inputs)
+}
 
-        let (permuted_inputs, permuted_table) = permuted_cols(
-            &trace_col_vecs[rangecheck::LIMB_LO],
-            &trace_col_vecs[rangecheck::FIX_RANGE_CHECK_U16],
+pub fn generate_keccak_trace<F: RichField>(
+    inputs: &[[u32; keccak::NUM_LIMBS]],
+) -> Vec<[F; keccak::COL_NUM_KECCAK]> {
+    let mut trace = Vec::with_capacity(inputs.len() * keccak::NUM_ROUNDS);
+    for input in inputs {
+        let mut state = words_to_lanes(input);
+        for round in 0..keccak::NUM_ROUNDS {
+            trace.push(generate_keccak_row(input, &mut state, round));
+        }
+    }
+
+    // Pad trace to power of two, the padding rows are zero.
+    let new_row_len = trace.len().next_power_of_two().max(2);
+    trace.resize(new_row_len, [F::ZERO; keccak::COL_NUM_KECCAK]);
+
+    trace
+}
+
+// The columns of the round `round` of the permutation of `preimage`, `state`
+// goes from the state at the start of the round to the state at its end.
+fn generate_keccak_row<F: RichField>(
+    preimage: &[u32; keccak::NUM_LIMBS],
+    state: &mut [u64; keccak::NUM_LANES],
+    round: usize,
+) -> [F; keccak::COL_NUM_KECCAK] {
+    let mut row = [F::ZERO; keccak::COL_NUM_KECCAK];
+    let bit = |lane: u64, z: usize| F::from_canonical_u64(lane >> z & 1);
+    let set_limbs = |row: &mut [F; keccak::COL_NUM_KECCAK],
+                     col: fn(usize) -> usize,
+                     lanes: &[u64; keccak::NUM_LANES]| {
+        for (i, word) in lanes_to_words(lanes).iter().enumerate() {
+            row[col(i)] = F::from_canonical_u32(*word);
+        }
+    };
+
+    row[keccak::col_step(round)] = F::ONE;
+    for (i, word) in preimage.iter().enumerate() {
+        row[keccak::col_preimage(i)] = F::from_canonical_u32(*word);
+    }
+    set_limbs(&mut row, keccak::col_a, state);
+
+    let c: [u64; 5] = std::array::from_fn(|x| (0..5).fold(0, |acc, y| acc ^ state[x + 5 * y]));
+    let c_prime: [u64; 5] =
+        std::array::from_fn(|x| c[x] ^ c[(x + 4) % 5] ^ c[(x + 1) % 5].rotate_left(1));
+    for x in 0..5 {
+        for z in 0..keccak::LANE_BITS {
+            row[keccak::col_c(x, z)] = bit(c[x], z);
+            row[keccak::col_c_prime(x, z)] = bit(c_prime[x], z);
+            for y in 0..5 {
+                let a_prime = state[x + 5 * y] ^ c[x] ^ c_prime[x];
+                row[keccak::col_a_prime(x, y, z)] = bit(a_prime, z);
+            }
+        }
+    }
+
+    keccak_round(state, round);
+    let mut a_prime_prime = *state;
+    a_prime_prime[0] ^= ROUND_CONSTANTS[round];
+    set_limbs(&mut row, keccak::col_a_prime_prime, &a_prime_prime);
+    for z in 0..keccak::LANE_BITS {
+        row[keccak::col_a_prime_prime_0_0_bit(z)] = bit(a_prime_prime[0], z);
+    }
+    for part in 0..2 {
+        row[keccak::col_a_prime_prime_prime_0_0(part)] =
+            F::from_canonical_u32((state[0] >> (32 * part)) as u32);
+    }
+    row
+}
+
+pub fn generate_builtins_ecdsa_trace<F: RichField>(
+    cells: &[EcdsaRow],
+) -> Vec<[F; ecdsa::COL_NUM_ECDSA]> {
+    let mut trace = Vec::with_capacity(cells.len() * ecdsa::ROWS_PER_VERIFICATION);
+    for c in cells {
+        let input = EcdsaInput::from_words(&c.input())
+            .expect("the executor only records valid ecdsa inputs");
+        let mut rows = EcdsaRows::<F>::new(c.clk.to_canonical_u64(), c.addr.to_canonical_u64());
+        rows.verify(&input);
+        debug_assert_eq!(rows.rows.len(), ecdsa::ROWS_PER_VERIFICATION);
+        debug_assert_eq!(
+            rows.rows.last().unwrap()[ecdsa::col_sel(ecdsa::ROW_FIN_EQ)],
+            F::from_canonical_u64(c.flag.to_canonical_u64())
         );
+        trace.extend(rows.rows);
+    }
+
+    // Pad trace to power of two with at least one PAD row.
+    let new_row_len = (trace.len() + 1).next_power_of_two().max(2);
+    let mut padding = EcdsaRows::<F>::new(0, 0);
+    padding.push(ecdsa::ROW_PAD, [0; 4]);
+    trace.resize(new_row_len, padding.rows[0]);
+
+    trace
+}
 
-        trace_col_vecs[rangecheck::LIMB_LO_PERMUTED] = permuted_inputs;
-        trace_col_vecs[rangecheck::FIX_RANGE_CHECK_U16_PERMUTED_LO] = permuted_table;
+// The rows of a verification, `state` holds the columns kept from a row to
+// the next one other than the registers.
+struct EcdsaRows<F: RichField> {
+    rows: Vec<[F; ecdsa::COL_NUM_ECDSA]>,
+    state: [F; ecdsa::COL_NUM_ECDSA],
+    regs: [[u64; 4]; ecdsa::NUM_REGS],
+}
 
-        let (permuted_inputs, permuted_table) = permuted_cols(
-            &trace_col_vecs[rangecheck::LIMB_HI],
-            &trace_col_vecs[rangecheck::FIX_RANGE_CHECK_U16],
+impl<F: RichField> EcdsaRows<F> {
+    fn new(clk: u64, addr: u64) -> Self {
+        let mut state = [F::ZERO; ecdsa::COL_NUM_ECDSA];
+        state[ecdsa::col_phase(ecdsa::PHASE_QK)] = F::ONE;
+        state[ecdsa::col_fsel(0)] = F::ONE;
+        state[ecdsa::CLK] = F::from_canonical_u64(clk);
+        state[ecdsa::ADDR] = F::from_canonical_u64(addr);
+        Self {
+            rows: Vec::new(),
+            state,
+            regs: [[0; 4]; ecdsa::NUM_REGS],
+        }
+    }
+
+    fn verify(&mut self, input: &EcdsaInput) {
+        let (qx, qy) = (input.q.x.0, input.q.y.0);
+        let (r, s) = (input.r.0, input.s.0);
+        let w = input.s.inverse();
+        let (u1, u2) = input.ladder_scalars();
+        let (u1, u2) = (canonical_words(&u1), canonical_words(&u2));
+
+        self.push(ecdsa::ROW_LOAD_QX, qx);
+        // p - 1 - qx, and so on for the other CHK rows
+        self.push(
+            ecdsa::ROW_CHK_QX,
+            canonical_words(&-(input.q.x + Secp256K1Base::ONE)),
+        );
+        self.push(ecdsa::ROW_LOAD_QY, qy);
+        self.push(
+            ecdsa::ROW_CHK_QY,
+            canonical_words(&-(input.q.y + Secp256K1Base::ONE)),
+        );
+        self.push(ecdsa::ROW_SQR_QX, canonical_words(&input.q.x.square()));
+        self.push(ecdsa::ROW_ON_CURVE, [0; 4]);
+        self.push(ecdsa::ROW_LOAD_R, r);
+        self.push(
+            ecdsa::ROW_CHK_R,
+            canonical_words(&-(input.r + Secp256K1Scalar::ONE)),
+        );
+        self.push(ecdsa::ROW_INV_R, canonical_words(&input.r.inverse()));
+        self.push(ecdsa::ROW_LOAD_S, s);
+        self.push(
+            ecdsa::ROW_CHK_S,
+            canonical_words(&-(input.s + Secp256K1Scalar::ONE)),
         );
+        self.push(ecdsa::ROW_INV_S, canonical_words(&w));
+        self.push(ecdsa::ROW_LOAD_Z, input.z);
+        self.push(ecdsa::ROW_U1, u1);
+        self.push(ecdsa::ROW_U2, u2);
+
+        self.add();
+        self.push(ecdsa::ROW_SAVE_QK, [0; 4]);
+        self.set_phase(ecdsa::PHASE_GQK);
+        self.add();
+        self.push(ecdsa::ROW_SAVE_GQK, [0; 4]);
+        self.set_phase(ecdsa::PHASE_LADDER);
+        self.state[ecdsa::BIT] = F::from_canonical_usize(ecdsa::LIMB_BITS - 1);
+        self.state[ecdsa::LIMB] = F::from_canonical_usize(ecdsa::N_LIMBS - 1);
+
+        for i in (0..LADDER_STEPS).rev() {
+            let (b1, b2) = (bit(&u1, i) as usize, bit(&u2, i) as usize);
+            let (bit_index, limb_index) = (i % ecdsa::LIMB_BITS, i / ecdsa::LIMB_BITS);
+            for k in 0..4 {
+                self.state[ecdsa::col_fsel(k)] = F::from_bool(k == b1 + 2 * b2);
+            }
+            self.state[ecdsa::BIT_INV] = F::from_canonical_usize(bit_index)
+                .try_inverse()
+                .unwrap_or(F::ZERO);
+            self.state[ecdsa::IS_LIMB_END] = F::from_bool(bit_index == 0);
+            self.state[ecdsa::LIMB_INV] = F::from_canonical_usize(limb_index)
+                .try_inverse()
+                .unwrap_or(F::ZERO);
+            self.state[ecdsa::IS_LAST_LIMB] = F::from_bool(limb_index == 0);
+            self.state[ecdsa::IS_LAST_STEP] = F::from_bool(i == 0);
+            self.double();
+
+            for (acc, b) in [(ecdsa::ACC1, b1), (ecdsa::ACC2, b2)] {
+                self.state[acc] = if bit_index == 0 {
+                    F::ZERO
+                } else {
+                    self.state[acc].double() + F::from_canonical_usize(b)
+                };
+            }
+            if bit_index == 0 {
+                self.state[ecdsa::BIT] = F::from_canonical_usize(ecdsa::LIMB_BITS - 1);
+                self.state[ecdsa::LIMB] -= F::ONE;
+            } else {
+                self.state[ecdsa::BIT] -= F::ONE;
+            }
+            let doubled = self.doubled();
+            self.push(ecdsa::ROW_DBL2, canonical_words(&doubled.x));
+            self.push(ecdsa::ROW_DBL3, canonical_words(&doubled.y));
+            self.add();
+        }
+
+        self.push(ecdsa::ROW_SET_C, [0; 4]);
+        self.set_phase(ecdsa::PHASE_FINAL);
+        self.add();
+        let x = self.point(ecdsa::REG_X).x;
+        self.push(
+            ecdsa::ROW_CHK_X,
+            canonical_words(&-(x + Secp256K1Base::ONE)),
+        );
+        // x < p < 2n
+        match (Secp256K1Scalar(x.0) - input.r).try_inverse() {
+            Some(inv) => self.push(ecdsa::ROW_FIN_NE, canonical_words(&inv)),
+            None => self.push(ecdsa::ROW_FIN_EQ, [0; 4]),
+        }
+    }
+
+    fn set_phase(&mut self, phase: usize) {
+        for p in 0..ecdsa::NUM_PHASES {
+            self.state[ecdsa::col_phase(p)] = F::from_bool(p == phase);
+        }
+    }
 
-        trace_col_vecs[rangecheck::LIMB_HI_PERMUTED] = permuted_inputs;
-        trace_col_vecs[rangecheck::FIX_RANGE_CHECK_U16_PERMUTED_HI] = permuted_table;
+    // The point in the registers `x` and `x + 1`.
+    fn point(&self, x: usize) -> AffinePoint {
+        AffinePoint {
+            x: Secp256K1Base(self.regs[x]),
+            y: Secp256K1Base(self.regs[x + 1]),
+        }
+    }
+
+    // 2 * (X, Y) from the slope written to L by DBL1.
+    fn doubled(&self) -> AffinePoint {
+        let lambda = Secp256K1Base(self.regs[ecdsa::REG_L]);
+        let x = self.point(ecdsa::REG_X).x;
+        let x3 = lambda.square() - x - x;
+        AffinePoint {
+            x: x3,
+            y: lambda * (x - x3) - Secp256K1Base(self.regs[ecdsa::REG_Y]),
+        }
+    }
+
+    // DBL1, the slope of the tangent at (X, Y).
+    fn double(&mut self) {
+        let p = self.point(ecdsa::REG_X);
+        let lambda = p.x.square() * Secp256K1Base::from_canonical_u64(3) / p.y.double();
+        self.push(ecdsa::ROW_DBL1, canonical_words(&lambda));
+    }
 
-        let final_trace = transpose(&trace_col_vecs);
+    // ADD1..ADD4, (X, Y) + (AX, AY).
+    fn add(&mut self) {
+        let (p, a) = (self.point(ecdsa::REG_X), self.point(ecdsa::REG_AX));
+        let inv = (a.x - p.x)
+            .try_inverse()
+            .expect("the executor rejects a ladder addition doubling a point");
+        let sum = p.add(&a).unwrap();
+        self.push(ecdsa::ROW_ADD1, canonical_words(&inv));
+        self.push(ecdsa::ROW_ADD2, canonical_words(&((a.y - p.y) * inv)));
+        self.push(ecdsa::ROW_ADD3, canonical_words(&sum.x));
+        self.push(ecdsa::ROW_ADD4, canonical_words(&sum.y));
+    }
 
-        let trace_row_vecs: Vec<_> = final_trace
-            .into_iter()
-            .map(|row| vec_to_ary_rc(row))
-            .collect();
+    // Push a row of type `row_type` writing `new`, then update the registers
+    // as in `REGISTER_WRITES`.
+    fn push(&mut self, row_type: usize, new: [u64; 4]) {
+        let mut row = self.state;
+        row[ecdsa::col_sel(row_type)] = F::ONE;
+        for i in 0..ecdsa::N_LIMBS {
+            for (reg, value) in self.regs.iter().enumerate() {
+                row[ecdsa::col_reg(reg, i)] = F::from_canonical_u64(limb(value, i));
+            }
+            row[ecdsa::col_new(i)] = F::from_canonical_u64(limb(&new, i));
+        }
+        fill_ecdsa_relation(&mut row, row_type);
+        self.rows.push(row);
+
+        let fsel = (0..4)
+            .find(|&k| self.state[ecdsa::col_fsel(k)].is_one())
+            .unwrap();
+        let is_limb_end = self.state[ecdsa::IS_LIMB_END].is_one();
+        let writes = REGISTER_WRITES
+            .iter()
+            .filter(|w| w.0 == row_type)
+            .map(|&(_, reg, source)| {
+                let value = match source {
+                    Source::New => new,
+                    Source::Reg(r) => self.regs[r],
+                    Source::Const(v) => v,
+                    Source::LadderPoint { y } => {
+                        let point = match fsel {
+                            0 => LADDER_OFFSET,
+                            1 => GENERATOR_PLUS_OFFSET,
+                            2 => self.point(ecdsa::REG_QKX),
+                            _ => self.point(ecdsa::REG_GQKX),
+                        };
+                        canonical_words(if y { &point.y } else { &point.x })
+                    }
+                    Source::ShiftUp if is_limb_end => {
+                        let value = self.regs[reg];
+                        let mut shifted = [value[0] << ecdsa::LIMB_BITS, 0, 0, 0];
+                        for k in 1..4 {
+                            shifted[k] = value[k] << ecdsa::LIMB_BITS
+                                | value[k - 1] >> (64 - ecdsa::LIMB_BITS);
+                        }
+                        shifted
+                    }
+                    Source::ShiftUp => self.regs[reg],
+                };
+                (reg, value)
+            })
+            .collect_vec();
+        for (reg, value) in writes {
+            self.regs[reg] = value;
+        }
+    }
+}
 
-        trace_row_vecs
+// A coefficient of the relation, whose absolute value is far below the field
+// order.
+fn signed<F: RichField>(x: F) -> i128 {
+    let x = x.to_canonical_u64();
+    if x > F::ORDER / 2 {
+        x as i128 - F::ORDER as i128
+    } else {
+        x as i128
     }
 }
 
-pub fn vec_to_ary_rc<F: RichField>(input: Vec<F>) -> [F; rangecheck::COL_NUM_RC] {
-    let mut ary = [F::ZERO; rangecheck::COL_NUM_RC];
-
-    ary[rangecheck::CPU_FILTER] = input[rangecheck::CPU_FILTER];
-    ary[rangecheck::MEMORY_FILTER] = input[rangecheck::MEMORY_FILTER];
-    ary[rangecheck::CMP_FILTER] = input[rangecheck::CMP_FILTER];
-    ary[rangecheck::VAL] = input[rangecheck::VAL];
-    ary[rangecheck::LIMB_LO] = input[rangecheck::LIMB_LO];
-    ary[rangecheck::LIMB_HI] = input[rangecheck::LIMB_HI];
-    ary[rangecheck::LIMB_LO_PERMUTED] = input[rangecheck::LIMB_LO_PERMUTED];
-    ary[rangecheck::LIMB_HI_PERMUTED] = input[rangecheck::LIMB_HI_PERMUTED];
-    ary[rangecheck::FIX_RANGE_CHECK_U16] = input[rangecheck::FIX_RANGE_CHECK_U16];
-    ary[rangecheck::FIX_RANGE_CHECK_U16_PERMUTED_LO] =
-        input[rangecheck::FIX_RANGE_CHECK_U16_PERMUTED_LO];
-    ary[rangecheck::FIX_RANGE_CHECK_U16_PERMUTED_HI] =
-        input[rangecheck::FIX_RANGE_CHECK_U16_PERMUTED_HI];
-    ary
+// The quotient and the carries of the relation of `row`.
+fn fill_ecdsa_relation<F: RichField>(row: &mut [F; ecdsa::COL_NUM_ECDSA], row_type: usize) {
+    let modulus = if BASE_ROWS.contains(&row_type) {
+        Some(BASE_ORDER)
+    } else if SCALAR_ROWS.contains(&row_type) {
+        Some(SCALAR_ORDER)
+    } else {
+        None
+    };
+    if let Some(modulus) = modulus {
+        // The coefficients with a zero quotient are those of Q * M.
+        let value = relation_coefficients(row)
+            .iter()
+            .rev()
+            .fold(BigInt::zero(), |acc, e| {
+                (acc << ecdsa::LIMB_BITS) + signed(*e)
+            });
+        let modulus = (0..ecdsa::N_LIMBS).rev().fold(BigInt::zero(), |acc, i| {
+            (acc << ecdsa::LIMB_BITS) + limb(&modulus, i)
+        });
+        let (quot, rem) = value.div_rem(&modulus);
+        debug_assert!(rem.is_zero() && !quot.is_negative());
+        let (_, words) = quot.to_u64_digits();
+        let mut words = words;
+        words.resize(5, 0);
+        for i in 0..ecdsa::N_QUOT_LIMBS {
+            let limb = (words[i / 4] >> (ecdsa::LIMB_BITS * (i % 4))) & 0xffff;
+            row[ecdsa::col_quot(i)] = F::from_canonical_u64(limb);
+        }
+    }
+
+    let e = relation_coefficients(row);
+    let mut carry = 0i128;
+    for j in 0..ecdsa::N_LIMBS {
+        let group = signed(e[2 * j]) + (signed(e[2 * j + 1]) << ecdsa::LIMB_BITS) + carry;
+        debug_assert_eq!(group & 0xffff_ffff, 0);
+        carry = group >> (2 * ecdsa::LIMB_BITS);
+        if j < ecdsa::N_CARRIES {
+            let offset_carry = (carry + (1 << (2 * ecdsa::LIMB_BITS - 1))) as u64;
+            row[ecdsa::col_carry_lo(j)] = F::from_canonical_u64(offset_carry & 0xffff);
+            row[ecdsa::col_carry_hi(j)] = F::from_canonical_u64(offset_carry >> ecdsa::LIMB_BITS);
+        } else {
+            debug_assert_eq!(group, 0);
+        }
+    }
 }
@@ -0,0 +1,75 @@
+use plonky2::hash::hash_types::RichField;
+
+use crate::builtins::bitwise::columns as bitwise;
+use crate::builtins::ecdsa::columns as ecdsa;
+use crate::builtins::rangecheck::columns as rangecheck;
+use crate::fixed_table::bitwise_fixed::columns as bitwise_fixed;
+use crate::fixed_table::rangecheck_fixed::columns as rangecheck_fixed;
+
+/// The u16 table, with how many times each value is looked up by the limbs of
+/// `rangecheck_rows` and the u16 columns of `ecdsa_rows`.
+pub fn generate_rangecheck_fixed_trace<F: RichField>(
+    rangecheck_rows: &[[F; rangecheck::COL_NUM_RC]],
+    ecdsa_rows: &[[F; ecdsa::COL_NUM_ECDSA]],
+) -> Vec<[F; rangecheck_fixed::COL_NUM]> {
+    let mut frequencies = vec![0u64; rangecheck_fixed::RANGE_CHECK_U16_SIZE];
+    let rangecheck_limbs = rangecheck_rows
+        .iter()
+        .flat_map(|row| [row[rangecheck::LIMB_LO], row[rangecheck::LIMB_HI]]);
+    let ecdsa_limbs = ecdsa_rows
+        .iter()
+        .flat_map(|row| ecdsa::range_checked_columns().map(|col| row[col]));
+    for limb in rangecheck_limbs.chain(ecdsa_limbs) {
+        // A limb out of the table can't be proven, leave it to the verifier
+        // to reject.
+        if let Some(frequency) = frequencies.get_mut(limb.to_canonical_u64() as usize) {
+            *frequency += 1;
+        }
+    }
+
+    frequencies
+        .into_iter()
+        .enumerate()
+        .map(|(val, frequency)| {
+            let mut row = [F::ZERO; rangecheck_fixed::COL_NUM];
+            row[rangecheck_fixed::VAL] = F::from_canonical_usize(val);
+            row[rangecheck_fixed::FREQ] = F::from_canonical_u64(frequency);
+            row
+        })
+        .collect()
+}
+
+/// The u8 AND table, with how many times each pair of bytes is looked up by
+/// the limbs of `bitwise_rows`.
+pub fn generate_bitwise_fixed_trace<F: RichField>(
+    bitwise_rows: &[[F; bitwise::COL_NUM_BITWISE]],
+) -> Vec<[F; bitwise_fixed::COL_NUM]> {
+    let mut frequencies = vec![0u64; bitwise_fixed::BITWISE_U8_SIZE];
+    for row in bitwise_rows {
+        for i in 0..4 {
+            let [op0, op1, _] = bitwise::bitwise_tuple(i).map(|col| row[col].to_canonical_u64());
+            if op0 < 1 << 8 && op1 < 1 << 8 {
+                frequencies[(op0 << 8 | op1) as usize] += 1;
+            }
+        }
+    }
+
+    frequencies
+        .into_iter()
+        .enumerate()
+        .map(|(index, frequency)| {
+            let (op0, op1) = (index >> 8, index & 0xff);
+            let mut row = [F::ZERO; bitwise_fixed::COL_NUM];
+            for (i, (op0_bit, op1_bit)) in bitwise_fixed::OP0_BITS
+                .zip(bitwise_fixed::OP1_BITS)
+                .enumerate()
+            {
+                row[op0_bit] = F::from_canonical_usize(op0 >> i & 1);
+                row[op1_bit] = F::from_canonical_usize(op1 >> i & 1);
+            }
+            row[bitwise_fixed::RES_AND] = F::from_canonical_usize(op0 & op1);
+            row[bitwise_fixed::FREQ] = F::from_canonical_u64(frequency);
+            row
+        })
+        .collect()
+}
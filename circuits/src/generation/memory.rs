@@ -1,3 +1,4 @@
+use core::program::instruction::Opcode;
 use core::trace::trace::MemoryTraceCell;
 use std::ops::Sub;
 
@@ -37,6 +38,10 @@ pub fn generate_memory_trace<F: RichField>(
             row[memory::COL_MEM_RC_VALUE] = F::from_canonical_u64(c.rc_value.to_canonical_u64());
             row[memory::COL_MEM_FILTER_LOOKING_RC] =
                 F::from_canonical_u64(c.filter_looking_rc.to_canonical_u64());
+            row[memory::COL_MEM_FILTER_INIT] =
+                F::from_canonical_u64(c.filter_init.to_canonical_u64());
+            row[memory::COL_MEM_FILTER_FINAL] =
+                F::from_canonical_u64(c.filter_final.to_canonical_u64());
             row
         })
         .collect();
@@ -95,5 +100,16 @@ pub fn generate_memory_trace<F: RichField>(
         }
     }
 
+    for row in trace.iter_mut() {
+        let op = row[memory::COL_MEM_OP];
+        let d_op = |opcode: Opcode| op - F::from_canonical_u64(1 << opcode as u64);
+        row[memory::COL_MEM_OP_AUX0] =
+            d_op(Opcode::MLOAD) * d_op(Opcode::CALL) * d_op(Opcode::RET) * d_op(Opcode::PSDN);
+        row[memory::COL_MEM_OP_AUX1] =
+            row[memory::COL_MEM_OP_AUX0] * d_op(Opcode::ECDSA) * d_op(Opcode::KECCAK);
+        row[memory::COL_MEM_OP_AUX2] =
+            op * d_op(Opcode::MSTORE) * d_op(Opcode::CALL) * d_op(Opcode::PSDN);
+    }
+
     trace
 }
@@ -1,10 +1,14 @@
 //use std::collections::HashMap;
 
 use core::program::Program;
+use core::trace::trace::StepSink;
+
+use anyhow::Result;
 
 //use eth_trie_utils::partial_trie::PartialTrie;
 use plonky2::field::extension::Extendable;
 use plonky2::field::polynomial::PolynomialValues;
+use plonky2::field::types::{Field, PrimeField64};
 use plonky2::hash::hash_types::RichField;
 use serde::{Deserialize, Serialize};
 
@@ -13,45 +17,97 @@ use crate::stark::proof::PublicValues;
 use crate::stark::util::trace_rows_to_poly_values;
 
 use self::builtin::{
-    generate_builtins_bitwise_trace, generate_builtins_cmp_trace,
-    generate_builtins_rangecheck_trace,
+    generate_builtins_bitwise_trace, generate_builtins_cmp_trace, generate_builtins_ecdsa_trace,
+    generate_builtins_keccak_sponge_trace, generate_builtins_keccak_trace,
+    generate_builtins_poseidon_trace, generate_builtins_rangecheck_trace,
 };
-use self::cpu::generate_cpu_trace;
+use self::cpu::{cpu_columns_public_values, CpuTraceBuilder};
+use self::fixed_table::{generate_bitwise_fixed_trace, generate_rangecheck_fixed_trace};
 use self::memory::generate_memory_trace;
+use self::program::{generate_program_trace, program_hash_inputs, program_trace_hash};
 
 pub mod builtin;
 pub mod cpu;
+pub mod fixed_table;
 pub mod memory;
+pub mod program;
 
 #[derive(Clone, Debug, Deserialize, Serialize, Default)]
 /// Inputs needed for trace generation.
 pub struct GenerationInputs {}
 
+/// Traces of `program`, its steps are moved into the cpu trace columns.
 pub fn generate_traces<F: RichField + Extendable<D>, const D: usize>(
+    mut program: Program,
+    all_stark: &mut AllStark<F, D>,
+) -> Result<([Vec<PolynomialValues<F>>; NUM_TABLES], PublicValues)> {
+    let exec = std::mem::take(&mut program.trace.exec);
+    let mut cpu_trace = CpuTraceBuilder::with_capacity(exec.len());
+    for step in exec.into_iter() {
+        cpu_trace.push_step(step);
+    }
+    generate_traces_with_cpu_trace(&program, cpu_trace, all_stark)
+}
+
+/// Like `generate_traces`, with the steps already written into `cpu_trace` by
+/// `Process::execute_into` instead of kept in `program.trace.exec`.
+pub fn generate_traces_with_cpu_trace<F: RichField + Extendable<D>, const D: usize>(
     program: &Program,
+    cpu_trace: CpuTraceBuilder<F>,
     all_stark: &mut AllStark<F, D>,
-) -> ([Vec<PolynomialValues<F>>; NUM_TABLES], PublicValues) {
-    let (cpu_rows, cpu_beta) =
-        generate_cpu_trace::<F>(&program.trace.exec, &program.trace.raw_binary_instructions);
-    let cpu_trace = trace_rows_to_poly_values(cpu_rows);
+) -> Result<([Vec<PolynomialValues<F>>; NUM_TABLES], PublicValues)> {
+    let cpu_columns = cpu_trace.into_columns(&program.trace.raw_binary_instructions)?;
+    let cpu_public_values = cpu_columns_public_values(&cpu_columns);
+    let cpu_trace = cpu_columns.into_iter().map(PolynomialValues::new).collect();
     let memory_rows = generate_memory_trace::<F>(&program.trace.memory);
     let memory_trace = trace_rows_to_poly_values(memory_rows);
-    let (bitwise_rows, bitwise_beta) =
+    let bitwise_rows =
         generate_builtins_bitwise_trace::<F>(&program.trace.builtin_bitwise_combined);
+    let bitwise_fixed_rows = generate_bitwise_fixed_trace(&bitwise_rows);
+    let bitwise_fixed_trace = trace_rows_to_poly_values(bitwise_fixed_rows);
     let bitwise_trace = trace_rows_to_poly_values(bitwise_rows);
     let cmp_rows = generate_builtins_cmp_trace(&program.trace.builtin_cmp);
     let cmp_trace = trace_rows_to_poly_values(cmp_rows);
     let rangecheck_rows = generate_builtins_rangecheck_trace(&program.trace.builtin_rangecheck);
+    let ecdsa_rows = generate_builtins_ecdsa_trace(&program.trace.builtin_ecdsa);
+    let rangecheck_fixed_rows = generate_rangecheck_fixed_trace(&rangecheck_rows, &ecdsa_rows);
+    let ecdsa_trace = trace_rows_to_poly_values(ecdsa_rows);
+    let rangecheck_fixed_trace = trace_rows_to_poly_values(rangecheck_fixed_rows);
     let rangecheck_trace = trace_rows_to_poly_values(rangecheck_rows);
+    let program_rows = generate_program_trace::<F>(
+        &program.trace.raw_binary_instructions,
+        &program.trace.static_memory,
+    );
+    let program_hash = program_trace_hash(&program_rows);
+    let mut hash_inputs = program_hash_inputs(&program_rows);
+    let program_trace = trace_rows_to_poly_values(program_rows);
+    // The memory left to the next segment, in address order like the memory
+    // table.
+    let final_memory: Vec<(u64, u64)> = program
+        .trace
+        .memory
+        .iter()
+        .filter(|c| c.filter_final.is_one())
+        .map(|c| (c.addr.to_canonical_u64(), c.value.to_canonical_u64()))
+        .collect();
+    let final_program_rows =
+        generate_program_trace::<F>(&program.trace.raw_binary_instructions, &final_memory);
+    let final_program_hash = program_trace_hash(&final_program_rows);
+    hash_inputs.extend(program_hash_inputs(&final_program_rows));
+    let final_program_trace = trace_rows_to_poly_values(final_program_rows);
+    let poseidon_rows =
+        generate_builtins_poseidon_trace(&program.trace.builtin_poseidon, &hash_inputs);
+    let poseidon_trace = trace_rows_to_poly_values(poseidon_rows);
+    let keccak_rows = generate_builtins_keccak_trace(&program.trace.builtin_keccak);
+    let keccak_trace = trace_rows_to_poly_values(keccak_rows);
+    let keccak_sponge_rows = generate_builtins_keccak_sponge_trace(&program.trace.builtin_keccak);
+    let keccak_sponge_trace = trace_rows_to_poly_values(keccak_sponge_rows);
 
+    all_stark.cpu_stark.set_public_values(cpu_public_values);
+    all_stark.program_stark.set_program_hash(program_hash);
     all_stark
-        .cpu_stark
-        .set_compress_challenge(cpu_beta)
-        .unwrap();
-    all_stark
-        .bitwise_stark
-        .set_compress_challenge(bitwise_beta)
-        .unwrap();
+        .final_program_stark
+        .set_program_hash(final_program_hash);
 
     let traces = [
         cpu_trace,
@@ -59,7 +115,30 @@ pub fn generate_traces<F: RichField + Extendable<D>, const D: usize>(
         bitwise_trace,
         cmp_trace,
         rangecheck_trace,
+        program_trace,
+        bitwise_fixed_trace,
+        rangecheck_fixed_trace,
+        final_program_trace,
+        poseidon_trace,
+        ecdsa_trace,
+        keccak_trace,
+        keccak_sponge_trace,
     ];
-    let public_values = PublicValues {};
-    (traces, public_values)
+    let public_values = PublicValues {
+        program_hash: program_hash.map(|e| e.to_canonical_u64()),
+        final_program_hash: final_program_hash.map(|e| e.to_canonical_u64()),
+        initial_registers: cpu_public_values
+            .initial_registers
+            .map(|r| r.to_canonical_u64()),
+        final_registers: cpu_public_values
+            .final_registers
+            .map(|r| r.to_canonical_u64()),
+        initial_clk: cpu_public_values.initial_clk.to_canonical_u64(),
+        initial_pc: cpu_public_values.initial_pc.to_canonical_u64(),
+        final_clk: cpu_public_values.final_clk.to_canonical_u64(),
+        final_pc: cpu_public_values.final_pc.to_canonical_u64(),
+        is_final: cpu_public_values.is_final.is_one(),
+        ..Default::default()
+    };
+    Ok((traces, public_values))
 }
@@ -0,0 +1,166 @@
+use core::program::instruction::{
+    IMM_FLAG_FIELD_BITS_MASK, IMM_FLAG_FIELD_BIT_POSITION, IMM_INSTRUCTION_LEN,
+    NO_IMM_INSTRUCTION_LEN,
+};
+
+use plonky2::field::extension::Extendable;
+use plonky2::fri::oracle::PolynomialBatch;
+use plonky2::hash::hash_types::RichField;
+use plonky2::hash::hashing::SPONGE_WIDTH;
+use plonky2::hash::merkle_tree::MerkleCap;
+use plonky2::plonk::config::{GenericConfig, Hasher};
+use plonky2::util::timing::TimingTree;
+
+use crate::builtins::poseidon::columns as poseidon;
+use crate::program::columns::{self as program, PROGRAM_HASH_LEN};
+use crate::stark::config::StarkConfig;
+use crate::stark::util::trace_rows_to_poly_values;
+
+/// Split the encoded program into `(pc, inst, imm)` instructions, imm is the
+/// word following an instruction with the imm flag set, 0 otherwise.
+pub(crate) fn decode_program_instructions(raw_instructions: &[String]) -> Vec<(u64, u64, u64)> {
+    let words: Vec<u64> = raw_instructions
+        .iter()
+        .map(|ri| u64::from_str_radix(ri.trim().trim_start_matches("0x"), 16).unwrap())
+        .collect();
+
+    let mut insts = Vec::new();
+    let mut pc = 0;
+    while pc < words.len() {
+        let inst = words[pc];
+        let imm_flag = (inst >> IMM_FLAG_FIELD_BIT_POSITION) & IMM_FLAG_FIELD_BITS_MASK;
+        let step = if imm_flag == 1 {
+            IMM_INSTRUCTION_LEN
+        } else {
+            NO_IMM_INSTRUCTION_LEN
+        };
+        let imm = if imm_flag == 1 {
+            words.get(pc + 1).copied().unwrap_or_default()
+        } else {
+            0
+        };
+        insts.push((pc as u64, inst, imm));
+        pc += step as usize;
+    }
+    insts
+}
+
+/// `static_memory` is the `(addr, value)` list of the program static data,
+/// as recorded in `Trace.static_memory`.
+pub fn generate_program_trace<F: RichField>(
+    raw_instructions: &[String],
+    static_memory: &[(u64, u64)],
+) -> Vec<[F; program::NUM_PROG_COLS]> {
+    let insts = decode_program_instructions(raw_instructions);
+    let mut trace: Vec<[F; program::NUM_PROG_COLS]> =
+        Vec::with_capacity(insts.len() + static_memory.len());
+    for (pc, inst, imm) in insts {
+        let mut row = [F::ZERO; program::NUM_PROG_COLS];
+        row[program::COL_PROG_PC] = F::from_canonical_u64(pc);
+        row[program::COL_PROG_INST] = F::from_canonical_u64(inst);
+        row[program::COL_PROG_IMM] = F::from_canonical_u64(imm);
+        row[program::COL_PROG_FILTER] = F::ONE;
+        trace.push(row);
+    }
+    for (addr, value) in static_memory {
+        let mut row = [F::ZERO; program::NUM_PROG_COLS];
+        row[program::COL_PROG_DATA_ADDR] = F::from_canonical_u64(*addr);
+        row[program::COL_PROG_DATA_VALUE] = F::from_canonical_u64(*value);
+        row[program::COL_PROG_DATA_FILTER] = F::ONE;
+        trace.push(row);
+    }
+
+    // Pad trace to power of two, padding rows are filtered out of the cpu,
+    // memory and poseidon lookups.
+    let trace_len = trace.len();
+    let new_row_len = trace_len.next_power_of_two().max(2);
+    trace.resize(new_row_len, [F::ZERO; program::NUM_PROG_COLS]);
+
+    let mut hash = [F::ZERO; PROGRAM_HASH_LEN];
+    for (i, row) in trace.iter_mut().enumerate() {
+        row[program::COL_PROG_PREV_HASH].copy_from_slice(&hash);
+        if i < trace_len {
+            let mut state = [F::ZERO; SPONGE_WIDTH];
+            state[..poseidon::NUM_INPUTS].copy_from_slice(&program_hash_input(row));
+            hash.copy_from_slice(&F::poseidon(state)[..PROGRAM_HASH_LEN]);
+        }
+        row[program::COL_PROG_HASH].copy_from_slice(&hash);
+    }
+
+    trace
+}
+
+// The previous hash followed by the row, as
+// `program_stark::ctl_data_with_poseidon`.
+fn program_hash_input<F: RichField>(
+    row: &[F; program::NUM_PROG_COLS],
+) -> [F; poseidon::NUM_INPUTS] {
+    let mut input = [F::ZERO; poseidon::NUM_INPUTS];
+    input[..PROGRAM_HASH_LEN].copy_from_slice(&row[program::COL_PROG_PREV_HASH]);
+    input[PROGRAM_HASH_LEN..].copy_from_slice(&[
+        row[program::COL_PROG_PC] + row[program::COL_PROG_DATA_ADDR],
+        row[program::COL_PROG_INST] + row[program::COL_PROG_DATA_VALUE],
+        row[program::COL_PROG_IMM],
+        row[program::COL_PROG_DATA_FILTER],
+    ]);
+    input
+}
+
+/// Inputs of the permutations of the program hash chain of `program_rows`,
+/// they are proven by the poseidon table.
+pub fn program_hash_inputs<F: RichField>(
+    program_rows: &[[F; program::NUM_PROG_COLS]],
+) -> Vec<[F; poseidon::NUM_INPUTS]> {
+    program_rows
+        .iter()
+        .filter(|row| (row[program::COL_PROG_FILTER] + row[program::COL_PROG_DATA_FILTER]).is_one())
+        .map(program_hash_input)
+        .collect()
+}
+
+/// Hash of the last row of the program table.
+pub fn program_trace_hash<F: RichField>(
+    program_rows: &[[F; program::NUM_PROG_COLS]],
+) -> [F; PROGRAM_HASH_LEN] {
+    program_rows.last().unwrap()[program::COL_PROG_HASH]
+        .try_into()
+        .unwrap()
+}
+
+/// Merkle cap of the program table commitment, it only depends on the
+/// encoded instructions, the static data and the config.
+pub fn program_trace_cap<F, C, const D: usize>(
+    raw_instructions: &[String],
+    static_memory: &[(u64, u64)],
+    config: &StarkConfig,
+) -> MerkleCap<F, C::Hasher>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    [(); C::Hasher::HASH_SIZE]:,
+{
+    let program_rows = generate_program_trace::<F>(raw_instructions, static_memory);
+    let program_commitment = PolynomialBatch::<F, C, D>::from_values(
+        trace_rows_to_poly_values(program_rows),
+        config.fri_config.rate_bits,
+        false,
+        config.fri_config.cap_height,
+        &mut TimingTree::default(),
+        None,
+    );
+    program_commitment.merkle_tree.cap
+}
+
+/// Program hash of the encoded instructions and static data, as checked by
+/// `verify_proof`. It is a Poseidon chain over the program table rows, so it
+/// doesn't depend on the `StarkConfig`.
+pub fn program_hash<F: RichField>(
+    raw_instructions: &[String],
+    static_memory: &[(u64, u64)],
+) -> [u64; 4] {
+    program_trace_hash(&generate_program_trace::<F>(
+        raw_instructions,
+        static_memory,
+    ))
+    .map(|e| e.to_canonical_u64())
+}
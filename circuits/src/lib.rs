@@ -8,4 +8,3 @@ pub mod generation;
 pub mod memory;
 pub mod program;
 pub mod stark;
-pub mod snark;
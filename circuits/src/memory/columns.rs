@@ -25,4 +25,20 @@ pub(crate) const COL_MEM_REGION_ECDSA: usize = COL_MEM_REGION_POSEIDON + 1;
 pub(crate) const COL_MEM_RC_VALUE: usize = COL_MEM_REGION_ECDSA + 1;
 pub(crate) const COL_MEM_FILTER_LOOKING_RC: usize = COL_MEM_RC_VALUE + 1;
 
-pub(crate) const NUM_MEM_COLS: usize = COL_MEM_FILTER_LOOKING_RC + 1;
+// static data rows, written before the first step and looked up in the program.
+pub(crate) const COL_MEM_FILTER_INIT: usize = COL_MEM_FILTER_LOOKING_RC + 1;
+// last row of a read write address or of a prophet output, its value is
+// looked up in the final memory of the segment.
+pub(crate) const COL_MEM_FILTER_FINAL: usize = COL_MEM_FILTER_INIT + 1;
+
+// products of the op selectors, keep the constraint degree at 5.
+// aux0 = (op - mload) * (op - call) * (op - ret) * (op - poseidon), zero for
+// the ops which read.
+pub(crate) const COL_MEM_OP_AUX0: usize = COL_MEM_FILTER_FINAL + 1;
+// aux1 = aux0 * (op - ecdsa) * (op - keccak)
+pub(crate) const COL_MEM_OP_AUX1: usize = COL_MEM_OP_AUX0 + 1;
+// aux2 = op * (op - mstore) * (op - call) * (op - poseidon), zero for the ops
+// which write.
+pub(crate) const COL_MEM_OP_AUX2: usize = COL_MEM_OP_AUX1 + 1;
+
+pub(crate) const NUM_MEM_COLS: usize = COL_MEM_OP_AUX2 + 1;
@@ -63,6 +63,22 @@ pub fn ctl_filter<F: Field>() -> Column<F> {
     Column::single(COL_MEM_FILTER_LOOKED_FOR_MAIN)
 }
 
+pub fn ctl_data_mem_init<F: Field>() -> Vec<Column<F>> {
+    Column::singles([COL_MEM_ADDR, COL_MEM_VALUE]).collect()
+}
+
+pub fn ctl_filter_mem_init<F: Field>() -> Column<F> {
+    Column::single(COL_MEM_FILTER_INIT)
+}
+
+pub fn ctl_data_mem_final<F: Field>() -> Vec<Column<F>> {
+    Column::singles([COL_MEM_ADDR, COL_MEM_VALUE]).collect()
+}
+
+pub fn ctl_filter_mem_final<F: Field>() -> Column<F> {
+    Column::single(COL_MEM_FILTER_FINAL)
+}
+
 #[derive(Copy, Clone, Default)]
 pub struct MemoryStark<F, const D: usize> {
     pub f: PhantomData<F>,
@@ -104,29 +120,74 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for MemoryStark<F
         let nv_rw_addr_unchanged = nv[COL_MEM_RW_ADDR_UNCHANGED];
         let diff_addr_cond = lv[COL_MEM_DIFF_ADDR_COND];
         let value = lv[COL_MEM_VALUE];
-        let nv_value = lv[COL_MEM_VALUE];
+        let nv_value = nv[COL_MEM_VALUE];
         let diff_clk = lv[COL_MEM_DIFF_CLK];
         let rc_value = lv[COL_MEM_RC_VALUE];
         let filter_looking_rc = lv[COL_MEM_FILTER_LOOKING_RC];
+        let filter_init = lv[COL_MEM_FILTER_INIT];
+        let nv_filter_init = nv[COL_MEM_FILTER_INIT];
+        let filter_final = lv[COL_MEM_FILTER_FINAL];
+        let op_aux0 = lv[COL_MEM_OP_AUX0];
+        let op_aux1 = lv[COL_MEM_OP_AUX1];
+        let op_aux2 = lv[COL_MEM_OP_AUX2];
 
         let op_mload = P::Scalar::from_canonical_u64(2_u64.pow(25));
         let op_mstore = P::Scalar::from_canonical_u64(2_u64.pow(24));
         let op_call = P::Scalar::from_canonical_u64(2_u64.pow(27));
         let op_ret = P::Scalar::from_canonical_u64(2_u64.pow(26));
-        // op is one of mload, mstore, call, ret or prophet write 0.
-        yield_constr
-            .constraint(op * (op - op_mload) * (op - op_mstore) * (op - op_call) * (op - op_ret));
-        // when op is 0, is_rw must be zero.
+        let op_psdn = P::Scalar::from_canonical_u64(2_u64.pow(15));
+        let op_ecdsa = P::Scalar::from_canonical_u64(2_u64.pow(14));
+        let op_keccak = P::Scalar::from_canonical_u64(2_u64.pow(13));
+        // products of the op selectors.
         yield_constr.constraint(
-            (op - op_mload) * (op - op_mstore) * (op - op_call) * (op - op_ret) * is_rw,
+            (op - op_mload) * (op - op_call) * (op - op_ret) * (op - op_psdn) - op_aux0,
         );
-
-        // constraint is_write and op. When write, op can be mstore, call and 0; When
-        // read, op can be mload, call, ret. call can both write and read, does
-        // not need a constraint rule.
+        yield_constr.constraint(op_aux0 * (op - op_ecdsa) * (op - op_keccak) - op_aux1);
+        yield_constr.constraint(op * (op - op_mstore) * (op - op_call) * (op - op_psdn) - op_aux2);
+
+        // op is one of mload, mstore, call, ret, poseidon, ecdsa, keccak or
+        // prophet write 0.
+        let op_not_zero = op_aux1 * (op - op_mstore);
+        yield_constr.constraint(op * op_not_zero);
+        // when op is 0, is_rw must be zero unless it is a static data row. A
+        // prophet row may be a static data row, carried from the previous
+        // segment.
+        yield_constr.constraint(op_not_zero * (is_rw - filter_init + region_prophet * filter_init));
+
+        // static data row: a read write or prophet region write with op 0, not
+        // from main, and the first access of its address.
+        yield_constr.constraint(filter_init * (P::ONES - filter_init));
+        yield_constr.constraint(filter_init * op);
+        yield_constr.constraint(filter_init * (P::ONES - is_write));
+        yield_constr.constraint(filter_init * filter_looked_for_main);
+        yield_constr
+            .constraint_transition(nv_filter_init * (P::ONES - nv_diff_addr * nv_diff_addr_inv));
+
+        // final row: a read write or prophet region row followed by another
+        // address, so it holds the last value of its address. Every such read
+        // write row, or a read write last row, is a final row. So is the last
+        // row of a prophet output read or carried in this segment, an output
+        // never read may be left out like the padding rows.
+        yield_constr.constraint(filter_final * (P::ONES - filter_final));
+        yield_constr.constraint(filter_final * (P::ONES - is_rw - region_prophet));
         yield_constr
-            .constraint((op - op_mload) * (op - op_call) * (op - op_ret) * (P::ONES - is_write));
-        yield_constr.constraint(op * (op - op_mstore) * (op - op_call) * is_write);
+            .constraint_transition(filter_final * (P::ONES - nv_diff_addr * nv_diff_addr_inv));
+        yield_constr.constraint_transition(
+            is_rw * nv_diff_addr * nv_diff_addr_inv * (P::ONES - filter_final),
+        );
+        yield_constr.constraint_last_row(is_rw * (P::ONES - filter_final));
+        let prophet_kept = region_prophet * (P::ONES - is_write + filter_init);
+        yield_constr.constraint_transition(
+            prophet_kept * nv_diff_addr * nv_diff_addr_inv * (P::ONES - filter_final),
+        );
+        yield_constr.constraint_last_row(prophet_kept * (P::ONES - filter_final));
+
+        // constraint is_write and op. When write, op can be mstore, call, poseidon,
+        // keccak and 0; When read, op can be mload, call, ret, poseidon, ecdsa,
+        // keccak. call, poseidon and keccak can both write and read, does not need
+        // a constraint rule.
+        yield_constr.constraint(op_aux1 * (P::ONES - is_write));
+        yield_constr.constraint(op_aux2 * (op - op_keccak) * is_write);
 
         // when op is not 0, filter_looked_for_main need to be enabled.
         yield_constr.constraint(op * (P::ONES - filter_looked_for_main));
@@ -224,10 +285,16 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for MemoryStark<F
         let nv_rw_addr_unchanged = nv[COL_MEM_RW_ADDR_UNCHANGED];
         let diff_addr_cond = lv[COL_MEM_DIFF_ADDR_COND];
         let value = lv[COL_MEM_VALUE];
-        let nv_value = lv[COL_MEM_VALUE];
+        let nv_value = nv[COL_MEM_VALUE];
         let diff_clk = lv[COL_MEM_DIFF_CLK];
         let rc_value = lv[COL_MEM_RC_VALUE];
         let filter_looking_rc = lv[COL_MEM_FILTER_LOOKING_RC];
+        let filter_init = lv[COL_MEM_FILTER_INIT];
+        let nv_filter_init = nv[COL_MEM_FILTER_INIT];
+        let filter_final = lv[COL_MEM_FILTER_FINAL];
+        let op_aux0 = lv[COL_MEM_OP_AUX0];
+        let op_aux1 = lv[COL_MEM_OP_AUX1];
+        let op_aux2 = lv[COL_MEM_OP_AUX2];
 
         let op_mload =
             builder.constant_extension(F::Extension::from_canonical_usize(2_usize.pow(25)));
@@ -237,32 +304,100 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for MemoryStark<F
             builder.constant_extension(F::Extension::from_canonical_usize(2_usize.pow(27)));
         let op_ret =
             builder.constant_extension(F::Extension::from_canonical_usize(2_usize.pow(26)));
-        // op is one of mload, mstore, call, ret or prophet write 0.
+        let op_psdn =
+            builder.constant_extension(F::Extension::from_canonical_usize(2_usize.pow(15)));
+        let op_ecdsa =
+            builder.constant_extension(F::Extension::from_canonical_usize(2_usize.pow(14)));
+        let op_keccak =
+            builder.constant_extension(F::Extension::from_canonical_usize(2_usize.pow(13)));
+        // products of the op selectors.
         let d_op_mload = builder.sub_extension(op, op_mload);
         let d_op_mstore = builder.sub_extension(op, op_mstore);
         let d_op_call = builder.sub_extension(op, op_call);
         let d_op_ret = builder.sub_extension(op, op_ret);
+        let d_op_psdn = builder.sub_extension(op, op_psdn);
+        let d_op_ecdsa = builder.sub_extension(op, op_ecdsa);
+        let d_op_keccak = builder.sub_extension(op, op_keccak);
         let one_m_is_write = builder.sub_extension(one, is_write);
-        let op_inter_1 = builder.mul_extension(d_op_mload, d_op_mstore);
-        let op_inter_2 = builder.mul_extension(op_inter_1, d_op_call);
-        let op_inter_3 = builder.mul_extension(op_inter_2, d_op_ret);
-        let op_inter_4 = builder.mul_extension(op_inter_3, op);
-        yield_constr.constraint(builder, op_inter_4);
-        // when op is 0, is_rw must be zero.
-        let op_inter_rw = builder.mul_extension(op_inter_3, is_rw);
+        let aux0_inter_1 = builder.mul_extension(d_op_mload, d_op_call);
+        let aux0_inter_2 = builder.mul_extension(aux0_inter_1, d_op_ret);
+        let aux0_inter_3 = builder.mul_extension(aux0_inter_2, d_op_psdn);
+        let constraint_aux0 = builder.sub_extension(aux0_inter_3, op_aux0);
+        yield_constr.constraint(builder, constraint_aux0);
+        let aux1_inter_1 = builder.mul_extension(op_aux0, d_op_ecdsa);
+        let aux1_inter_2 = builder.mul_extension(aux1_inter_1, d_op_keccak);
+        let constraint_aux1 = builder.sub_extension(aux1_inter_2, op_aux1);
+        yield_constr.constraint(builder, constraint_aux1);
+        let aux2_inter_1 = builder.mul_extension(op, d_op_mstore);
+        let aux2_inter_2 = builder.mul_extension(aux2_inter_1, d_op_call);
+        let aux2_inter_3 = builder.mul_extension(aux2_inter_2, d_op_psdn);
+        let constraint_aux2 = builder.sub_extension(aux2_inter_3, op_aux2);
+        yield_constr.constraint(builder, constraint_aux2);
+
+        // op is one of mload, mstore, call, ret, poseidon, ecdsa, keccak or
+        // prophet write 0.
+        let op_not_zero = builder.mul_extension(op_aux1, d_op_mstore);
+        let op_inter = builder.mul_extension(op_not_zero, op);
+        yield_constr.constraint(builder, op_inter);
+        // when op is 0, is_rw must be zero unless it is a static data row. A
+        // prophet row may be a static data row, carried from the previous
+        // segment.
+        let rw_m_init = builder.sub_extension(is_rw, filter_init);
+        let prophet_init = builder.mul_extension(region_prophet, filter_init);
+        let rw_m_init = builder.add_extension(rw_m_init, prophet_init);
+        let op_inter_rw = builder.mul_extension(op_not_zero, rw_m_init);
         yield_constr.constraint(builder, op_inter_rw);
 
-        // constraint is_write and op. When write, op can be mstore, call and 0; When
-        // read, op can be mload, call, ret. call can both write and read, does
-        // not need a constraint rule.
-        let is_write_inter_1 = builder.mul_extension(d_op_mload, d_op_call);
-        let is_write_inter_2 = builder.mul_extension(is_write_inter_1, d_op_ret);
-        let is_write_inter_3 = builder.mul_extension(is_write_inter_2, one_m_is_write);
-        yield_constr.constraint(builder, is_write_inter_3);
-        let is_write_inter_2_1 = builder.mul_extension(op, d_op_mstore);
-        let is_write_inter_2_2 = builder.mul_extension(is_write_inter_2_1, d_op_call);
-        let is_write_inter_2_3 = builder.mul_extension(is_write_inter_2_2, is_write);
-        yield_constr.constraint(builder, is_write_inter_2_3);
+        // static data row: a read write or prophet region write with op 0, not
+        // from main, and the first access of its address.
+        let one_m_init = builder.sub_extension(one, filter_init);
+        let binary_init = builder.mul_extension(filter_init, one_m_init);
+        yield_constr.constraint(builder, binary_init);
+        let init_op = builder.mul_extension(filter_init, op);
+        yield_constr.constraint(builder, init_op);
+        let init_write = builder.mul_extension(filter_init, one_m_is_write);
+        yield_constr.constraint(builder, init_write);
+        let init_main = builder.mul_extension(filter_init, filter_looked_for_main);
+        yield_constr.constraint(builder, init_main);
+        let nv_addr_changed = builder.mul_extension(nv_diff_addr, nv_diff_addr_inv);
+        let nv_addr_unchanged = builder.sub_extension(one, nv_addr_changed);
+        let init_first = builder.mul_extension(nv_filter_init, nv_addr_unchanged);
+        yield_constr.constraint_transition(builder, init_first);
+
+        // final row: a read write or prophet region row followed by another
+        // address, so it holds the last value of its address. Every such read
+        // write row, or a read write last row, is a final row. So is the last
+        // row of a prophet output read or carried in this segment, an output
+        // never read may be left out like the padding rows.
+        let one_m_final = builder.sub_extension(one, filter_final);
+        let binary_final = builder.mul_extension(filter_final, one_m_final);
+        yield_constr.constraint(builder, binary_final);
+        let one_m_rw = builder.sub_extension(one, is_rw);
+        let one_m_rw_m_prophet = builder.sub_extension(one_m_rw, region_prophet);
+        let final_rw = builder.mul_extension(filter_final, one_m_rw_m_prophet);
+        yield_constr.constraint(builder, final_rw);
+        let final_last = builder.mul_extension(filter_final, nv_addr_unchanged);
+        yield_constr.constraint_transition(builder, final_last);
+        let rw_not_final = builder.mul_extension(is_rw, one_m_final);
+        let rw_last = builder.mul_extension(rw_not_final, nv_addr_changed);
+        yield_constr.constraint_transition(builder, rw_last);
+        yield_constr.constraint_last_row(builder, rw_not_final);
+        let read_or_init = builder.add_extension(one_m_is_write, filter_init);
+        let prophet_kept = builder.mul_extension(region_prophet, read_or_init);
+        let prophet_not_final = builder.mul_extension(prophet_kept, one_m_final);
+        let prophet_last = builder.mul_extension(prophet_not_final, nv_addr_changed);
+        yield_constr.constraint_transition(builder, prophet_last);
+        yield_constr.constraint_last_row(builder, prophet_not_final);
+
+        // constraint is_write and op. When write, op can be mstore, call, poseidon,
+        // keccak and 0; When read, op can be mload, call, ret, poseidon, ecdsa,
+        // keccak. call, poseidon and keccak can both write and read, does not need
+        // a constraint rule.
+        let is_write_inter_1 = builder.mul_extension(op_aux1, one_m_is_write);
+        yield_constr.constraint(builder, is_write_inter_1);
+        let is_write_inter_2_1 = builder.mul_extension(op_aux2, d_op_keccak);
+        let is_write_inter_2_2 = builder.mul_extension(is_write_inter_2_1, is_write);
+        yield_constr.constraint(builder, is_write_inter_2_2);
 
         // when op is not 0, filter_looked_for_main need to be enabled.
         let one_m_filter_looked_for_main = builder.sub_extension(one, filter_looked_for_main);
@@ -278,6 +413,7 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for MemoryStark<F
         let nv_diff_mul_inv = builder.mul_extension(nv_diff_addr, nv_diff_addr_inv);
         let one_m_unchanged = builder.sub_extension(one, nv_rw_addr_unchanged);
         let constraint_unchanged = builder.sub_extension(one_m_unchanged, nv_diff_mul_inv);
+        let constraint_unchanged = builder.mul_extension(is_rw, constraint_unchanged);
         yield_constr.constraint_transition(builder, constraint_unchanged);
 
         // for region division: 1. one of four region is selected; 2. binary
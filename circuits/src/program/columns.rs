@@ -1,24 +1,61 @@
-// // 2022-12-27: written by xb
-
-// /* Program Table construction as follows:
-// // +-----+--------------+-------+----------+
-// // | PC  |      INS     |  IMM  | COMPRESS |
-// // +-----+--------------+-------+----------+
-// // +-----+--------------+-------+----------+
-// // |  1  |  0x********  |  U32  |   Field  |
-// // +-----+--------------+-------+----------+
-// // +-----+--------------+-------+----------+
-// // |  2  |  0x********  |  U32  |   Field  |
-// // +-----+--------------+-------+----------++
-// // +-----+--------------+-------+----------+
-// // |  3  |  0x********  |  U32  |   Field  |
-// // +-----+--------------+-------+----------+
-// */
-
-// pub(crate) const PC: usize = 0;
-
-// pub(crate) const INS: usize = PC + 1;
-
-// pub(crate) const IMM: usize = INS + 1;
-
-// pub(crate) const COL_NUM: usize = IMM + 1; // 3
+// 2022-12-27: written by xb
+
+use std::ops::Range;
+
+/// Field elements of the program hash.
+pub const PROGRAM_HASH_LEN: usize = 4;
+
+/* Program Table construction as follows:
++-----+------------+-----+--------+-----------+------------+-------------+--------------+---------+
+| PC  |     INS    | IMM | FILTER | DATA_ADDR | DATA_VALUE | DATA_FILTER | PREV_HASH[4] | HASH[4] |
++-----+------------+-----+--------+-----------+------------+-------------+--------------+---------+
+|  0  | 0x******** | U64 |   1    |     0     |     0      |      0      |   0,0,0,0    |   h_0   |
++-----+------------+-----+--------+-----------+------------+-------------+--------------+---------+
+|  2  | 0x******** |  0  |   1    |     0     |     0      |      0      |     h_0      |   h_1   |
++-----+------------+-----+--------+-----------+------------+-------------+--------------+---------+
+|  0  |      0     |  0  |   0    |  0x1000   |    U64     |      1      |     h_1      |   h_2   |
++-----+------------+-----+--------+-----------+------------+-------------+--------------+---------+
+|  0  |      0     |  0  |   0    |     0     |     0      |      0      |     h_2      |   h_2   |
++-----+------------+-----+--------+-----------+------------+-------------+--------------+---------+
+
+One row per instruction of the program, pc is the index of the instruction
+word in `raw_binary_instructions` and imm is the word following it when the
+instruction carries an immediate (0 otherwise). The static data words of the
+program follow, one per row. Padding rows have FILTER = DATA_FILTER = 0.
+
+HASH is a Poseidon chain over the rows, each instruction or data row hashes
+PREV_HASH with (pc + data_addr, inst + data_value, imm, data_filter), so the
+last HASH only depends on the encoded instructions and static data. It is the
+program hash public value, see `generation::program::program_hash`.
+
+Constraints as follows:
+1. FILTER and DATA_FILTER are binary and not both set, the columns of the
+   other kind of row are zero
+2. PREV_HASH is zero on the first row and HASH of the previous row after,
+   padding rows keep it
+3. HASH of the last row is the program hash
+4. Cross Lookup for (pc, ins, imm) from the CPU raw program columns
+    Lookup {<raw_pc, raw_inst, raw_imm>; program}
+5. Cross Lookup for (data_addr, data_value) from the memory init rows
+    Lookup {<addr, value>; program data}
+6. Cross Lookup for (PREV_HASH, row, HASH) in the Poseidon permutations
+    Lookup {<input[8], output[4]>; poseidon}
+*/
+
+pub(crate) const COL_PROG_PC: usize = 0;
+pub(crate) const COL_PROG_INST: usize = COL_PROG_PC + 1;
+pub(crate) const COL_PROG_IMM: usize = COL_PROG_INST + 1;
+pub(crate) const COL_PROG_FILTER: usize = COL_PROG_IMM + 1;
+
+pub(crate) const COL_PROG_DATA_ADDR: usize = COL_PROG_FILTER + 1;
+pub(crate) const COL_PROG_DATA_VALUE: usize = COL_PROG_DATA_ADDR + 1;
+pub(crate) const COL_PROG_DATA_FILTER: usize = COL_PROG_DATA_VALUE + 1;
+
+pub(crate) const COL_PROG_PREV_HASH_START: usize = COL_PROG_DATA_FILTER + 1;
+pub(crate) const COL_PROG_PREV_HASH: Range<usize> =
+    COL_PROG_PREV_HASH_START..COL_PROG_PREV_HASH_START + PROGRAM_HASH_LEN;
+pub(crate) const COL_PROG_HASH_START: usize = COL_PROG_PREV_HASH.end;
+pub(crate) const COL_PROG_HASH: Range<usize> =
+    COL_PROG_HASH_START..COL_PROG_HASH_START + PROGRAM_HASH_LEN;
+
+pub(crate) const NUM_PROG_COLS: usize = COL_PROG_HASH.end; // 15
@@ -1,2 +1,2 @@
-// pub mod columns;
-// pub mod program_stark;
+pub mod columns;
+pub mod program_stark;
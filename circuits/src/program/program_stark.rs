@@ -1,57 +1,169 @@
-// use crate::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
-// use crate::cross_table_lookup::Column;
-// use crate::program::columns::*;
-// use crate::stark::Stark;
-// use crate::vars::{StarkEvaluationTargets, StarkEvaluationVars};
-// use itertools::Itertools;
-// use plonky2::field::extension::{Extendable, FieldExtension};
-// use plonky2::field::packed::PackedField;
-// use plonky2::field::types::Field;
-// use plonky2::hash::hash_types::RichField;
-// use plonky2::plonk::circuit_builder::CircuitBuilder;
-// use std::marker::PhantomData;
-
-// #[derive(Copy, Clone, Default)]
-// pub struct ProgramStark<F, const D: usize> {
-//     pub _phantom: PhantomData<F>,
-// }
-
-// impl<F: RichField, const D: usize> ProgramStark<F, D> {
-//     const BASE: usize = 1 << 8;
-// }
-
-// impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for ProgramStark<F, D> {
-//     const COLUMNS: usize = COL_NUM;
-
-//     fn eval_packed_generic<FE, P, const D2: usize>(
-//         &self,
-//         _vars: StarkEvaluationVars<FE, P, { COL_NUM }>,
-//         _yield_constr: &mut ConstraintConsumer<P>,
-//     ) where
-//         FE: FieldExtension<D2, BaseField = F>,
-//         P: PackedField<Scalar = FE>,
-//     {
-//     }
-
-//     fn eval_ext_circuit(
-//         &self,
-//         _builder: &mut CircuitBuilder<F, D>,
-//         _vars: StarkEvaluationTargets<D, { COL_NUM }>,
-//         _yield_constr: &mut RecursiveConstraintConsumer<F, D>,
-//     ) {
-//     }
-
-//     fn constraint_degree(&self) -> usize {
-//         1
-//     }
-// }
-
-// // Get the column info for Cross_Lookup<Cpu_table, Bitwise_table>
-// pub fn ctl_data_with_cpu<F: Field>() -> Vec<Column<F>> {
-//     let res = Column::singles([PC, INS, IMM]).collect_vec();
-//     res
-// }
-
-// pub fn ctl_filter_with_cpu<F: Field>() -> Column<F> {
-//     Column::one()
-// }
+use crate::program::columns::*;
+use crate::stark::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+use crate::stark::cross_table_lookup::Column;
+use crate::stark::stark::Stark;
+use crate::stark::vars::{StarkEvaluationTargets, StarkEvaluationVars};
+use itertools::Itertools;
+use plonky2::field::extension::{Extendable, FieldExtension};
+use plonky2::field::packed::PackedField;
+use plonky2::field::types::Field;
+use plonky2::hash::hash_types::RichField;
+use plonky2::iop::target::Target;
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use std::marker::PhantomData;
+
+#[derive(Copy, Clone, Default)]
+pub struct ProgramStark<F, const D: usize> {
+    program_hash: Option<[F; PROGRAM_HASH_LEN]>,
+    program_hash_target: Option<[Target; PROGRAM_HASH_LEN]>,
+    pub _phantom: PhantomData<F>,
+}
+
+impl<F: RichField, const D: usize> ProgramStark<F, D> {
+    /// Set the hash of the last row, which is a public value.
+    pub fn set_program_hash(&mut self, program_hash: [F; PROGRAM_HASH_LEN]) {
+        self.program_hash = Some(program_hash);
+    }
+
+    pub fn get_program_hash(&self) -> Option<[F; PROGRAM_HASH_LEN]> {
+        self.program_hash
+    }
+
+    /// Set the hash of the last row as targets of a recursive verifier, they
+    /// take precedence over `program_hash` in `eval_ext_circuit`.
+    pub fn set_program_hash_target(&mut self, program_hash_target: [Target; PROGRAM_HASH_LEN]) {
+        self.program_hash_target = Some(program_hash_target);
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for ProgramStark<F, D> {
+    const COLUMNS: usize = NUM_PROG_COLS;
+
+    // The program table carries no execution logic, its rows are chained by
+    // a Poseidon hash ending with the program hash public value. The filters
+    // used by the cpu and memory lookups must be binary.
+    fn eval_packed_generic<FE, P, const D2: usize>(
+        &self,
+        vars: StarkEvaluationVars<FE, P, { NUM_PROG_COLS }>,
+        yield_constr: &mut ConstraintConsumer<P>,
+    ) where
+        FE: FieldExtension<D2, BaseField = F>,
+        P: PackedField<Scalar = FE>,
+    {
+        let lv = vars.local_values;
+        let nv = vars.next_values;
+        let filter = lv[COL_PROG_FILTER];
+        yield_constr.constraint(filter * (P::ONES - filter));
+        let data_filter = lv[COL_PROG_DATA_FILTER];
+        yield_constr.constraint(data_filter * (P::ONES - data_filter));
+
+        // A row is an instruction, a data word or padding, the columns of the
+        // other kinds are zero so the hashed row determines them.
+        yield_constr.constraint(filter * data_filter);
+        for col in [COL_PROG_PC, COL_PROG_INST, COL_PROG_IMM] {
+            yield_constr.constraint((P::ONES - filter) * lv[col]);
+        }
+        for col in [COL_PROG_DATA_ADDR, COL_PROG_DATA_VALUE] {
+            yield_constr.constraint((P::ONES - data_filter) * lv[col]);
+        }
+
+        // Hash chain, padding rows keep the hash.
+        let program_hash = self.get_program_hash().unwrap();
+        for (i, (prev, hash)) in COL_PROG_PREV_HASH.zip(COL_PROG_HASH).enumerate() {
+            yield_constr.constraint_first_row(lv[prev]);
+            yield_constr.constraint_transition(nv[prev] - lv[hash]);
+            yield_constr.constraint((P::ONES - filter - data_filter) * (lv[hash] - lv[prev]));
+            yield_constr.constraint_last_row(lv[hash] - FE::from_basefield(program_hash[i]));
+        }
+    }
+
+    fn eval_ext_circuit(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        vars: StarkEvaluationTargets<D, { NUM_PROG_COLS }>,
+        yield_constr: &mut RecursiveConstraintConsumer<F, D>,
+    ) {
+        let lv = vars.local_values;
+        let nv = vars.next_values;
+        let one = builder.one_extension();
+        let filter = lv[COL_PROG_FILTER];
+        let filter_boolean = builder.sub_extension(one, filter);
+        let filter_boolean_cs = builder.mul_extension(filter, filter_boolean);
+        yield_constr.constraint(builder, filter_boolean_cs);
+        let data_filter = lv[COL_PROG_DATA_FILTER];
+        let data_filter_boolean = builder.sub_extension(one, data_filter);
+        let data_filter_boolean_cs = builder.mul_extension(data_filter, data_filter_boolean);
+        yield_constr.constraint(builder, data_filter_boolean_cs);
+
+        let kind_cs = builder.mul_extension(filter, data_filter);
+        yield_constr.constraint(builder, kind_cs);
+        for col in [COL_PROG_PC, COL_PROG_INST, COL_PROG_IMM] {
+            let cs = builder.mul_extension(filter_boolean, lv[col]);
+            yield_constr.constraint(builder, cs);
+        }
+        for col in [COL_PROG_DATA_ADDR, COL_PROG_DATA_VALUE] {
+            let cs = builder.mul_extension(data_filter_boolean, lv[col]);
+            yield_constr.constraint(builder, cs);
+        }
+
+        let program_hash = match self.program_hash_target {
+            Some(targets) => targets.map(|t| builder.convert_to_ext(t)),
+            None => self
+                .get_program_hash()
+                .unwrap()
+                .map(|h| builder.constant_extension(F::Extension::from_basefield(h))),
+        };
+        let is_padding = builder.sub_extension(filter_boolean, data_filter);
+        for (i, (prev, hash)) in COL_PROG_PREV_HASH.zip(COL_PROG_HASH).enumerate() {
+            yield_constr.constraint_first_row(builder, lv[prev]);
+            let chain_cs = builder.sub_extension(nv[prev], lv[hash]);
+            yield_constr.constraint_transition(builder, chain_cs);
+            let keep_cs = builder.sub_extension(lv[hash], lv[prev]);
+            let keep_cs = builder.mul_extension(is_padding, keep_cs);
+            yield_constr.constraint(builder, keep_cs);
+            let last_cs = builder.sub_extension(lv[hash], program_hash[i]);
+            yield_constr.constraint_last_row(builder, last_cs);
+        }
+    }
+
+    fn constraint_degree(&self) -> usize {
+        3
+    }
+}
+
+// Get the column info for Cross_Lookup<Cpu_table, Program_table>
+pub fn ctl_data_with_cpu<F: Field>() -> Vec<Column<F>> {
+    Column::singles([COL_PROG_PC, COL_PROG_INST, COL_PROG_IMM]).collect_vec()
+}
+
+pub fn ctl_filter_with_cpu<F: Field>() -> Column<F> {
+    Column::single(COL_PROG_FILTER)
+}
+
+// Get the column info for Cross_Lookup<Memory_table, Program_table>
+pub fn ctl_data_with_memory<F: Field>() -> Vec<Column<F>> {
+    Column::singles([COL_PROG_DATA_ADDR, COL_PROG_DATA_VALUE]).collect_vec()
+}
+
+pub fn ctl_filter_with_memory<F: Field>() -> Column<F> {
+    Column::single(COL_PROG_DATA_FILTER)
+}
+
+// Get the column info for Cross_Lookup<Program_table, Poseidon_table>, the
+// previous hash and the row are the inputs of the permutation, the hash its
+// output.
+pub fn ctl_data_with_poseidon<F: Field>() -> Vec<Column<F>> {
+    let mut data = Column::singles(COL_PROG_PREV_HASH).collect_vec();
+    data.extend([
+        Column::sum([COL_PROG_PC, COL_PROG_DATA_ADDR]),
+        Column::sum([COL_PROG_INST, COL_PROG_DATA_VALUE]),
+        Column::single(COL_PROG_IMM),
+        Column::single(COL_PROG_DATA_FILTER),
+    ]);
+    data.extend(Column::singles(COL_PROG_HASH));
+    data
+}
+
+pub fn ctl_filter_with_poseidon<F: Field>() -> Column<F> {
+    Column::sum([COL_PROG_FILTER, COL_PROG_DATA_FILTER])
+}
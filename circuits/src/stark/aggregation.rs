@@ -0,0 +1,407 @@
+use anyhow::{ensure, Result};
+use plonky2::field::extension::Extendable;
+use plonky2::hash::hash_types::{HashOut, RichField};
+use plonky2::hash::merkle_tree::MerkleCap;
+use plonky2::iop::target::{BoolTarget, Target};
+use plonky2::iop::witness::{PartialWitness, Witness};
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2::plonk::circuit_data::{CircuitConfig, CircuitData, VerifierCircuitTarget};
+use plonky2::plonk::config::{AlgebraicHasher, GenericConfig, Hasher};
+use plonky2::plonk::proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget};
+use plonky2_util::log2_ceil;
+
+use super::all_stark::{AllStark, NUM_TABLES};
+use super::config::StarkConfig;
+use super::preprocessed::AllVerifierKey;
+use super::proof::{AllProof, PublicValues};
+use super::recursive_verifier::AllProofRecursionCircuit;
+use super::stark::Stark;
+use crate::builtins::bitwise::bitwise_stark::BitwiseStark;
+use crate::builtins::ecdsa::ecdsa_stark::EcdsaStark;
+use crate::builtins::keccak::keccak_stark::KeccakStark;
+use crate::builtins::keccak_sponge::keccak_sponge_stark::KeccakSpongeStark;
+use crate::builtins::poseidon::poseidon_stark::PoseidonStark;
+use crate::builtins::rangecheck::rangecheck_stark::RangeCheckStark;
+use crate::cpu::cpu_stark::CpuStark;
+use crate::fixed_table::bitwise_fixed::bitwise_fixed_stark::BitwiseFixedStark;
+use crate::fixed_table::rangecheck_fixed::rangecheck_fixed_stark::RangecheckFixedStark;
+use crate::memory::memory_stark::MemoryStark;
+use crate::program::program_stark::ProgramStark;
+
+/// Aggregates up to `capacity()` `AllProof`s of the same trace lengths into a
+/// single plonky2 proof.
+///
+/// Every `AllProof` is first wrapped by the leaf `AllProofRecursionCircuit`,
+/// then the wrappers are verified pairwise in a binary tree of aggregation
+/// circuits. When the batch doesn't fill the tree, a missing right child is
+/// skipped with `conditionally_verify_proof_or_dummy` and its public values
+/// are zeroed.
+///
+/// The public inputs of the root proof are the number of aggregated proofs
+/// followed by `capacity()` slots of `PublicValues::to_field_elements`, in the
+/// order of the batch. Unused slots are zero.
+pub struct AllProofAggregation<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+> {
+    pub leaf: AllProofRecursionCircuit<F, C, D>,
+    /// One circuit per tree level, the last one is the root.
+    nodes: Vec<AggregationNode<F, C, D>>,
+}
+
+/// A level of the aggregation tree, verifying two proofs of the level below.
+struct AggregationNode<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> {
+    circuit: CircuitData<F, C, D>,
+    left: ProofWithPublicInputsTarget<D>,
+    right: ProofWithPublicInputsTarget<D>,
+    right_is_real: BoolTarget,
+    dummy: ProofWithPublicInputsTarget<D>,
+    dummy_verifier_data: VerifierCircuitTarget,
+    child_constants_sigmas_cap: MerkleCap<F, C::Hasher>,
+    child_circuit_digest: HashOut<F>,
+}
+
+impl<F, C, const D: usize> AllProofAggregation<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    C::Hasher: AlgebraicHasher<F>,
+    [(); C::Hasher::HASH_SIZE]:,
+{
+    /// Build the aggregation circuits for batches of at most `max_proofs`
+    /// proofs, rounded up to a power of two.
+    pub fn new(
+        all_stark: &AllStark<F, D>,
+        verifier_key: &AllVerifierKey<F, C, D>,
+        inner_config: &StarkConfig,
+        degree_bits: &[usize; NUM_TABLES],
+        max_proofs: usize,
+        circuit_config: CircuitConfig,
+    ) -> Result<Self>
+    where
+        [(); CpuStark::<F, D>::COLUMNS]:,
+        [(); MemoryStark::<F, D>::COLUMNS]:,
+        [(); BitwiseStark::<F, D>::COLUMNS]:,
+        [(); RangeCheckStark::<F, D>::COLUMNS]:,
+        [(); BitwiseFixedStark::<F, D>::COLUMNS]:,
+        [(); ProgramStark::<F, D>::COLUMNS]:,
+        [(); RangecheckFixedStark::<F, D>::COLUMNS]:,
+        [(); PoseidonStark::<F, D>::COLUMNS]:,
+        [(); EcdsaStark::<F, D>::COLUMNS]:,
+        [(); KeccakStark::<F, D>::COLUMNS]:,
+        [(); KeccakSpongeStark::<F, D>::COLUMNS]:,
+    {
+        ensure!(max_proofs > 0, "Cannot aggregate an empty batch");
+        let leaf = AllProofRecursionCircuit::<F, C, D>::new(
+            all_stark,
+            verifier_key,
+            inner_config,
+            degree_bits,
+            circuit_config.clone(),
+        );
+        let height = log2_ceil(max_proofs).max(1);
+        let mut nodes = vec![AggregationNode::<F, C, D>::new(
+            &leaf.circuit,
+            true,
+            circuit_config.clone(),
+        )];
+        for _ in 1..height {
+            let child = &nodes.last().unwrap().circuit;
+            let node = AggregationNode::<F, C, D>::new(child, false, circuit_config.clone());
+            nodes.push(node);
+        }
+        Ok(Self { leaf, nodes })
+    }
+
+    /// Maximum number of proofs in a batch.
+    pub fn capacity(&self) -> usize {
+        1 << self.nodes.len()
+    }
+
+    /// Wrap every `AllProof` with the leaf circuit and aggregate the wrappers.
+    pub fn prove(
+        &self,
+        all_proofs: &[AllProof<F, C, D>],
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        let leaf_proofs = all_proofs
+            .iter()
+            .map(|all_proof| self.leaf.prove(all_proof))
+            .collect::<Result<Vec<_>>>()?;
+        self.aggregate(leaf_proofs)
+    }
+
+    /// Aggregate proofs of the leaf circuit.
+    pub fn aggregate(
+        &self,
+        leaf_proofs: Vec<ProofWithPublicInputs<F, C, D>>,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        ensure!(!leaf_proofs.is_empty(), "Cannot aggregate an empty batch");
+        ensure!(
+            leaf_proofs.len() <= self.capacity(),
+            "Too many proofs: {} > {}",
+            leaf_proofs.len(),
+            self.capacity()
+        );
+        let mut proofs = leaf_proofs;
+        for node in &self.nodes {
+            proofs = proofs
+                .chunks(2)
+                .map(|pair| node.prove(&pair[0], pair.get(1)))
+                .collect::<Result<Vec<_>>>()?;
+        }
+        debug_assert_eq!(proofs.len(), 1);
+        Ok(proofs.pop().unwrap())
+    }
+
+    pub fn verify(&self, proof: ProofWithPublicInputs<F, C, D>) -> Result<()> {
+        self.root().verify(proof)
+    }
+
+    pub fn root(&self) -> &CircuitData<F, C, D> {
+        &self.nodes.last().unwrap().circuit
+    }
+
+    /// The public values of every aggregated proof, in the order of the batch.
+    pub fn public_values(
+        &self,
+        proof: &ProofWithPublicInputs<F, C, D>,
+    ) -> Result<Vec<PublicValues>> {
+        let public_inputs = &proof.public_inputs;
+        ensure!(
+            public_inputs.len() == 1 + self.capacity() * PublicValues::NUM_FIELD_ELEMENTS,
+            "Invalid number of public inputs: {}",
+            public_inputs.len()
+        );
+        let num_proofs = public_inputs[0].to_canonical_u64() as usize;
+        ensure!(
+            num_proofs <= self.capacity(),
+            "Invalid number of aggregated proofs: {}",
+            num_proofs
+        );
+        public_inputs[1..]
+            .chunks(PublicValues::NUM_FIELD_ELEMENTS)
+            .take(num_proofs)
+            .map(PublicValues::from_field_elements)
+            .collect()
+    }
+}
+
+impl<F, C, const D: usize> AggregationNode<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    C::Hasher: AlgebraicHasher<F>,
+    [(); C::Hasher::HASH_SIZE]:,
+{
+    fn new(
+        child: &CircuitData<F, C, D>,
+        child_is_leaf: bool,
+        circuit_config: CircuitConfig,
+    ) -> Self {
+        let mut builder = CircuitBuilder::<F, D>::new(circuit_config);
+        let left = builder.add_virtual_proof_with_pis(&child.common);
+        let right = builder.add_virtual_proof_with_pis(&child.common);
+        let right_is_real = builder.add_virtual_bool_target_safe();
+
+        let child_verifier_data = VerifierCircuitTarget {
+            constants_sigmas_cap: builder
+                .constant_merkle_cap(&child.verifier_only.constants_sigmas_cap),
+            circuit_digest: builder.constant_hash(child.verifier_only.circuit_digest),
+        };
+        builder.verify_proof(left.clone(), &child_verifier_data, &child.common);
+        let (dummy, dummy_verifier_data) = builder.conditionally_verify_proof_or_dummy(
+            right_is_real,
+            &right,
+            &child_verifier_data,
+            &child.common,
+        );
+
+        let (left_num_proofs, left_values) =
+            split_public_inputs(&mut builder, &left, child_is_leaf);
+        let (right_num_proofs, right_values) =
+            split_public_inputs(&mut builder, &right, child_is_leaf);
+
+        // Real proofs fill the slots from the left, so the right child is only
+        // real when the left one is full.
+        let left_capacity = builder.constant(F::from_canonical_usize(
+            left_values.len() / PublicValues::NUM_FIELD_ELEMENTS,
+        ));
+        let left_missing = builder.sub(left_capacity, left_num_proofs);
+        let left_missing_cs = builder.mul(right_is_real.target, left_missing);
+        builder.assert_zero(left_missing_cs);
+
+        let num_proofs = builder.mul_add(right_is_real.target, right_num_proofs, left_num_proofs);
+        builder.register_public_input(num_proofs);
+        builder.register_public_inputs(&left_values);
+        // The public inputs of a skipped right child are unconstrained.
+        for v in right_values {
+            let v = builder.mul(right_is_real.target, v);
+            builder.register_public_input(v);
+        }
+
+        Self {
+            circuit: builder.build::<C>(),
+            left,
+            right,
+            right_is_real,
+            dummy,
+            dummy_verifier_data,
+            child_constants_sigmas_cap: child.verifier_only.constants_sigmas_cap.clone(),
+            child_circuit_digest: child.verifier_only.circuit_digest,
+        }
+    }
+
+    fn prove(
+        &self,
+        left: &ProofWithPublicInputs<F, C, D>,
+        right: Option<&ProofWithPublicInputs<F, C, D>>,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        let mut pw = PartialWitness::new();
+        pw.set_proof_with_pis_target(&self.left, left);
+        pw.set_bool_target(self.right_is_real, right.is_some());
+        // Any proof of the child circuit can stand in for a missing right child
+        // and for the dummy proof, the left one is at hand.
+        pw.set_proof_with_pis_target(&self.right, right.unwrap_or(left));
+        pw.set_proof_with_pis_target(&self.dummy, left);
+        pw.set_cap_target(
+            &self.dummy_verifier_data.constants_sigmas_cap,
+            &self.child_constants_sigmas_cap,
+        );
+        pw.set_hash_target(
+            self.dummy_verifier_data.circuit_digest,
+            self.child_circuit_digest,
+        );
+        self.circuit.prove(pw)
+    }
+}
+
+/// The number of proofs verified by a child and their public values. A leaf
+/// proof verifies a single `AllProof`.
+fn split_public_inputs<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    proof: &ProofWithPublicInputsTarget<D>,
+    is_leaf: bool,
+) -> (Target, Vec<Target>) {
+    if is_leaf {
+        (builder.one(), proof.public_inputs.clone())
+    } else {
+        (proof.public_inputs[0], proof.public_inputs[1..].to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use core::program::Program;
+    use executor::Process;
+    use plonky2::plonk::circuit_data::CircuitConfig;
+    use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use plonky2::util::timing::TimingTree;
+
+    use crate::generation::generate_traces;
+    use crate::stark::aggregation::AllProofAggregation;
+    use crate::stark::all_stark::AllStark;
+    use crate::stark::config::StarkConfig;
+    use crate::stark::preprocessed::AllPreprocessed;
+    use crate::stark::proof::AllProof;
+    use crate::stark::prover::prove_with_preprocessed;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    fn prove_program(
+        program_src: &str,
+        preprocessed: &AllPreprocessed<F, C, D>,
+        config: &StarkConfig,
+    ) -> Result<AllProof<F, C, D>> {
+        let mut program: Program = Program {
+            instructions: Vec::new(),
+            trace: Default::default(),
+        };
+        for inst in program_src.split('\n') {
+            program.instructions.push(inst.to_string());
+        }
+
+        let mut process = Process::new();
+        let _ = process.execute(&mut program);
+
+        let mut all_stark = AllStark::default();
+        let (traces, public_values) = generate_traces(program, &mut all_stark)?;
+        prove_with_preprocessed::<F, C, D>(
+            &all_stark,
+            preprocessed,
+            config,
+            traces,
+            public_values,
+            &mut TimingTree::default(),
+        )
+    }
+
+    #[test]
+    fn aggregate_all_proofs() -> Result<()> {
+        //mov r0 8
+        //mov r1 2
+        //add r3 r0 r1
+        //end
+        let add_src = "0x4000000840000000
+            0x8
+            0x4000001040000000
+            0x2
+            0x0020204400000000
+            0x0000000000800000";
+        //mov r0 5
+        //mov r1 7
+        //mul r3 r0 r1
+        //end
+        let mul_src = "0x4000000840000000
+            0x5
+            0x4000001040000000
+            0x7
+            0x0020204200000000
+            0x0000000000800000";
+
+        let all_stark = AllStark::default();
+        let config = StarkConfig::standard_fast_config();
+        let preprocessed =
+            AllPreprocessed::<F, C, D>::new(&all_stark, &config, &mut TimingTree::default());
+        let add_proof = prove_program(add_src, &preprocessed, &config)?;
+        let mul_proof = prove_program(mul_src, &preprocessed, &config)?;
+        assert_eq!(
+            add_proof.degree_bits(&config),
+            mul_proof.degree_bits(&config)
+        );
+
+        // Three proofs in a tree of capacity four, the last leaf is skipped.
+        let aggregation = AllProofAggregation::<F, C, D>::new(
+            &all_stark,
+            &preprocessed.verifier_key(),
+            &config,
+            &add_proof.degree_bits(&config),
+            3,
+            CircuitConfig::standard_recursion_config(),
+        )?;
+        assert_eq!(aggregation.capacity(), 4);
+        let all_proofs = [add_proof, mul_proof.clone(), mul_proof];
+        let proof = aggregation.prove(&all_proofs)?;
+
+        let public_values = aggregation.public_values(&proof)?;
+        assert_eq!(public_values.len(), 3);
+        for (public_values, all_proof) in public_values.iter().zip(all_proofs.iter()) {
+            assert_eq!(
+                public_values.program_hash,
+                all_proof.public_values.program_hash
+            );
+            assert_eq!(
+                public_values.final_registers,
+                all_proof.public_values.final_registers
+            );
+        }
+        assert_eq!(public_values[0].final_registers[3], 10);
+        assert_eq!(public_values[1].final_registers[3], 35);
+        assert_ne!(public_values[0].program_hash, public_values[1].program_hash);
+
+        aggregation.verify(proof)
+    }
+}
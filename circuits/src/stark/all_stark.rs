@@ -3,13 +3,26 @@ use super::cross_table_lookup::{CrossTableLookup, TableWithColumns};
 use super::stark::Stark;
 use crate::builtins::bitwise::bitwise_stark::{self, BitwiseStark};
 use crate::builtins::cmp::cmp_stark::{self, CmpStark};
+use crate::builtins::ecdsa::columns::range_checked_columns as ecdsa_range_checked_columns;
+use crate::builtins::ecdsa::ecdsa_stark::{self, EcdsaStark};
+use crate::builtins::keccak::keccak_stark::{self, KeccakStark};
+use crate::builtins::keccak_sponge::columns::RATE_WORDS as KECCAK_RATE_WORDS;
+use crate::builtins::keccak_sponge::keccak_sponge_stark::{self, KeccakSpongeStark};
+use crate::builtins::poseidon::columns::{
+    NUM_INPUTS as POSEIDON_NUM_INPUTS, NUM_OUTPUTS as POSEIDON_NUM_OUTPUTS,
+};
+use crate::builtins::poseidon::poseidon_stark::{self, PoseidonStark};
 use crate::builtins::rangecheck::rangecheck_stark::{self, RangeCheckStark};
 use crate::cpu::cpu_stark;
 use crate::cpu::cpu_stark::CpuStark;
+use crate::fixed_table::bitwise_fixed::bitwise_fixed_stark::{self, BitwiseFixedStark};
+use crate::fixed_table::rangecheck_fixed::rangecheck_fixed_stark::{self, RangecheckFixedStark};
 use crate::memory::memory_stark::{
-    ctl_data as mem_ctl_data, ctl_data_mem_rc, ctl_filter as mem_ctl_filter, ctl_filter_mem_rc,
-    MemoryStark,
+    self as memory_stark, ctl_data as mem_ctl_data, ctl_data_mem_rc, ctl_filter as mem_ctl_filter,
+    ctl_filter_mem_rc, MemoryStark,
 };
+use crate::program::program_stark::{self, ProgramStark};
+use core::trace::trace::{BitwiseOperation, ECDSA_WORD_NUM, KECCAK_OUTPUT_NUM};
 use plonky2::field::extension::Extendable;
 use plonky2::field::types::Field;
 use plonky2::hash::hash_types::RichField;
@@ -20,8 +33,22 @@ pub struct AllStark<F: RichField + Extendable<D>, const D: usize> {
     pub memory_stark: MemoryStark<F, D>,
     // builtins
     pub bitwise_stark: BitwiseStark<F, D>,
+    // The functions over all tables bound the `COLUMNS` of every table but
+    // this one: it equals `RangeCheckStark::COLUMNS`, and the compiler fails
+    // to unify a second explicit bound with the same value.
     pub cmp_stark: CmpStark<F, D>,
     pub rangecheck_stark: RangeCheckStark<F, D>,
+    // program
+    pub program_stark: ProgramStark<F, D>,
+    // fixed tables
+    pub bitwise_fixed_stark: BitwiseFixedStark<F, D>,
+    pub rangecheck_fixed_stark: RangecheckFixedStark<F, D>,
+    // program of the next segment
+    pub final_program_stark: ProgramStark<F, D>,
+    pub poseidon_stark: PoseidonStark<F, D>,
+    pub ecdsa_stark: EcdsaStark<F, D>,
+    pub keccak_stark: KeccakStark<F, D>,
+    pub keccak_sponge_stark: KeccakSpongeStark<F, D>,
 
     pub cross_table_lookups: Vec<CrossTableLookup<F>>,
 }
@@ -34,6 +61,14 @@ impl<F: RichField + Extendable<D>, const D: usize> Default for AllStark<F, D> {
             bitwise_stark: BitwiseStark::default(),
             cmp_stark: CmpStark::default(),
             rangecheck_stark: RangeCheckStark::default(),
+            program_stark: ProgramStark::default(),
+            bitwise_fixed_stark: BitwiseFixedStark::default(),
+            rangecheck_fixed_stark: RangecheckFixedStark::default(),
+            final_program_stark: ProgramStark::default(),
+            poseidon_stark: PoseidonStark::default(),
+            ecdsa_stark: EcdsaStark::default(),
+            keccak_stark: KeccakStark::default(),
+            keccak_sponge_stark: KeccakSpongeStark::default(),
             cross_table_lookups: all_cross_table_lookups(),
         }
     }
@@ -47,6 +82,51 @@ impl<F: RichField + Extendable<D>, const D: usize> AllStark<F, D> {
             self.bitwise_stark.num_permutation_batches(config),
             self.cmp_stark.num_permutation_batches(config),
             self.rangecheck_stark.num_permutation_batches(config),
+            self.program_stark.num_permutation_batches(config),
+            self.bitwise_fixed_stark.num_permutation_batches(config),
+            self.rangecheck_fixed_stark.num_permutation_batches(config),
+            self.final_program_stark.num_permutation_batches(config),
+            self.poseidon_stark.num_permutation_batches(config),
+            self.ecdsa_stark.num_permutation_batches(config),
+            self.keccak_stark.num_permutation_batches(config),
+            self.keccak_sponge_stark.num_permutation_batches(config),
+        ]
+    }
+
+    pub(crate) fn nums_lookup_zs(&self, config: &StarkConfig) -> [usize; NUM_TABLES] {
+        [
+            self.cpu_stark.num_lookup_helper_columns(config),
+            self.memory_stark.num_lookup_helper_columns(config),
+            self.bitwise_stark.num_lookup_helper_columns(config),
+            self.cmp_stark.num_lookup_helper_columns(config),
+            self.rangecheck_stark.num_lookup_helper_columns(config),
+            self.program_stark.num_lookup_helper_columns(config),
+            self.bitwise_fixed_stark.num_lookup_helper_columns(config),
+            self.rangecheck_fixed_stark
+                .num_lookup_helper_columns(config),
+            self.final_program_stark.num_lookup_helper_columns(config),
+            self.poseidon_stark.num_lookup_helper_columns(config),
+            self.ecdsa_stark.num_lookup_helper_columns(config),
+            self.keccak_stark.num_lookup_helper_columns(config),
+            self.keccak_sponge_stark.num_lookup_helper_columns(config),
+        ]
+    }
+
+    pub(crate) fn nums_preprocessed_columns(&self) -> [usize; NUM_TABLES] {
+        [
+            self.cpu_stark.num_preprocessed_columns(),
+            self.memory_stark.num_preprocessed_columns(),
+            self.bitwise_stark.num_preprocessed_columns(),
+            self.cmp_stark.num_preprocessed_columns(),
+            self.rangecheck_stark.num_preprocessed_columns(),
+            self.program_stark.num_preprocessed_columns(),
+            self.bitwise_fixed_stark.num_preprocessed_columns(),
+            self.rangecheck_fixed_stark.num_preprocessed_columns(),
+            self.final_program_stark.num_preprocessed_columns(),
+            self.poseidon_stark.num_preprocessed_columns(),
+            self.ecdsa_stark.num_preprocessed_columns(),
+            self.keccak_stark.num_preprocessed_columns(),
+            self.keccak_sponge_stark.num_preprocessed_columns(),
         ]
     }
 
@@ -57,6 +137,14 @@ impl<F: RichField + Extendable<D>, const D: usize> AllStark<F, D> {
             self.bitwise_stark.permutation_batch_size(),
             self.cmp_stark.permutation_batch_size(),
             self.rangecheck_stark.permutation_batch_size(),
+            self.program_stark.permutation_batch_size(),
+            self.bitwise_fixed_stark.permutation_batch_size(),
+            self.rangecheck_fixed_stark.permutation_batch_size(),
+            self.final_program_stark.permutation_batch_size(),
+            self.poseidon_stark.permutation_batch_size(),
+            self.ecdsa_stark.permutation_batch_size(),
+            self.keccak_stark.permutation_batch_size(),
+            self.keccak_sponge_stark.permutation_batch_size(),
         ]
     }
 }
@@ -69,13 +157,20 @@ pub enum Table {
     Bitwise = 2,
     Cmp = 3,
     RangeCheck = 4,
-    BitwiseFixed = 5,
-    RangecheckFixed = 6,
     // program table
-    Program = 7,
+    Program = 5,
+    // fixed tables
+    BitwiseFixed = 6,
+    RangecheckFixed = 7,
+    // program table of the next segment, with the final memory as static data
+    FinalProgram = 8,
+    Poseidon = 9,
+    Ecdsa = 10,
+    Keccak = 11,
+    KeccakSponge = 12,
 }
 
-pub(crate) const NUM_TABLES: usize = 5;
+pub(crate) const NUM_TABLES: usize = 13;
 
 pub(crate) fn all_cross_table_lookups<F: Field>() -> Vec<CrossTableLookup<F>> {
     vec![
@@ -85,6 +180,17 @@ pub(crate) fn all_cross_table_lookups<F: Field>() -> Vec<CrossTableLookup<F>> {
         ctl_cmp_cpu(),
         ctl_cmp_rangecheck(),
         ctl_rangecheck_cpu(),
+        ctl_poseidon_cpu(),
+        ctl_ecdsa_cpu(),
+        ctl_keccak_sponge_cpu(),
+        ctl_keccak_sponge_keccak(),
+        ctl_correct_program_cpu(),
+        ctl_memory_init_program(),
+        ctl_program_final_program(),
+        ctl_memory_final_program(),
+        ctl_poseidon_program(),
+        ctl_bitwise_bitwise_fixed_table(),
+        ctl_rangecheck_rangecheck_fixed(),
     ]
 }
 
@@ -109,15 +215,59 @@ fn ctl_cpu_memory<F: Field>() -> CrossTableLookup<F> {
         cpu_stark::ctl_data_cpu_mem_call_ret_fp(),
         Some(cpu_stark::ctl_filter_cpu_mem_call_ret()),
     );
-    let all_cpu_lookers = vec![
+    let mut all_lookers = vec![
         cpu_mem_mstore,
         cpu_mem_mload,
         cpu_mem_call_ret_pc,
         cpu_mem_call_ret_fp,
     ];
+    // Memory accesses of the poseidon instructions.
+    all_lookers.extend((0..POSEIDON_NUM_INPUTS).map(|i| {
+        TableWithColumns::new(
+            Table::Poseidon,
+            poseidon_stark::ctl_data_with_memory_input(i),
+            Some(poseidon_stark::ctl_filter_with_memory()),
+        )
+    }));
+    all_lookers.extend((0..POSEIDON_NUM_OUTPUTS).map(|i| {
+        TableWithColumns::new(
+            Table::Poseidon,
+            poseidon_stark::ctl_data_with_memory_output(i),
+            Some(poseidon_stark::ctl_filter_with_memory()),
+        )
+    }));
+    // Memory reads of the ecdsa instructions, a u32 word of each LOAD row.
+    all_lookers.extend((0..ECDSA_WORD_NUM).map(|i| {
+        TableWithColumns::new(
+            Table::Ecdsa,
+            ecdsa_stark::ctl_data_with_memory(i),
+            Some(ecdsa_stark::ctl_filter_with_memory()),
+        )
+    }));
+    // Memory accesses of the keccak instructions, the length and the words of
+    // each block, and the digest of the final block.
+    all_lookers.push(TableWithColumns::new(
+        Table::KeccakSponge,
+        keccak_sponge_stark::ctl_data_with_memory_len(),
+        Some(keccak_sponge_stark::ctl_filter_with_memory_len()),
+    ));
+    all_lookers.extend((0..KECCAK_RATE_WORDS).map(|j| {
+        TableWithColumns::new(
+            Table::KeccakSponge,
+            keccak_sponge_stark::ctl_data_with_memory_block(j),
+            Some(keccak_sponge_stark::ctl_filter_with_memory_block(j)),
+        )
+    }));
+    all_lookers.extend((0..KECCAK_OUTPUT_NUM).map(|i| {
+        TableWithColumns::new(
+            Table::KeccakSponge,
+            keccak_sponge_stark::ctl_data_with_memory_output(i),
+            Some(keccak_sponge_stark::ctl_filter_with_memory_output()),
+        )
+    }));
     let memory_looked =
         TableWithColumns::new(Table::Memory, mem_ctl_data(), Some(mem_ctl_filter()));
-    CrossTableLookup::new(all_cpu_lookers, memory_looked, None)
+    CrossTableLookup::new(all_lookers, memory_looked, None)
 }
 
 fn ctl_memory_rc<F: Field>() -> CrossTableLookup<F> {
@@ -149,30 +299,48 @@ fn ctl_memory_rc<F: Field>() -> CrossTableLookup<F> {
 // +-----+-----+-----+-----+------------+------------+-----------+------------+---
 //
 // Filter bitwise from CPU Table
-// 1. (sel_add + sel_or + sel_xor) * (op0, op1, dst) = looking_table
+// 1. sel_and * (AND, op0, op1, dst) = looking_table
+// 2. sel_or * (OR, op0, op1, dst) = looking_table
+// 3. sel_xor * (XOR, op0, op1, dst) = looking_table
+// Filter bitwise from KeccakSponge Table
+// 4. (is_full + is_final) * (XOR, state_j, padded_j, xored_j) = looking_table
+// 5. is_final * (AND, tail_word, mask, tail_word) = looking_table
 // Filter bitwise from Bitwsie Table
-// 1. (op0, op1, res) = looked_table
+// 1. (tag, op0, op1, res) = looked_table
 
 // Cross_Lookup_Table(looking_table, looked_table)
 fn ctl_bitwise_cpu<F: Field>() -> CrossTableLookup<F> {
+    let mut all_lookers = vec![
+        TableWithColumns::new(
+            Table::Cpu,
+            cpu_stark::ctl_data_with_bitwise(BitwiseOperation::And),
+            Some(cpu_stark::ctl_filter_with_bitwise_and()),
+        ),
+        TableWithColumns::new(
+            Table::Cpu,
+            cpu_stark::ctl_data_with_bitwise(BitwiseOperation::Or),
+            Some(cpu_stark::ctl_filter_with_bitwise_or()),
+        ),
+        TableWithColumns::new(
+            Table::Cpu,
+            cpu_stark::ctl_data_with_bitwise(BitwiseOperation::Xor),
+            Some(cpu_stark::ctl_filter_with_bitwise_xor()),
+        ),
+    ];
+    all_lookers.extend((0..KECCAK_RATE_WORDS).map(|j| {
+        TableWithColumns::new(
+            Table::KeccakSponge,
+            keccak_sponge_stark::ctl_data_with_bitwise_xor(j),
+            Some(keccak_sponge_stark::ctl_filter_with_bitwise_xor()),
+        )
+    }));
+    all_lookers.push(TableWithColumns::new(
+        Table::KeccakSponge,
+        keccak_sponge_stark::ctl_data_with_bitwise_tail(),
+        Some(keccak_sponge_stark::ctl_filter_with_bitwise_tail()),
+    ));
     CrossTableLookup::new(
-        vec![
-            TableWithColumns::new(
-                Table::Cpu,
-                cpu_stark::ctl_data_with_bitwise(),
-                Some(cpu_stark::ctl_filter_with_bitwise_and()),
-            ),
-            TableWithColumns::new(
-                Table::Cpu,
-                cpu_stark::ctl_data_with_bitwise(),
-                Some(cpu_stark::ctl_filter_with_bitwise_or()),
-            ),
-            TableWithColumns::new(
-                Table::Cpu,
-                cpu_stark::ctl_data_with_bitwise(),
-                Some(cpu_stark::ctl_filter_with_bitwise_xor()),
-            ),
-        ],
+        all_lookers,
         TableWithColumns::new(
             Table::Bitwise,
             bitwise_stark::ctl_data_with_cpu(),
@@ -232,71 +400,159 @@ fn ctl_rangecheck_cpu<F: Field>() -> CrossTableLookup<F> {
     )
 }
 
-// Cross_Lookup_Table(looking_table, looked_table)
-/*fn ctl_bitwise_bitwise_fixed_table<F: Field>() -> CrossTableLookup<F> {
+// Every ecdsa instruction of the cpu table is the last row of a verification
+// of the ecdsa table, with its input address and flag.
+fn ctl_ecdsa_cpu<F: Field>() -> CrossTableLookup<F> {
     CrossTableLookup::new(
         vec![TableWithColumns::new(
-            Table::BitwiseFixed,
-            bitwise_fixed_stark::ctl_data_with_bitwise(),
-            Some(bitwise_fixed_stark::ctl_filter_with_bitwise()),
+            Table::Cpu,
+            cpu_stark::ctl_data_with_ecdsa(),
+            Some(cpu_stark::ctl_filter_with_ecdsa()),
         )],
         TableWithColumns::new(
-            Table::Bitwise,
-            bitwise_stark::ctl_data_with_bitwise_fixed(),
-            Some(bitwise_stark::ctl_filter_with_bitwise_fixed()),
+            Table::Ecdsa,
+            ecdsa_stark::ctl_data_with_cpu(),
+            Some(ecdsa_stark::ctl_filter_with_cpu()),
         ),
         None,
     )
-}*/
+}
 
-/*fn ctl_rangecheck_rangecheck_fixed<F: Field>() -> CrossTableLookup<F> {
+// Every keccak instruction of the cpu table is the final block of a hash of
+// the keccak sponge table, with its input and output addresses.
+fn ctl_keccak_sponge_cpu<F: Field>() -> CrossTableLookup<F> {
     CrossTableLookup::new(
         vec![TableWithColumns::new(
-            Table::RangecheckFixed,
-            rangecheck_fixed_stark::ctl_data_with_rangecheck(),
-            Some(rangecheck_fixed_stark::ctl_filter_with_rangecheck()),
+            Table::Cpu,
+            cpu_stark::ctl_data_with_keccak(),
+            Some(cpu_stark::ctl_filter_with_keccak()),
         )],
         TableWithColumns::new(
-            Table::RangeCheck,
-            rangecheck_stark::ctl_data_with_rangecheck_fixed(),
-            Some(rangecheck_stark::ctl_filter_with_rangecheck_fixed()),
+            Table::KeccakSponge,
+            keccak_sponge_stark::ctl_data_with_cpu(),
+            Some(keccak_sponge_stark::ctl_filter_with_cpu()),
+        ),
+        None,
+    )
+}
+
+// Every block of the keccak sponge table is a permutation of the keccak table,
+// the state before and after its 24 rounds.
+fn ctl_keccak_sponge_keccak<F: Field>() -> CrossTableLookup<F> {
+    CrossTableLookup::new(
+        vec![TableWithColumns::new(
+            Table::KeccakSponge,
+            keccak_sponge_stark::ctl_data_with_keccak(),
+            Some(keccak_sponge_stark::ctl_filter_with_keccak()),
+        )],
+        TableWithColumns::new(
+            Table::Keccak,
+            keccak_stark::ctl_data_with_sponge(),
+            Some(keccak_stark::ctl_filter_with_sponge()),
+        ),
+        None,
+    )
+}
+
+// Every poseidon instruction of the cpu table is one row of the poseidon
+// table, with its input and output addresses.
+fn ctl_poseidon_cpu<F: Field>() -> CrossTableLookup<F> {
+    CrossTableLookup::new(
+        vec![TableWithColumns::new(
+            Table::Cpu,
+            cpu_stark::ctl_data_with_poseidon(),
+            Some(cpu_stark::ctl_filter_with_poseidon()),
+        )],
+        TableWithColumns::new(
+            Table::Poseidon,
+            poseidon_stark::ctl_data_with_cpu(),
+            Some(poseidon_stark::ctl_filter_with_cpu()),
         ),
         None,
     )
-}*/
+}
+
+// Every <op0_limb_i, op1_limb_i, and_limb_i> of the bitwise table is a row of
+// the fixed AND table, which counts how many times each row is looked up.
+fn ctl_bitwise_bitwise_fixed_table<F: Field>() -> CrossTableLookup<F> {
+    CrossTableLookup::new_logup(
+        (0..4)
+            .map(|i| {
+                TableWithColumns::new(
+                    Table::Bitwise,
+                    bitwise_stark::ctl_data_with_bitwise_fixed(i),
+                    None,
+                )
+            })
+            .collect(),
+        TableWithColumns::new(
+            Table::BitwiseFixed,
+            bitwise_fixed_stark::ctl_data_with_bitwise(),
+            Some(bitwise_fixed_stark::ctl_filter_with_bitwise()),
+        ),
+    )
+}
+
+// Both limbs of every rangecheck row and the u16 columns of every ecdsa row
+// are in the fixed u16 table.
+fn ctl_rangecheck_rangecheck_fixed<F: Field>() -> CrossTableLookup<F> {
+    let mut all_lookers = vec![
+        TableWithColumns::new(
+            Table::RangeCheck,
+            rangecheck_stark::ctl_data_limb_lo_with_rangecheck_fixed(),
+            None,
+        ),
+        TableWithColumns::new(
+            Table::RangeCheck,
+            rangecheck_stark::ctl_data_limb_hi_with_rangecheck_fixed(),
+            None,
+        ),
+    ];
+    all_lookers.extend(ecdsa_range_checked_columns().map(|col| {
+        TableWithColumns::new(
+            Table::Ecdsa,
+            ecdsa_stark::ctl_data_with_rangecheck_fixed(col),
+            None,
+        )
+    }));
+    CrossTableLookup::new_logup(
+        all_lookers,
+        TableWithColumns::new(
+            Table::RangecheckFixed,
+            rangecheck_fixed_stark::ctl_data_with_rangecheck(),
+            Some(rangecheck_fixed_stark::ctl_filter_with_rangecheck()),
+        ),
+    )
+}
 
 // check the correct program with lookup
 
 // Program table
-// +-----+--------------+-------+----------+
-// | PC  |      INS     |  IMM  | COMPRESS |
-// +-----+--------------+-------+----------+
-// +-----+--------------+-------+----------+
-// |  1  |  0x********  |  U32  |   Field  |
-// +-----+--------------+-------+----------+
-// +-----+--------------+-------+----------+
-// |  2  |  0x********  |  U32  |   Field  |
-// +-----+--------------+-------+----------++
-// +-----+--------------+-------+----------+
-// |  3  |  0x********  |  U32  |   Field  |
-// +-----+--------------+-------+----------+
+// +-----+--------------+-------+--------+
+// | PC  |      INS     |  IMM  | FILTER |
+// +-----+--------------+-------+--------+
+// +-----+--------------+-------+--------+
+// |  0  |  0x********  |  U64  |   1    |
+// +-----+--------------+-------+--------+
+// +-----+--------------+-------+--------+
+// |  2  |  0x********  |   0   |   1    |
+// +-----+--------------+-------+--------+
 
 // CPU table
-// +-----+-----+--------------+-------+----------+
-// | ... | PC  |      INS     |  IMM  | COMPRESS |
-// +-----+-----+--------------+-------+----------+
-// +-----+-----+--------------+-------+----------+
-// | ... |  1  |  0x********  |  U32  |   Field  |
-// +-----+-----+--------------+-------+----------+
-// +-----+-----+--------------+-------+----------+
-// | ... |  2  |  0x********  |  U32  |   Field  |
-// +-----+-----+--------------+-------+----------++
-// +-----+-----+--------------+-------+----------+
-// | ... |  3  |  0x********  |  U32  |   Field  |
-// +-----+-----+--------------+-------+----------+
-
-// Note that COMPRESS will be computed by vector lookup argument protocol
-/*fn ctl_correct_program_cpu<F: Field>() -> CrossTableLookup<F> {
+// +-----+--------+--------------+---------+------------+
+// | ... | RAW_PC |   RAW_INST   | RAW_IMM | RAW_FILTER |
+// +-----+--------+--------------+---------+------------+
+// +-----+--------+--------------+---------+------------+
+// | ... |   0    |  0x********  |   U64   |      1     |
+// +-----+--------+--------------+---------+------------+
+// +-----+--------+--------------+---------+------------+
+// | ... |   2    |  0x********  |    0    |      1     |
+// +-----+--------+--------------+---------+------------+
+
+// Every raw program row of the cpu table is looked up exactly once in the
+// program table, the executed instructions are then checked against the raw
+// rows with the cpu LogUp lookup of (pc, inst, imm) in the raw rows.
+fn ctl_correct_program_cpu<F: Field>() -> CrossTableLookup<F> {
     CrossTableLookup::new(
         vec![TableWithColumns::new(
             Table::Cpu,
@@ -310,29 +566,120 @@ fn ctl_rangecheck_cpu<F: Field>() -> CrossTableLookup<F> {
         ),
         None,
     )
-}*/
+}
+
+// Every static data row of the memory table is one data row of the program,
+// so the initial memory is committed with the program.
+fn ctl_memory_init_program<F: Field>() -> CrossTableLookup<F> {
+    CrossTableLookup::new(
+        vec![TableWithColumns::new(
+            Table::Memory,
+            memory_stark::ctl_data_mem_init(),
+            Some(memory_stark::ctl_filter_mem_init()),
+        )],
+        TableWithColumns::new(
+            Table::Program,
+            program_stark::ctl_data_with_memory(),
+            Some(program_stark::ctl_filter_with_memory()),
+        ),
+        None,
+    )
+}
+
+// The program of the next segment has the same instructions.
+fn ctl_program_final_program<F: Field>() -> CrossTableLookup<F> {
+    CrossTableLookup::new(
+        vec![TableWithColumns::new(
+            Table::Program,
+            program_stark::ctl_data_with_cpu(),
+            Some(program_stark::ctl_filter_with_cpu()),
+        )],
+        TableWithColumns::new(
+            Table::FinalProgram,
+            program_stark::ctl_data_with_cpu(),
+            Some(program_stark::ctl_filter_with_cpu()),
+        ),
+        None,
+    )
+}
+
+// The static data of the next segment is the last value of every read write
+// address and prophet output of the memory table. Addresses are unique in both
+// tables, so `final_program_hash` commits to the memory left by this segment.
+fn ctl_memory_final_program<F: Field>() -> CrossTableLookup<F> {
+    CrossTableLookup::new(
+        vec![TableWithColumns::new(
+            Table::Memory,
+            memory_stark::ctl_data_mem_final(),
+            Some(memory_stark::ctl_filter_mem_final()),
+        )],
+        TableWithColumns::new(
+            Table::FinalProgram,
+            program_stark::ctl_data_with_memory(),
+            Some(program_stark::ctl_filter_with_memory()),
+        ),
+        None,
+    )
+}
 
+// Every instruction and data row of both program tables is one permutation
+// of the poseidon table chaining the program hash, so `program_hash` and
+// `final_program_hash` are the hashes of the committed rows.
+fn ctl_poseidon_program<F: Field>() -> CrossTableLookup<F> {
+    CrossTableLookup::new(
+        vec![
+            TableWithColumns::new(
+                Table::Program,
+                program_stark::ctl_data_with_poseidon(),
+                Some(program_stark::ctl_filter_with_poseidon()),
+            ),
+            TableWithColumns::new(
+                Table::FinalProgram,
+                program_stark::ctl_data_with_poseidon(),
+                Some(program_stark::ctl_filter_with_poseidon()),
+            ),
+        ],
+        TableWithColumns::new(
+            Table::Poseidon,
+            poseidon_stark::ctl_data_with_program(),
+            Some(poseidon_stark::ctl_filter_with_program()),
+        ),
+        None,
+    )
+}
+
+#[cfg(test)]
 #[allow(unused_imports)]
 mod tests {
     use crate::generation::builtin::{
         generate_builtins_bitwise_trace, generate_builtins_cmp_trace,
         generate_builtins_rangecheck_trace,
     };
-    use crate::generation::cpu::generate_cpu_trace;
-    use crate::generation::generate_traces;
+    use crate::generation::cpu::{generate_cpu_trace, CpuTraceBuilder};
     use crate::generation::memory::generate_memory_trace;
-    use crate::stark::all_stark::AllStark;
+    use crate::generation::program::program_hash;
+    use crate::generation::{generate_traces, generate_traces_with_cpu_trace};
+    use crate::stark::all_stark::{AllStark, Table};
     use crate::stark::config::StarkConfig;
+    use crate::stark::preprocessed::AllPreprocessed;
     use crate::stark::proof::PublicValues;
-    use crate::stark::prover::prove_with_traces;
-    use crate::stark::serialization::Buffer;
+    use crate::stark::prover::{prove_with_preprocessed, prove_with_traces};
+    use crate::stark::serialization::{Buffer, ProofContainerError};
     use crate::stark::stark::Stark;
     use crate::stark::util::trace_rows_to_poly_values;
-    use crate::stark::verifier::verify_proof;
+    use crate::stark::verifier::{
+        verify_program, verify_proof, verify_proof_with_key, verify_segment_proofs,
+    };
     use anyhow::Result;
-    use core::program::Program;
+    use assembler::fixtures::u32_program;
+    use core::program::{Program, REGISTER_NUM};
+    use executor::coprocessor::ecdsa::EcdsaInput;
+    use executor::coprocessor::keccak::bytes_to_words;
     use executor::Process;
     use log::debug;
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::field::secp256k1_scalar::Secp256K1Scalar;
+    use plonky2::field::types::{Field, PrimeField64};
     use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
     use plonky2::util::timing::TimingTree;
     use std::mem;
@@ -379,7 +726,7 @@ mod tests {
         let _ = process.execute(&mut program);
 
         let mut all_stark = AllStark::default();
-        let (traces, public_values) = generate_traces(&program, &mut all_stark);
+        let (traces, public_values) = generate_traces(program.clone(), &mut all_stark)?;
         let config = StarkConfig::standard_fast_config();
         let proof = prove_with_traces::<F, C, D>(
             &all_stark,
@@ -400,8 +747,243 @@ mod tests {
         let de_proof = de_buffer.read_all_proof::<F, C, D>()?;
         // println!("deserialized_proof: {:?}", de_proof);
 
+        // r3 = 8 + 2, r4 = r3 * 3
+        assert_eq!(de_proof.public_values.initial_registers, [0; REGISTER_NUM]);
+        assert_eq!(de_proof.public_values.final_registers[3], 10);
+        assert_eq!(de_proof.public_values.final_registers[4], 30);
+
+        let mut wrong_proof = de_proof.clone();
+        wrong_proof.public_values.final_registers[4] = 31;
+        assert!(verify_proof(AllStark::default(), wrong_proof, &config, None).is_err());
+
+        let program_hash = program_hash::<F>(
+            &program.trace.raw_binary_instructions,
+            &program.trace.static_memory,
+        );
         let all_stark = AllStark::default();
-        verify_proof(all_stark, de_proof, &config)
+        verify_proof(all_stark, de_proof, &config, Some(program_hash))
+    }
+
+    #[test]
+    fn proof_container() -> Result<()> {
+        //mov r0 8
+        //mov r1 2
+        //mov r2 3
+        //add r3 r0 r1
+        //mul r4 r3 r2
+        //end
+        let code: [u64; 9] = [
+            0x4000000840000000,
+            0x8,
+            0x4000001040000000,
+            0x2,
+            0x4000002040000000,
+            0x3,
+            0x0020204400000000,
+            0x0100408200000000,
+            0x0000000000800000,
+        ];
+        let mut program = Program {
+            instructions: code.iter().map(|word| format!("0x{word:0>16x}")).collect(),
+            trace: Default::default(),
+        };
+        let mut process = Process::new();
+        process.execute(&mut program).unwrap();
+
+        let mut all_stark = AllStark::default();
+        let (traces, public_values) = generate_traces(program, &mut all_stark)?;
+        let config = StarkConfig::standard_fast_config();
+        let proof = prove_with_traces::<F, C, D>(
+            &all_stark,
+            &config,
+            traces,
+            public_values,
+            &mut TimingTree::default(),
+        )?;
+
+        let mut buffer = Buffer::new(Vec::new());
+        buffer.write_proof_container(&config, &proof)?;
+        let bytes = buffer.bytes();
+        let read = |bytes: Vec<u8>| Buffer::new(bytes).read_proof_container::<F, C, D>(&config);
+
+        let container = read(bytes.clone())?;
+        assert_eq!(container.config.security_bits, config.security_bits);
+        assert_eq!(container.config.num_challenges, config.num_challenges);
+        assert_eq!(container.config.fri_config, config.fri_config);
+        assert_eq!(container.degree_bits, proof.degree_bits(&config));
+        assert_eq!(
+            container.proof.public_values.final_registers,
+            proof.public_values.final_registers
+        );
+
+        let mut wrong = bytes.clone();
+        wrong[0] ^= 1;
+        assert!(matches!(read(wrong), Err(ProofContainerError::BadMagic)));
+        let mut wrong = bytes.clone();
+        wrong[4] = 2;
+        assert!(matches!(
+            read(wrong),
+            Err(ProofContainerError::UnsupportedVersion(2))
+        ));
+        // magic, version, config with `ConstantArityBits`, number of tables
+        let degree_bits_offset = 4 + 2 + 5 * 4 + 1 + 2 * 4 + 4 + 4;
+        let mut wrong = bytes.clone();
+        wrong[degree_bits_offset + Table::Cpu as usize] += 1;
+        assert!(matches!(
+            read(wrong),
+            Err(ProofContainerError::DegreeBitsMismatch { table: 0, .. })
+        ));
+        assert!(matches!(
+            read(bytes[..bytes.len() - 1].to_vec()),
+            Err(ProofContainerError::Truncated)
+        ));
+        let mut wrong = bytes.clone();
+        wrong.push(0);
+        assert!(matches!(
+            read(wrong),
+            Err(ProofContainerError::TrailingBytes(1))
+        ));
+
+        // The FRI parameters must reach the claimed security.
+        let mut weak_config = config.clone();
+        weak_config.fri_config.num_query_rounds = 1;
+        let mut buffer = Buffer::new(Vec::new());
+        buffer.write_proof_container(&weak_config, &proof)?;
+        assert!(matches!(
+            read(buffer.bytes()),
+            Err(ProofContainerError::InvalidConfig(_))
+        ));
+        // A valid config must still be the one the verifier expects.
+        let mut other_config = config.clone();
+        other_config.fri_config.num_query_rounds += 1;
+        let mut buffer = Buffer::new(Vec::new());
+        buffer.write_proof_container(&other_config, &proof)?;
+        assert!(matches!(
+            read(buffer.bytes()),
+            Err(ProofContainerError::ConfigMismatch)
+        ));
+
+        verify_proof(
+            AllStark::default(),
+            container.proof,
+            &container.config,
+            None,
+        )
+    }
+
+    #[test]
+    fn add_mul_with_inputs() -> Result<()> {
+        //add r3 r0 r1
+        //mul r4 r3 r2
+        //end
+        let program_src = "0x0020204400000000
+            0x0100408200000000
+            0x0000000000800000";
+
+        let instructions = program_src.split('\n');
+        let mut program: Program = Program {
+            instructions: Vec::new(),
+            trace: Default::default(),
+        };
+
+        for inst in instructions.into_iter() {
+            program.instructions.push(inst.to_string());
+        }
+
+        let inputs = [8, 2, 3].map(GoldilocksField::from_canonical_u64);
+        let mut process = Process::new();
+        process.execute_with_inputs(&mut program, &inputs).unwrap();
+
+        let mut all_stark = AllStark::default();
+        let (traces, public_values) = generate_traces(program, &mut all_stark)?;
+        let config = StarkConfig::standard_fast_config();
+        let proof = prove_with_traces::<F, C, D>(
+            &all_stark,
+            &config,
+            traces,
+            public_values,
+            &mut TimingTree::default(),
+        )?;
+
+        // inputs are exposed as the initial registers
+        assert_eq!(proof.public_values.initial_registers[..3], [8, 2, 3]);
+        assert_eq!(proof.public_values.final_registers[4], 30);
+
+        let mut wrong_proof = proof.clone();
+        wrong_proof.public_values.initial_registers[0] = 9;
+        assert!(verify_proof(AllStark::default(), wrong_proof, &config, None).is_err());
+
+        verify_proof(AllStark::default(), proof, &config, None)
+    }
+
+    #[test]
+    fn static_data() -> Result<()> {
+        // mov r1 values
+        // mload r0 r1
+        // add r1 r1 1
+        // mload r2 r1
+        // mload r4 last
+        // add r3 r0 r2
+        // end
+        let program_src = "0x4000001040000000
+            0x0000000010000000
+            0x0000200802000000
+            0x4040001400000000
+            0x0000000000000001
+            0x0000202002000000
+            0x4000008002000000
+            0x0000000010000002
+            0x0020404400000000
+            0x0000000000800000";
+
+        let prove_static_data = |data: &[u64], tamper: bool| {
+            let mut program: Program = Program {
+                instructions: program_src.split('\n').map(|s| s.to_string()).collect(),
+                trace: Default::default(),
+            };
+            let mut process = Process::new();
+            process.init_memory(&mut program, 0x10000000, data).unwrap();
+            process.execute(&mut program).unwrap();
+            if tamper {
+                // claim other static data than the executed one
+                program.trace.static_memory[1].1 += 1;
+            }
+
+            let mut all_stark = AllStark::default();
+            let (traces, public_values) = generate_traces(program.clone(), &mut all_stark)?;
+            let config = StarkConfig::standard_fast_config();
+            let proof = prove_with_traces::<F, C, D>(
+                &all_stark,
+                &config,
+                traces,
+                public_values,
+                &mut TimingTree::default(),
+            )
+            .unwrap();
+            (program, proof)
+        };
+
+        let config = StarkConfig::standard_fast_config();
+        let (program, proof) = prove_static_data(&[7, 9, 11], false);
+        assert_eq!(proof.public_values.final_registers[3], 16);
+        verify_program(
+            &proof,
+            &program.trace.raw_binary_instructions,
+            &program.trace.static_memory,
+            &config,
+        )?;
+        assert!(verify_program(
+            &proof,
+            &program.trace.raw_binary_instructions,
+            &[(0x10000000, 7), (0x10000001, 10), (0x10000002, 11)],
+            &config,
+        )
+        .is_err());
+        verify_proof(AllStark::default(), proof, &config, None)?;
+
+        let (_, proof) = prove_static_data(&[7, 9, 11], true);
+        assert!(verify_proof(AllStark::default(), proof, &config, None).is_err());
+        Ok(())
     }
 
     #[test]
@@ -455,7 +1037,7 @@ mod tests {
         let _ = process.execute(&mut program);
 
         let mut all_stark = AllStark::default();
-        let (traces, public_values) = generate_traces(&program, &mut all_stark);
+        let (traces, public_values) = generate_traces(program, &mut all_stark)?;
         let config = StarkConfig::standard_fast_config();
         let proof = prove_with_traces::<F, C, D>(
             &all_stark,
@@ -466,7 +1048,7 @@ mod tests {
         )?;
         println!("{}", mem::size_of_val(&proof));
         let all_stark = AllStark::default();
-        verify_proof(all_stark, proof, &config)
+        verify_proof(all_stark, proof, &config, None)
     }
 
     #[test]
@@ -515,7 +1097,7 @@ mod tests {
         let _ = process.execute(&mut program);
 
         let mut all_stark = AllStark::default();
-        let (traces, public_values) = generate_traces(&program, &mut all_stark);
+        let (traces, public_values) = generate_traces(program, &mut all_stark)?;
         let config = StarkConfig::standard_fast_config();
         let proof = prove_with_traces::<F, C, D>(
             &all_stark,
@@ -525,7 +1107,7 @@ mod tests {
             &mut TimingTree::default(),
         )?;
         let all_stark = AllStark::default();
-        verify_proof(all_stark, proof, &config)
+        verify_proof(all_stark, proof, &config, None)
     }
 
     #[test]
@@ -582,7 +1164,7 @@ mod tests {
         let _ = process.execute(&mut program);
 
         let mut all_stark = AllStark::default();
-        let (traces, public_values) = generate_traces(&program, &mut all_stark);
+        let (traces, public_values) = generate_traces(program, &mut all_stark)?;
         let config = StarkConfig::standard_fast_config();
         let proof = prove_with_traces::<F, C, D>(
             &all_stark,
@@ -592,7 +1174,7 @@ mod tests {
             &mut TimingTree::default(),
         )?;
         let all_stark = AllStark::default();
-        verify_proof(all_stark, proof, &config)
+        verify_proof(all_stark, proof, &config, None)
     }
 
     #[test]
@@ -630,7 +1212,10 @@ mod tests {
         let _ = process.execute(&mut program);
 
         let mut all_stark = AllStark::default();
-        let (traces, public_values) = generate_traces(&program, &mut all_stark);
+        let (traces, public_values) = generate_traces(program, &mut all_stark)?;
+        // The u16 table is its own stark, the rangecheck trace only holds the
+        // checked values.
+        assert_eq!(traces[Table::RangeCheck as usize][0].len(), 2);
         let config = StarkConfig::standard_fast_config();
         let proof = prove_with_traces::<F, C, D>(
             &all_stark,
@@ -640,7 +1225,7 @@ mod tests {
             &mut TimingTree::default(),
         )?;
         let all_stark = AllStark::default();
-        verify_proof(all_stark, proof, &config)
+        verify_proof(all_stark, proof, &config, None)
     }
 
     #[test]
@@ -678,7 +1263,7 @@ mod tests {
         let _ = process.execute(&mut program);
 
         let mut all_stark = AllStark::default();
-        let (traces, public_values) = generate_traces(&program, &mut all_stark);
+        let (traces, public_values) = generate_traces(program, &mut all_stark)?;
         let config = StarkConfig::standard_fast_config();
         let proof = prove_with_traces::<F, C, D>(
             &all_stark,
@@ -688,7 +1273,7 @@ mod tests {
             &mut TimingTree::default(),
         )?;
         let all_stark = AllStark::default();
-        verify_proof(all_stark, proof, &config)
+        verify_proof(all_stark, proof, &config, None)
     }
 
     #[test]
@@ -726,7 +1311,298 @@ mod tests {
         let _ = process.execute(&mut program);
 
         let mut all_stark = AllStark::default();
-        let (traces, public_values) = generate_traces(&program, &mut all_stark);
+        let (traces, public_values) = generate_traces(program, &mut all_stark)?;
+        let config = StarkConfig::standard_fast_config();
+        let proof = prove_with_traces::<F, C, D>(
+            &all_stark,
+            &config,
+            traces,
+            public_values,
+            &mut TimingTree::default(),
+        )?;
+        let all_stark = AllStark::default();
+        verify_proof(all_stark, proof, &config, None)
+    }
+
+    #[test]
+    fn poseidon_test() -> Result<()> {
+        // mov r1 i + 1
+        // mstore 0x100 + i r1
+        //   for i in 0..8
+        // mov r0 0x100
+        // poseidon 0x200 r0
+        // mload r2 0x200
+        // end
+        let mut code: Vec<u64> = Vec::new();
+        for i in 0..8 {
+            code.extend([0x4000001040000000, i + 1, 0x4040000001000000, 0x100 + i]);
+        }
+        code.extend([
+            0x4000000840000000,
+            0x100,
+            0x4020000000008000,
+            0x200,
+            0x4000002002000000,
+            0x200,
+            0x0000000000800000,
+        ]);
+        let mut program: Program = Program {
+            instructions: code.iter().map(|word| format!("0x{word:0>16x}")).collect(),
+            trace: Default::default(),
+        };
+
+        let mut process = Process::new();
+        process.execute(&mut program).unwrap();
+        assert_eq!(program.trace.builtin_poseidon.len(), 1);
+
+        let mut all_stark = AllStark::default();
+        let (traces, public_values) = generate_traces(program, &mut all_stark)?;
+        let config = StarkConfig::standard_fast_config();
+        let proof = prove_with_traces::<F, C, D>(
+            &all_stark,
+            &config,
+            traces,
+            public_values,
+            &mut TimingTree::default(),
+        )?;
+        let all_stark = AllStark::default();
+        verify_proof(all_stark, proof, &config, None)
+    }
+
+    #[test]
+    fn keccak_test() -> Result<()> {
+        // mov r1 input[i]
+        // mstore addr + i r1
+        //   for i in 0..len_words + 1, the byte length and the words of a 200
+        //   bytes input at 0x100 and a 3 bytes input at 0x300
+        // mov r0 0x100
+        // keccak 0x200 r0
+        // mov r0 0x300
+        // keccak 0x400 r0
+        // end
+        let inputs = [(0x100, 200), (0x300, 3)].map(|(addr, len)| {
+            let bytes: Vec<u8> = (0..len).map(|i| (i * 7 + 1) as u8).collect();
+            (addr, bytes)
+        });
+        let mut code: Vec<u64> = Vec::new();
+        for (addr, bytes) in &inputs {
+            let words = bytes_to_words(bytes);
+            let buffer = [bytes.len() as u64]
+                .into_iter()
+                .chain(words.iter().map(|word| *word as u64));
+            for (i, word) in buffer.enumerate() {
+                code.extend([
+                    0x4000001040000000,
+                    word,
+                    0x4040000001000000,
+                    addr + i as u64,
+                ]);
+            }
+        }
+        for (addr, _) in &inputs {
+            code.extend([0x4000000840000000, *addr, 0x4020000000002000, addr + 0x100]);
+        }
+        code.push(0x0000000000800000);
+        let mut program: Program = Program {
+            instructions: code.iter().map(|word| format!("0x{word:0>16x}")).collect(),
+            trace: Default::default(),
+        };
+
+        let mut process = Process::new();
+        process.execute(&mut program).unwrap();
+        assert_eq!(program.trace.builtin_keccak.len(), 2);
+        for ((_, bytes), row) in inputs.iter().zip(&program.trace.builtin_keccak) {
+            let digest = bytes_to_words(&keccak_hash::keccak(bytes).0);
+            assert!(row
+                .output
+                .iter()
+                .zip(digest)
+                .all(|(value, word)| value.to_canonical_u64() == word as u64));
+        }
+
+        let mut all_stark = AllStark::default();
+        let (traces, public_values) = generate_traces(program, &mut all_stark)?;
+        let config = StarkConfig::standard_fast_config();
+        let proof = prove_with_traces::<F, C, D>(
+            &all_stark,
+            &config,
+            traces,
+            public_values,
+            &mut TimingTree::default(),
+        )?;
+        let all_stark = AllStark::default();
+        verify_proof(all_stark, proof, &config, None)
+    }
+
+    #[test]
+    fn prophet_test() -> Result<()> {
+        // main:
+        //   mov r1 3
+        //   inv r0
+        //   mov r1 9
+        //   sqrt r3
+        //   mov r1 100
+        //   mov r2 7
+        //   divmod r4 r5
+        //   end
+        let code: [u64; 46] = [
+            0x4000001040000000,
+            0x3,
+            0x4000020000001000,
+            0x1,
+            0x0004020002000000,
+            0x0044040200000000,
+            0x5000000080000000,
+            0x1,
+            0x0004000840000000,
+            0x4000001040000000,
+            0x9,
+            0x4000020000001000,
+            0x3,
+            0x0004020002000000,
+            0x0804040200000000,
+            0x1000200080000000,
+            0x0004004040000000,
+            0x4000001040000000,
+            0x64,
+            0x4000002040000000,
+            0x7,
+            0x4000020000001000,
+            0x2,
+            0x0000200000400000,
+            0x0000400000400000,
+            0x4800040400000000,
+            0x1,
+            0x0008040002000000,
+            0x0008000000400000,
+            0x0008040000040000,
+            0x1000440400000000,
+            0x0008000000400000,
+            0x0004040002000000,
+            0x0008000000400000,
+            0x1000440200000000,
+            0x0008040000040000,
+            0x1000208400000000,
+            0x4200008400000000,
+            0x1,
+            0x4800040400000000,
+            0x1,
+            0x0008040002000000,
+            0x1001000080000000,
+            0x0008010040000000,
+            0x0004008002000000,
+            0x0000000000800000,
+        ];
+        let mut program: Program = Program {
+            instructions: code.iter().map(|word| format!("0x{word:0>16x}")).collect(),
+            trace: Default::default(),
+        };
+
+        let mut process = Process::new();
+        process.execute(&mut program).unwrap();
+        assert_eq!(
+            process.registers[4..6],
+            [GoldilocksField(14), GoldilocksField(2)]
+        );
+
+        let mut all_stark = AllStark::default();
+        let (traces, public_values) = generate_traces(program, &mut all_stark)?;
+        let config = StarkConfig::standard_fast_config();
+        let proof = prove_with_traces::<F, C, D>(
+            &all_stark,
+            &config,
+            traces,
+            public_values,
+            &mut TimingTree::default(),
+        )?;
+        let all_stark = AllStark::default();
+        verify_proof(all_stark, proof, &config, None)
+    }
+
+    #[test]
+    fn u32_test() -> Result<()> {
+        let mut program: Program = Program {
+            instructions: u32_program(7, u32::MAX as u64),
+            trace: Default::default(),
+        };
+
+        let mut process = Process::new();
+        process.execute(&mut program).unwrap();
+        // 7 * (2^32 - 1) = (2^32 - 7) + 6 * 2^32
+        assert_eq!(
+            process.registers[3..6],
+            [
+                GoldilocksField(u32::MAX as u64 - 6),
+                GoldilocksField(6),
+                GoldilocksField::ZERO
+            ]
+        );
+
+        let mut all_stark = AllStark::default();
+        let (traces, public_values) = generate_traces(program, &mut all_stark)?;
+        let config = StarkConfig::standard_fast_config();
+        let proof = prove_with_traces::<F, C, D>(
+            &all_stark,
+            &config,
+            traces,
+            public_values,
+            &mut TimingTree::default(),
+        )?;
+        let all_stark = AllStark::default();
+        verify_proof(all_stark, proof, &config, None)
+    }
+
+    #[test]
+    fn ecdsa_test() -> Result<()> {
+        // mov r1 input[i]
+        // mstore addr + i r1
+        //   for i in 0..40, for a valid signature at 0x100 and a tampered
+        //   one at 0x200
+        // ecdsa 0x100
+        // ecdsa 0x200
+        // end
+        let z = [0x1234, 0x5678, 0x9abc, 0xdef0];
+        let d = Secp256K1Scalar::from_canonical_u64(0xdead_beef);
+        let k = Secp256K1Scalar::from_canonical_u64(0x1337);
+        let signed = EcdsaInput::sign(z, &d, &k).unwrap();
+        let mut tampered = signed;
+        tampered.z[0] ^= 1;
+
+        let mut code: Vec<u64> = Vec::new();
+        for (addr, input) in [(0x100, signed), (0x200, tampered)] {
+            for (i, word) in input.to_words().iter().enumerate() {
+                code.extend([
+                    0x4000001040000000,
+                    word.to_canonical_u64(),
+                    0x4040000001000000,
+                    addr + i as u64,
+                ]);
+            }
+        }
+        code.extend([
+            0x4000000000004000,
+            0x100,
+            0x4000000000004000,
+            0x200,
+            0x0000000000800000,
+        ]);
+        let mut program: Program = Program {
+            instructions: code.iter().map(|word| format!("0x{word:0>16x}")).collect(),
+            trace: Default::default(),
+        };
+
+        let mut process = Process::new();
+        process.execute(&mut program).unwrap();
+        let flags: Vec<_> = program
+            .trace
+            .builtin_ecdsa
+            .iter()
+            .map(|row| row.flag)
+            .collect();
+        assert_eq!(flags, [GoldilocksField::ONE, GoldilocksField::ZERO]);
+
+        let mut all_stark = AllStark::default();
+        let (traces, public_values) = generate_traces(program, &mut all_stark)?;
         let config = StarkConfig::standard_fast_config();
         let proof = prove_with_traces::<F, C, D>(
             &all_stark,
@@ -736,7 +1612,7 @@ mod tests {
             &mut TimingTree::default(),
         )?;
         let all_stark = AllStark::default();
-        verify_proof(all_stark, proof, &config)
+        verify_proof(all_stark, proof, &config, None)
     }
 
     #[test]
@@ -813,7 +1689,47 @@ mod tests {
         let _ = process.execute(&mut program);
 
         let mut all_stark = AllStark::default();
-        let (traces, public_values) = generate_traces(&program, &mut all_stark);
+        let (traces, public_values) = generate_traces(program, &mut all_stark)?;
+        let config = StarkConfig::standard_fast_config();
+        let proof = prove_with_traces::<F, C, D>(
+            &all_stark,
+            &config,
+            traces,
+            public_values,
+            &mut TimingTree::default(),
+        )?;
+        let all_stark = AllStark::default();
+        verify_proof(all_stark, proof, &config, None)
+    }
+
+    #[test]
+    fn program_binding_test() -> Result<()> {
+        //mov r0 8
+        //mov r1 2
+        //add r3 r0 r1
+        //end
+        let program_src = "0x4000000840000000
+            0x8
+            0x4000001040000000
+            0x2
+            0x0020204400000000
+            0x0000000000800000";
+
+        let instructions = program_src.split('\n');
+        let mut program: Program = Program {
+            instructions: Vec::new(),
+            trace: Default::default(),
+        };
+
+        for inst in instructions.into_iter() {
+            program.instructions.push(inst.to_string());
+        }
+
+        let mut process = Process::new();
+        let _ = process.execute(&mut program);
+
+        let mut all_stark = AllStark::default();
+        let (traces, public_values) = generate_traces(program.clone(), &mut all_stark)?;
         let config = StarkConfig::standard_fast_config();
         let proof = prove_with_traces::<F, C, D>(
             &all_stark,
@@ -822,7 +1738,280 @@ mod tests {
             public_values,
             &mut TimingTree::default(),
         )?;
+
+        verify_program(
+            &proof,
+            &program.trace.raw_binary_instructions,
+            &program.trace.static_memory,
+            &config,
+        )?;
+
+        // mov r0 9
+        let mut other_program = program.trace.raw_binary_instructions.clone();
+        other_program[1] = "0x9".to_string();
+        assert!(verify_program(&proof, &other_program, &[], &config).is_err());
+
+        let other_hash = program_hash::<F>(&other_program, &[]);
+        assert!(verify_proof(
+            AllStark::default(),
+            proof.clone(),
+            &config,
+            Some(other_hash)
+        )
+        .is_err());
+
+        let program_hash = program_hash::<F>(
+            &program.trace.raw_binary_instructions,
+            &program.trace.static_memory,
+        );
+        assert_eq!(proof.public_values.program_hash, program_hash);
         let all_stark = AllStark::default();
-        verify_proof(all_stark, proof, &config)
+        verify_proof(all_stark, proof, &config, Some(program_hash))
+    }
+
+    #[test]
+    fn preprocessed_test() -> Result<()> {
+        //mov r0 8
+        //mov r1 2
+        //add r3 r0 r1
+        //end
+        let program_src = "0x4000000840000000
+            0x8
+            0x4000001040000000
+            0x2
+            0x0020204400000000
+            0x0000000000800000";
+
+        let instructions = program_src.split('\n');
+        let mut program: Program = Program {
+            instructions: Vec::new(),
+            trace: Default::default(),
+        };
+
+        for inst in instructions.into_iter() {
+            program.instructions.push(inst.to_string());
+        }
+
+        let mut process = Process::new();
+        let _ = process.execute(&mut program);
+
+        let mut all_stark = AllStark::default();
+        let (traces, public_values) = generate_traces(program, &mut all_stark)?;
+        let config = StarkConfig::standard_fast_config();
+        let mut timing = TimingTree::default();
+        let preprocessed = AllPreprocessed::<F, C, D>::new(&all_stark, &config, &mut timing);
+        let verifier_key = preprocessed.verifier_key();
+        assert!(verifier_key.preprocessed_caps[Table::Cpu as usize].is_none());
+        assert_eq!(
+            verifier_key.preprocessed_caps[Table::RangecheckFixed as usize]
+                .as_ref()
+                .unwrap()
+                .degree_bits,
+            16
+        );
+
+        let proof = prove_with_preprocessed::<F, C, D>(
+            &all_stark,
+            &preprocessed,
+            &config,
+            traces,
+            public_values,
+            &mut timing,
+        )?;
+        // Only the frequencies of the fixed tables are in the proof.
+        assert_eq!(
+            proof.stark_proofs[Table::RangecheckFixed as usize]
+                .openings
+                .local_values
+                .len(),
+            1
+        );
+
+        // The fixed tables are bound to the verifier key.
+        let mut wrong_key = verifier_key.clone();
+        wrong_key.preprocessed_caps[Table::RangecheckFixed as usize] =
+            wrong_key.preprocessed_caps[Table::BitwiseFixed as usize].clone();
+        assert!(verify_proof_with_key(
+            AllStark::default(),
+            &wrong_key,
+            proof.clone(),
+            &config,
+            None
+        )
+        .is_err());
+
+        verify_proof_with_key(AllStark::default(), &verifier_key, proof, &config, None)
+    }
+
+    #[test]
+    fn segments_test() -> Result<()> {
+        // fibo_use_loop_memory with 10 iterations, the loop state is carried
+        // through memory from a segment to the next.
+        let program_src = format!(
+            "0x4000000840000000
+            0x1
+            0x4000002040000000
+            0x1
+            0x4020000001000000
+            0x80
+            0x4020000001000000
+            0x87
+            0x4000000840000000
+            {:#x}
+            0x4000004040000000
+            0x0
+            0x0020800100000000
+            0x4000000010000000
+            0x1e
+            0x4000001002000000
+            0x80
+            0x0040400080000000
+            0x4000002002000000
+            0x87
+            0x0040408400000000
+            0x4080000001000000
+            0x80
+            0x4200000001000000
+            0x87
+            0x4000008040000000
+            0x1
+            0x0101004400000000
+            0x4000000020000000
+            0xc
+            0x0000800000400000
+            0x0000000000800000",
+            10
+        );
+        let mut program = Program {
+            instructions: program_src.split('\n').map(|s| s.to_string()).collect(),
+            trace: Default::default(),
+        };
+
+        let config = StarkConfig::standard_fast_config();
+        let mut timing = TimingTree::default();
+        let preprocessed =
+            AllPreprocessed::<F, C, D>::new(&AllStark::default(), &config, &mut timing);
+        let verifier_key = preprocessed.verifier_key();
+
+        let mut process = Process::new();
+        let mut proofs = Vec::new();
+        let mut reached_end = false;
+        while !reached_end {
+            reached_end = process.execute_segment(&mut program, 32).unwrap();
+            let mut all_stark = AllStark::default();
+            let (traces, public_values) = generate_traces(program.clone(), &mut all_stark)?;
+            proofs.push(prove_with_preprocessed::<F, C, D>(
+                &all_stark,
+                &preprocessed,
+                &config,
+                traces,
+                public_values,
+                &mut timing,
+            )?);
+        }
+        assert!(proofs.len() > 1);
+        let program_hash = program_hash::<F>(&program.trace.raw_binary_instructions, &[]);
+
+        // A segment alone isn't a whole execution.
+        assert!(verify_proof_with_key(
+            AllStark::default(),
+            &verifier_key,
+            proofs[0].clone(),
+            &config,
+            Some(program_hash)
+        )
+        .is_err());
+        // Segments must be chained in order.
+        let mut swapped = proofs.clone();
+        swapped.swap(0, 1);
+        assert!(verify_segment_proofs(
+            AllStark::default(),
+            &verifier_key,
+            swapped,
+            &config,
+            Some(program_hash)
+        )
+        .is_err());
+        assert!(verify_segment_proofs(
+            AllStark::default(),
+            &verifier_key,
+            proofs[1..].to_vec(),
+            &config,
+            None
+        )
+        .is_err());
+
+        verify_segment_proofs(
+            AllStark::default(),
+            &verifier_key,
+            proofs,
+            &config,
+            Some(program_hash),
+        )
+    }
+
+    #[test]
+    fn execute_into_cpu_trace() -> Result<()> {
+        // mov r0 8
+        // mstore  0x100 r0
+        // mov r1 2
+        // mstore  0x200 r1
+        // mov r0 20
+        // mload r1 0x100
+        // mload r2 0x200
+        // mload r3 0x200
+        // add r0 r1 r1
+        // end
+        let program_src = "0x4000000840000000
+            0x8
+            0x4020000001000000
+            0x100
+            0x4000001040000000
+            0x2
+            0x4040000001000000
+            0x200
+            0x4000000840000000
+            0x14
+            0x4000001002000000
+            0x100
+            0x4000002002000000
+            0x200
+            0x4000004002000000
+            0x200
+            0x0040200c00000000
+            0x0000000000800000";
+        let new_program = || Program {
+            instructions: program_src.split('\n').map(|s| s.to_string()).collect(),
+            trace: Default::default(),
+        };
+
+        let mut program = new_program();
+        Process::new().execute(&mut program).unwrap();
+        let (traces, public_values) = generate_traces(program, &mut AllStark::<F, D>::default())?;
+
+        let mut program = new_program();
+        let mut cpu_trace = CpuTraceBuilder::default();
+        Process::new()
+            .execute_into(&mut program, &mut cpu_trace)
+            .unwrap();
+        assert!(program.trace.exec.is_empty());
+        let mut all_stark = AllStark::default();
+        let (streamed_traces, streamed_public_values) =
+            generate_traces_with_cpu_trace(&program, cpu_trace, &mut all_stark)?;
+        assert_eq!(streamed_traces, traces);
+        assert_eq!(
+            streamed_public_values.to_field_elements::<F>(),
+            public_values.to_field_elements::<F>()
+        );
+
+        let config = StarkConfig::standard_fast_config();
+        let proof = prove_with_traces::<F, C, D>(
+            &all_stark,
+            &config,
+            streamed_traces,
+            streamed_public_values,
+            &mut TimingTree::default(),
+        )?;
+        verify_proof(AllStark::default(), proof, &config, None)
     }
 }
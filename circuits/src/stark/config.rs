@@ -1,6 +1,7 @@
 use plonky2::fri::reduction_strategies::FriReductionStrategy;
 use plonky2::fri::{FriConfig, FriParams};
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct StarkConfig {
     pub security_bits: usize,
 
@@ -164,6 +164,11 @@ pub struct CrossTableLookup<F: Field> {
     looked_table: TableWithColumns<F>,
     /// Default value if filters are not used.
     default: Option<Vec<F>>,
+    /// Whether the looked filter is a multiplicity column, i.e. a looked row
+    /// may be looked up any number of times. Such lookups are checked with
+    /// running sums of `filter / combination` (LogUp) instead of grand
+    /// products.
+    logup: bool,
 }
 
 impl<F: Field> CrossTableLookup<F> {
@@ -189,6 +194,28 @@ impl<F: Field> CrossTableLookup<F> {
             looking_tables,
             looked_table,
             default,
+            logup: false,
+        }
+    }
+
+    /// A lookup of the looking rows in the looked table, whose filter gives
+    /// how many times each looked row is looked up.
+    pub fn new_logup(
+        looking_tables: Vec<TableWithColumns<F>>,
+        looked_table: TableWithColumns<F>,
+    ) -> Self {
+        assert!(looking_tables
+            .iter()
+            .all(|twc| twc.columns.len() == looked_table.columns.len()));
+        assert!(
+            looked_table.filter_column.is_some(),
+            "The looked table needs a multiplicity column."
+        );
+        Self {
+            looking_tables,
+            looked_table,
+            default: None,
+            logup: true,
         }
     }
 
@@ -216,6 +243,7 @@ pub(crate) struct CtlZData<F: Field> {
     pub(crate) challenge: GrandProductChallenge<F>,
     pub(crate) columns: Vec<Column<F>>,
     pub(crate) filter_column: Option<Column<F>>,
+    pub(crate) logup: bool,
 }
 
 impl<F: Field> CtlData<F> {
@@ -247,46 +275,62 @@ pub fn cross_table_lookup_data<F: RichField, C: GenericConfig<D, F = F>, const D
         looking_tables,
         looked_table,
         default,
+        logup,
     } in cross_table_lookups
     {
+        let partial_zs = if *logup {
+            partial_sums
+        } else {
+            partial_products
+        };
         for &challenge in &challenges.challenges {
             let zs_looking = looking_tables.iter().map(|table| {
-                partial_products(
+                partial_zs(
                     &trace_poly_values[table.table as usize],
                     &table.columns,
                     &table.filter_column,
                     challenge,
                 )
             });
-            let z_looked = partial_products(
+            let z_looked = partial_zs(
                 &trace_poly_values[looked_table.table as usize],
                 &looked_table.columns,
                 &looked_table.filter_column,
                 challenge,
             );
 
-            debug_assert_eq!(
-                zs_looking
-                    .clone()
-                    .map(|z| *z.values.last().unwrap())
-                    .product::<F>(),
-                *z_looked.values.last().unwrap()
-                    * default
-                        .as_ref()
-                        .map(|default| {
-                            challenge.combine(default).exp_u64(
-                                looking_tables
-                                    .iter()
-                                    .map(|table| {
-                                        trace_poly_values[table.table as usize][0].len() as u64
-                                    })
-                                    .sum::<u64>()
-                                    - trace_poly_values[looked_table.table as usize][0].len()
-                                        as u64,
-                            )
-                        })
-                        .unwrap_or(F::ONE)
-            );
+            if *logup {
+                debug_assert_eq!(
+                    zs_looking
+                        .clone()
+                        .map(|z| *z.values.last().unwrap())
+                        .sum::<F>(),
+                    *z_looked.values.last().unwrap()
+                );
+            } else {
+                debug_assert_eq!(
+                    zs_looking
+                        .clone()
+                        .map(|z| *z.values.last().unwrap())
+                        .product::<F>(),
+                    *z_looked.values.last().unwrap()
+                        * default
+                            .as_ref()
+                            .map(|default| {
+                                challenge.combine(default).exp_u64(
+                                    looking_tables
+                                        .iter()
+                                        .map(|table| {
+                                            trace_poly_values[table.table as usize][0].len() as u64
+                                        })
+                                        .sum::<u64>()
+                                        - trace_poly_values[looked_table.table as usize][0].len()
+                                            as u64,
+                                )
+                            })
+                            .unwrap_or(F::ONE)
+                );
+            }
 
             for (table, z) in looking_tables.iter().zip(zs_looking) {
                 ctl_data_per_table[table.table as usize]
@@ -296,6 +340,7 @@ pub fn cross_table_lookup_data<F: RichField, C: GenericConfig<D, F = F>, const D
                         challenge,
                         columns: table.columns.clone(),
                         filter_column: table.filter_column.clone(),
+                        logup: *logup,
                     });
             }
             ctl_data_per_table[looked_table.table as usize]
@@ -305,6 +350,7 @@ pub fn cross_table_lookup_data<F: RichField, C: GenericConfig<D, F = F>, const D
                     challenge,
                     columns: looked_table.columns.clone(),
                     filter_column: looked_table.filter_column.clone(),
+                    logup: *logup,
                 });
         }
     }
@@ -340,6 +386,40 @@ fn partial_products<F: Field>(
     res.into()
 }
 
+/// Running sum of `filter / combination` over the rows, the filter may be any
+/// multiplicity.
+fn partial_sums<F: Field>(
+    trace: &[PolynomialValues<F>],
+    columns: &[Column<F>],
+    filter_column: &Option<Column<F>>,
+    challenge: GrandProductChallenge<F>,
+) -> PolynomialValues<F> {
+    let degree = trace[0].len();
+    let combined = (0..degree)
+        .map(|i| {
+            let evals = columns
+                .iter()
+                .map(|c| c.eval_table(trace, i))
+                .collect::<Vec<_>>();
+            challenge.combine(evals.iter())
+        })
+        .collect::<Vec<_>>();
+    let inverses = F::batch_multiplicative_inverse(&combined);
+
+    let mut partial_sum = F::ZERO;
+    let mut res = Vec::with_capacity(degree);
+    for (i, inverse) in inverses.into_iter().enumerate() {
+        let filter = if let Some(column) = filter_column {
+            column.eval_table(trace, i)
+        } else {
+            F::ONE
+        };
+        partial_sum += filter * inverse;
+        res.push(partial_sum);
+    }
+    res.into()
+}
+
 #[derive(Clone)]
 pub struct CtlCheckVars<'a, F, FE, P, const D2: usize>
 where
@@ -352,6 +432,7 @@ where
     pub(crate) challenges: GrandProductChallenge<F>,
     pub(crate) columns: &'a [Column<F>],
     pub(crate) filter_column: &'a Option<Column<F>>,
+    pub(crate) logup: bool,
 }
 
 impl<'a, F: RichField + Extendable<D>, const D: usize>
@@ -361,15 +442,15 @@ impl<'a, F: RichField + Extendable<D>, const D: usize>
         proofs: &[StarkProof<F, C, D>; NUM_TABLES],
         cross_table_lookups: &'a [CrossTableLookup<F>],
         ctl_challenges: &'a GrandProductChallengeSet<F>,
-        num_permutation_zs: &[usize; NUM_TABLES],
+        nums_ctl_zs_start: &[usize; NUM_TABLES],
     ) -> [Vec<Self>; NUM_TABLES] {
         let mut ctl_zs = proofs
             .iter()
-            .zip(num_permutation_zs)
-            .map(|(p, &num_perms)| {
+            .zip(nums_ctl_zs_start)
+            .map(|(p, &ctl_zs_start)| {
                 let openings = &p.openings;
-                let ctl_zs = openings.permutation_ctl_zs.iter().skip(num_perms);
-                let ctl_zs_next = openings.permutation_ctl_zs_next.iter().skip(num_perms);
+                let ctl_zs = openings.permutation_ctl_zs.iter().skip(ctl_zs_start);
+                let ctl_zs_next = openings.permutation_ctl_zs_next.iter().skip(ctl_zs_start);
                 ctl_zs.zip(ctl_zs_next)
             })
             .collect::<Vec<_>>();
@@ -378,6 +459,7 @@ impl<'a, F: RichField + Extendable<D>, const D: usize>
         for CrossTableLookup {
             looking_tables,
             looked_table,
+            logup,
             ..
         } in cross_table_lookups
         {
@@ -390,6 +472,7 @@ impl<'a, F: RichField + Extendable<D>, const D: usize>
                         challenges,
                         columns: &table.columns,
                         filter_column: &table.filter_column,
+                        logup: *logup,
                     });
                 }
 
@@ -400,6 +483,7 @@ impl<'a, F: RichField + Extendable<D>, const D: usize>
                     challenges,
                     columns: &looked_table.columns,
                     filter_column: &looked_table.filter_column,
+                    logup: *logup,
                 });
             }
         }
@@ -425,6 +509,7 @@ pub(crate) fn eval_cross_table_lookup_checks<F, FE, P, C, S, const D: usize, con
             challenges,
             columns,
             filter_column,
+            logup,
         } = lookup_vars;
         let combine = |v: &[P]| -> P {
             let evals = columns.iter().map(|c| c.eval(v)).collect::<Vec<_>>();
@@ -439,6 +524,17 @@ pub(crate) fn eval_cross_table_lookup_checks<F, FE, P, C, S, const D: usize, con
         };
         let local_filter = filter(vars.local_values);
         let next_filter = filter(vars.next_values);
+
+        if *logup {
+            // Check `Z(1) * combination = filter`
+            consumer.constraint_first_row(*local_z * combine(vars.local_values) - local_filter);
+            // Check `(Z(gw) - Z(w)) * combination = filter`
+            consumer.constraint_transition(
+                (*next_z - *local_z) * combine(vars.next_values) - next_filter,
+            );
+            continue;
+        }
+
         let select = |filter, x| filter * x + P::ONES - filter;
 
         // Check value of `Z(1)`
@@ -457,24 +553,24 @@ pub struct CtlCheckVarsTarget<'a, F: Field, const D: usize> {
     pub(crate) challenges: GrandProductChallenge<Target>,
     pub(crate) columns: &'a [Column<F>],
     pub(crate) filter_column: &'a Option<Column<F>>,
+    pub(crate) logup: bool,
 }
 
 impl<'a, F: Field, const D: usize> CtlCheckVarsTarget<'a, F, D> {
-    #[allow(unused)]
     pub(crate) fn from_proof(
         table: Table,
         proof: &StarkProofTarget<D>,
         cross_table_lookups: &'a [CrossTableLookup<F>],
         ctl_challenges: &'a GrandProductChallengeSet<Target>,
-        num_permutation_zs: usize,
+        num_ctl_zs_start: usize,
     ) -> Vec<Self> {
         let mut ctl_zs = {
             let openings = &proof.openings;
-            let ctl_zs = openings.permutation_ctl_zs.iter().skip(num_permutation_zs);
+            let ctl_zs = openings.permutation_ctl_zs.iter().skip(num_ctl_zs_start);
             let ctl_zs_next = openings
                 .permutation_ctl_zs_next
                 .iter()
-                .skip(num_permutation_zs);
+                .skip(num_ctl_zs_start);
             ctl_zs.zip(ctl_zs_next)
         };
 
@@ -482,6 +578,7 @@ impl<'a, F: Field, const D: usize> CtlCheckVarsTarget<'a, F, D> {
         for CrossTableLookup {
             looking_tables,
             looked_table,
+            logup,
             ..
         } in cross_table_lookups
         {
@@ -495,6 +592,7 @@ impl<'a, F: Field, const D: usize> CtlCheckVarsTarget<'a, F, D> {
                             challenges,
                             columns: &looking_table.columns,
                             filter_column: &looking_table.filter_column,
+                            logup: *logup,
                         });
                     }
                 }
@@ -507,6 +605,7 @@ impl<'a, F: Field, const D: usize> CtlCheckVarsTarget<'a, F, D> {
                         challenges,
                         columns: &looked_table.columns,
                         filter_column: &looked_table.filter_column,
+                        logup: *logup,
                     });
                 }
             }
@@ -533,6 +632,7 @@ pub(crate) fn eval_cross_table_lookup_checks_circuit<
             challenges,
             columns,
             filter_column,
+            logup,
         } = lookup_vars;
 
         let one = builder.one_extension();
@@ -556,21 +656,33 @@ pub(crate) fn eval_cross_table_lookup_checks_circuit<
             builder.mul_add_extension(filter, x, tmp) // filter * x + 1 - filter
         }
 
-        // Check value of `Z(1)`
         let local_columns_eval = columns
             .iter()
             .map(|c| c.eval_circuit(builder, vars.local_values))
             .collect::<Vec<_>>();
         let combined_local = challenges.combine_circuit(builder, &local_columns_eval);
-        let selected_local = select(builder, local_filter, combined_local);
-        let first_row = builder.sub_extension(*local_z, selected_local);
-        consumer.constraint_first_row(builder, first_row);
-        // Check `Z(gw) = combination * Z(w)`
         let next_columns_eval = columns
             .iter()
             .map(|c| c.eval_circuit(builder, vars.next_values))
             .collect::<Vec<_>>();
         let combined_next = challenges.combine_circuit(builder, &next_columns_eval);
+
+        if *logup {
+            // Check `Z(1) * combination = filter`
+            let first_row = builder.mul_sub_extension(*local_z, combined_local, local_filter);
+            consumer.constraint_first_row(builder, first_row);
+            // Check `(Z(gw) - Z(w)) * combination = filter`
+            let z_diff = builder.sub_extension(*next_z, *local_z);
+            let transition = builder.mul_sub_extension(z_diff, combined_next, next_filter);
+            consumer.constraint_transition(builder, transition);
+            continue;
+        }
+
+        // Check value of `Z(1)`
+        let selected_local = select(builder, local_filter, combined_local);
+        let first_row = builder.sub_extension(*local_z, selected_local);
+        consumer.constraint_first_row(builder, first_row);
+        // Check `Z(gw) = combination * Z(w)`
         let selected_next = select(builder, next_filter, combined_next);
         let mut transition = builder.mul_extension(*local_z, selected_next);
         transition = builder.sub_extension(*next_z, transition);
@@ -596,11 +708,24 @@ pub(crate) fn verify_cross_table_lookups<
             looking_tables,
             looked_table,
             default,
-            ..
+            logup,
         },
     ) in cross_table_lookups.into_iter().enumerate()
     {
         for _ in 0..config.num_challenges {
+            if logup {
+                let looking_zs_sum = looking_tables
+                    .iter()
+                    .map(|table| *ctl_zs_openings[table.table as usize].next().unwrap())
+                    .sum::<F>();
+                let looked_z = *ctl_zs_openings[looked_table.table as usize].next().unwrap();
+                ensure!(
+                    looking_zs_sum == looked_z,
+                    "Cross-table lookup verification failed."
+                );
+                continue;
+            }
+
             let looking_degrees_sum = looking_tables
                 .iter()
                 .map(|table| 1 << degrees_bits[table.table as usize])
@@ -634,7 +759,6 @@ pub(crate) fn verify_cross_table_lookups<
     Ok(())
 }
 
-#[allow(unused)]
 pub(crate) fn verify_cross_table_lookups_circuit<
     F: RichField + Extendable<D>,
     C: GenericConfig<D, F = F>,
@@ -654,11 +778,22 @@ pub(crate) fn verify_cross_table_lookups_circuit<
             looking_tables,
             looked_table,
             default,
-            ..
+            logup,
         },
     ) in cross_table_lookups.into_iter().enumerate()
     {
         for _ in 0..inner_config.num_challenges {
+            if logup {
+                let looking_zs_sum = builder.add_many(
+                    looking_tables
+                        .iter()
+                        .map(|table| *ctl_zs_openings[table.table as usize].next().unwrap()),
+                );
+                let looked_z = *ctl_zs_openings[looked_table.table as usize].next().unwrap();
+                builder.connect(looking_zs_sum, looked_z);
+                continue;
+            }
+
             let looking_degrees_sum = looking_tables
                 .iter()
                 .map(|table| 1 << degrees_bits[table.table as usize])
@@ -678,7 +813,12 @@ pub(crate) fn verify_cross_table_lookups_circuit<
                     .collect::<Vec<_>>();
                 let combined_default = challenge.combine_base_circuit(builder, &default);
 
-                let pad = builder.exp_u64(combined_default, looking_degrees_sum - looked_degree);
+                let degree_diff = if looking_degrees_sum >= looked_degree {
+                    looking_degrees_sum - looked_degree
+                } else {
+                    looked_degree - looking_degrees_sum
+                };
+                let pad = builder.exp_u64(combined_default, degree_diff);
                 let padded_looked_z = builder.mul(looked_z, pad);
                 builder.connect(looking_zs_prod, padded_looked_z);
             } else {
@@ -721,8 +861,37 @@ pub(crate) mod testutils {
             looking_tables,
             looked_table,
             default,
+            logup,
         } = ctl;
 
+        // With multiplicities, every looking row only has to be in the looked
+        // table.
+        if *logup {
+            let mut looking_multiset = MultiSet::<F>::new();
+            for table in looking_tables {
+                process_table(trace_poly_values, table, &mut looking_multiset);
+            }
+            let trace = &trace_poly_values[looked_table.table as usize];
+            let looked_rows = (0..trace[0].len())
+                .map(|i| {
+                    looked_table
+                        .columns
+                        .iter()
+                        .map(|c| c.eval_table(trace, i))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            for (row, looking_locations) in &looking_multiset {
+                assert!(
+                    looked_rows.contains(row),
+                    "CTL #{ctl_index}:\n\
+                     Row {row:?} is missing from the looked table.\n\
+                     Looking locations (Table, Row index): {looking_locations:?}."
+                );
+            }
+            return;
+        }
+
         // Maps `m` with `(table, i) in m[row]` iff the `i`-th row of `table` is equal
         // to `row` and the filter is 1. Without default values, the CTL check
         // holds iff `looking_multiset == looked_multiset`.
@@ -8,8 +8,8 @@ use plonky2::plonk::config::{AlgebraicHasher, GenericConfig};
 use super::all_stark::{AllStark, NUM_TABLES};
 use super::config::StarkConfig;
 use super::permutation::{
-    get_grand_product_challenge_set, get_n_grand_product_challenge_sets,
-    get_n_grand_product_challenge_sets_target,
+    get_grand_product_challenge_set, get_grand_product_challenge_set_target,
+    get_n_grand_product_challenge_sets, get_n_grand_product_challenge_sets_target,
 };
 use super::proof::*;
 
@@ -26,13 +26,14 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> A
             challenger.observe_cap(&proof.trace_cap);
         }
 
-        // TODO: Observe public values.
+        challenger.observe_elements(&self.public_values.to_field_elements());
 
         let ctl_challenges =
             get_grand_product_challenge_set(&mut challenger, config.num_challenges);
 
         let num_permutation_zs = all_stark.nums_permutation_zs(config);
         let num_permutation_batch_sizes = all_stark.permutation_batch_sizes();
+        let num_lookup_zs = all_stark.nums_lookup_zs(config);
 
         AllProofChallenges {
             stark_challenges: std::array::from_fn(|i| {
@@ -41,6 +42,7 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> A
                     &mut challenger,
                     num_permutation_zs[i] > 0,
                     num_permutation_batch_sizes[i],
+                    num_lookup_zs[i] > 0,
                     config,
                 )
             }),
@@ -60,13 +62,14 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> A
             challenger.observe_cap(&proof.trace_cap);
         }
 
-        // TODO: Observe public values.
+        challenger.observe_elements(&self.public_values.to_field_elements());
 
         let ctl_challenges =
             get_grand_product_challenge_set(&mut challenger, config.num_challenges);
 
         let num_permutation_zs = all_stark.nums_permutation_zs(config);
         let num_permutation_batch_sizes = all_stark.permutation_batch_sizes();
+        let num_lookup_zs = all_stark.nums_lookup_zs(config);
 
         let mut challenger_states = vec![challenger.compact()];
         for i in 0..NUM_TABLES {
@@ -74,6 +77,7 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> A
                 &mut challenger,
                 num_permutation_zs[i] > 0,
                 num_permutation_batch_sizes[i],
+                num_lookup_zs[i] > 0,
                 config,
             );
             challenger_states.push(challenger.compact());
@@ -86,6 +90,50 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> A
     }
 }
 
+impl<const D: usize> AllProofTarget<D> {
+    /// Computes all Fiat-Shamir challenges used in the STARK proof, in a
+    /// recursive verifier circuit.
+    pub(crate) fn get_challenges<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>>(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        all_stark: &AllStark<F, D>,
+        config: &StarkConfig,
+    ) -> AllProofChallengesTarget<D>
+    where
+        C::Hasher: AlgebraicHasher<F>,
+    {
+        let mut challenger = RecursiveChallenger::<F, C::Hasher, D>::new(builder);
+
+        for proof in &self.stark_proofs {
+            challenger.observe_cap(&proof.trace_cap);
+        }
+
+        challenger.observe_elements(&self.public_values.to_targets());
+
+        let ctl_challenges =
+            get_grand_product_challenge_set_target(builder, &mut challenger, config.num_challenges);
+
+        let num_permutation_zs = all_stark.nums_permutation_zs(config);
+        let num_permutation_batch_sizes = all_stark.permutation_batch_sizes();
+        let num_lookup_zs = all_stark.nums_lookup_zs(config);
+
+        AllProofChallengesTarget {
+            stark_challenges: std::array::from_fn(|i| {
+                challenger.compact(builder);
+                self.stark_proofs[i].get_challenges::<F, C>(
+                    builder,
+                    &mut challenger,
+                    num_permutation_zs[i] > 0,
+                    num_permutation_batch_sizes[i],
+                    num_lookup_zs[i] > 0,
+                    config,
+                )
+            }),
+            ctl_challenges,
+        }
+    }
+}
+
 impl<F, C, const D: usize> StarkProof<F, C, D>
 where
     F: RichField + Extendable<D>,
@@ -97,6 +145,7 @@ where
         challenger: &mut Challenger<F, C::Hasher>,
         stark_use_permutation: bool,
         stark_permutation_batch_size: usize,
+        stark_use_lookups: bool,
         config: &StarkConfig,
     ) -> StarkProofChallenges<F, D> {
         let degree_bits = self.recover_degree_bits(config);
@@ -125,6 +174,9 @@ where
             )
         });
 
+        let lookup_challenge_set =
+            stark_use_lookups.then(|| get_grand_product_challenge_set(challenger, num_challenges));
+
         challenger.observe_cap(permutation_ctl_zs_cap);
 
         let stark_alphas = challenger.get_n_challenges(num_challenges);
@@ -136,6 +188,7 @@ where
 
         StarkProofChallenges {
             permutation_challenge_sets,
+            lookup_challenge_set,
             stark_alphas,
             stark_zeta,
             fri_challenges: challenger.fri_challenges::<C, D>(
@@ -150,13 +203,13 @@ where
 }
 
 impl<const D: usize> StarkProofTarget<D> {
-    #[allow(unused)]
     pub(crate) fn get_challenges<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>>(
         &self,
         builder: &mut CircuitBuilder<F, D>,
         challenger: &mut RecursiveChallenger<F, C::Hasher, D>,
         stark_use_permutation: bool,
         stark_permutation_batch_size: usize,
+        stark_use_lookups: bool,
         config: &StarkConfig,
     ) -> StarkProofChallengesTarget<D>
     where
@@ -187,6 +240,9 @@ impl<const D: usize> StarkProofTarget<D> {
             )
         });
 
+        let lookup_challenge_set = stark_use_lookups
+            .then(|| get_grand_product_challenge_set_target(builder, challenger, num_challenges));
+
         challenger.observe_cap(permutation_ctl_zs_cap);
 
         let stark_alphas = challenger.get_n_challenges(builder, num_challenges);
@@ -198,6 +254,7 @@ impl<const D: usize> StarkProofTarget<D> {
 
         StarkProofChallengesTarget {
             permutation_challenge_sets,
+            lookup_challenge_set,
             stark_alphas,
             stark_zeta,
             fri_challenges: challenger.fri_challenges::<C>(
@@ -1,176 +1,286 @@
-use std::cmp::Ordering;
+use std::collections::HashMap;
 
 use itertools::Itertools;
-use plonky2::field::extension::Extendable;
+use plonky2::field::extension::{Extendable, FieldExtension};
 use plonky2::field::packed::PackedField;
+use plonky2::field::polynomial::PolynomialValues;
 use plonky2::field::types::{Field, PrimeField64};
 use plonky2::hash::hash_types::RichField;
+use plonky2::iop::ext_target::ExtensionTarget;
+use plonky2::iop::target::Target;
 use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2_util::ceil_div_usize;
 
-use crate::stark::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
-use crate::stark::vars::{StarkEvaluationTargets, StarkEvaluationVars};
-
-pub(crate) fn eval_lookups<F: Field, P: PackedField<Scalar = F>, const COLS: usize>(
-    vars: StarkEvaluationVars<F, P, COLS>,
-    yield_constr: &mut ConstraintConsumer<P>,
-    col_permuted_input: usize,
-    col_permuted_table: usize,
-) {
-    let local_perm_input = vars.local_values[col_permuted_input];
-    let next_perm_table = vars.next_values[col_permuted_table];
-    let next_perm_input = vars.next_values[col_permuted_input];
-
-    // A "vertical" diff between the local and next permuted inputs.
-    let diff_input_prev = next_perm_input - local_perm_input;
-    // A "horizontal" diff between the next permuted input and permuted table value.
-    let diff_input_table = next_perm_input - next_perm_table;
-
-    yield_constr.constraint(diff_input_prev * diff_input_table);
-
-    // This is actually constraining the first row, as per the spec, since
-    // `diff_input_table` is a diff of the next row's values. In the context of
-    // `constraint_last_row`, the next row is the first row.
-    yield_constr.constraint_last_row(diff_input_table);
-}
+use super::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+use super::permutation::{GrandProductChallenge, GrandProductChallengeSet};
+use super::stark::Stark;
+use super::vars::{StarkEvaluationTargets, StarkEvaluationVars};
 
-#[allow(unused)]
-pub(crate) fn eval_lookups_circuit<
-    F: RichField + Extendable<D>,
-    const D: usize,
-    const COLS: usize,
->(
-    builder: &mut CircuitBuilder<F, D>,
-    vars: StarkEvaluationTargets<D, COLS>,
-    yield_constr: &mut RecursiveConstraintConsumer<F, D>,
-    col_permuted_input: usize,
-    col_permuted_table: usize,
-) {
-    let local_perm_input = vars.local_values[col_permuted_input];
-    let next_perm_table = vars.next_values[col_permuted_table];
-    let next_perm_input = vars.next_values[col_permuted_input];
-
-    // A "vertical" diff between the local and next permuted inputs.
-    let diff_input_prev = builder.sub_extension(next_perm_input, local_perm_input);
-    // A "horizontal" diff between the next permuted input and permuted table value.
-    let diff_input_table = builder.sub_extension(next_perm_input, next_perm_table);
-
-    let diff_product = builder.mul_extension(diff_input_prev, diff_input_table);
-    yield_constr.constraint(builder, diff_product);
-
-    // This is actually constraining the first row, as per the spec, since
-    // `diff_input_table` is a diff of the next row's values. In the context of
-    // `constraint_last_row`, the next row is the first row.
-    yield_constr.constraint_last_row(builder, diff_input_table);
+/// Tuples of `columns` that are looked up in the tuples of `table_columns`,
+/// checked with a logarithmic derivative (LogUp) argument:
+///     sum_i sum_row 1/(gamma + a_i(row)) = sum_row m(row)/(gamma + t(row))
+/// where tuples are combined with powers of `beta` and `m` is the
+/// `frequencies_column`, how many times each table row is looked up.
+pub struct Lookup {
+    /// Each entry is a tuple of columns looked up in the table.
+    pub columns: Vec<Vec<usize>>,
+    pub table_columns: Vec<usize>,
+    pub frequencies_column: usize,
 }
 
-/// Given an input column and a table column, generate the permuted input and
-/// permuted table columns used in the Halo2 permutation argument.
-pub fn permuted_cols<F: PrimeField64>(inputs: &[F], table: &[F]) -> (Vec<F>, Vec<F>) {
-    let n = inputs.len();
+impl Lookup {
+    /// Single columns looked up in a single table column.
+    pub fn singletons(
+        columns: impl IntoIterator<Item = usize>,
+        table_column: usize,
+        frequencies_column: usize,
+    ) -> Self {
+        Self {
+            columns: columns.into_iter().map(|col| vec![col]).collect(),
+            table_columns: vec![table_column],
+            frequencies_column,
+        }
+    }
 
-    // The permuted inputs do not have to be ordered, but we found that sorting was
-    // faster than hash-based grouping. We also sort the table, as this helps us
-    // identify "unused" table elements efficiently.
+    /// One helper column per `batch_size` looked up tuples, plus the running
+    /// sum `Z`.
+    pub(crate) fn num_helper_columns(&self, batch_size: usize) -> usize {
+        ceil_div_usize(self.columns.len(), batch_size) + 1
+    }
+}
 
-    // To compare elements, e.g. for sorting, we first need them in canonical form.
-    // It would be wasteful to canonicalize in each comparison, as a single
-    // element may be involved in many comparisons. So we will canonicalize once
-    // upfront, then use `to_noncanonical_u64` when comparing elements.
+/// Frequencies of the `table` rows in `inputs`, a tuple appearing in several
+/// table rows is counted on the first of them.
+pub fn lookup_frequencies<F: PrimeField64, const N: usize>(
+    inputs: impl IntoIterator<Item = [F; N]>,
+    table: &[[F; N]],
+) -> Vec<F> {
+    let key = |t: &[F; N]| t.map(|x| x.to_canonical_u64());
+    let mut rows = HashMap::new();
+    for (i, t) in table.iter().enumerate().rev() {
+        rows.insert(key(t), i);
+    }
 
-    let sorted_inputs = inputs
-        .iter()
-        .map(|x| x.to_canonical())
-        .sorted_unstable_by_key(|x| x.to_noncanonical_u64())
-        .collect_vec();
-    let sorted_table = table
+    let mut frequencies = vec![0u64; table.len()];
+    for t in inputs {
+        // A tuple missing from the table can't be proven, leave it to the
+        // verifier to reject.
+        if let Some(&i) = rows.get(&key(&t)) {
+            frequencies[i] += 1;
+        }
+    }
+    frequencies.into_iter().map(F::from_canonical_u64).collect()
+}
+
+/// Compute the helper columns and running sum `Z` of every lookup of `stark`,
+/// for each challenge.
+pub(crate) fn compute_lookup_helper_columns<F, S, const D: usize>(
+    stark: &S,
+    trace_poly_values: &[PolynomialValues<F>],
+    lookup_challenges: &GrandProductChallengeSet<F>,
+) -> Vec<PolynomialValues<F>>
+where
+    F: RichField + Extendable<D>,
+    S: Stark<F, D>,
+{
+    let batch_size = stark.lookup_batch_size();
+    stark
+        .lookups()
         .iter()
-        .map(|x| x.to_canonical())
-        .sorted_unstable_by_key(|x| x.to_noncanonical_u64())
-        .collect_vec();
+        .flat_map(|lookup| {
+            lookup_challenges
+                .challenges
+                .iter()
+                .flat_map(move |challenge| {
+                    lookup_helper_columns(lookup, trace_poly_values, *challenge, batch_size)
+                })
+        })
+        .collect()
+}
 
-    let mut unused_table_inds = Vec::with_capacity(n);
-    let mut unused_table_vals = Vec::with_capacity(n);
-    let mut permuted_table = vec![F::ZERO; n];
-    let mut i = 0;
-    let mut j = 0;
-    while (j < n) && (i < n) {
-        let input_val = sorted_inputs[i].to_noncanonical_u64();
-        let table_val = sorted_table[j].to_noncanonical_u64();
-        match input_val.cmp(&table_val) {
-            Ordering::Greater => {
-                unused_table_vals.push(sorted_table[j]);
-                j += 1;
-            }
-            Ordering::Less => {
-                if let Some(x) = unused_table_vals.pop() {
-                    permuted_table[i] = x;
-                } else {
-                    unused_table_inds.push(i);
+fn lookup_helper_columns<F: Field>(
+    lookup: &Lookup,
+    trace_poly_values: &[PolynomialValues<F>],
+    challenge: GrandProductChallenge<F>,
+    batch_size: usize,
+) -> Vec<PolynomialValues<F>> {
+    let degree = trace_poly_values[0].len();
+    let combined_inverses = |cols: &[usize]| {
+        let combined = (0..degree)
+            .map(|row| {
+                challenge.combine::<F, F, _, 1>(
+                    cols.iter().map(|&col| &trace_poly_values[col].values[row]),
+                )
+            })
+            .collect_vec();
+        F::batch_multiplicative_inverse(&combined)
+    };
+
+    // h(x) = sum_i 1/(gamma + a_i(x)) over a batch of looked up tuples.
+    let mut helper_columns = lookup
+        .columns
+        .chunks(batch_size)
+        .map(|chunk| {
+            let mut helper = vec![F::ZERO; degree];
+            for cols in chunk {
+                for (h, inv) in helper.iter_mut().zip(combined_inverses(cols)) {
+                    *h += inv;
                 }
-                i += 1;
-            }
-            Ordering::Equal => {
-                permuted_table[i] = sorted_table[j];
-                i += 1;
-                j += 1;
             }
-        }
-    }
+            helper
+        })
+        .collect_vec();
 
-    #[allow(clippy::needless_range_loop)] // indexing is just more natural here
-    for jj in j..n {
-        unused_table_vals.push(sorted_table[jj]);
-    }
-    for ii in i..n {
-        unused_table_inds.push(ii);
-    }
-    for (ind, val) in unused_table_inds.into_iter().zip_eq(unused_table_vals) {
-        permuted_table[ind] = val;
+    // Z(g x) = Z(x) + sum h(x) - m(x)/(gamma + t(x)), the sum over the whole
+    // trace is zero, so Z wraps around.
+    let table_inverses = combined_inverses(&lookup.table_columns);
+    let frequencies = &trace_poly_values[lookup.frequencies_column].values;
+    let mut z = Vec::with_capacity(degree);
+    z.push(F::ZERO);
+    for row in 0..degree - 1 {
+        let sum_h: F = helper_columns.iter().map(|h| h[row]).sum();
+        z.push(z[row] + sum_h - frequencies[row] * table_inverses[row]);
     }
+    helper_columns.push(z);
 
-    (sorted_inputs, permuted_table)
+    helper_columns
+        .into_iter()
+        .map(PolynomialValues::new)
+        .collect()
 }
 
-// add by xb 2023-1-5
-// case 1:
-// looking_table:
-// <0,1,2,3,4,5>
-// looked_table:
-// <0,1,2,3,4,5,6,7>
-// Extend:
-//      looking_table: <0,1,2,3,4,5,5,5>
-//      looked_table: <0,1,2,3,4,5,6,7>
-
-// case 2:
-// looking_table:
-// <0,1,2,3,4,5>
-// looked_table:
-// <0,1,2,3,4,5,6,7,8,9>
-// Extend:
-//      looking_table: <0,1,2,3,4,5,5,5,5,5,5,5,5,5,5,5>
-//      looked_table: <0,1,2,3,4,5,6,7,8,9,10,11,12,13,14,15>
-pub fn extend_to_power_of_2<F: PrimeField64>(inputs: &[F], table: &[F]) -> (Vec<F>, Vec<F>) {
-    let looking_trace_len = inputs.len();
-    let looked_trace_len = table.len();
-
-    let expect_trace_len = looking_trace_len.max(looked_trace_len).next_power_of_two();
-
-    let mut extend_input = Vec::with_capacity(expect_trace_len);
-    let extend_table = Vec::with_capacity(expect_trace_len);
-
-    for i in 0..expect_trace_len {
-        if i < looking_trace_len {
-            extend_input.push(inputs[i])
-        } else {
-            extend_input.push(inputs[looking_trace_len - 1])
-        }
+pub struct LookupCheckVars<F, FE, P, const D2: usize>
+where
+    F: Field,
+    FE: FieldExtension<D2, BaseField = F>,
+    P: PackedField<Scalar = FE>,
+{
+    pub(crate) local_values: Vec<P>,
+    pub(crate) next_values: Vec<P>,
+    pub(crate) challenges: GrandProductChallengeSet<F>,
+}
 
-        if i < looked_trace_len {
-            extend_input.push(table[i])
-        } else {
-            extend_input.push(table[looked_trace_len - 1])
+/// Constraints of every lookup of `stark`:
+///     h(x) * prod_i (gamma + a_i(x)) = sum_i prod_{j != i} (gamma + a_j(x))
+///     (Z(g x) - Z(x) - sum h(x)) * (gamma + t(x)) + m(x) = 0
+pub(crate) fn eval_lookup_checks<F, FE, P, S, const D: usize, const D2: usize>(
+    stark: &S,
+    vars: StarkEvaluationVars<FE, P, { S::COLUMNS }>,
+    lookup_vars: LookupCheckVars<F, FE, P, D2>,
+    consumer: &mut ConstraintConsumer<P>,
+) where
+    F: RichField + Extendable<D>,
+    FE: FieldExtension<D2, BaseField = F>,
+    P: PackedField<Scalar = FE>,
+    S: Stark<F, D>,
+{
+    let batch_size = stark.lookup_batch_size();
+    let mut start = 0;
+    for lookup in stark.lookups() {
+        let num_helper_columns = lookup.num_helper_columns(batch_size);
+        for challenge in &lookup_vars.challenges.challenges {
+            let helpers = &lookup_vars.local_values[start..start + num_helper_columns - 1];
+            let z = lookup_vars.local_values[start + num_helper_columns - 1];
+            let next_z = lookup_vars.next_values[start + num_helper_columns - 1];
+
+            for (cols, &h) in lookup.columns.chunks(batch_size).zip(helpers) {
+                let combined = cols
+                    .iter()
+                    .map(|cols| challenge.combine(cols.iter().map(|&col| &vars.local_values[col])))
+                    .collect_vec();
+                let product: P = combined.iter().copied().product();
+                let sum: P = (0..combined.len())
+                    .map(|i| {
+                        combined
+                            .iter()
+                            .enumerate()
+                            .filter(|&(j, _)| j != i)
+                            .map(|(_, &a)| a)
+                            .product::<P>()
+                    })
+                    .sum();
+                consumer.constraint(h * product - sum);
+            }
+
+            let table = challenge.combine(
+                lookup
+                    .table_columns
+                    .iter()
+                    .map(|&col| &vars.local_values[col]),
+            );
+            let frequencies = vars.local_values[lookup.frequencies_column];
+            let sum_h: P = helpers.iter().copied().sum();
+            consumer.constraint((next_z - z - sum_h) * table + frequencies);
+
+            start += num_helper_columns;
         }
     }
+}
 
-    (extend_input, extend_table)
+pub struct LookupCheckVarsTarget<const D: usize> {
+    pub(crate) local_values: Vec<ExtensionTarget<D>>,
+    pub(crate) next_values: Vec<ExtensionTarget<D>>,
+    pub(crate) challenges: GrandProductChallengeSet<Target>,
+}
+
+pub(crate) fn eval_lookup_checks_circuit<F, S, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    stark: &S,
+    vars: StarkEvaluationTargets<D, { S::COLUMNS }>,
+    lookup_vars: LookupCheckVarsTarget<D>,
+    consumer: &mut RecursiveConstraintConsumer<F, D>,
+) where
+    F: RichField + Extendable<D>,
+    S: Stark<F, D>,
+    [(); S::COLUMNS]:,
+{
+    let batch_size = stark.lookup_batch_size();
+    let mut start = 0;
+    for lookup in stark.lookups() {
+        let num_helper_columns = lookup.num_helper_columns(batch_size);
+        for challenge in &lookup_vars.challenges.challenges {
+            let helpers = &lookup_vars.local_values[start..start + num_helper_columns - 1];
+            let z = lookup_vars.local_values[start + num_helper_columns - 1];
+            let next_z = lookup_vars.next_values[start + num_helper_columns - 1];
+
+            for (cols, &h) in lookup.columns.chunks(batch_size).zip(helpers) {
+                let combined = cols
+                    .iter()
+                    .map(|cols| {
+                        let terms = cols.iter().map(|&col| vars.local_values[col]).collect_vec();
+                        challenge.combine_circuit(builder, &terms)
+                    })
+                    .collect_vec();
+                let product = builder.mul_many_extension(combined.clone());
+                let mut sum = builder.zero_extension();
+                for i in 0..combined.len() {
+                    let others = combined
+                        .iter()
+                        .enumerate()
+                        .filter(|&(j, _)| j != i)
+                        .map(|(_, &a)| a)
+                        .collect_vec();
+                    let others = builder.mul_many_extension(others);
+                    sum = builder.add_extension(sum, others);
+                }
+                let constraint = builder.mul_sub_extension(h, product, sum);
+                consumer.constraint(builder, constraint);
+            }
+
+            let table_terms = lookup
+                .table_columns
+                .iter()
+                .map(|&col| vars.local_values[col])
+                .collect_vec();
+            let table = challenge.combine_circuit(builder, &table_terms);
+            let frequencies = vars.local_values[lookup.frequencies_column];
+            let z_diff = builder.sub_extension(next_z, z);
+            let sum_h = builder.add_many_extension(helpers.iter().copied());
+            let z_diff = builder.sub_extension(z_diff, sum_h);
+            let constraint = builder.mul_add_extension(z_diff, table, frequencies);
+            consumer.constraint(builder, constraint);
+
+            start += num_helper_columns;
+        }
+    }
 }
@@ -1,3 +1,4 @@
+pub mod aggregation;
 pub mod all_stark;
 pub mod config;
 pub mod constraint_consumer;
@@ -5,10 +6,14 @@ pub mod cross_table_lookup;
 mod get_challenges;
 pub mod lookup;
 pub mod permutation;
+pub mod preprocessed;
 pub mod proof;
 pub mod prover;
+pub mod recursive_verifier;
 pub mod serialization;
 pub mod stark;
+#[cfg(test)]
+pub(crate) mod stark_testing;
 pub mod util;
 pub mod vanishing_poly;
 pub mod vars;
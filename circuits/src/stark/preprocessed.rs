@@ -0,0 +1,115 @@
+use plonky2::field::extension::Extendable;
+use plonky2::fri::oracle::PolynomialBatch;
+use plonky2::hash::hash_types::RichField;
+use plonky2::hash::merkle_tree::MerkleCap;
+use plonky2::plonk::config::{GenericConfig, Hasher};
+use plonky2::timed;
+use plonky2::util::timing::TimingTree;
+use plonky2_util::log2_strict;
+use serde::{Deserialize, Serialize};
+
+use super::all_stark::{AllStark, NUM_TABLES};
+use super::config::StarkConfig;
+use super::stark::Stark;
+
+/// Commitments to the preprocessed columns of every table, computed once per
+/// `StarkConfig` and shared by all proofs. `None` for tables without
+/// preprocessed columns.
+pub struct AllPreprocessed<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+{
+    pub commitments: [Option<PolynomialBatch<F, C, D>>; NUM_TABLES],
+}
+
+/// What the verifier knows about the preprocessed columns of a table.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct PreprocessedCap<F: RichField, C: GenericConfig<D, F = F>, const D: usize> {
+    /// The preprocessed columns fix the trace length.
+    pub degree_bits: usize,
+    /// Merkle cap of LDEs of preprocessed values.
+    pub cap: MerkleCap<F, C::Hasher>,
+}
+
+/// The verifier side of `AllPreprocessed`.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct AllVerifierKey<F: RichField, C: GenericConfig<D, F = F>, const D: usize> {
+    pub preprocessed_caps: [Option<PreprocessedCap<F, C, D>>; NUM_TABLES],
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+    AllPreprocessed<F, C, D>
+{
+    pub fn new(all_stark: &AllStark<F, D>, config: &StarkConfig, timing: &mut TimingTree) -> Self
+    where
+        [(); C::Hasher::HASH_SIZE]:,
+    {
+        let commitments = timed!(
+            timing,
+            "compute preprocessed commitments",
+            [
+                commit_preprocessed(&all_stark.cpu_stark, config, timing),
+                commit_preprocessed(&all_stark.memory_stark, config, timing),
+                commit_preprocessed(&all_stark.bitwise_stark, config, timing),
+                commit_preprocessed(&all_stark.cmp_stark, config, timing),
+                commit_preprocessed(&all_stark.rangecheck_stark, config, timing),
+                commit_preprocessed(&all_stark.program_stark, config, timing),
+                commit_preprocessed(&all_stark.bitwise_fixed_stark, config, timing),
+                commit_preprocessed(&all_stark.rangecheck_fixed_stark, config, timing),
+                commit_preprocessed(&all_stark.final_program_stark, config, timing),
+                commit_preprocessed(&all_stark.poseidon_stark, config, timing),
+                commit_preprocessed(&all_stark.ecdsa_stark, config, timing),
+                commit_preprocessed(&all_stark.keccak_stark, config, timing),
+                commit_preprocessed(&all_stark.keccak_sponge_stark, config, timing),
+            ]
+        );
+        Self { commitments }
+    }
+
+    pub fn verifier_key(&self) -> AllVerifierKey<F, C, D> {
+        AllVerifierKey {
+            preprocessed_caps: std::array::from_fn(|i| {
+                self.commitments[i].as_ref().map(|c| PreprocessedCap {
+                    degree_bits: log2_strict(c.polynomials[0].len()),
+                    cap: c.merkle_tree.cap.clone(),
+                })
+            }),
+        }
+    }
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+    AllVerifierKey<F, C, D>
+{
+    pub fn new(all_stark: &AllStark<F, D>, config: &StarkConfig) -> Self
+    where
+        [(); C::Hasher::HASH_SIZE]:,
+    {
+        AllPreprocessed::new(all_stark, config, &mut TimingTree::default()).verifier_key()
+    }
+}
+
+fn commit_preprocessed<F, C, S, const D: usize>(
+    stark: &S,
+    config: &StarkConfig,
+    timing: &mut TimingTree,
+) -> Option<PolynomialBatch<F, C, D>>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    S: Stark<F, D>,
+    [(); C::Hasher::HASH_SIZE]:,
+{
+    stark.uses_preprocessed_columns().then(|| {
+        let values = stark.preprocessed_trace();
+        debug_assert_eq!(values.len(), stark.num_preprocessed_columns());
+        PolynomialBatch::from_values(
+            values,
+            config.fri_config.rate_bits,
+            false,
+            config.fri_config.cap_height,
+            timing,
+            None,
+        )
+    })
+}
@@ -1,7 +1,11 @@
+use core::program::REGISTER_NUM;
+
+use anyhow::{ensure, Result};
 use ethereum_types::{Address, H256, U256};
 use itertools::Itertools;
 use maybe_rayon::*;
 use plonky2::field::extension::{Extendable, FieldExtension};
+use plonky2::field::types::{Field, PrimeField64};
 use plonky2::fri::oracle::PolynomialBatch;
 use plonky2::fri::proof::{FriChallenges, FriChallengesTarget, FriProof, FriProofTarget};
 use plonky2::fri::structure::{
@@ -18,12 +22,12 @@ use serde::{Deserialize, Serialize};
 use super::all_stark::NUM_TABLES;
 use super::config::StarkConfig;
 use super::permutation::GrandProductChallengeSet;
+use crate::cpu::cpu_stark::CpuPublicValues;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(bound = "")]
 pub struct AllProof<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> {
     pub stark_proofs: [StarkProof<F, C, D>; NUM_TABLES],
-    pub compress_challenges: [F; NUM_TABLES],
     pub public_values: PublicValues,
 }
 
@@ -38,6 +42,11 @@ pub(crate) struct AllProofChallenges<F: RichField + Extendable<D>, const D: usiz
     pub ctl_challenges: GrandProductChallengeSet<F>,
 }
 
+pub(crate) struct AllProofChallengesTarget<const D: usize> {
+    pub stark_challenges: [StarkProofChallengesTarget<D>; NUM_TABLES],
+    pub ctl_challenges: GrandProductChallengeSet<Target>,
+}
+
 #[allow(unused)] // TODO: should be used soon
 pub(crate) struct AllChallengerState<F: RichField + Extendable<D>, const D: usize> {
     /// Sponge state of the challenger before starting each proof,
@@ -55,12 +64,102 @@ pub struct AllProofTarget<const D: usize> {
 /// Memory values which are public.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PublicValues {
+    /// Poseidon hash of the encoded instructions and static data, see
+    /// `generation::program::program_hash`.
+    pub program_hash: [u64; 4],
+    /// Registers of the first row of the cpu trace, the program inputs.
+    pub initial_registers: [u64; REGISTER_NUM],
+    /// Registers of the last row of the cpu trace, the program outputs.
+    pub final_registers: [u64; REGISTER_NUM],
+    /// Program hash of the next segment, the program with the read write
+    /// memory and prophet outputs left by this one as static data.
+    pub final_program_hash: [u64; 4],
+    /// Clock and pc of the first and last rows of the cpu trace.
+    pub initial_clk: u64,
+    pub initial_pc: u64,
+    pub final_clk: u64,
+    pub final_pc: u64,
+    /// Whether the execution ends with `END`, otherwise it continues in the
+    /// segment starting from the final state.
+    pub is_final: bool,
     // TODO:
     // pub trie_roots_before: TrieRoots,
     // pub trie_roots_after: TrieRoots,
     // pub block_metadata: BlockMetadata,
 }
 
+impl PublicValues {
+    pub const NUM_FIELD_ELEMENTS: usize = 4 + 2 * REGISTER_NUM + 4 + 5;
+
+    /// Public values as field elements, in the order they are observed by the
+    /// challenger.
+    pub(crate) fn to_field_elements<F: Field>(&self) -> Vec<F> {
+        self.program_hash
+            .iter()
+            .chain(self.initial_registers.iter())
+            .chain(self.final_registers.iter())
+            .chain(self.final_program_hash.iter())
+            .chain(
+                [
+                    self.initial_clk,
+                    self.initial_pc,
+                    self.final_clk,
+                    self.final_pc,
+                    self.is_final as u64,
+                ]
+                .iter(),
+            )
+            .map(|e| F::from_canonical_u64(*e))
+            .collect()
+    }
+
+    /// The public values checked by the cpu table.
+    pub(crate) fn cpu_public_values<F: Field>(&self) -> CpuPublicValues<F> {
+        CpuPublicValues {
+            initial_clk: self.initial_clk,
+            initial_pc: self.initial_pc,
+            initial_registers: self.initial_registers,
+            final_clk: self.final_clk,
+            final_pc: self.final_pc,
+            final_registers: self.final_registers,
+            is_final: self.is_final as u64,
+        }
+        .map(F::from_canonical_u64)
+    }
+
+    /// Inverse of `to_field_elements`, e.g. to read the public inputs of a
+    /// recursive proof.
+    pub fn from_field_elements<F: PrimeField64>(elements: &[F]) -> Result<Self> {
+        ensure!(
+            elements.len() == Self::NUM_FIELD_ELEMENTS,
+            "Invalid number of public values: {}",
+            elements.len()
+        );
+        let values = elements
+            .iter()
+            .map(|e| e.to_canonical_u64())
+            .collect::<Vec<_>>();
+        let (program_hash, values) = values.split_at(4);
+        let (initial_registers, values) = values.split_at(REGISTER_NUM);
+        let (final_registers, values) = values.split_at(REGISTER_NUM);
+        let (final_program_hash, values) = values.split_at(4);
+        let [initial_clk, initial_pc, final_clk, final_pc, is_final]: [u64; 5] =
+            values.try_into().unwrap();
+        ensure!(is_final <= 1, "Invalid is_final public value: {}", is_final);
+        Ok(Self {
+            program_hash: program_hash.try_into().unwrap(),
+            initial_registers: initial_registers.try_into().unwrap(),
+            final_registers: final_registers.try_into().unwrap(),
+            final_program_hash: final_program_hash.try_into().unwrap(),
+            initial_clk,
+            initial_pc,
+            final_clk,
+            final_pc,
+            is_final: is_final == 1,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct TrieRoots {
     pub state_root: H256,
@@ -79,13 +178,53 @@ pub struct BlockMetadata {
     pub block_base_fee: U256,
 }
 
-/// Memory values which are public.
-/// Note: All the larger integers are encoded with 32-bit limbs in little-endian
-/// order.
+/// Targets of `PublicValues` in a recursive verifier circuit.
 pub struct PublicValuesTarget {
-    pub trie_roots_before: TrieRootsTarget,
-    pub trie_roots_after: TrieRootsTarget,
-    pub block_metadata: BlockMetadataTarget,
+    pub program_hash: [Target; 4],
+    pub initial_registers: [Target; REGISTER_NUM],
+    pub final_registers: [Target; REGISTER_NUM],
+    pub final_program_hash: [Target; 4],
+    pub initial_clk: Target,
+    pub initial_pc: Target,
+    pub final_clk: Target,
+    pub final_pc: Target,
+    pub is_final: Target,
+}
+
+impl PublicValuesTarget {
+    /// Public values targets, in the order of
+    /// `PublicValues::to_field_elements`.
+    pub fn to_targets(&self) -> Vec<Target> {
+        self.program_hash
+            .iter()
+            .chain(self.initial_registers.iter())
+            .chain(self.final_registers.iter())
+            .chain(self.final_program_hash.iter())
+            .chain(
+                [
+                    self.initial_clk,
+                    self.initial_pc,
+                    self.final_clk,
+                    self.final_pc,
+                    self.is_final,
+                ]
+                .iter(),
+            )
+            .copied()
+            .collect()
+    }
+
+    pub(crate) fn cpu_public_values(&self) -> CpuPublicValues<Target> {
+        CpuPublicValues {
+            initial_clk: self.initial_clk,
+            initial_pc: self.initial_pc,
+            initial_registers: self.initial_registers,
+            final_clk: self.final_clk,
+            final_pc: self.final_pc,
+            final_registers: self.final_registers,
+            is_final: self.is_final,
+        }
+    }
 }
 
 pub struct TrieRootsTarget {
@@ -159,6 +298,9 @@ pub(crate) struct StarkProofChallenges<F: RichField + Extendable<D>, const D: us
     /// Randomness used in any permutation arguments.
     pub permutation_challenge_sets: Option<Vec<GrandProductChallengeSet<F>>>,
 
+    /// Randomness used in any lookup arguments.
+    pub lookup_challenge_set: Option<GrandProductChallengeSet<F>>,
+
     /// Random values used to combine STARK constraints.
     pub stark_alphas: Vec<F>,
 
@@ -168,9 +310,9 @@ pub(crate) struct StarkProofChallenges<F: RichField + Extendable<D>, const D: us
     pub fri_challenges: FriChallenges<F, D>,
 }
 
-#[allow(unused)]
 pub(crate) struct StarkProofChallengesTarget<const D: usize> {
     pub permutation_challenge_sets: Option<Vec<GrandProductChallengeSet<Target>>>,
+    pub lookup_challenge_set: Option<GrandProductChallengeSet<Target>>,
     pub stark_alphas: Vec<Target>,
     pub stark_zeta: ExtensionTarget<D>,
     pub fri_challenges: FriChallengesTarget<D>,
@@ -184,27 +326,33 @@ pub struct StarkOpeningSet<F: RichField + Extendable<D>, const D: usize> {
     pub local_values: Vec<F::Extension>,
     /// Openings of trace polynomials at `g * zeta`.
     pub next_values: Vec<F::Extension>,
-    /// Openings of permutations and cross-table lookups `Z` polynomials at
-    /// `zeta`.
+    /// Openings of permutations, lookups helpers and cross-table lookups `Z`
+    /// polynomials at `zeta`.
     pub permutation_ctl_zs: Vec<F::Extension>,
-    /// Openings of permutations and cross-table lookups `Z` polynomials at `g *
-    /// zeta`.
+    /// Openings of permutations, lookups helpers and cross-table lookups `Z`
+    /// polynomials at `g * zeta`.
     pub permutation_ctl_zs_next: Vec<F::Extension>,
     /// Openings of cross-table lookups `Z` polynomials at `g^-1`.
     pub ctl_zs_last: Vec<F>,
     /// Openings of quotient polynomials at `zeta`.
     pub quotient_polys: Vec<F::Extension>,
+    /// Openings of preprocessed polynomials at `zeta`.
+    pub preprocessed_local_values: Vec<F::Extension>,
+    /// Openings of preprocessed polynomials at `g * zeta`.
+    pub preprocessed_next_values: Vec<F::Extension>,
 }
 
 impl<F: RichField + Extendable<D>, const D: usize> StarkOpeningSet<F, D> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new<C: GenericConfig<D, F = F>>(
         zeta: F::Extension,
         g: F,
         trace_commitment: &PolynomialBatch<F, C, D>,
         permutation_ctl_zs_commitment: &PolynomialBatch<F, C, D>,
         quotient_commitment: &PolynomialBatch<F, C, D>,
+        preprocessed_commitment: Option<&PolynomialBatch<F, C, D>>,
         degree_bits: usize,
-        num_permutation_zs: usize,
+        num_permutation_lookup_zs: usize,
     ) -> Self {
         let eval_commitment = |z: F::Extension, c: &PolynomialBatch<F, C, D>| {
             c.polynomials
@@ -227,9 +375,15 @@ impl<F: RichField + Extendable<D>, const D: usize> StarkOpeningSet<F, D> {
             ctl_zs_last: eval_commitment_base(
                 F::primitive_root_of_unity(degree_bits).inverse(),
                 permutation_ctl_zs_commitment,
-            )[num_permutation_zs..]
+            )[num_permutation_lookup_zs..]
                 .to_vec(),
             quotient_polys: eval_commitment(zeta, quotient_commitment),
+            preprocessed_local_values: preprocessed_commitment
+                .map(|c| eval_commitment(zeta, c))
+                .unwrap_or_default(),
+            preprocessed_next_values: preprocessed_commitment
+                .map(|c| eval_commitment(zeta_next, c))
+                .unwrap_or_default(),
         }
     }
 
@@ -240,6 +394,7 @@ impl<F: RichField + Extendable<D>, const D: usize> StarkOpeningSet<F, D> {
                 .iter()
                 .chain(&self.permutation_ctl_zs)
                 .chain(&self.quotient_polys)
+                .chain(&self.preprocessed_local_values)
                 .copied()
                 .collect_vec(),
         };
@@ -248,6 +403,7 @@ impl<F: RichField + Extendable<D>, const D: usize> StarkOpeningSet<F, D> {
                 .next_values
                 .iter()
                 .chain(&self.permutation_ctl_zs_next)
+                .chain(&self.preprocessed_next_values)
                 .copied()
                 .collect_vec(),
         };
@@ -274,10 +430,11 @@ pub struct StarkOpeningSetTarget<const D: usize> {
     pub permutation_ctl_zs_next: Vec<ExtensionTarget<D>>,
     pub ctl_zs_last: Vec<Target>,
     pub quotient_polys: Vec<ExtensionTarget<D>>,
+    pub preprocessed_local_values: Vec<ExtensionTarget<D>>,
+    pub preprocessed_next_values: Vec<ExtensionTarget<D>>,
 }
 
 impl<const D: usize> StarkOpeningSetTarget<D> {
-    #[allow(unused)]
     pub(crate) fn to_fri_openings(&self, zero: Target) -> FriOpeningsTarget<D> {
         let zeta_batch = FriOpeningBatchTarget {
             values: self
@@ -285,6 +442,7 @@ impl<const D: usize> StarkOpeningSetTarget<D> {
                 .iter()
                 .chain(&self.permutation_ctl_zs)
                 .chain(&self.quotient_polys)
+                .chain(&self.preprocessed_local_values)
                 .copied()
                 .collect_vec(),
         };
@@ -293,6 +451,7 @@ impl<const D: usize> StarkOpeningSetTarget<D> {
                 .next_values
                 .iter()
                 .chain(&self.permutation_ctl_zs_next)
+                .chain(&self.preprocessed_next_values)
                 .copied()
                 .collect_vec(),
         };
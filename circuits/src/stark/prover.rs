@@ -20,27 +20,36 @@ use plonky2_util::{log2_ceil, log2_strict};
 
 use super::all_stark::{AllStark, Table, NUM_TABLES};
 use crate::builtins::bitwise::bitwise_stark::BitwiseStark;
-use crate::builtins::cmp::cmp_stark::CmpStark;
+use crate::builtins::ecdsa::ecdsa_stark::EcdsaStark;
+use crate::builtins::keccak::keccak_stark::KeccakStark;
+use crate::builtins::keccak_sponge::keccak_sponge_stark::KeccakSpongeStark;
+use crate::builtins::poseidon::poseidon_stark::PoseidonStark;
 use crate::builtins::rangecheck::rangecheck_stark::RangeCheckStark;
 //use crate::columns::NUM_CPU_COLS;
 use super::config::StarkConfig;
 use super::constraint_consumer::ConstraintConsumer;
 use super::cross_table_lookup::{cross_table_lookup_data, CtlCheckVars, CtlData};
+use super::lookup::{compute_lookup_helper_columns, LookupCheckVars};
 use super::permutation::PermutationCheckVars;
 use super::permutation::{
-    compute_permutation_z_polys, get_n_grand_product_challenge_sets, GrandProductChallengeSet,
+    compute_permutation_z_polys, get_grand_product_challenge_set,
+    get_n_grand_product_challenge_sets, GrandProductChallengeSet,
 };
+use super::preprocessed::AllPreprocessed;
 use super::proof::{AllProof, PublicValues, StarkOpeningSet, StarkProof};
 use super::stark::Stark;
 use super::vanishing_poly::eval_vanishing_poly;
 use super::vars::StarkEvaluationVars;
 use crate::cpu::cpu_stark::CpuStark;
+use crate::fixed_table::bitwise_fixed::bitwise_fixed_stark::BitwiseFixedStark;
+use crate::fixed_table::rangecheck_fixed::rangecheck_fixed_stark::RangecheckFixedStark;
 use crate::generation::generate_traces;
 use crate::memory::memory_stark::MemoryStark;
+use crate::program::program_stark::ProgramStark;
 
 /// Generate traces, then create all STARK proofs.
 pub fn prove<F, C, const D: usize>(
-    program: &Program,
+    program: Program,
     all_stark: &mut AllStark<F, D>,
     config: &StarkConfig,
     timing: &mut TimingTree,
@@ -52,14 +61,21 @@ where
     [(); CpuStark::<F, D>::COLUMNS]:,
     [(); MemoryStark::<F, D>::COLUMNS]:,
     [(); BitwiseStark::<F, D>::COLUMNS]:,
-    [(); CmpStark::<F, D>::COLUMNS]:,
     [(); RangeCheckStark::<F, D>::COLUMNS]:,
+    [(); BitwiseFixedStark::<F, D>::COLUMNS]:,
+    [(); ProgramStark::<F, D>::COLUMNS]:,
+    [(); RangecheckFixedStark::<F, D>::COLUMNS]:,
+    [(); PoseidonStark::<F, D>::COLUMNS]:,
+    [(); EcdsaStark::<F, D>::COLUMNS]:,
+    [(); KeccakStark::<F, D>::COLUMNS]:,
+    [(); KeccakSpongeStark::<F, D>::COLUMNS]:,
 {
-    let (traces, public_values) = generate_traces(program, all_stark);
-    prove_with_traces(all_stark, config, traces, public_values, timing)
+    let (traces, public_values) = generate_traces(program, all_stark)?;
+    prove_with_traces::<F, C, D>(all_stark, config, traces, public_values, timing)
 }
 
-/// Compute all STARK proofs.
+/// Compute all STARK proofs, committing to the preprocessed columns first. Use
+/// `prove_with_preprocessed` to share these commitments between proofs.
 pub fn prove_with_traces<F, C, const D: usize>(
     all_stark: &AllStark<F, D>,
     config: &StarkConfig,
@@ -74,23 +90,68 @@ where
     [(); CpuStark::<F, D>::COLUMNS]:,
     [(); MemoryStark::<F, D>::COLUMNS]:,
     [(); BitwiseStark::<F, D>::COLUMNS]:,
-    [(); CmpStark::<F, D>::COLUMNS]:,
     [(); RangeCheckStark::<F, D>::COLUMNS]:,
+    [(); BitwiseFixedStark::<F, D>::COLUMNS]:,
+    [(); ProgramStark::<F, D>::COLUMNS]:,
+    [(); RangecheckFixedStark::<F, D>::COLUMNS]:,
+    [(); PoseidonStark::<F, D>::COLUMNS]:,
+    [(); EcdsaStark::<F, D>::COLUMNS]:,
+    [(); KeccakStark::<F, D>::COLUMNS]:,
+    [(); KeccakSpongeStark::<F, D>::COLUMNS]:,
+{
+    let preprocessed = AllPreprocessed::new(all_stark, config, timing);
+    prove_with_preprocessed(
+        all_stark,
+        &preprocessed,
+        config,
+        trace_poly_values,
+        public_values,
+        timing,
+    )
+}
+
+/// Compute all STARK proofs. The traces hold all columns, the preprocessed
+/// ones are only used to compute the lookups and aren't committed again.
+pub fn prove_with_preprocessed<F, C, const D: usize>(
+    all_stark: &AllStark<F, D>,
+    preprocessed: &AllPreprocessed<F, C, D>,
+    config: &StarkConfig,
+    trace_poly_values: [Vec<PolynomialValues<F>>; NUM_TABLES],
+    public_values: PublicValues,
+    timing: &mut TimingTree,
+) -> Result<AllProof<F, C, D>>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    [(); C::Hasher::HASH_SIZE]:,
+    [(); CpuStark::<F, D>::COLUMNS]:,
+    [(); MemoryStark::<F, D>::COLUMNS]:,
+    [(); BitwiseStark::<F, D>::COLUMNS]:,
+    [(); RangeCheckStark::<F, D>::COLUMNS]:,
+    [(); BitwiseFixedStark::<F, D>::COLUMNS]:,
+    [(); ProgramStark::<F, D>::COLUMNS]:,
+    [(); RangecheckFixedStark::<F, D>::COLUMNS]:,
+    [(); PoseidonStark::<F, D>::COLUMNS]:,
+    [(); EcdsaStark::<F, D>::COLUMNS]:,
+    [(); KeccakStark::<F, D>::COLUMNS]:,
+    [(); KeccakSpongeStark::<F, D>::COLUMNS]:,
 {
     let rate_bits = config.fri_config.rate_bits;
     let cap_height = config.fri_config.cap_height;
+    let nums_preprocessed_columns = all_stark.nums_preprocessed_columns();
 
     let trace_commitments = timed!(
         timing,
         "compute trace commitments",
         trace_poly_values
             .iter()
-            .map(|trace| {
+            .zip(nums_preprocessed_columns)
+            .map(|(trace, num_preprocessed_columns)| {
                 PolynomialBatch::<F, C, D>::from_values(
                     // TODO: Cloning this isn't great; consider having `from_values` accept a
                     // reference, or having `compute_permutation_z_polys` read
                     // trace values from the `PolynomialBatch`.
-                    trace.clone(),
+                    trace[num_preprocessed_columns..].to_vec(),
                     rate_bits,
                     false,
                     cap_height,
@@ -110,6 +171,8 @@ where
         challenger.observe_cap(cap);
     }
 
+    challenger.observe_elements(&public_values.to_field_elements());
+
     let ctl_data_per_table = cross_table_lookup_data::<F, C, D>(
         config,
         &trace_poly_values,
@@ -122,6 +185,7 @@ where
         config,
         &trace_poly_values[Table::Cpu as usize],
         &trace_commitments[Table::Cpu as usize],
+        preprocessed.commitments[Table::Cpu as usize].as_ref(),
         &ctl_data_per_table[Table::Cpu as usize],
         &mut challenger,
         timing,
@@ -131,6 +195,7 @@ where
         config,
         &trace_poly_values[Table::Memory as usize],
         &trace_commitments[Table::Memory as usize],
+        preprocessed.commitments[Table::Memory as usize].as_ref(),
         &ctl_data_per_table[Table::Memory as usize],
         &mut challenger,
         timing,
@@ -141,6 +206,7 @@ where
         config,
         &trace_poly_values[Table::Bitwise as usize],
         &trace_commitments[Table::Bitwise as usize],
+        preprocessed.commitments[Table::Bitwise as usize].as_ref(),
         &ctl_data_per_table[Table::Bitwise as usize],
         &mut challenger,
         timing,
@@ -150,6 +216,7 @@ where
         config,
         &trace_poly_values[Table::Cmp as usize],
         &trace_commitments[Table::Cmp as usize],
+        preprocessed.commitments[Table::Cmp as usize].as_ref(),
         &ctl_data_per_table[Table::Cmp as usize],
         &mut challenger,
         timing,
@@ -159,10 +226,92 @@ where
         config,
         &trace_poly_values[Table::RangeCheck as usize],
         &trace_commitments[Table::RangeCheck as usize],
+        preprocessed.commitments[Table::RangeCheck as usize].as_ref(),
         &ctl_data_per_table[Table::RangeCheck as usize],
         &mut challenger,
         timing,
     )?;
+    let program_proof = prove_single_table(
+        &all_stark.program_stark,
+        config,
+        &trace_poly_values[Table::Program as usize],
+        &trace_commitments[Table::Program as usize],
+        preprocessed.commitments[Table::Program as usize].as_ref(),
+        &ctl_data_per_table[Table::Program as usize],
+        &mut challenger,
+        timing,
+    )?;
+
+    let bitwise_fixed_proof = prove_single_table(
+        &all_stark.bitwise_fixed_stark,
+        config,
+        &trace_poly_values[Table::BitwiseFixed as usize],
+        &trace_commitments[Table::BitwiseFixed as usize],
+        preprocessed.commitments[Table::BitwiseFixed as usize].as_ref(),
+        &ctl_data_per_table[Table::BitwiseFixed as usize],
+        &mut challenger,
+        timing,
+    )?;
+    let rangecheck_fixed_proof = prove_single_table(
+        &all_stark.rangecheck_fixed_stark,
+        config,
+        &trace_poly_values[Table::RangecheckFixed as usize],
+        &trace_commitments[Table::RangecheckFixed as usize],
+        preprocessed.commitments[Table::RangecheckFixed as usize].as_ref(),
+        &ctl_data_per_table[Table::RangecheckFixed as usize],
+        &mut challenger,
+        timing,
+    )?;
+    let final_program_proof = prove_single_table(
+        &all_stark.final_program_stark,
+        config,
+        &trace_poly_values[Table::FinalProgram as usize],
+        &trace_commitments[Table::FinalProgram as usize],
+        preprocessed.commitments[Table::FinalProgram as usize].as_ref(),
+        &ctl_data_per_table[Table::FinalProgram as usize],
+        &mut challenger,
+        timing,
+    )?;
+    let poseidon_proof = prove_single_table(
+        &all_stark.poseidon_stark,
+        config,
+        &trace_poly_values[Table::Poseidon as usize],
+        &trace_commitments[Table::Poseidon as usize],
+        preprocessed.commitments[Table::Poseidon as usize].as_ref(),
+        &ctl_data_per_table[Table::Poseidon as usize],
+        &mut challenger,
+        timing,
+    )?;
+    let ecdsa_proof = prove_single_table(
+        &all_stark.ecdsa_stark,
+        config,
+        &trace_poly_values[Table::Ecdsa as usize],
+        &trace_commitments[Table::Ecdsa as usize],
+        preprocessed.commitments[Table::Ecdsa as usize].as_ref(),
+        &ctl_data_per_table[Table::Ecdsa as usize],
+        &mut challenger,
+        timing,
+    )?;
+    let keccak_proof = prove_single_table(
+        &all_stark.keccak_stark,
+        config,
+        &trace_poly_values[Table::Keccak as usize],
+        &trace_commitments[Table::Keccak as usize],
+        preprocessed.commitments[Table::Keccak as usize].as_ref(),
+        &ctl_data_per_table[Table::Keccak as usize],
+        &mut challenger,
+        timing,
+    )?;
+    let keccak_sponge_proof = prove_single_table(
+        &all_stark.keccak_sponge_stark,
+        config,
+        &trace_poly_values[Table::KeccakSponge as usize],
+        &trace_commitments[Table::KeccakSponge as usize],
+        preprocessed.commitments[Table::KeccakSponge as usize].as_ref(),
+        &ctl_data_per_table[Table::KeccakSponge as usize],
+        &mut challenger,
+        timing,
+    )?;
 
     let stark_proofs = [
         cpu_proof,
@@ -170,29 +319,30 @@ where
         bitwise_proof,
         cmp_proof,
         rangecheck_proof,
-    ];
-
-    let compress_challenges = [
-        all_stark.cpu_stark.get_compress_challenge().unwrap(),
-        F::ZERO,
-        all_stark.bitwise_stark.get_compress_challenge().unwrap(),
-        F::ZERO,
-        F::ZERO,
+        program_proof,
+        bitwise_fixed_proof,
+        rangecheck_fixed_proof,
+        final_program_proof,
+        poseidon_proof,
+        ecdsa_proof,
+        keccak_proof,
+        keccak_sponge_proof,
     ];
 
     Ok(AllProof {
         stark_proofs,
-        compress_challenges,
         public_values,
     })
 }
 
 /// Compute proof for a single STARK table.
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn prove_single_table<F, C, S, const D: usize>(
     stark: &S,
     config: &StarkConfig,
     trace_poly_values: &[PolynomialValues<F>],
     trace_commitment: &PolynomialBatch<F, C, D>,
+    preprocessed_commitment: Option<&PolynomialBatch<F, C, D>>,
     ctl_data: &CtlData<F>,
     challenger: &mut Challenger<F, C::Hasher>,
     timing: &mut TimingTree,
@@ -233,13 +383,25 @@ where
     });
     let num_permutation_zs = permutation_zs.as_ref().map(|v| v.len()).unwrap_or(0);
 
-    let z_polys = match permutation_zs {
-        None => ctl_data.z_polys(),
-        Some(mut permutation_zs) => {
-            permutation_zs.extend(ctl_data.z_polys());
-            permutation_zs
-        }
-    };
+    // Lookup arguments.
+    let lookup_challenges = stark
+        .uses_lookups()
+        .then(|| get_grand_product_challenge_set(challenger, config.num_challenges));
+    let lookup_helper_columns = lookup_challenges.as_ref().map(|challenges| {
+        timed!(
+            timing,
+            "compute lookup helper columns",
+            compute_lookup_helper_columns::<F, S, D>(stark, trace_poly_values, challenges)
+        )
+    });
+    let num_lookup_columns = lookup_helper_columns.as_ref().map(|v| v.len()).unwrap_or(0);
+
+    let z_polys = permutation_zs
+        .into_iter()
+        .chain(lookup_helper_columns)
+        .flatten()
+        .chain(ctl_data.z_polys())
+        .collect::<Vec<_>>();
     assert!(!z_polys.is_empty(), "No CTL?");
 
     let permutation_ctl_zs_commitment = timed!(
@@ -278,12 +440,15 @@ where
         compute_quotient_polys::<F, <F as Packable>::Packing, C, S, D>(
             stark,
             trace_commitment,
+            preprocessed_commitment,
             &permutation_ctl_zs_commitment,
             permutation_challenges.as_ref(),
+            lookup_challenges.as_ref(),
             ctl_data,
             alphas,
             degree_bits,
             num_permutation_zs,
+            num_lookup_columns,
             config,
         )
     );
@@ -335,16 +500,20 @@ where
         trace_commitment,
         &permutation_ctl_zs_commitment,
         &quotient_commitment,
+        preprocessed_commitment,
         degree_bits,
-        stark.num_permutation_batches(config),
+        num_permutation_zs + num_lookup_columns,
     );
     challenger.observe_openings(&openings.to_fri_openings());
 
-    let initial_merkle_trees = vec![
+    let initial_merkle_trees = [
         trace_commitment,
         &permutation_ctl_zs_commitment,
         &quotient_commitment,
-    ];
+    ]
+    .into_iter()
+    .chain(preprocessed_commitment)
+    .collect::<Vec<_>>();
 
     let opening_proof = timed!(
         timing,
@@ -372,12 +541,15 @@ where
 fn compute_quotient_polys<'a, F, P, C, S, const D: usize>(
     stark: &S,
     trace_commitment: &'a PolynomialBatch<F, C, D>,
+    preprocessed_commitment: Option<&'a PolynomialBatch<F, C, D>>,
     permutation_ctl_zs_commitment: &'a PolynomialBatch<F, C, D>,
     permutation_challenges: Option<&'a Vec<GrandProductChallengeSet<F>>>,
+    lookup_challenges: Option<&'a GrandProductChallengeSet<F>>,
     ctl_data: &CtlData<F>,
     alphas: Vec<F>,
     degree_bits: usize,
     num_permutation_zs: usize,
+    num_lookup_columns: usize,
     config: &StarkConfig,
 ) -> Vec<PolynomialCoeffs<F>>
 where
@@ -408,10 +580,14 @@ where
 
     let z_h_on_coset = ZeroPolyOnCoset::<F>::new(degree_bits, quotient_degree_bits);
 
-    // Retrieve the LDE values at index `i`.
+    // Retrieve the LDE values at index `i`, preprocessed columns first.
     let get_trace_values_packed = |i_start| -> [P; S::COLUMNS] {
-        trace_commitment
-            .get_lde_values_packed(i_start, step)
+        preprocessed_commitment
+            .map(|c| c.get_lde_values_packed(i_start, step))
+            .unwrap_or_default()
+            .into_iter()
+            .chain(trace_commitment.get_lde_values_packed(i_start, step))
+            .collect::<Vec<_>>()
             .try_into()
             .unwrap()
     };
@@ -459,18 +635,29 @@ where
                         .to_vec(),
                     permutation_challenge_sets: permutation_challenge_sets.to_vec(),
                 });
+            let lookup_zs = num_permutation_zs..num_permutation_zs + num_lookup_columns;
+            let lookup_check_vars = lookup_challenges.map(|challenges| LookupCheckVars {
+                local_values: permutation_ctl_zs_commitment.get_lde_values_packed(i_start, step)
+                    [lookup_zs.clone()]
+                .to_vec(),
+                next_values: permutation_ctl_zs_commitment
+                    .get_lde_values_packed(i_next_start, step)[lookup_zs.clone()]
+                .to_vec(),
+                challenges: challenges.clone(),
+            });
             let ctl_vars = ctl_data
                 .zs_columns
                 .iter()
                 .enumerate()
                 .map(|(i, zs_columns)| CtlCheckVars::<F, F, P, 1> {
                     local_z: permutation_ctl_zs_commitment.get_lde_values_packed(i_start, step)
-                        [num_permutation_zs + i],
+                        [lookup_zs.end + i],
                     next_z: permutation_ctl_zs_commitment.get_lde_values_packed(i_next_start, step)
-                        [num_permutation_zs + i],
+                        [lookup_zs.end + i],
                     challenges: zs_columns.challenge,
                     columns: &zs_columns.columns,
                     filter_column: &zs_columns.filter_column,
+                    logup: zs_columns.logup,
                 })
                 .collect::<Vec<_>>();
             eval_vanishing_poly::<F, F, P, C, S, D, 1>(
@@ -478,6 +665,7 @@ where
                 config,
                 vars,
                 permutation_check_vars,
+                lookup_check_vars,
                 &ctl_vars,
                 &mut consumer,
             );
@@ -512,12 +700,15 @@ where
 fn check_constraints<'a, F, C, S, const D: usize>(
     stark: &S,
     trace_commitment: &'a PolynomialBatch<F, C, D>,
+    preprocessed_commitment: Option<&'a PolynomialBatch<F, C, D>>,
     permutation_ctl_zs_commitment: &'a PolynomialBatch<F, C, D>,
     permutation_challenges: Option<&'a Vec<GrandProductChallengeSet<F>>>,
+    lookup_challenges: Option<&'a GrandProductChallengeSet<F>>,
     ctl_data: &CtlData<F>,
     alphas: Vec<F>,
     degree_bits: usize,
     num_permutation_zs: usize,
+    num_lookup_columns: usize,
     config: &StarkConfig,
 ) where
     F: RichField + Extendable<D>,
@@ -549,6 +740,14 @@ fn check_constraints<'a, F, C, S, const D: usize>(
     };
 
     let trace_subgroup_evals = get_subgroup_evals(trace_commitment);
+    let trace_subgroup_evals = match preprocessed_commitment {
+        Some(c) => get_subgroup_evals(c)
+            .into_iter()
+            .zip(trace_subgroup_evals)
+            .map(|(preprocessed, trace)| [preprocessed, trace].concat())
+            .collect(),
+        None => trace_subgroup_evals,
+    };
     let permutation_ctl_zs_subgroup_evals = get_subgroup_evals(permutation_ctl_zs_commitment);
 
     // Last element of the subgroup.
@@ -580,17 +779,24 @@ fn check_constraints<'a, F, C, S, const D: usize>(
                         .to_vec(),
                     permutation_challenge_sets: permutation_challenge_sets.to_vec(),
                 });
+            let lookup_zs = num_permutation_zs..num_permutation_zs + num_lookup_columns;
+            let lookup_check_vars = lookup_challenges.map(|challenges| LookupCheckVars {
+                local_values: permutation_ctl_zs_subgroup_evals[i][lookup_zs.clone()].to_vec(),
+                next_values: permutation_ctl_zs_subgroup_evals[i_next][lookup_zs.clone()].to_vec(),
+                challenges: challenges.clone(),
+            });
 
             let ctl_vars = ctl_data
                 .zs_columns
                 .iter()
                 .enumerate()
                 .map(|(iii, zs_columns)| CtlCheckVars::<F, F, F, 1> {
-                    local_z: permutation_ctl_zs_subgroup_evals[i][num_permutation_zs + iii],
-                    next_z: permutation_ctl_zs_subgroup_evals[i_next][num_permutation_zs + iii],
+                    local_z: permutation_ctl_zs_subgroup_evals[i][lookup_zs.end + iii],
+                    next_z: permutation_ctl_zs_subgroup_evals[i_next][lookup_zs.end + iii],
                     challenges: zs_columns.challenge,
                     columns: &zs_columns.columns,
                     filter_column: &zs_columns.filter_column,
+                    logup: zs_columns.logup,
                 })
                 .collect::<Vec<_>>();
             eval_vanishing_poly::<F, F, F, C, S, D, 1>(
@@ -598,6 +804,7 @@ fn check_constraints<'a, F, C, S, const D: usize>(
                 config,
                 vars,
                 permutation_check_vars,
+                lookup_check_vars,
                 &ctl_vars,
                 &mut consumer,
             );
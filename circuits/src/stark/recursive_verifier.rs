@@ -0,0 +1,727 @@
+use anyhow::{ensure, Result};
+use itertools::Itertools;
+use plonky2::field::extension::Extendable;
+use plonky2::field::types::Field;
+use plonky2::fri::witness_util::set_fri_proof_target;
+use plonky2::hash::hash_types::RichField;
+use plonky2::iop::ext_target::ExtensionTarget;
+use plonky2::iop::target::BoolTarget;
+use plonky2::iop::witness::{PartialWitness, Witness};
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2::plonk::circuit_data::{CircuitConfig, CircuitData};
+use plonky2::plonk::config::{AlgebraicHasher, GenericConfig, Hasher};
+use plonky2::plonk::proof::ProofWithPublicInputs;
+use plonky2::util::reducing::ReducingFactorTarget;
+use plonky2::with_context;
+
+use super::all_stark::{AllStark, Table, NUM_TABLES};
+use super::config::StarkConfig;
+use super::constraint_consumer::RecursiveConstraintConsumer;
+use super::cross_table_lookup::{
+    verify_cross_table_lookups_circuit, CrossTableLookup, CtlCheckVarsTarget,
+};
+use super::lookup::LookupCheckVarsTarget;
+use super::permutation::PermutationCheckDataTarget;
+use super::preprocessed::{AllVerifierKey, PreprocessedCap};
+use super::proof::{
+    AllProof, AllProofChallengesTarget, AllProofTarget, PublicValues, PublicValuesTarget,
+    StarkOpeningSet, StarkOpeningSetTarget, StarkProof, StarkProofChallengesTarget,
+    StarkProofTarget,
+};
+use super::stark::Stark;
+use super::vanishing_poly::eval_vanishing_poly_circuit;
+use super::vars::StarkEvaluationTargets;
+use crate::builtins::bitwise::bitwise_stark::BitwiseStark;
+use crate::builtins::ecdsa::ecdsa_stark::EcdsaStark;
+use crate::builtins::keccak::keccak_stark::KeccakStark;
+use crate::builtins::keccak_sponge::keccak_sponge_stark::KeccakSpongeStark;
+use crate::builtins::poseidon::poseidon_stark::PoseidonStark;
+use crate::builtins::rangecheck::rangecheck_stark::RangeCheckStark;
+use crate::cpu::cpu_stark::CpuStark;
+use crate::fixed_table::bitwise_fixed::bitwise_fixed_stark::BitwiseFixedStark;
+use crate::fixed_table::rangecheck_fixed::rangecheck_fixed_stark::RangecheckFixedStark;
+use crate::memory::memory_stark::MemoryStark;
+use crate::program::program_stark::ProgramStark;
+
+/// A circuit verifying an `AllProof` with the given trace lengths, its public
+/// inputs are the proof public values.
+pub struct AllProofRecursionCircuit<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+> {
+    pub circuit: CircuitData<F, C, D>,
+    all_proof_target: AllProofTarget<D>,
+    inner_config: StarkConfig,
+    degree_bits: [usize; NUM_TABLES],
+}
+
+impl<F, C, const D: usize> AllProofRecursionCircuit<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    C::Hasher: AlgebraicHasher<F>,
+    [(); C::Hasher::HASH_SIZE]:,
+{
+    pub fn new(
+        all_stark: &AllStark<F, D>,
+        verifier_key: &AllVerifierKey<F, C, D>,
+        inner_config: &StarkConfig,
+        degree_bits: &[usize; NUM_TABLES],
+        circuit_config: CircuitConfig,
+    ) -> Self
+    where
+        [(); CpuStark::<F, D>::COLUMNS]:,
+        [(); MemoryStark::<F, D>::COLUMNS]:,
+        [(); BitwiseStark::<F, D>::COLUMNS]:,
+        [(); RangeCheckStark::<F, D>::COLUMNS]:,
+        [(); BitwiseFixedStark::<F, D>::COLUMNS]:,
+        [(); ProgramStark::<F, D>::COLUMNS]:,
+        [(); RangecheckFixedStark::<F, D>::COLUMNS]:,
+        [(); PoseidonStark::<F, D>::COLUMNS]:,
+        [(); EcdsaStark::<F, D>::COLUMNS]:,
+        [(); KeccakStark::<F, D>::COLUMNS]:,
+        [(); KeccakSpongeStark::<F, D>::COLUMNS]:,
+    {
+        let mut builder = CircuitBuilder::<F, D>::new(circuit_config);
+        let all_proof_target =
+            add_virtual_all_proof::<F, C, D>(&mut builder, all_stark, inner_config, degree_bits);
+        builder.register_public_inputs(&all_proof_target.public_values.to_targets());
+        verify_proof_circuit::<F, C, D>(
+            &mut builder,
+            all_stark.clone(),
+            verifier_key,
+            &all_proof_target,
+            inner_config,
+        );
+        Self {
+            circuit: builder.build::<C>(),
+            all_proof_target,
+            inner_config: inner_config.clone(),
+            degree_bits: *degree_bits,
+        }
+    }
+
+    pub fn prove(&self, all_proof: &AllProof<F, C, D>) -> Result<ProofWithPublicInputs<F, C, D>> {
+        let degree_bits = all_proof.degree_bits(&self.inner_config);
+        ensure!(
+            degree_bits == self.degree_bits,
+            "Proof degree bits {:?} don't match the circuit degree bits {:?}",
+            degree_bits,
+            self.degree_bits
+        );
+        let mut pw = PartialWitness::new();
+        set_all_proof_target(&mut pw, &self.all_proof_target, all_proof);
+        self.circuit.prove(pw)
+    }
+
+    pub fn verify(&self, proof: ProofWithPublicInputs<F, C, D>) -> Result<()> {
+        self.circuit.verify(proof)
+    }
+}
+
+/// Recursively verify an `AllProof`. The circuit is built for the trace
+/// lengths of `all_proof_target`, see `add_virtual_all_proof`.
+pub fn verify_proof_circuit<F, C, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    all_stark: AllStark<F, D>,
+    verifier_key: &AllVerifierKey<F, C, D>,
+    all_proof_target: &AllProofTarget<D>,
+    inner_config: &StarkConfig,
+) where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    C::Hasher: AlgebraicHasher<F>,
+    [(); CpuStark::<F, D>::COLUMNS]:,
+    [(); MemoryStark::<F, D>::COLUMNS]:,
+    [(); BitwiseStark::<F, D>::COLUMNS]:,
+    [(); RangeCheckStark::<F, D>::COLUMNS]:,
+    [(); BitwiseFixedStark::<F, D>::COLUMNS]:,
+    [(); ProgramStark::<F, D>::COLUMNS]:,
+    [(); RangecheckFixedStark::<F, D>::COLUMNS]:,
+    [(); PoseidonStark::<F, D>::COLUMNS]:,
+    [(); EcdsaStark::<F, D>::COLUMNS]:,
+    [(); KeccakStark::<F, D>::COLUMNS]:,
+    [(); KeccakSpongeStark::<F, D>::COLUMNS]:,
+{
+    let AllProofTarget {
+        stark_proofs,
+        public_values,
+    } = all_proof_target;
+
+    builder.assert_bool(BoolTarget::new_unsafe(public_values.is_final));
+
+    let AllProofChallengesTarget {
+        stark_challenges,
+        ctl_challenges,
+    } = all_proof_target.get_challenges::<F, C>(builder, &all_stark, inner_config);
+
+    let nums_permutation_zs = all_stark.nums_permutation_zs(inner_config);
+    let nums_lookup_zs = all_stark.nums_lookup_zs(inner_config);
+
+    let AllStark {
+        mut cpu_stark,
+        memory_stark,
+        bitwise_stark,
+        cmp_stark,
+        rangecheck_stark,
+        mut program_stark,
+        bitwise_fixed_stark,
+        rangecheck_fixed_stark,
+        mut final_program_stark,
+        poseidon_stark,
+        ecdsa_stark,
+        keccak_stark,
+        keccak_sponge_stark,
+        cross_table_lookups,
+    } = all_stark;
+
+    // Boundary state always comes from the proof public values.
+    cpu_stark.set_public_value_targets(public_values.cpu_public_values());
+    program_stark.set_program_hash_target(public_values.program_hash);
+    final_program_stark.set_program_hash_target(public_values.final_program_hash);
+
+    let ctl_vars_per_table: [Vec<CtlCheckVarsTarget<F, D>>; NUM_TABLES] =
+        std::array::from_fn(|i| {
+            CtlCheckVarsTarget::from_proof(
+                TABLES[i],
+                &stark_proofs[i],
+                &cross_table_lookups,
+                &ctl_challenges,
+                nums_permutation_zs[i] + nums_lookup_zs[i],
+            )
+        });
+
+    macro_rules! verify_table {
+        ($stark:expr, $table:expr) => {
+            with_context!(
+                builder,
+                &format!("verify {:?} proof", $table),
+                verify_stark_proof_with_challenges_circuit::<F, C, _, D>(
+                    builder,
+                    $stark,
+                    &stark_proofs[$table as usize],
+                    verifier_key.preprocessed_caps[$table as usize].as_ref(),
+                    &stark_challenges[$table as usize],
+                    &ctl_vars_per_table[$table as usize],
+                    inner_config,
+                )
+            )
+        };
+    }
+    verify_table!(cpu_stark, Table::Cpu);
+    verify_table!(memory_stark, Table::Memory);
+    verify_table!(bitwise_stark, Table::Bitwise);
+    verify_table!(cmp_stark, Table::Cmp);
+    verify_table!(rangecheck_stark, Table::RangeCheck);
+    verify_table!(program_stark, Table::Program);
+    verify_table!(bitwise_fixed_stark, Table::BitwiseFixed);
+    verify_table!(rangecheck_fixed_stark, Table::RangecheckFixed);
+    verify_table!(final_program_stark, Table::FinalProgram);
+    verify_table!(poseidon_stark, Table::Poseidon);
+    verify_table!(ecdsa_stark, Table::Ecdsa);
+    verify_table!(keccak_stark, Table::Keccak);
+    verify_table!(keccak_sponge_stark, Table::KeccakSponge);
+
+    let degrees_bits = std::array::from_fn(|i| stark_proofs[i].recover_degree_bits(inner_config));
+    verify_cross_table_lookups_circuit::<F, C, D>(
+        builder,
+        cross_table_lookups,
+        std::array::from_fn(|i| stark_proofs[i].openings.ctl_zs_last.clone()),
+        degrees_bits,
+        ctl_challenges,
+        inner_config,
+    );
+}
+
+const TABLES: [Table; NUM_TABLES] = [
+    Table::Cpu,
+    Table::Memory,
+    Table::Bitwise,
+    Table::Cmp,
+    Table::RangeCheck,
+    Table::Program,
+    Table::BitwiseFixed,
+    Table::RangecheckFixed,
+    Table::FinalProgram,
+    Table::Poseidon,
+    Table::Ecdsa,
+    Table::Keccak,
+    Table::KeccakSponge,
+];
+
+/// Recursively verifies an inner proof.
+fn verify_stark_proof_with_challenges_circuit<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    S: Stark<F, D>,
+    const D: usize,
+>(
+    builder: &mut CircuitBuilder<F, D>,
+    stark: S,
+    proof: &StarkProofTarget<D>,
+    preprocessed_cap: Option<&PreprocessedCap<F, C, D>>,
+    challenges: &StarkProofChallengesTarget<D>,
+    ctl_vars: &[CtlCheckVarsTarget<F, D>],
+    inner_config: &StarkConfig,
+) where
+    C::Hasher: AlgebraicHasher<F>,
+    [(); S::COLUMNS]:,
+{
+    let zero = builder.zero();
+    let one = builder.one_extension();
+
+    let StarkOpeningSetTarget {
+        local_values,
+        next_values,
+        permutation_ctl_zs,
+        permutation_ctl_zs_next,
+        ctl_zs_last,
+        quotient_polys,
+        preprocessed_local_values,
+        preprocessed_next_values,
+    } = &proof.openings;
+    // The preprocessed columns are the leading columns of the table.
+    let vars = StarkEvaluationTargets {
+        local_values: &[preprocessed_local_values.as_slice(), local_values]
+            .concat()
+            .try_into()
+            .unwrap(),
+        next_values: &[preprocessed_next_values.as_slice(), next_values]
+            .concat()
+            .try_into()
+            .unwrap(),
+    };
+
+    let degree_bits = proof.recover_degree_bits(inner_config);
+    if let Some(preprocessed_cap) = preprocessed_cap {
+        assert_eq!(
+            degree_bits, preprocessed_cap.degree_bits,
+            "Trace length mismatch with the preprocessed columns."
+        );
+    }
+    let zeta_pow_deg = builder.exp_power_of_2_extension(challenges.stark_zeta, degree_bits);
+    let z_h_zeta = builder.sub_extension(zeta_pow_deg, one);
+    let (l_0, l_last) =
+        eval_l_0_and_l_last_circuit(builder, degree_bits, challenges.stark_zeta, z_h_zeta);
+    let last =
+        builder.constant_extension(F::Extension::primitive_root_of_unity(degree_bits).inverse());
+    let z_last = builder.sub_extension(challenges.stark_zeta, last);
+
+    let mut consumer = RecursiveConstraintConsumer::<F, D>::new(
+        builder.zero_extension(),
+        challenges.stark_alphas.clone(),
+        z_last,
+        l_0,
+        l_last,
+    );
+
+    let num_permutation_zs = stark.num_permutation_batches(inner_config);
+    let permutation_data = stark
+        .uses_permutation_args()
+        .then(|| PermutationCheckDataTarget {
+            local_zs: permutation_ctl_zs[..num_permutation_zs].to_vec(),
+            next_zs: permutation_ctl_zs_next[..num_permutation_zs].to_vec(),
+            permutation_challenge_sets: challenges.permutation_challenge_sets.clone().unwrap(),
+        });
+    let lookup_zs =
+        num_permutation_zs..num_permutation_zs + stark.num_lookup_helper_columns(inner_config);
+    let lookup_data = stark.uses_lookups().then(|| LookupCheckVarsTarget {
+        local_values: permutation_ctl_zs[lookup_zs.clone()].to_vec(),
+        next_values: permutation_ctl_zs_next[lookup_zs].to_vec(),
+        challenges: challenges.lookup_challenge_set.clone().unwrap(),
+    });
+
+    with_context!(
+        builder,
+        "evaluate vanishing polynomial",
+        eval_vanishing_poly_circuit::<F, C, S, D>(
+            builder,
+            &stark,
+            inner_config,
+            vars,
+            permutation_data,
+            lookup_data,
+            ctl_vars,
+            &mut consumer,
+        )
+    );
+    let vanishing_polys_zeta = consumer.accumulators();
+
+    // Check each polynomial identity, of the form `vanishing(x) = Z_H(x)
+    // quotient(x)`, at zeta.
+    let mut scale = ReducingFactorTarget::new(zeta_pow_deg);
+    for (i, chunk) in quotient_polys
+        .chunks(stark.quotient_degree_factor())
+        .enumerate()
+    {
+        let recombined_quotient = scale.reduce(chunk, builder);
+        let computed_vanishing_poly = builder.mul_extension(z_h_zeta, recombined_quotient);
+        builder.connect_extension(vanishing_polys_zeta[i], computed_vanishing_poly);
+    }
+
+    let merkle_caps = [
+        proof.trace_cap.clone(),
+        proof.permutation_ctl_zs_cap.clone(),
+        proof.quotient_polys_cap.clone(),
+    ]
+    .into_iter()
+    .chain(preprocessed_cap.map(|p| builder.constant_merkle_cap(&p.cap)))
+    .collect::<Vec<_>>();
+
+    let fri_instance = stark.fri_instance_target(
+        builder,
+        challenges.stark_zeta,
+        F::primitive_root_of_unity(degree_bits),
+        degree_bits,
+        ctl_zs_last.len(),
+        inner_config,
+    );
+    builder.verify_fri_proof::<C>(
+        &fri_instance,
+        &proof.openings.to_fri_openings(zero),
+        &challenges.fri_challenges,
+        &merkle_caps,
+        &proof.opening_proof,
+        &inner_config.fri_params(degree_bits),
+    );
+}
+
+/// Evaluate the Lagrange polynomials `L_0` and `L_(n-1)` at a point `x`, with
+/// `z_x = x^n - 1`. See `verifier::eval_l_0_and_l_last`.
+fn eval_l_0_and_l_last_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    log_n: usize,
+    x: ExtensionTarget<D>,
+    z_x: ExtensionTarget<D>,
+) -> (ExtensionTarget<D>, ExtensionTarget<D>) {
+    let n = builder.constant_extension(F::Extension::from_canonical_usize(1 << log_n));
+    let g = builder.constant_extension(F::Extension::primitive_root_of_unity(log_n));
+    let one = builder.one_extension();
+    let l_0_deno = builder.mul_sub_extension(n, x, n);
+    let l_last_deno = builder.mul_sub_extension(g, x, one);
+    let l_last_deno = builder.mul_extension(n, l_last_deno);
+
+    (
+        builder.div_extension(z_x, l_0_deno),
+        builder.div_extension(z_x, l_last_deno),
+    )
+}
+
+/// Targets of an `AllProof` with the given trace lengths.
+pub fn add_virtual_all_proof<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    builder: &mut CircuitBuilder<F, D>,
+    all_stark: &AllStark<F, D>,
+    config: &StarkConfig,
+    degree_bits: &[usize; NUM_TABLES],
+) -> AllProofTarget<D> {
+    let nums_ctl_zs: [usize; NUM_TABLES] = std::array::from_fn(|i| {
+        CrossTableLookup::num_ctl_zs(
+            &all_stark.cross_table_lookups,
+            TABLES[i],
+            config.num_challenges,
+        )
+    });
+
+    macro_rules! add_virtual_table {
+        ($stark:expr, $table:expr) => {
+            add_virtual_stark_proof(
+                builder,
+                &$stark,
+                config,
+                degree_bits[$table as usize],
+                nums_ctl_zs[$table as usize],
+            )
+        };
+    }
+    let stark_proofs = [
+        add_virtual_table!(all_stark.cpu_stark, Table::Cpu),
+        add_virtual_table!(all_stark.memory_stark, Table::Memory),
+        add_virtual_table!(all_stark.bitwise_stark, Table::Bitwise),
+        add_virtual_table!(all_stark.cmp_stark, Table::Cmp),
+        add_virtual_table!(all_stark.rangecheck_stark, Table::RangeCheck),
+        add_virtual_table!(all_stark.program_stark, Table::Program),
+        add_virtual_table!(all_stark.bitwise_fixed_stark, Table::BitwiseFixed),
+        add_virtual_table!(all_stark.rangecheck_fixed_stark, Table::RangecheckFixed),
+        add_virtual_table!(all_stark.final_program_stark, Table::FinalProgram),
+        add_virtual_table!(all_stark.poseidon_stark, Table::Poseidon),
+        add_virtual_table!(all_stark.ecdsa_stark, Table::Ecdsa),
+        add_virtual_table!(all_stark.keccak_stark, Table::Keccak),
+        add_virtual_table!(all_stark.keccak_sponge_stark, Table::KeccakSponge),
+    ];
+
+    AllProofTarget {
+        stark_proofs,
+        public_values: add_virtual_public_values(builder),
+    }
+}
+
+pub fn add_virtual_public_values<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+) -> PublicValuesTarget {
+    PublicValuesTarget {
+        program_hash: builder.add_virtual_target_arr(),
+        initial_registers: builder.add_virtual_target_arr(),
+        final_registers: builder.add_virtual_target_arr(),
+        final_program_hash: builder.add_virtual_target_arr(),
+        initial_clk: builder.add_virtual_target(),
+        initial_pc: builder.add_virtual_target(),
+        final_clk: builder.add_virtual_target(),
+        final_pc: builder.add_virtual_target(),
+        is_final: builder.add_virtual_target(),
+    }
+}
+
+pub fn add_virtual_stark_proof<F: RichField + Extendable<D>, S: Stark<F, D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    stark: &S,
+    config: &StarkConfig,
+    degree_bits: usize,
+    num_ctl_zs: usize,
+) -> StarkProofTarget<D> {
+    let fri_params = config.fri_params(degree_bits);
+    let cap_height = fri_params.config.cap_height;
+
+    let num_zs = num_ctl_zs
+        + stark.num_permutation_batches(config)
+        + stark.num_lookup_helper_columns(config);
+    let num_leaves_per_oracle = [
+        S::COLUMNS - stark.num_preprocessed_columns(),
+        num_zs,
+        stark.num_quotient_polys(config),
+    ]
+    .into_iter()
+    .chain(
+        stark
+            .uses_preprocessed_columns()
+            .then(|| stark.num_preprocessed_columns()),
+    )
+    .collect::<Vec<_>>();
+
+    StarkProofTarget {
+        trace_cap: builder.add_virtual_cap(cap_height),
+        permutation_ctl_zs_cap: builder.add_virtual_cap(cap_height),
+        quotient_polys_cap: builder.add_virtual_cap(cap_height),
+        openings: add_virtual_stark_opening_set(builder, stark, num_ctl_zs, config),
+        opening_proof: builder.add_virtual_fri_proof(&num_leaves_per_oracle, &fri_params),
+    }
+}
+
+fn add_virtual_stark_opening_set<F: RichField + Extendable<D>, S: Stark<F, D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    stark: &S,
+    num_ctl_zs: usize,
+    config: &StarkConfig,
+) -> StarkOpeningSetTarget<D> {
+    let num_trace_columns = S::COLUMNS - stark.num_preprocessed_columns();
+    let num_zs = num_ctl_zs
+        + stark.num_permutation_batches(config)
+        + stark.num_lookup_helper_columns(config);
+    StarkOpeningSetTarget {
+        local_values: builder.add_virtual_extension_targets(num_trace_columns),
+        next_values: builder.add_virtual_extension_targets(num_trace_columns),
+        permutation_ctl_zs: builder.add_virtual_extension_targets(num_zs),
+        permutation_ctl_zs_next: builder.add_virtual_extension_targets(num_zs),
+        ctl_zs_last: builder.add_virtual_targets(num_ctl_zs),
+        quotient_polys: builder.add_virtual_extension_targets(stark.num_quotient_polys(config)),
+        preprocessed_local_values: builder
+            .add_virtual_extension_targets(stark.num_preprocessed_columns()),
+        preprocessed_next_values: builder
+            .add_virtual_extension_targets(stark.num_preprocessed_columns()),
+    }
+}
+
+pub fn set_all_proof_target<F, C: GenericConfig<D, F = F>, W, const D: usize>(
+    witness: &mut W,
+    all_proof_target: &AllProofTarget<D>,
+    all_proof: &AllProof<F, C, D>,
+) where
+    F: RichField + Extendable<D>,
+    C::Hasher: AlgebraicHasher<F>,
+    W: Witness<F>,
+{
+    for (pt, p) in all_proof_target
+        .stark_proofs
+        .iter()
+        .zip_eq(&all_proof.stark_proofs)
+    {
+        set_stark_proof_target(witness, pt, p);
+    }
+    set_public_value_targets(
+        witness,
+        &all_proof_target.public_values,
+        &all_proof.public_values,
+    );
+}
+
+pub fn set_stark_proof_target<F, C: GenericConfig<D, F = F>, W, const D: usize>(
+    witness: &mut W,
+    proof_target: &StarkProofTarget<D>,
+    proof: &StarkProof<F, C, D>,
+) where
+    F: RichField + Extendable<D>,
+    C::Hasher: AlgebraicHasher<F>,
+    W: Witness<F>,
+{
+    witness.set_cap_target(&proof_target.trace_cap, &proof.trace_cap);
+    witness.set_cap_target(
+        &proof_target.permutation_ctl_zs_cap,
+        &proof.permutation_ctl_zs_cap,
+    );
+    witness.set_cap_target(&proof_target.quotient_polys_cap, &proof.quotient_polys_cap);
+
+    let StarkOpeningSetTarget {
+        local_values,
+        next_values,
+        permutation_ctl_zs,
+        permutation_ctl_zs_next,
+        ctl_zs_last,
+        quotient_polys,
+        preprocessed_local_values,
+        preprocessed_next_values,
+    } = &proof_target.openings;
+    let openings: &StarkOpeningSet<F, D> = &proof.openings;
+    witness.set_extension_targets(local_values, &openings.local_values);
+    witness.set_extension_targets(next_values, &openings.next_values);
+    witness.set_extension_targets(permutation_ctl_zs, &openings.permutation_ctl_zs);
+    witness.set_extension_targets(permutation_ctl_zs_next, &openings.permutation_ctl_zs_next);
+    for (&t, &x) in ctl_zs_last.iter().zip_eq(&openings.ctl_zs_last) {
+        witness.set_target(t, x);
+    }
+    witness.set_extension_targets(quotient_polys, &openings.quotient_polys);
+    witness.set_extension_targets(
+        preprocessed_local_values,
+        &openings.preprocessed_local_values,
+    );
+    witness.set_extension_targets(preprocessed_next_values, &openings.preprocessed_next_values);
+
+    set_fri_proof_target(witness, &proof_target.opening_proof, &proof.opening_proof);
+}
+
+pub fn set_public_value_targets<F: RichField, W: Witness<F>>(
+    witness: &mut W,
+    public_values_target: &PublicValuesTarget,
+    public_values: &PublicValues,
+) {
+    for (&t, x) in public_values_target
+        .to_targets()
+        .iter()
+        .zip_eq(public_values.to_field_elements::<F>())
+    {
+        witness.set_target(t, x);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use core::program::{Program, REGISTER_NUM};
+    use executor::Process;
+    use plonky2::field::types::Field;
+    use plonky2::plonk::circuit_data::CircuitConfig;
+    use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use plonky2::util::timing::TimingTree;
+
+    use crate::cpu::cpu_stark::CpuPublicValues;
+    use crate::generation::generate_traces;
+    use crate::stark::all_stark::AllStark;
+    use crate::stark::config::StarkConfig;
+    use crate::stark::preprocessed::AllPreprocessed;
+    use crate::stark::prover::prove_with_preprocessed;
+    use crate::stark::recursive_verifier::AllProofRecursionCircuit;
+    use crate::stark::stark_testing::test_stark_circuit_constraints;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn stark_circuit_constraints() -> Result<()> {
+        let mut all_stark = AllStark::<F, D>::default();
+        all_stark.cpu_stark.set_public_values(CpuPublicValues {
+            initial_clk: F::rand(),
+            initial_pc: F::rand(),
+            initial_registers: F::rand_arr(),
+            final_clk: F::rand(),
+            final_pc: F::rand(),
+            final_registers: F::rand_arr(),
+            is_final: F::rand(),
+        });
+        test_stark_circuit_constraints::<F, C, _, D>(all_stark.cpu_stark)?;
+        test_stark_circuit_constraints::<F, C, _, D>(all_stark.memory_stark)?;
+        test_stark_circuit_constraints::<F, C, _, D>(all_stark.bitwise_stark)?;
+        test_stark_circuit_constraints::<F, C, _, D>(all_stark.cmp_stark)?;
+        test_stark_circuit_constraints::<F, C, _, D>(all_stark.rangecheck_stark)?;
+        test_stark_circuit_constraints::<F, C, _, D>(all_stark.program_stark)?;
+        test_stark_circuit_constraints::<F, C, _, D>(all_stark.bitwise_fixed_stark)?;
+        test_stark_circuit_constraints::<F, C, _, D>(all_stark.rangecheck_fixed_stark)?;
+        test_stark_circuit_constraints::<F, C, _, D>(all_stark.final_program_stark)?;
+        test_stark_circuit_constraints::<F, C, _, D>(all_stark.poseidon_stark)?;
+        test_stark_circuit_constraints::<F, C, _, D>(all_stark.ecdsa_stark)?;
+        test_stark_circuit_constraints::<F, C, _, D>(all_stark.keccak_stark)?;
+        test_stark_circuit_constraints::<F, C, _, D>(all_stark.keccak_sponge_stark)
+    }
+
+    #[test]
+    fn recursive_all_proof() -> Result<()> {
+        //mov r0 8
+        //mov r1 2
+        //add r3 r0 r1
+        //end
+        let program_src = "0x4000000840000000
+            0x8
+            0x4000001040000000
+            0x2
+            0x0020204400000000
+            0x0000000000800000";
+
+        let mut program: Program = Program {
+            instructions: Vec::new(),
+            trace: Default::default(),
+        };
+        for inst in program_src.split('\n') {
+            program.instructions.push(inst.to_string());
+        }
+
+        let mut process = Process::new();
+        let _ = process.execute(&mut program);
+
+        let mut all_stark = AllStark::default();
+        let (traces, public_values) = generate_traces(program, &mut all_stark)?;
+        let config = StarkConfig::standard_fast_config();
+        let mut timing = TimingTree::default();
+        let preprocessed = AllPreprocessed::<F, C, D>::new(&all_stark, &config, &mut timing);
+        let proof = prove_with_preprocessed::<F, C, D>(
+            &all_stark,
+            &preprocessed,
+            &config,
+            traces,
+            public_values,
+            &mut timing,
+        )?;
+
+        let circuit = AllProofRecursionCircuit::<F, C, D>::new(
+            &AllStark::default(),
+            &preprocessed.verifier_key(),
+            &config,
+            &proof.degree_bits(&config),
+            CircuitConfig::standard_recursion_config(),
+        );
+        let recursive_proof = circuit.prove(&proof)?;
+        // The public inputs are the public values of the inner proof.
+        assert_eq!(
+            recursive_proof.public_inputs,
+            proof.public_values.to_field_elements::<F>()
+        );
+        // r3 = 8 + 2
+        assert_eq!(
+            recursive_proof.public_inputs[4 + REGISTER_NUM + 3],
+            F::from_canonical_u64(10)
+        );
+        circuit.verify(recursive_proof)
+    }
+}
@@ -1,18 +1,138 @@
+use std::fmt;
 use std::io::Cursor;
-use std::io::{Read, Result, Write};
+use std::io::{ErrorKind, Read, Result, Write};
 
 use plonky2::field::extension::{Extendable, FieldExtension};
 use plonky2::field::polynomial::PolynomialCoeffs;
 use plonky2::field::types::{Field64, PrimeField64};
 
 use plonky2::fri::proof::{FriInitialTreeProof, FriProof, FriQueryRound, FriQueryStep};
+use plonky2::fri::reduction_strategies::FriReductionStrategy;
+use plonky2::fri::FriConfig;
 use plonky2::hash::hash_types::RichField;
 use plonky2::hash::merkle_proofs::MerkleProof;
 use plonky2::hash::merkle_tree::MerkleCap;
 use plonky2::plonk::config::{GenericConfig, GenericHashOut, Hasher};
 
+use super::all_stark::NUM_TABLES;
+use super::config::StarkConfig;
 use super::proof::{AllProof, PublicValues, StarkOpeningSet, StarkProof};
 
+/*  Proof container layout, all integers little endian:
++------------------------------------------------------------------+
+| magic "OLAP" | version u16                                       |
++------------------------------------------------------------------+
+| security_bits u32 | num_challenges u32                           |
+| rate_bits u32 | cap_height u32 | proof_of_work_bits u32          |
+| reduction strategy: tag u8, then                                 |
+|   0 Fixed: len u32 | arity_bits u32 * len                        |
+|   1 ConstantArityBits: arity_bits u32 | final_poly_bits u32      |
+|   2 MinSize: has_max u8 | max_arity_bits u32                     |
+| num_query_rounds u32                                             |
++------------------------------------------------------------------+
+| num_tables u32 | degree_bits u8 * num_tables                     |
++------------------------------------------------------------------+
+| public values, see `Buffer::write_public_values`                 |
++------------------------------------------------------------------+
+| stark proofs of the tables, see `Buffer::write_proof`            |
++------------------------------------------------------------------+
+*/
+
+pub const PROOF_MAGIC: [u8; 4] = *b"OLAP";
+pub const PROOF_FORMAT_VERSION: u16 = 1;
+
+/// An `AllProof` with the `StarkConfig` it was generated with.
+#[derive(Debug, Clone)]
+pub struct ProofContainer<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+{
+    pub config: StarkConfig,
+    /// Trace length of every table.
+    pub degree_bits: [usize; NUM_TABLES],
+    pub proof: AllProof<F, C, D>,
+}
+
+#[derive(Debug)]
+pub enum ProofContainerError {
+    /// file does not start with the proof magic
+    BadMagic,
+    /// written by an incompatible format version
+    UnsupportedVersion(u16),
+    /// unknown FRI reduction strategy tag
+    UnsupportedReductionStrategy(u8),
+    /// config without query rounds, challenges or blowup, or whose FRI
+    /// parameters don't reach the claimed security bits
+    InvalidConfig(String),
+    /// config differs from the one the verifier expects
+    ConfigMismatch,
+    /// number of tables differs from `NUM_TABLES`
+    TableCountMismatch { expected: usize, found: usize },
+    /// degree bits in the header differ from the trace length of the proof
+    DegreeBitsMismatch {
+        table: usize,
+        header: usize,
+        proof: usize,
+    },
+    /// number of FRI query rounds of a table proof differs from the config
+    QueryRoundsMismatch {
+        table: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// file ends inside a section
+    Truncated,
+    /// bytes left after the proof body
+    TrailingBytes(usize),
+    /// a value of the proof body can't be decoded
+    Malformed(std::io::Error),
+}
+
+impl fmt::Display for ProofContainerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadMagic => write!(f, "not an OlaVM proof file"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported proof format version {v}"),
+            Self::UnsupportedReductionStrategy(tag) => {
+                write!(f, "unsupported FRI reduction strategy {tag}")
+            }
+            Self::InvalidConfig(reason) => write!(f, "invalid stark config: {reason}"),
+            Self::ConfigMismatch => write!(f, "stark config differs from the expected one"),
+            Self::TableCountMismatch { expected, found } => {
+                write!(f, "proof has {found} tables, expected {expected}")
+            }
+            Self::DegreeBitsMismatch {
+                table,
+                header,
+                proof,
+            } => write!(
+                f,
+                "table {table} has degree bits {header} in the header but {proof} in the proof"
+            ),
+            Self::QueryRoundsMismatch {
+                table,
+                expected,
+                found,
+            } => write!(
+                f,
+                "table {table} has {found} FRI query rounds, the config has {expected}"
+            ),
+            Self::Truncated => write!(f, "proof file is truncated"),
+            Self::TrailingBytes(n) => write!(f, "{n} trailing bytes after the proof"),
+            Self::Malformed(e) => write!(f, "malformed proof: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ProofContainerError {}
+
+impl From<std::io::Error> for ProofContainerError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            ErrorKind::UnexpectedEof => Self::Truncated,
+            _ => Self::Malformed(e),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Buffer(Cursor<Vec<u8>>);
 
@@ -47,6 +167,15 @@ impl Buffer {
         Ok(u32::from_le_bytes(buf))
     }
 
+    fn write_u64(&mut self, x: u64) -> Result<()> {
+        self.0.write_all(&x.to_le_bytes())
+    }
+    fn read_u64(&mut self) -> Result<u64> {
+        let mut buf = [0; std::mem::size_of::<u64>()];
+        self.0.read_exact(&mut buf)?;
+        Ok(u64::from_le_bytes(buf))
+    }
+
     fn write_field<F: PrimeField64>(&mut self, x: F) -> Result<()> {
         self.0.write_all(&x.to_canonical_u64().to_le_bytes())
     }
@@ -171,6 +300,8 @@ impl Buffer {
         self.write_field_ext_vec::<F, D>(&sos.permutation_ctl_zs_next)?;
         self.write_field_vec::<F>(&sos.ctl_zs_last)?;
         self.write_field_ext_vec::<F, D>(&sos.quotient_polys)?;
+        self.write_field_ext_vec::<F, D>(&sos.preprocessed_local_values)?;
+        self.write_field_ext_vec::<F, D>(&sos.preprocessed_next_values)?;
         Ok(())
     }
     pub fn read_opening_set<F: RichField + Extendable<D>, const D: usize>(
@@ -182,6 +313,8 @@ impl Buffer {
         let permutation_ctl_zs_next = self.read_field_ext_vec::<F, D>()?;
         let ctl_zs_last = self.read_field_vec()?;
         let quotient_polys = self.read_field_ext_vec::<F, D>()?;
+        let preprocessed_local_values = self.read_field_ext_vec::<F, D>()?;
+        let preprocessed_next_values = self.read_field_ext_vec::<F, D>()?;
         Ok(StarkOpeningSet {
             local_values,
             next_values,
@@ -189,6 +322,8 @@ impl Buffer {
             permutation_ctl_zs_next,
             ctl_zs_last,
             quotient_polys,
+            preprocessed_local_values,
+            preprocessed_next_values,
         })
     }
 
@@ -374,6 +509,49 @@ impl Buffer {
         })
     }
 
+    pub fn write_public_values(&mut self, public_values: &PublicValues) -> Result<()> {
+        for e in public_values
+            .program_hash
+            .iter()
+            .chain(public_values.initial_registers.iter())
+            .chain(public_values.final_registers.iter())
+            .chain(public_values.final_program_hash.iter())
+            .chain([
+                &public_values.initial_clk,
+                &public_values.initial_pc,
+                &public_values.final_clk,
+                &public_values.final_pc,
+            ])
+        {
+            self.write_u64(*e)?;
+        }
+        self.write_u8(public_values.is_final as u8)
+    }
+    pub fn read_public_values(&mut self) -> Result<PublicValues> {
+        let mut public_values = PublicValues::default();
+        for e in public_values
+            .program_hash
+            .iter_mut()
+            .chain(public_values.initial_registers.iter_mut())
+            .chain(public_values.final_registers.iter_mut())
+            .chain(public_values.final_program_hash.iter_mut())
+            .chain([
+                &mut public_values.initial_clk,
+                &mut public_values.initial_pc,
+                &mut public_values.final_clk,
+                &mut public_values.final_pc,
+            ])
+        {
+            *e = self.read_u64()?;
+        }
+        public_values.is_final = match self.read_u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(std::io::Error::from(std::io::ErrorKind::InvalidData)),
+        };
+        Ok(public_values)
+    }
+
     pub fn write_all_proof<
         F: RichField + Extendable<D>,
         C: GenericConfig<D, F = F>,
@@ -387,9 +565,7 @@ impl Buffer {
             self.write_proof(p)?;
         }
 
-        self.write_field_vec(&proof.compress_challenges)?;
-        // PublicValues
-        Ok(())
+        self.write_public_values(&proof.public_values)
     }
     pub fn read_all_proof<
         F: RichField + Extendable<D>,
@@ -403,11 +579,223 @@ impl Buffer {
         for _ in 0..len {
             stark_proofs.push(self.read_proof()?);
         }
-        let compress_challenges = self.read_field_vec()?;
+        let public_values = self.read_public_values()?;
         Ok(AllProof {
             stark_proofs: stark_proofs.try_into().unwrap(),
-            compress_challenges: compress_challenges.try_into().unwrap(),
-            public_values: PublicValues::default(),
+            public_values,
         })
     }
+
+    fn write_usize(&mut self, x: usize) -> Result<()> {
+        self.write_u32(x as u32)
+    }
+    fn read_usize(&mut self) -> Result<usize> {
+        Ok(self.read_u32()? as usize)
+    }
+
+    pub fn write_stark_config(&mut self, config: &StarkConfig) -> Result<()> {
+        let fri_config = &config.fri_config;
+        self.write_usize(config.security_bits)?;
+        self.write_usize(config.num_challenges)?;
+        self.write_usize(fri_config.rate_bits)?;
+        self.write_usize(fri_config.cap_height)?;
+        self.write_u32(fri_config.proof_of_work_bits)?;
+        match &fri_config.reduction_strategy {
+            FriReductionStrategy::Fixed(arities) => {
+                self.write_u8(0)?;
+                self.write_usize(arities.len())?;
+                for &arity_bits in arities {
+                    self.write_usize(arity_bits)?;
+                }
+            }
+            FriReductionStrategy::ConstantArityBits(arity_bits, final_poly_bits) => {
+                self.write_u8(1)?;
+                self.write_usize(*arity_bits)?;
+                self.write_usize(*final_poly_bits)?;
+            }
+            FriReductionStrategy::MinSize(max_arity_bits) => {
+                self.write_u8(2)?;
+                self.write_u8(max_arity_bits.is_some() as u8)?;
+                self.write_usize(max_arity_bits.unwrap_or_default())?;
+            }
+        }
+        self.write_usize(fri_config.num_query_rounds)
+    }
+    pub fn read_stark_config(&mut self) -> std::result::Result<StarkConfig, ProofContainerError> {
+        let security_bits = self.read_usize()?;
+        let num_challenges = self.read_usize()?;
+        let rate_bits = self.read_usize()?;
+        let cap_height = self.read_usize()?;
+        let proof_of_work_bits = self.read_u32()?;
+        let reduction_strategy = match self.read_u8()? {
+            0 => {
+                let len = self.read_usize()?;
+                // Every arity halves the degree at least once.
+                if len > 64 {
+                    return Err(ProofContainerError::InvalidConfig(format!(
+                        "{len} FRI reduction arities"
+                    )));
+                }
+                let arities = (0..len)
+                    .map(|_| self.read_usize())
+                    .collect::<Result<Vec<_>>>()?;
+                FriReductionStrategy::Fixed(arities)
+            }
+            1 => FriReductionStrategy::ConstantArityBits(self.read_usize()?, self.read_usize()?),
+            2 => {
+                let has_max = self.read_u8()?;
+                let max_arity_bits = self.read_usize()?;
+                FriReductionStrategy::MinSize((has_max != 0).then_some(max_arity_bits))
+            }
+            tag => return Err(ProofContainerError::UnsupportedReductionStrategy(tag)),
+        };
+        let num_query_rounds = self.read_usize()?;
+
+        let config = StarkConfig {
+            security_bits,
+            num_challenges,
+            fri_config: FriConfig {
+                rate_bits,
+                cap_height,
+                proof_of_work_bits,
+                reduction_strategy,
+                num_query_rounds,
+            },
+        };
+        validate_stark_config(&config)?;
+        Ok(config)
+    }
+
+    /// Write `proof` in the self-describing container layout, together with
+    /// the `config` it was generated with.
+    pub fn write_proof_container<
+        F: RichField + Extendable<D>,
+        C: GenericConfig<D, F = F>,
+        const D: usize,
+    >(
+        &mut self,
+        config: &StarkConfig,
+        proof: &AllProof<F, C, D>,
+    ) -> Result<()> {
+        self.0.write_all(&PROOF_MAGIC)?;
+        self.0.write_all(&PROOF_FORMAT_VERSION.to_le_bytes())?;
+        self.write_stark_config(config)?;
+        self.write_usize(NUM_TABLES)?;
+        for degree_bits in proof.degree_bits(config) {
+            self.write_u8(degree_bits as u8)?;
+        }
+        self.write_public_values(&proof.public_values)?;
+        for p in &proof.stark_proofs {
+            self.write_proof(p)?;
+        }
+        Ok(())
+    }
+    /// Read a proof written by `write_proof_container`, checking the header
+    /// against the proof body and its config against `expected_config`.
+    pub fn read_proof_container<
+        F: RichField + Extendable<D>,
+        C: GenericConfig<D, F = F>,
+        const D: usize,
+    >(
+        &mut self,
+        expected_config: &StarkConfig,
+    ) -> std::result::Result<ProofContainer<F, C, D>, ProofContainerError> {
+        let mut magic = [0; 4];
+        self.0
+            .read_exact(&mut magic)
+            .map_err(|_| ProofContainerError::BadMagic)?;
+        if magic != PROOF_MAGIC {
+            return Err(ProofContainerError::BadMagic);
+        }
+        let mut version = [0; 2];
+        self.0.read_exact(&mut version)?;
+        let version = u16::from_le_bytes(version);
+        if version != PROOF_FORMAT_VERSION {
+            return Err(ProofContainerError::UnsupportedVersion(version));
+        }
+        // The config comes from the prover, the verifier pins it.
+        let config = self.read_stark_config()?;
+        if config != *expected_config {
+            return Err(ProofContainerError::ConfigMismatch);
+        }
+
+        let num_tables = self.read_usize()?;
+        if num_tables != NUM_TABLES {
+            return Err(ProofContainerError::TableCountMismatch {
+                expected: NUM_TABLES,
+                found: num_tables,
+            });
+        }
+        let mut degree_bits = [0; NUM_TABLES];
+        for d in degree_bits.iter_mut() {
+            *d = self.read_u8()? as usize;
+        }
+        let public_values = self.read_public_values()?;
+        let mut stark_proofs = Vec::with_capacity(NUM_TABLES);
+        for _ in 0..NUM_TABLES {
+            stark_proofs.push(self.read_proof()?);
+        }
+        let remaining = self.len() - self.0.position() as usize;
+        if remaining != 0 {
+            return Err(ProofContainerError::TrailingBytes(remaining));
+        }
+
+        for (table, (proof, &header)) in stark_proofs.iter().zip(&degree_bits).enumerate() {
+            let query_rounds = &proof.opening_proof.query_round_proofs;
+            if query_rounds.len() != config.fri_config.num_query_rounds {
+                return Err(ProofContainerError::QueryRoundsMismatch {
+                    table,
+                    expected: config.fri_config.num_query_rounds,
+                    found: query_rounds.len(),
+                });
+            }
+            // `StarkProof::recover_degree_bits`, without underflowing on a
+            // malformed proof.
+            let lde_bits = query_rounds[0]
+                .initial_trees_proof
+                .evals_proofs
+                .first()
+                .map_or(0, |(_, merkle_proof)| merkle_proof.siblings.len())
+                + config.fri_config.cap_height;
+            if lde_bits != header + config.fri_config.rate_bits {
+                return Err(ProofContainerError::DegreeBitsMismatch {
+                    table,
+                    header,
+                    proof: lde_bits.saturating_sub(config.fri_config.rate_bits),
+                });
+            }
+        }
+
+        Ok(ProofContainer {
+            config,
+            degree_bits,
+            proof: AllProof {
+                stark_proofs: stark_proofs.try_into().unwrap(),
+                public_values,
+            },
+        })
+    }
+}
+
+/// Checks the FRI parameters read from a proof container reach the claimed
+/// security, a prover could otherwise weaken them.
+fn validate_stark_config(config: &StarkConfig) -> std::result::Result<(), ProofContainerError> {
+    let fri_config = &config.fri_config;
+    let invalid = |reason: String| Err(ProofContainerError::InvalidConfig(reason));
+    if config.num_challenges == 0 {
+        return invalid("no challenges".to_string());
+    }
+    if fri_config.rate_bits == 0 || fri_config.num_query_rounds == 0 {
+        return invalid("no FRI blowup or query rounds".to_string());
+    }
+    // Conjectured FRI security: every query round gives `rate_bits` bits.
+    let fri_security_bits =
+        fri_config.rate_bits * fri_config.num_query_rounds + fri_config.proof_of_work_bits as usize;
+    if fri_security_bits < config.security_bits {
+        return invalid(format!(
+            "FRI parameters give {fri_security_bits} bits of security, {} claimed",
+            config.security_bits
+        ));
+    }
+    Ok(())
 }
@@ -1,5 +1,6 @@
 use plonky2::field::extension::{Extendable, FieldExtension};
 use plonky2::field::packed::PackedField;
+use plonky2::field::polynomial::PolynomialValues;
 use plonky2::field::types::Field;
 use plonky2::fri::structure::{
     FriBatchInfo, FriBatchInfoTarget, FriInstanceInfo, FriInstanceInfoTarget, FriOracleInfo,
@@ -12,6 +13,7 @@ use plonky2_util::ceil_div_usize;
 
 use super::config::StarkConfig;
 use super::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+use super::lookup::Lookup;
 use super::permutation::PermutationPair;
 use super::vars::StarkEvaluationTargets;
 use super::vars::StarkEvaluationVars;
@@ -19,6 +21,7 @@ use super::vars::StarkEvaluationVars;
 const TRACE_ORACLE_INDEX: usize = 0;
 const PERMUTATION_CTL_ORACLE_INDEX: usize = 1;
 const QUOTIENT_ORACLE_INDEX: usize = 2;
+const PREPROCESSED_ORACLE_INDEX: usize = 3;
 
 /// Represents a STARK system.
 pub trait Stark<F: RichField + Extendable<D>, const D: usize>: Sync {
@@ -83,6 +86,22 @@ pub trait Stark<F: RichField + Extendable<D>, const D: usize>: Sync {
         self.quotient_degree_factor() * config.num_challenges
     }
 
+    /// The number of leading columns fixed at setup, see `preprocessed_trace`.
+    /// They are committed once per `StarkConfig` in their own oracle instead of
+    /// with the trace of every proof. None by default.
+    fn num_preprocessed_columns(&self) -> usize {
+        0
+    }
+
+    fn uses_preprocessed_columns(&self) -> bool {
+        self.num_preprocessed_columns() > 0
+    }
+
+    /// Values of the preprocessed columns, the same for every proof.
+    fn preprocessed_trace(&self) -> Vec<PolynomialValues<F>> {
+        vec![]
+    }
+
     /// Computes the FRI instance used to prove this Stark.
     fn fri_instance(
         &self,
@@ -92,14 +111,24 @@ pub trait Stark<F: RichField + Extendable<D>, const D: usize>: Sync {
         num_ctl_zs: usize,
         config: &StarkConfig,
     ) -> FriInstanceInfo<F, D> {
+        let num_preprocessed_columns = self.num_preprocessed_columns();
+        let num_trace_columns = Self::COLUMNS - num_preprocessed_columns;
         let trace_oracle = FriOracleInfo {
-            num_polys: Self::COLUMNS,
+            num_polys: num_trace_columns,
+            blinding: false,
+        };
+        let trace_info = FriPolynomialInfo::from_range(TRACE_ORACLE_INDEX, 0..num_trace_columns);
+        let preprocessed_oracle = FriOracleInfo {
+            num_polys: num_preprocessed_columns,
             blinding: false,
         };
-        let trace_info = FriPolynomialInfo::from_range(TRACE_ORACLE_INDEX, 0..Self::COLUMNS);
+        let preprocessed_info =
+            FriPolynomialInfo::from_range(PREPROCESSED_ORACLE_INDEX, 0..num_preprocessed_columns);
 
         let num_permutation_batches = self.num_permutation_batches(config);
-        let num_perutation_ctl_polys = num_permutation_batches + num_ctl_zs;
+        let num_lookup_columns = self.num_lookup_helper_columns(config);
+        let num_ctl_zs_start = num_permutation_batches + num_lookup_columns;
+        let num_perutation_ctl_polys = num_ctl_zs_start + num_ctl_zs;
         let permutation_ctl_oracle = FriOracleInfo {
             num_polys: num_perutation_ctl_polys,
             blinding: false,
@@ -111,7 +140,7 @@ pub trait Stark<F: RichField + Extendable<D>, const D: usize>: Sync {
 
         let ctl_zs_info = FriPolynomialInfo::from_range(
             PERMUTATION_CTL_ORACLE_INDEX,
-            num_permutation_batches..num_permutation_batches + num_ctl_zs,
+            num_ctl_zs_start..num_ctl_zs_start + num_ctl_zs,
         );
 
         let num_quotient_polys = self.num_quotient_polys(config);
@@ -128,19 +157,26 @@ pub trait Stark<F: RichField + Extendable<D>, const D: usize>: Sync {
                 trace_info.clone(),
                 permutation_ctl_zs_info.clone(),
                 quotient_info,
+                preprocessed_info.clone(),
             ]
             .concat(),
         };
         let zeta_next_batch = FriBatchInfo {
             point: zeta.scalar_mul(g),
-            polynomials: [trace_info, permutation_ctl_zs_info].concat(),
+            polynomials: [trace_info, permutation_ctl_zs_info, preprocessed_info].concat(),
         };
         let ctl_last_batch = FriBatchInfo {
             point: F::Extension::primitive_root_of_unity(degree_bits).inverse(),
             polynomials: ctl_zs_info,
         };
+        // The preprocessed oracle only exists for tables with preprocessed
+        // columns.
+        let mut oracles = vec![trace_oracle, permutation_ctl_oracle, quotient_oracle];
+        if num_preprocessed_columns > 0 {
+            oracles.push(preprocessed_oracle);
+        }
         FriInstanceInfo {
-            oracles: vec![trace_oracle, permutation_ctl_oracle, quotient_oracle],
+            oracles,
             batches: vec![zeta_batch, zeta_next_batch, ctl_last_batch],
         }
     }
@@ -155,14 +191,24 @@ pub trait Stark<F: RichField + Extendable<D>, const D: usize>: Sync {
         num_ctl_zs: usize,
         inner_config: &StarkConfig,
     ) -> FriInstanceInfoTarget<D> {
+        let num_preprocessed_columns = self.num_preprocessed_columns();
+        let num_trace_columns = Self::COLUMNS - num_preprocessed_columns;
         let trace_oracle = FriOracleInfo {
-            num_polys: Self::COLUMNS,
+            num_polys: num_trace_columns,
+            blinding: false,
+        };
+        let trace_info = FriPolynomialInfo::from_range(TRACE_ORACLE_INDEX, 0..num_trace_columns);
+        let preprocessed_oracle = FriOracleInfo {
+            num_polys: num_preprocessed_columns,
             blinding: false,
         };
-        let trace_info = FriPolynomialInfo::from_range(TRACE_ORACLE_INDEX, 0..Self::COLUMNS);
+        let preprocessed_info =
+            FriPolynomialInfo::from_range(PREPROCESSED_ORACLE_INDEX, 0..num_preprocessed_columns);
 
         let num_permutation_batches = self.num_permutation_batches(inner_config);
-        let num_perutation_ctl_polys = num_permutation_batches + num_ctl_zs;
+        let num_lookup_columns = self.num_lookup_helper_columns(inner_config);
+        let num_ctl_zs_start = num_permutation_batches + num_lookup_columns;
+        let num_perutation_ctl_polys = num_ctl_zs_start + num_ctl_zs;
         let permutation_ctl_oracle = FriOracleInfo {
             num_polys: num_perutation_ctl_polys,
             blinding: false,
@@ -174,7 +220,7 @@ pub trait Stark<F: RichField + Extendable<D>, const D: usize>: Sync {
 
         let ctl_zs_info = FriPolynomialInfo::from_range(
             PERMUTATION_CTL_ORACLE_INDEX,
-            num_permutation_batches..num_permutation_batches + num_ctl_zs,
+            num_ctl_zs_start..num_ctl_zs_start + num_ctl_zs,
         );
 
         let num_quotient_polys = self.num_quotient_polys(inner_config);
@@ -191,21 +237,26 @@ pub trait Stark<F: RichField + Extendable<D>, const D: usize>: Sync {
                 trace_info.clone(),
                 permutation_ctl_zs_info.clone(),
                 quotient_info,
+                preprocessed_info.clone(),
             ]
             .concat(),
         };
         let zeta_next = builder.mul_const_extension(g, zeta);
         let zeta_next_batch = FriBatchInfoTarget {
             point: zeta_next,
-            polynomials: [trace_info, permutation_ctl_zs_info].concat(),
+            polynomials: [trace_info, permutation_ctl_zs_info, preprocessed_info].concat(),
         };
         let ctl_last_batch = FriBatchInfoTarget {
             point: builder
                 .constant_extension(F::Extension::primitive_root_of_unity(degree_bits).inverse()),
             polynomials: ctl_zs_info,
         };
+        let mut oracles = vec![trace_oracle, permutation_ctl_oracle, quotient_oracle];
+        if num_preprocessed_columns > 0 {
+            oracles.push(preprocessed_oracle);
+        }
         FriInstanceInfoTarget {
-            oracles: vec![trace_oracle, permutation_ctl_oracle, quotient_oracle],
+            oracles,
             batches: vec![zeta_batch, zeta_next_batch, ctl_last_batch],
         }
     }
@@ -242,4 +293,31 @@ pub trait Stark<F: RichField + Extendable<D>, const D: usize>: Sync {
             self.permutation_batch_size(),
         )
     }
+
+    /// Lookups of columns in table columns of this Stark, checked with a
+    /// logarithmic derivative argument. Empty by default.
+    fn lookups(&self) -> Vec<Lookup> {
+        vec![]
+    }
+
+    fn uses_lookups(&self) -> bool {
+        !self.lookups().is_empty()
+    }
+
+    /// The number of looked up tuples combined into a single helper column.
+    fn lookup_batch_size(&self) -> usize {
+        // A helper column constraint multiplies the helper by the batch
+        // combinations, so the batch size is one less than our constraint
+        // degree, like for permutation arguments.
+        self.quotient_degree_factor()
+    }
+
+    fn num_lookup_helper_columns(&self, config: &StarkConfig) -> usize {
+        let batch_size = self.lookup_batch_size();
+        self.lookups()
+            .iter()
+            .map(|lookup| lookup.num_helper_columns(batch_size))
+            .sum::<usize>()
+            * config.num_challenges
+    }
 }
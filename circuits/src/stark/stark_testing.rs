@@ -0,0 +1,87 @@
+use anyhow::Result;
+use plonky2::field::extension::{Extendable, FieldExtension};
+use plonky2::field::types::Field;
+use plonky2::hash::hash_types::RichField;
+use plonky2::iop::witness::{PartialWitness, Witness};
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2::plonk::circuit_data::CircuitConfig;
+use plonky2::plonk::config::{GenericConfig, Hasher};
+
+use super::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+use super::stark::Stark;
+use super::vars::{StarkEvaluationTargets, StarkEvaluationVars};
+
+/// Tests that the circuit constraints imposed by the given STARK are coherent
+/// with the native constraints.
+pub(crate) fn test_stark_circuit_constraints<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    S: Stark<F, D>,
+    const D: usize,
+>(
+    stark: S,
+) -> Result<()>
+where
+    [(); S::COLUMNS]:,
+    [(); C::Hasher::HASH_SIZE]:,
+{
+    // Compute native constraint evaluation on random values.
+    let vars = StarkEvaluationVars {
+        local_values: &F::Extension::rand_arr::<{ S::COLUMNS }>(),
+        next_values: &F::Extension::rand_arr::<{ S::COLUMNS }>(),
+    };
+    let alphas = F::rand_vec(1);
+    let z_last = F::Extension::rand();
+    let lagrange_first = F::Extension::rand();
+    let lagrange_last = F::Extension::rand();
+    let mut consumer = ConstraintConsumer::<F::Extension>::new(
+        alphas
+            .iter()
+            .copied()
+            .map(F::Extension::from_basefield)
+            .collect(),
+        z_last,
+        lagrange_first,
+        lagrange_last,
+    );
+    stark.eval_ext(vars, &mut consumer);
+    let native_eval = consumer.accumulators()[0];
+
+    // Compute circuit constraint evaluation on same random values.
+    let circuit_config = CircuitConfig::standard_recursion_config();
+    let mut builder = CircuitBuilder::<F, D>::new(circuit_config);
+    let mut pw = PartialWitness::<F>::new();
+
+    let locals_t = builder.add_virtual_extension_targets(S::COLUMNS);
+    pw.set_extension_targets(&locals_t, vars.local_values);
+    let nexts_t = builder.add_virtual_extension_targets(S::COLUMNS);
+    pw.set_extension_targets(&nexts_t, vars.next_values);
+    let alphas_t = builder.add_virtual_targets(1);
+    pw.set_target(alphas_t[0], alphas[0]);
+    let z_last_t = builder.add_virtual_extension_target();
+    pw.set_extension_target(z_last_t, z_last);
+    let lagrange_first_t = builder.add_virtual_extension_target();
+    pw.set_extension_target(lagrange_first_t, lagrange_first);
+    let lagrange_last_t = builder.add_virtual_extension_target();
+    pw.set_extension_target(lagrange_last_t, lagrange_last);
+
+    let vars = StarkEvaluationTargets::<D, { S::COLUMNS }> {
+        local_values: &locals_t.try_into().unwrap(),
+        next_values: &nexts_t.try_into().unwrap(),
+    };
+    let mut consumer = RecursiveConstraintConsumer::<F, D>::new(
+        builder.zero_extension(),
+        alphas_t,
+        z_last_t,
+        lagrange_first_t,
+        lagrange_last_t,
+    );
+    stark.eval_ext_circuit(&mut builder, vars, &mut consumer);
+    let circuit_eval = consumer.accumulators()[0];
+    let native_eval_t = builder.constant_extension(native_eval);
+    builder.connect_extension(circuit_eval, native_eval_t);
+
+    let data = builder.build::<C>();
+    let proof = data.prove(pw)?;
+    data.verify(proof)
+}
@@ -10,6 +10,9 @@ use super::cross_table_lookup::{
     eval_cross_table_lookup_checks, eval_cross_table_lookup_checks_circuit, CtlCheckVars,
     CtlCheckVarsTarget,
 };
+use super::lookup::{
+    eval_lookup_checks, eval_lookup_checks_circuit, LookupCheckVars, LookupCheckVarsTarget,
+};
 use super::permutation::{
     eval_permutation_checks, eval_permutation_checks_circuit, PermutationCheckDataTarget,
     PermutationCheckVars,
@@ -22,6 +25,7 @@ pub(crate) fn eval_vanishing_poly<F, FE, P, C, S, const D: usize, const D2: usiz
     config: &StarkConfig,
     vars: StarkEvaluationVars<FE, P, { S::COLUMNS }>,
     permutation_vars: Option<PermutationCheckVars<F, FE, P, D2>>,
+    lookup_vars: Option<LookupCheckVars<F, FE, P, D2>>,
     ctl_vars: &[CtlCheckVars<F, FE, P, D2>],
     consumer: &mut ConstraintConsumer<P>,
 ) where
@@ -41,16 +45,20 @@ pub(crate) fn eval_vanishing_poly<F, FE, P, C, S, const D: usize, const D2: usiz
             consumer,
         );
     }
+    if let Some(lookup_vars) = lookup_vars {
+        eval_lookup_checks::<F, FE, P, S, D, D2>(stark, vars, lookup_vars, consumer);
+    }
     eval_cross_table_lookup_checks::<F, FE, P, C, S, D, D2>(vars, ctl_vars, consumer);
 }
 
-#[allow(unused)]
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn eval_vanishing_poly_circuit<F, C, S, const D: usize>(
     builder: &mut CircuitBuilder<F, D>,
     stark: &S,
     config: &StarkConfig,
     vars: StarkEvaluationTargets<D, { S::COLUMNS }>,
     permutation_data: Option<PermutationCheckDataTarget<D>>,
+    lookup_vars: Option<LookupCheckVarsTarget<D>>,
     ctl_vars: &[CtlCheckVarsTarget<F, D>],
     consumer: &mut RecursiveConstraintConsumer<F, D>,
 ) where
@@ -70,5 +78,8 @@ pub(crate) fn eval_vanishing_poly_circuit<F, C, S, const D: usize>(
             consumer,
         );
     }
+    if let Some(lookup_vars) = lookup_vars {
+        eval_lookup_checks_circuit::<F, S, D>(builder, stark, vars, lookup_vars, consumer);
+    }
     eval_cross_table_lookup_checks_circuit::<S, F, D>(builder, vars, ctl_vars, consumer);
 }
@@ -1,4 +1,5 @@
-use anyhow::{ensure, Result};
+use anyhow::{anyhow, ensure, Result};
+use itertools::Itertools;
 use plonky2::field::extension::{Extendable, FieldExtension};
 use plonky2::field::types::Field;
 use plonky2::fri::verifier::verify_fri_proof;
@@ -6,11 +7,13 @@ use plonky2::hash::hash_types::RichField;
 use plonky2::plonk::config::{GenericConfig, Hasher};
 use plonky2::plonk::plonk_common::reduce_with_powers;
 
-use super::all_stark::{AllStark, Table};
+use super::all_stark::{AllStark, Table, NUM_TABLES};
 use super::config::StarkConfig;
 use super::constraint_consumer::ConstraintConsumer;
 use super::cross_table_lookup::{verify_cross_table_lookups, CtlCheckVars};
+use super::lookup::LookupCheckVars;
 use super::permutation::PermutationCheckVars;
+use super::preprocessed::{AllVerifierKey, PreprocessedCap};
 use super::proof::{
     AllProof, AllProofChallenges, StarkOpeningSet, StarkProof, StarkProofChallenges,
 };
@@ -18,62 +21,238 @@ use super::stark::Stark;
 use super::vanishing_poly::eval_vanishing_poly;
 use super::vars::StarkEvaluationVars;
 use crate::builtins::bitwise::bitwise_stark::BitwiseStark;
-use crate::builtins::cmp::cmp_stark::CmpStark;
+use crate::builtins::ecdsa::ecdsa_stark::EcdsaStark;
+use crate::builtins::keccak::keccak_stark::KeccakStark;
+use crate::builtins::keccak_sponge::keccak_sponge_stark::KeccakSpongeStark;
+use crate::builtins::poseidon::poseidon_stark::PoseidonStark;
 use crate::builtins::rangecheck::rangecheck_stark::RangeCheckStark;
 use crate::cpu::cpu_stark::CpuStark;
+use crate::fixed_table::bitwise_fixed::bitwise_fixed_stark::BitwiseFixedStark;
+use crate::fixed_table::rangecheck_fixed::rangecheck_fixed_stark::RangecheckFixedStark;
+use crate::generation::program::program_trace_cap;
 use crate::memory::memory_stark::MemoryStark;
+use crate::program::program_stark::ProgramStark;
 
+/// Verify `all_proof` of a whole execution, if `program_hash` is given also
+/// check that the proof is about the program with this hash (see
+/// `generation::program::program_hash`). This commits to the preprocessed
+/// columns first, use `verify_proof_with_key` to share them between
+/// verifications.
 pub fn verify_proof<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
     all_stark: AllStark<F, D>,
     all_proof: AllProof<F, C, D>,
     config: &StarkConfig,
+    program_hash: Option<[u64; 4]>,
 ) -> Result<()>
 where
     [(); CpuStark::<F, D>::COLUMNS]:,
     [(); MemoryStark::<F, D>::COLUMNS]:,
     [(); BitwiseStark::<F, D>::COLUMNS]:,
-    [(); CmpStark::<F, D>::COLUMNS]:,
     [(); RangeCheckStark::<F, D>::COLUMNS]:,
+    [(); BitwiseFixedStark::<F, D>::COLUMNS]:,
+    [(); ProgramStark::<F, D>::COLUMNS]:,
+    [(); RangecheckFixedStark::<F, D>::COLUMNS]:,
+    [(); PoseidonStark::<F, D>::COLUMNS]:,
+    [(); EcdsaStark::<F, D>::COLUMNS]:,
+    [(); KeccakStark::<F, D>::COLUMNS]:,
+    [(); KeccakSpongeStark::<F, D>::COLUMNS]:,
     [(); C::Hasher::HASH_SIZE]:,
 {
+    let verifier_key = AllVerifierKey::new(&all_stark, config);
+    verify_proof_with_key(all_stark, &verifier_key, all_proof, config, program_hash)
+}
+
+/// Verify `all_proof` against the preprocessed columns in `verifier_key`, see
+/// `verify_proof`.
+pub fn verify_proof_with_key<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    all_stark: AllStark<F, D>,
+    verifier_key: &AllVerifierKey<F, C, D>,
+    all_proof: AllProof<F, C, D>,
+    config: &StarkConfig,
+    program_hash: Option<[u64; 4]>,
+) -> Result<()>
+where
+    [(); CpuStark::<F, D>::COLUMNS]:,
+    [(); MemoryStark::<F, D>::COLUMNS]:,
+    [(); BitwiseStark::<F, D>::COLUMNS]:,
+    [(); RangeCheckStark::<F, D>::COLUMNS]:,
+    [(); BitwiseFixedStark::<F, D>::COLUMNS]:,
+    [(); ProgramStark::<F, D>::COLUMNS]:,
+    [(); RangecheckFixedStark::<F, D>::COLUMNS]:,
+    [(); PoseidonStark::<F, D>::COLUMNS]:,
+    [(); EcdsaStark::<F, D>::COLUMNS]:,
+    [(); KeccakStark::<F, D>::COLUMNS]:,
+    [(); KeccakSpongeStark::<F, D>::COLUMNS]:,
+    [(); C::Hasher::HASH_SIZE]:,
+{
+    let public_values = &all_proof.public_values;
+    ensure!(
+        public_values.initial_clk == 0 && public_values.initial_pc == 0,
+        "Execution doesn't start at the program entry."
+    );
+    ensure!(
+        public_values.is_final,
+        "Proof of a segment, see `verify_segment_proofs`."
+    );
+    verify_segment_proof_with_key(all_stark, verifier_key, all_proof, config, program_hash)
+}
+
+/// Verify the proofs of consecutive segments of an execution, as produced by
+/// `Process::execute_segment`. Each segment starts from the state the
+/// previous one ends with: same registers, pc and clk, and its program hash
+/// commits to the memory left by the previous segment.
+pub fn verify_segment_proofs<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    all_stark: AllStark<F, D>,
+    verifier_key: &AllVerifierKey<F, C, D>,
+    all_proofs: Vec<AllProof<F, C, D>>,
+    config: &StarkConfig,
+    program_hash: Option<[u64; 4]>,
+) -> Result<()>
+where
+    [(); CpuStark::<F, D>::COLUMNS]:,
+    [(); MemoryStark::<F, D>::COLUMNS]:,
+    [(); BitwiseStark::<F, D>::COLUMNS]:,
+    [(); RangeCheckStark::<F, D>::COLUMNS]:,
+    [(); BitwiseFixedStark::<F, D>::COLUMNS]:,
+    [(); ProgramStark::<F, D>::COLUMNS]:,
+    [(); RangecheckFixedStark::<F, D>::COLUMNS]:,
+    [(); PoseidonStark::<F, D>::COLUMNS]:,
+    [(); EcdsaStark::<F, D>::COLUMNS]:,
+    [(); KeccakStark::<F, D>::COLUMNS]:,
+    [(); KeccakSpongeStark::<F, D>::COLUMNS]:,
+    [(); C::Hasher::HASH_SIZE]:,
+{
+    let (first, last) = match (all_proofs.first(), all_proofs.last()) {
+        (Some(first), Some(last)) => (&first.public_values, &last.public_values),
+        _ => return Err(anyhow!("No segment proof.")),
+    };
+    ensure!(
+        first.initial_clk == 0 && first.initial_pc == 0,
+        "Execution doesn't start at the program entry."
+    );
+    ensure!(last.is_final, "Last segment doesn't end the execution.");
+    for (i, (segment, next)) in all_proofs.iter().tuple_windows().enumerate() {
+        let (segment, next) = (&segment.public_values, &next.public_values);
+        ensure!(
+            !segment.is_final
+                && segment.final_program_hash == next.program_hash
+                && segment.final_registers == next.initial_registers
+                && segment.final_clk == next.initial_clk
+                && segment.final_pc == next.initial_pc,
+            "Segment {} isn't continued by segment {}.",
+            i,
+            i + 1
+        );
+    }
+
+    let mut program_hash = program_hash;
+    for all_proof in all_proofs {
+        verify_segment_proof_with_key(
+            all_stark.clone(),
+            verifier_key,
+            all_proof,
+            config,
+            program_hash.take(),
+        )?;
+    }
+    Ok(())
+}
+
+/// Verify the proof of a single segment, which may not start at the program
+/// entry nor end the execution.
+pub fn verify_segment_proof_with_key<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    all_stark: AllStark<F, D>,
+    verifier_key: &AllVerifierKey<F, C, D>,
+    all_proof: AllProof<F, C, D>,
+    config: &StarkConfig,
+    program_hash: Option<[u64; 4]>,
+) -> Result<()>
+where
+    [(); CpuStark::<F, D>::COLUMNS]:,
+    [(); MemoryStark::<F, D>::COLUMNS]:,
+    [(); BitwiseStark::<F, D>::COLUMNS]:,
+    [(); RangeCheckStark::<F, D>::COLUMNS]:,
+    [(); BitwiseFixedStark::<F, D>::COLUMNS]:,
+    [(); ProgramStark::<F, D>::COLUMNS]:,
+    [(); RangecheckFixedStark::<F, D>::COLUMNS]:,
+    [(); PoseidonStark::<F, D>::COLUMNS]:,
+    [(); EcdsaStark::<F, D>::COLUMNS]:,
+    [(); KeccakStark::<F, D>::COLUMNS]:,
+    [(); KeccakSpongeStark::<F, D>::COLUMNS]:,
+    [(); C::Hasher::HASH_SIZE]:,
+{
+    if let Some(program_hash) = program_hash {
+        ensure!(
+            program_hash == all_proof.public_values.program_hash,
+            "Proof is not about the expected program."
+        );
+    }
+
     let AllProofChallenges {
         stark_challenges,
         ctl_challenges,
     } = all_proof.get_challenges(&all_stark, config);
 
     let nums_permutation_zs = all_stark.nums_permutation_zs(config);
+    let nums_lookup_zs = all_stark.nums_lookup_zs(config);
+    let nums_ctl_zs_start = std::array::from_fn(|i| nums_permutation_zs[i] + nums_lookup_zs[i]);
 
     let AllStark {
         mut cpu_stark,
         memory_stark,
-        mut bitwise_stark,
+        bitwise_stark,
         cmp_stark,
         rangecheck_stark,
+        mut program_stark,
+        bitwise_fixed_stark,
+        rangecheck_fixed_stark,
+        mut final_program_stark,
+        poseidon_stark,
+        ecdsa_stark,
+        keccak_stark,
+        keccak_sponge_stark,
         cross_table_lookups,
     } = all_stark;
 
-    if cpu_stark.get_compress_challenge().is_none() {
-        cpu_stark
-            .set_compress_challenge(all_proof.compress_challenges[Table::Cpu as usize])
-            .unwrap();
-    }
-
-    if bitwise_stark.get_compress_challenge().is_none() {
-        bitwise_stark
-            .set_compress_challenge(all_proof.compress_challenges[Table::Bitwise as usize])
-            .unwrap();
-    }
+    // Boundary state and program hashes always come from the proof public
+    // values, the program tables end with them.
+    cpu_stark.set_public_values(all_proof.public_values.cpu_public_values());
+    program_stark.set_program_hash(
+        all_proof
+            .public_values
+            .program_hash
+            .map(F::from_canonical_u64),
+    );
+    final_program_stark.set_program_hash(
+        all_proof
+            .public_values
+            .final_program_hash
+            .map(F::from_canonical_u64),
+    );
 
     let ctl_vars_per_table = CtlCheckVars::from_proofs(
         &all_proof.stark_proofs,
         &cross_table_lookups,
         &ctl_challenges,
-        &nums_permutation_zs,
+        &nums_ctl_zs_start,
     );
 
     verify_stark_proof_with_challenges(
         cpu_stark,
         &all_proof.stark_proofs[Table::Cpu as usize],
+        verifier_key.preprocessed_caps[Table::Cpu as usize].as_ref(),
         &stark_challenges[Table::Cpu as usize],
         &ctl_vars_per_table[Table::Cpu as usize],
         config,
@@ -81,6 +260,7 @@ where
     verify_stark_proof_with_challenges(
         memory_stark,
         &all_proof.stark_proofs[Table::Memory as usize],
+        verifier_key.preprocessed_caps[Table::Memory as usize].as_ref(),
         &stark_challenges[Table::Memory as usize],
         &ctl_vars_per_table[Table::Memory as usize],
         config,
@@ -88,6 +268,7 @@ where
     verify_stark_proof_with_challenges(
         bitwise_stark,
         &all_proof.stark_proofs[Table::Bitwise as usize],
+        verifier_key.preprocessed_caps[Table::Bitwise as usize].as_ref(),
         &stark_challenges[Table::Bitwise as usize],
         &ctl_vars_per_table[Table::Bitwise as usize],
         config,
@@ -96,6 +277,7 @@ where
     verify_stark_proof_with_challenges(
         cmp_stark,
         &all_proof.stark_proofs[Table::Cmp as usize],
+        verifier_key.preprocessed_caps[Table::Cmp as usize].as_ref(),
         &stark_challenges[Table::Cmp as usize],
         &ctl_vars_per_table[Table::Cmp as usize],
         config,
@@ -104,12 +286,81 @@ where
     verify_stark_proof_with_challenges(
         rangecheck_stark,
         &all_proof.stark_proofs[Table::RangeCheck as usize],
+        verifier_key.preprocessed_caps[Table::RangeCheck as usize].as_ref(),
         &stark_challenges[Table::RangeCheck as usize],
         &ctl_vars_per_table[Table::RangeCheck as usize],
         config,
     )?;
 
-    let degrees_bits =
+    verify_stark_proof_with_challenges(
+        program_stark,
+        &all_proof.stark_proofs[Table::Program as usize],
+        verifier_key.preprocessed_caps[Table::Program as usize].as_ref(),
+        &stark_challenges[Table::Program as usize],
+        &ctl_vars_per_table[Table::Program as usize],
+        config,
+    )?;
+
+    verify_stark_proof_with_challenges(
+        bitwise_fixed_stark,
+        &all_proof.stark_proofs[Table::BitwiseFixed as usize],
+        verifier_key.preprocessed_caps[Table::BitwiseFixed as usize].as_ref(),
+        &stark_challenges[Table::BitwiseFixed as usize],
+        &ctl_vars_per_table[Table::BitwiseFixed as usize],
+        config,
+    )?;
+
+    verify_stark_proof_with_challenges(
+        rangecheck_fixed_stark,
+        &all_proof.stark_proofs[Table::RangecheckFixed as usize],
+        verifier_key.preprocessed_caps[Table::RangecheckFixed as usize].as_ref(),
+        &stark_challenges[Table::RangecheckFixed as usize],
+        &ctl_vars_per_table[Table::RangecheckFixed as usize],
+        config,
+    )?;
+
+    verify_stark_proof_with_challenges(
+        final_program_stark,
+        &all_proof.stark_proofs[Table::FinalProgram as usize],
+        verifier_key.preprocessed_caps[Table::FinalProgram as usize].as_ref(),
+        &stark_challenges[Table::FinalProgram as usize],
+        &ctl_vars_per_table[Table::FinalProgram as usize],
+        config,
+    )?;
+    verify_stark_proof_with_challenges(
+        poseidon_stark,
+        &all_proof.stark_proofs[Table::Poseidon as usize],
+        verifier_key.preprocessed_caps[Table::Poseidon as usize].as_ref(),
+        &stark_challenges[Table::Poseidon as usize],
+        &ctl_vars_per_table[Table::Poseidon as usize],
+        config,
+    )?;
+    verify_stark_proof_with_challenges(
+        ecdsa_stark,
+        &all_proof.stark_proofs[Table::Ecdsa as usize],
+        verifier_key.preprocessed_caps[Table::Ecdsa as usize].as_ref(),
+        &stark_challenges[Table::Ecdsa as usize],
+        &ctl_vars_per_table[Table::Ecdsa as usize],
+        config,
+    )?;
+    verify_stark_proof_with_challenges(
+        keccak_stark,
+        &all_proof.stark_proofs[Table::Keccak as usize],
+        verifier_key.preprocessed_caps[Table::Keccak as usize].as_ref(),
+        &stark_challenges[Table::Keccak as usize],
+        &ctl_vars_per_table[Table::Keccak as usize],
+        config,
+    )?;
+    verify_stark_proof_with_challenges(
+        keccak_sponge_stark,
+        &all_proof.stark_proofs[Table::KeccakSponge as usize],
+        verifier_key.preprocessed_caps[Table::KeccakSponge as usize].as_ref(),
+        &stark_challenges[Table::KeccakSponge as usize],
+        &ctl_vars_per_table[Table::KeccakSponge as usize],
+        config,
+    )?;
+
+    let degrees_bits: [usize; NUM_TABLES] =
         std::array::from_fn(|i| all_proof.stark_proofs[i].recover_degree_bits(config));
     verify_cross_table_lookups::<F, C, D>(
         cross_table_lookups,
@@ -120,6 +371,26 @@ where
     )
 }
 
+/// Check that `all_proof` is a proof about the program encoded in
+/// `raw_instructions` with `static_memory` data, by recomputing the program
+/// table commitment.
+pub fn verify_program<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    all_proof: &AllProof<F, C, D>,
+    raw_instructions: &[String],
+    static_memory: &[(u64, u64)],
+    config: &StarkConfig,
+) -> Result<()>
+where
+    [(); C::Hasher::HASH_SIZE]:,
+{
+    ensure!(
+        program_trace_cap::<F, C, D>(raw_instructions, static_memory, config)
+            == all_proof.stark_proofs[Table::Program as usize].trace_cap,
+        "Program commitment mismatch."
+    );
+    Ok(())
+}
+
 pub(crate) fn verify_stark_proof_with_challenges<
     F: RichField + Extendable<D>,
     C: GenericConfig<D, F = F>,
@@ -128,6 +399,7 @@ pub(crate) fn verify_stark_proof_with_challenges<
 >(
     stark: S,
     proof: &StarkProof<F, C, D>,
+    preprocessed_cap: Option<&PreprocessedCap<F, C, D>>,
     challenges: &StarkProofChallenges<F, D>,
     ctl_vars: &[CtlCheckVars<F, F::Extension, F::Extension, D>],
     config: &StarkConfig,
@@ -136,7 +408,7 @@ where
     [(); S::COLUMNS]:,
     [(); C::Hasher::HASH_SIZE]:,
 {
-    validate_proof_shape(&stark, proof, config, ctl_vars.len())?;
+    validate_proof_shape(&stark, proof, preprocessed_cap, config, ctl_vars.len())?;
     let StarkOpeningSet {
         local_values,
         next_values,
@@ -144,10 +416,19 @@ where
         permutation_ctl_zs_next,
         ctl_zs_last,
         quotient_polys,
+        preprocessed_local_values,
+        preprocessed_next_values,
     } = &proof.openings;
+    // The preprocessed columns are the leading columns of the table.
     let vars = StarkEvaluationVars {
-        local_values: &local_values.to_vec().try_into().unwrap(),
-        next_values: &next_values.to_vec().try_into().unwrap(),
+        local_values: &[preprocessed_local_values.as_slice(), local_values]
+            .concat()
+            .try_into()
+            .unwrap(),
+        next_values: &[preprocessed_next_values.as_slice(), next_values]
+            .concat()
+            .try_into()
+            .unwrap(),
     };
 
     let degree_bits = proof.recover_degree_bits(config);
@@ -170,11 +451,19 @@ where
         next_zs: permutation_ctl_zs_next[..num_permutation_zs].to_vec(),
         permutation_challenge_sets: challenges.permutation_challenge_sets.clone().unwrap(),
     });
+    let lookup_zs =
+        num_permutation_zs..num_permutation_zs + stark.num_lookup_helper_columns(config);
+    let lookup_data = stark.uses_lookups().then(|| LookupCheckVars {
+        local_values: permutation_ctl_zs[lookup_zs.clone()].to_vec(),
+        next_values: permutation_ctl_zs_next[lookup_zs].to_vec(),
+        challenges: challenges.lookup_challenge_set.clone().unwrap(),
+    });
     eval_vanishing_poly::<F, F::Extension, F::Extension, C, S, D, D>(
         &stark,
         config,
         vars,
         permutation_data,
+        lookup_data,
         ctl_vars,
         &mut consumer,
     );
@@ -201,11 +490,14 @@ where
         );
     }
 
-    let merkle_caps = vec![
+    let merkle_caps = [
         proof.trace_cap.clone(),
         proof.permutation_ctl_zs_cap.clone(),
         proof.quotient_polys_cap.clone(),
-    ];
+    ]
+    .into_iter()
+    .chain(preprocessed_cap.map(|p| p.cap.clone()))
+    .collect::<Vec<_>>();
 
     verify_fri_proof::<F, C, D>(
         &stark.fri_instance(
@@ -228,6 +520,7 @@ where
 fn validate_proof_shape<F, C, S, const D: usize>(
     stark: &S,
     proof: &StarkProof<F, C, D>,
+    preprocessed_cap: Option<&PreprocessedCap<F, C, D>>,
     config: &StarkConfig,
     num_ctl_zs: usize,
 ) -> anyhow::Result<()>
@@ -255,19 +548,35 @@ where
         permutation_ctl_zs_next,
         ctl_zs_last,
         quotient_polys,
+        preprocessed_local_values,
+        preprocessed_next_values,
     } = openings;
 
     let degree_bits = proof.recover_degree_bits(config);
     let fri_params = config.fri_params(degree_bits);
     let cap_height = fri_params.config.cap_height;
-    let num_zs = num_ctl_zs + stark.num_permutation_batches(config);
+    let num_zs = num_ctl_zs
+        + stark.num_permutation_batches(config)
+        + stark.num_lookup_helper_columns(config);
 
     ensure!(trace_cap.height() == cap_height);
     ensure!(permutation_ctl_zs_cap.height() == cap_height);
     ensure!(quotient_polys_cap.height() == cap_height);
 
-    ensure!(local_values.len() == S::COLUMNS);
-    ensure!(next_values.len() == S::COLUMNS);
+    let num_preprocessed_columns = stark.num_preprocessed_columns();
+    ensure!(preprocessed_cap.is_some() == stark.uses_preprocessed_columns());
+    if let Some(preprocessed_cap) = preprocessed_cap {
+        ensure!(
+            degree_bits == preprocessed_cap.degree_bits,
+            "Trace length mismatch with the preprocessed columns."
+        );
+        ensure!(preprocessed_cap.cap.height() == cap_height);
+    }
+
+    ensure!(local_values.len() == S::COLUMNS - num_preprocessed_columns);
+    ensure!(next_values.len() == S::COLUMNS - num_preprocessed_columns);
+    ensure!(preprocessed_local_values.len() == num_preprocessed_columns);
+    ensure!(preprocessed_next_values.len() == num_preprocessed_columns);
     ensure!(permutation_ctl_zs.len() == num_zs);
     ensure!(permutation_ctl_zs_next.len() == num_zs);
     ensure!(ctl_zs_last.len() == num_ctl_zs);
@@ -1,16 +1,26 @@
 extern crate clap;
 
+use assembler::binary::write_binary_program;
+use assembler::disasm::disassemble;
 use assembler::encode::Encoder;
+use circuits::generation::cpu::CpuTraceBuilder;
+use circuits::generation::program::program_hash;
+use circuits::generation::{generate_traces, generate_traces_with_cpu_trace};
 use circuits::stark::all_stark::AllStark;
 use circuits::stark::config::StarkConfig;
-use circuits::stark::prover::prove;
-use circuits::stark::serialization::Buffer;
+use circuits::stark::prover::prove_with_traces;
+use circuits::stark::serialization::{Buffer, ProofContainer};
 use circuits::stark::verifier::verify_proof;
-use clap::{arg, Command};
+use clap::{arg, ArgAction, Command};
+use core::program::binary::BinaryProgram;
 use core::program::Program;
+use core::trace::binary::{is_binary_trace, read_binary_trace, write_binary_trace};
 use core::trace::trace::Trace;
+use executor::error::ProcessorError;
 use executor::Process;
 use log::debug;
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::{Field, Field64};
 use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
 use plonky2::util::timing::TimingTree;
 use std::fs::{metadata, File};
@@ -23,6 +33,67 @@ type C = PoseidonGoldilocksConfig;
 #[allow(dead_code)]
 type F = <C as GenericConfig<D>>::F;
 
+fn format_program_hash(hash: &[u64]) -> String {
+    let mut res = String::from("0x");
+    for e in hash {
+        res.push_str(&format!("{:016x}", e));
+    }
+    res
+}
+
+// A program file, either in the binary program format or one `0x...` word per
+// line (without static data).
+fn read_program_file(path: &str) -> BinaryProgram {
+    let bytes = std::fs::read(path).unwrap();
+    if BinaryProgram::is_binary_program(&bytes) {
+        return BinaryProgram::from_bytes(&bytes).expect("invalid binary program file");
+    }
+    let code = String::from_utf8(bytes)
+        .expect("program file must be a binary program or hex lines")
+        .lines()
+        .map(|inst| inst.trim())
+        .filter(|inst| !inst.is_empty())
+        .map(|inst| {
+            u64::from_str_radix(inst.trim_start_matches("0x"), 16)
+                .expect("program word must be hex")
+        })
+        .collect();
+    BinaryProgram {
+        code,
+        ..Default::default()
+    }
+}
+
+// Program with its static data loaded by `process`.
+fn load_program(path: &str, process: &mut Process) -> Program {
+    let binary = read_program_file(path);
+    let mut program: Program = Program {
+        instructions: binary.instructions(),
+        trace: Default::default(),
+    };
+    process
+        .init_memory(&mut program, binary.data_addr, &binary.data)
+        .expect("OlaVM load static data fail");
+    program
+}
+
+fn parse_inputs(args: Option<&String>) -> Result<Vec<GoldilocksField>, ProcessorError> {
+    match args {
+        Some(args) => args
+            .split(',')
+            .map(|arg| {
+                arg.trim()
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|input| *input < GoldilocksField::ORDER)
+                    .map(GoldilocksField::from_canonical_u64)
+                    .ok_or_else(|| ProcessorError::MalformedInput(arg.to_string()))
+            })
+            .collect(),
+        None => Ok(Vec::new()),
+    }
+}
+
 fn main() {
     let matches = Command::new("olavm")
         .about("Olavm cli")
@@ -35,6 +106,16 @@ fn main() {
                 .args(&[
                     arg!(-i --input <INPUT> "Must set a input file for Ola-lang assemble language"),
                     arg!(-o --output <OUTPUT> "Must set a output file for OlaVM executable instruction code"),
+                    arg!(-b --binary "Write the binary program format instead of hex lines")
+                        .action(ArgAction::SetTrue),
+                ])
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("disasm")
+                .about("Print an OlaVM executable instruction code file as assembly")
+                .args(&[
+                    arg!(-i --input <INPUT> "Must set a input file, hex lines or binary program"),
                 ])
                 .arg_required_else_help(true),
         )
@@ -42,8 +123,15 @@ fn main() {
             Command::new("run")
                 .about("Run an program from an input code file")
                 .args(&[
-                    arg!(-i --input <INPUT> "Must set a input file for OlaVM executing"),
+                    arg!(-i --input <INPUT> "Must set a input file for OlaVM executing, hex lines or binary program"),
                     arg!(-o --output <OUTPUT> "Must set a output file for OlaVM executing"),
+                    arg!(-a --args [ARGS] "Comma separated program inputs, loaded into r0, r1, ..."),
+                    arg!(--max_cycles [CYCLES] "Stop executing after this many steps"),
+                    arg!(--gas_limit [GAS] "Stop executing once this much gas is used"),
+                    arg!(--json "Write the trace as JSON instead of the binary trace format, e.g. for infrastructure/trace_analyzer")
+                        .action(ArgAction::SetTrue),
+                    arg!(--zstd "Compress the binary trace with zstd")
+                        .action(ArgAction::SetTrue),
                 ])
                 .arg_required_else_help(true),
         )
@@ -51,15 +139,20 @@ fn main() {
             Command::new("prove")
                 .about("generate proof from executed program")
                 .args(&[
-                    arg!(-i --input <Trace> "Must set a trace file generated by OlaVM executor"),
+                    arg!(-i --input <Trace> "Must set a trace file generated by OlaVM executor, or a binary program to execute"),
                     arg!(-o --output <Proof> "Must set a file for save proofs"),
+                    arg!(-a --args [ARGS] "Comma separated program inputs when proving a binary program"),
                 ])
                 .arg_required_else_help(true),
         )
         .subcommand(
             Command::new("verify")
                 .about("verifiy generated proof")
-                .args(&[arg!(-i --input <Trace> "Must set a proof file generated by OlaVM prover")])
+                .args(&[
+                    arg!(-i --input <Trace> "Must set a proof file generated by OlaVM prover"),
+                    arg!(-p --program [PROGRAM] "Check the proof is about this OlaVM executable instruction code file"),
+                    arg!(--hash [HASH] "Check the proof is about the program with this hash"),
+                ])
                 .arg_required_else_help(true),
         )
         .get_matches();
@@ -83,16 +176,24 @@ fn main() {
                 }
             }
 
-            let raw_insts = encoder.assemble_link(asm_codes);
             let path = sub_matches.get_one::<String>("output").expect("required");
             println!("Output olavm raw codes file path: {}", path);
             let file = File::create(path).unwrap();
             let mut fout = BufWriter::new(file);
 
-            for line in raw_insts {
-                let res = fout.write_all((line + "\n").as_bytes());
-                if res.is_err() {
-                    debug!("file write_all err: {:?}", res);
+            if sub_matches.get_flag("binary") {
+                let program = encoder.assemble_binary(asm_codes);
+                write_binary_program(&program, &mut fout).expect("write binary program fail");
+            } else {
+                let raw_insts = encoder.assemble_link(asm_codes);
+                if !encoder.data.is_empty() {
+                    println!("Static data is only kept in the binary program format (-b)!");
+                }
+                for line in raw_insts {
+                    let res = fout.write_all((line + "\n").as_bytes());
+                    if res.is_err() {
+                        debug!("file write_all err: {:?}", res);
+                    }
                 }
             }
 
@@ -102,54 +203,94 @@ fn main() {
             }
             println!("Asm done!");
         }
+        Some(("disasm", sub_matches)) => {
+            let path = sub_matches.get_one::<String>("input").expect("required");
+            match disassemble(&read_program_file(path)) {
+                Ok(lines) => {
+                    for line in lines {
+                        println!("{}", line);
+                    }
+                }
+                Err(error) => println!("Disassemble failed due to: {:?}", error),
+            }
+        }
         Some(("run", sub_matches)) => {
             let path = sub_matches.get_one::<String>("input").expect("required");
             println!("Input program file path: {}", path);
 
-            let mut program: Program = Program {
-                instructions: Vec::new(),
-                trace: Default::default(),
-            };
-
-            let file = File::open(path).unwrap();
-            let mut input_lines = BufReader::new(file).lines();
-            loop {
-                let inst = input_lines.next();
-                if let Some(inst) = inst {
-                    debug!("inst:{:?}", inst);
-                    program.instructions.push(inst.unwrap());
-                } else {
-                    break;
-                }
-            }
+            let inputs = parse_inputs(sub_matches.get_one::<String>("args"))
+                .expect("program inputs must be field elements");
 
             let mut process = Process::new();
-            process.execute(&mut program).expect("OlaVM execute fail");
+            let mut program = load_program(path, &mut process);
+            if let Some(max_cycles) = sub_matches.get_one::<String>("max_cycles") {
+                process.max_cycles = Some(max_cycles.parse().expect("max cycles must be an u32"));
+            }
+            if let Some(gas_limit) = sub_matches.get_one::<String>("gas_limit") {
+                process.gas_limit = Some(gas_limit.parse().expect("gas limit must be an u64"));
+            }
+            process
+                .execute_with_inputs(&mut program, &inputs)
+                .expect("OlaVM execute fail");
+            println!("Gas used: {}", program.trace.gas_used);
             let path = sub_matches.get_one::<String>("output").expect("required");
             println!("Output trace file path: {}", path);
-            let file = File::create(path).unwrap();
-            serde_json::to_writer(file, &program.trace).unwrap();
+            let mut file = BufWriter::new(File::create(path).unwrap());
+            if sub_matches.get_flag("json") {
+                serde_json::to_writer(&mut file, &program.trace).unwrap();
+            } else {
+                write_binary_trace(&program.trace, sub_matches.get_flag("zstd"), &mut file)
+                    .expect("write binary trace fail");
+            }
+            file.flush().unwrap();
             println!("Run done!");
         }
         Some(("prove", sub_matches)) => {
             let path = sub_matches.get_one::<String>("input").expect("required");
             println!("Input trace file path: {}", path);
 
-            let file = File::open(path).unwrap();
-            let reader = BufReader::new(file);
-
-            let trace: Trace = serde_json::from_reader(reader).unwrap();
-            let program: Program = Program {
-                instructions: trace.raw_binary_instructions.clone(),
-                trace,
+            let bytes = std::fs::read(path).unwrap();
+            let mut all_stark = AllStark::<F, D>::default();
+            let (traces, public_values) = if BinaryProgram::is_binary_program(&bytes) {
+                // The steps go straight into the cpu trace columns.
+                let inputs = parse_inputs(sub_matches.get_one::<String>("args"))
+                    .expect("program inputs must be field elements");
+                let mut process = Process::new();
+                let mut program = load_program(path, &mut process);
+                let mut cpu_trace = CpuTraceBuilder::default();
+                process
+                    .set_inputs(&mut program, &inputs)
+                    .and_then(|_| process.execute_into(&mut program, &mut cpu_trace))
+                    .expect("OlaVM execute fail");
+                generate_traces_with_cpu_trace(&program, cpu_trace, &mut all_stark)
+                    .expect("generate traces fail")
+            } else if is_binary_trace(&bytes) {
+                // A binary trace written by `run`, its steps go straight into
+                // the cpu trace columns too.
+                let mut cpu_trace = CpuTraceBuilder::default();
+                let trace = read_binary_trace(&bytes[..], &mut cpu_trace)
+                    .expect("invalid binary trace file");
+                let program = Program {
+                    instructions: trace.raw_binary_instructions.clone(),
+                    trace,
+                };
+                generate_traces_with_cpu_trace(&program, cpu_trace, &mut all_stark)
+                    .expect("generate traces fail")
+            } else {
+                let trace: Trace = serde_json::from_slice(&bytes).unwrap();
+                let program = Program {
+                    instructions: trace.raw_binary_instructions.clone(),
+                    trace,
+                };
+                generate_traces(program, &mut all_stark).expect("generate traces fail")
             };
 
-            let mut all_stark = AllStark::<F, D>::default();
             let config = StarkConfig::standard_fast_config();
-            let proof = prove::<F, C, D>(
-                &program,
-                &mut all_stark,
+            let proof = prove_with_traces::<F, C, D>(
+                &all_stark,
                 &config,
+                traces,
+                public_values,
                 &mut TimingTree::default(),
             )
             .unwrap();
@@ -158,10 +299,14 @@ fn main() {
             println!("Output proof file path: {}", path);
             let mut file = File::create(path).unwrap();
             let mut buffer = Buffer::new(Vec::new());
-            buffer.write_all_proof(&proof).unwrap();
+            buffer.write_proof_container(&config, &proof).unwrap();
             let se_proof = buffer.bytes();
             file.write_all(&se_proof).unwrap();
 
+            println!(
+                "Program hash: {}",
+                format_program_hash(&proof.public_values.program_hash)
+            );
             println!("Proof size: {} bytes", se_proof.len());
             println!("Prove done!");
         }
@@ -173,19 +318,64 @@ fn main() {
             let mut file = File::open(path).unwrap();
             let metadata = metadata(&path).expect("unable to read metadata");
             let mut buffer = vec![0; metadata.len() as usize];
-            file.read(&mut buffer).expect("buffer overflow");
+            file.read_exact(&mut buffer).expect("buffer overflow");
 
+            // Only accept proofs generated with the config `prove` uses.
+            let expected_config = StarkConfig::standard_fast_config();
             let mut de_buffer = Buffer::new(buffer);
-            let de_proof = de_buffer.read_all_proof::<F, C, D>();
-            if de_proof.is_err() {
-                println!("Deserialize proof failed!");
-                return;
-            }
-            let de_proof = de_proof.unwrap();
+            let container = match de_buffer.read_proof_container::<F, C, D>(&expected_config) {
+                Ok(container) => container,
+                Err(error) => {
+                    println!("Deserialize proof failed: {error}");
+                    return;
+                }
+            };
+            let ProofContainer {
+                config,
+                degree_bits,
+                proof: de_proof,
+            } = container;
+            println!("Proof config: {:?}", config);
+            println!("Degree bits: {:?}", degree_bits);
 
             let all_stark = AllStark::<F, D>::default();
-            let config = StarkConfig::standard_fast_config();
-            match verify_proof(all_stark, de_proof, &config) {
+            let mut expected_hash = None;
+            if let Some(path) = sub_matches.get_one::<String>("program") {
+                println!("Input program file path: {}", path);
+                let binary = read_program_file(path);
+                expected_hash = Some(program_hash::<F>(
+                    &binary.instructions(),
+                    &binary
+                        .static_memory()
+                        .expect("static data out of the address space"),
+                ));
+            }
+            if let Some(hash) = sub_matches.get_one::<String>("hash") {
+                let hash = hash.trim_start_matches("0x");
+                if hash.len() != 64 {
+                    println!("Program hash must be 32 bytes hex!");
+                    return;
+                }
+                let mut elements = [0; 4];
+                for (i, e) in elements.iter_mut().enumerate() {
+                    *e = u64::from_str_radix(&hash[i * 16..(i + 1) * 16], 16)
+                        .expect("Program hash must be 32 bytes hex");
+                }
+                expected_hash = Some(elements);
+            }
+            println!(
+                "Program hash: {}",
+                format_program_hash(&de_proof.public_values.program_hash)
+            );
+            println!(
+                "Initial registers: {:?}",
+                de_proof.public_values.initial_registers
+            );
+            println!(
+                "Final registers: {:?}",
+                de_proof.public_values.final_registers
+            );
+            match verify_proof(all_stark, de_proof, &config, expected_hash) {
                 Err(error) => println!("Verify failed due to: {error}"),
                 _ => println!("Verify succeed!"),
             }
@@ -0,0 +1,169 @@
+/* OlaVM binary program (object file) layout, all integers little endian:
++--------------------------------------------------------------+
+| magic "OLAB" | version u16 | reserved u16                    |
++--------------------------------------------------------------+
+| code_len u64 | data_addr u64 | data_len u64                  |
+| symbol_count u64 | line_count u64                            |
++--------------------------------------------------------------+
+| code: code_len u64 words                                     |
++--------------------------------------------------------------+
+| data: data_len u64 words, loaded from memory addr data_addr  |
++--------------------------------------------------------------+
+| symbols: (name_len u32, name utf8, addr u64) * symbol_count  |
++--------------------------------------------------------------+
+| line info: (pc u64, source line u64) * line_count            |
++--------------------------------------------------------------+
+*/
+
+use crate::program::static_data_end;
+
+pub const BINARY_PROGRAM_MAGIC: [u8; 4] = *b"OLAB";
+pub const BINARY_PROGRAM_VERSION: u16 = 1;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum BinaryProgramError {
+    /// file does not start with the binary program magic
+    BadMagic,
+    /// written by an incompatible format version
+    UnsupportedVersion(u16),
+    /// file ends inside a section
+    Truncated,
+    /// symbol name is not utf8
+    InvalidSymbol,
+    /// static data runs into the prophet memory region
+    DataAddressOverflow,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BinaryProgram {
+    pub code: Vec<u64>,
+    pub data_addr: u64,
+    pub data: Vec<u64>,
+    // (label, address) from the assembler labels
+    pub symbols: Vec<(String, u64)>,
+    // (pc, line in the assembly source)
+    pub line_info: Vec<(u64, u64)>,
+}
+
+impl BinaryProgram {
+    pub fn is_binary_program(bytes: &[u8]) -> bool {
+        bytes.starts_with(&BINARY_PROGRAM_MAGIC)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BinaryProgramError> {
+        if !Self::is_binary_program(bytes) {
+            return Err(BinaryProgramError::BadMagic);
+        }
+        let mut reader = ByteReader {
+            bytes,
+            pos: BINARY_PROGRAM_MAGIC.len(),
+        };
+
+        let version = reader.read_u16()?;
+        if version != BINARY_PROGRAM_VERSION {
+            return Err(BinaryProgramError::UnsupportedVersion(version));
+        }
+        let _reserved = reader.read_u16()?;
+
+        let code_len = reader.read_u64()?;
+        let data_addr = reader.read_u64()?;
+        let data_len = reader.read_u64()?;
+        let symbol_count = reader.read_u64()?;
+        let line_count = reader.read_u64()?;
+        static_data_end(data_addr, data_len).ok_or(BinaryProgramError::DataAddressOverflow)?;
+
+        let code = reader.read_u64_vec(code_len)?;
+        let data = reader.read_u64_vec(data_len)?;
+
+        let mut symbols = Vec::new();
+        for _ in 0..symbol_count {
+            let name_len = reader.read_u32()? as usize;
+            let name = String::from_utf8(reader.read_bytes(name_len)?.to_vec())
+                .map_err(|_| BinaryProgramError::InvalidSymbol)?;
+            symbols.push((name, reader.read_u64()?));
+        }
+
+        let mut line_info = Vec::new();
+        for _ in 0..line_count {
+            line_info.push((reader.read_u64()?, reader.read_u64()?));
+        }
+
+        Ok(Self {
+            code,
+            data_addr,
+            data,
+            symbols,
+            line_info,
+        })
+    }
+
+    /// Code words in the `0x...` form used by `Program.instructions`.
+    pub fn instructions(&self) -> Vec<String> {
+        self.code
+            .iter()
+            .map(|word| format!("0x{word:0>16x}"))
+            .collect()
+    }
+
+    /// Address following the static data.
+    pub fn data_end(&self) -> Result<u64, BinaryProgramError> {
+        static_data_end(self.data_addr, self.data.len() as u64)
+            .ok_or(BinaryProgramError::DataAddressOverflow)
+    }
+
+    /// Static data as the `(addr, value)` list recorded in
+    /// `Trace.static_memory`.
+    pub fn static_memory(&self) -> Result<Vec<(u64, u64)>, BinaryProgramError> {
+        self.data_end()?;
+        Ok(self
+            .data
+            .iter()
+            .enumerate()
+            .map(|(offset, value)| (self.data_addr + offset as u64, *value))
+            .collect())
+    }
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], BinaryProgramError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(BinaryProgramError::Truncated)?;
+        let res = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(res)
+    }
+
+    fn read_u16(&mut self) -> Result<u16, BinaryProgramError> {
+        Ok(u16::from_le_bytes(self.read_bytes(2)?.try_into().unwrap()))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, BinaryProgramError> {
+        Ok(u32::from_le_bytes(self.read_bytes(4)?.try_into().unwrap()))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, BinaryProgramError> {
+        Ok(u64::from_le_bytes(self.read_bytes(8)?.try_into().unwrap()))
+    }
+
+    fn read_u64_vec(&mut self, len: u64) -> Result<Vec<u64>, BinaryProgramError> {
+        // check the length against the input before allocating
+        let bytes = self.read_bytes(
+            usize::try_from(len)
+                .ok()
+                .and_then(|len| len.checked_mul(8))
+                .ok_or(BinaryProgramError::Truncated)?,
+        )?;
+        Ok(bytes
+            .chunks_exact(8)
+            .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+            .collect())
+    }
+}
@@ -0,0 +1,74 @@
+use crate::program::instruction::{Opcode, *};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// word without a known opcode bit
+    UnknownOpcode(u64),
+    /// imm flag set but no immediate word follows
+    MissingImmediate(u64),
+    /// static data at this address runs into the prophet memory region
+    DataAddressOverflow(u64),
+}
+
+fn get_index(data: u64) -> u8 {
+    let mut i: i128 = 63;
+    while i >= 0 {
+        if ((data >> i) & 1) != 0 {
+            return i as u8;
+        }
+        i -= 1;
+    }
+    0xff
+}
+
+/// Opcode of an instruction word.
+pub fn decode_opcode(raw_inst: u64) -> Result<Opcode, DecodeError> {
+    let opcode_index = get_index(raw_inst & OPCODE_FIELD_BITS_MASK);
+    Opcode::try_from(opcode_index).map_err(|_| DecodeError::UnknownOpcode(raw_inst))
+}
+
+/// Decode an instruction word into its assembly text and length in words,
+/// `imm` is the word following it in the program if any.
+pub fn decode_instruction_word(
+    raw_inst: u64,
+    imm: Option<u64>,
+) -> Result<(String, u64), DecodeError> {
+    let mut step = NO_IMM_INSTRUCTION_LEN;
+    let op_code = decode_opcode(raw_inst)?;
+
+    let imm_flag = raw_inst >> IMM_FLAG_FIELD_BIT_POSITION & IMM_FLAG_FIELD_BITS_MASK;
+    let reg0 = get_index(raw_inst >> REG0_FIELD_BIT_POSITION & REG_FIELD_BITS_MASK);
+    let reg2 = get_index(raw_inst >> REG1_FIELD_BIT_POSITION & REG_FIELD_BITS_MASK);
+    let reg1 = get_index(raw_inst >> REG2_FIELD_BIT_POSITION & REG_FIELD_BITS_MASK);
+
+    // the last operand, an immediate or op1 register
+    let mut op1 = || {
+        if imm_flag == 1 {
+            step = IMM_INSTRUCTION_LEN;
+            imm.map(|imm| imm.to_string())
+                .ok_or(DecodeError::MissingImmediate(raw_inst))
+        } else {
+            Ok(format!("r{reg2}"))
+        }
+    };
+
+    let instruction = match op_code {
+        Opcode::ADD | Opcode::MUL | Opcode::SUB | Opcode::AND | Opcode::OR | Opcode::XOR => {
+            format!("{} r{} r{} {}", op_code, reg0, reg1, op1()?)
+        }
+        Opcode::EQ | Opcode::ASSERT | Opcode::NEQ | Opcode::GTE => {
+            format!("{} r{} {}", op_code, reg1, op1()?)
+        }
+        Opcode::MOV | Opcode::MLOAD | Opcode::NOT | Opcode::PROPHET => {
+            format!("{} r{} {}", op_code, reg0, op1()?)
+        }
+        Opcode::MSTORE | Opcode::PSDN | Opcode::KECCAK => {
+            format!("{} {} r{}", op_code, op1()?, reg1)
+        }
+        Opcode::JMP | Opcode::CJMP | Opcode::CALL | Opcode::RC | Opcode::ECDSA => {
+            format!("{} {}", op_code, op1()?)
+        }
+        Opcode::RET | Opcode::END => op_code.to_string(),
+    };
+    Ok((instruction, step))
+}
@@ -143,6 +143,57 @@ pub struct Gte {
     pub a: ImmediateOrRegName,
 }
 
+/// Hash the 8 words from address `[ri]` and write the 4 words of the digest
+/// from address `a`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Poseidon {
+    pub a: ImmediateOrRegName,
+    pub ri: u8,
+}
+
+/// Verify the secp256k1 signature read from the 40 words at address `a` and
+/// set the flag if it is valid. The message hash, the public key x and y and
+/// the signature r and s follow each other as 8 u32 words, least significant
+/// first.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Ecdsa {
+    pub a: ImmediateOrRegName,
+}
+
+/// Hash with Keccak-256 the buffer at address `[ri]` and write the 8 words of
+/// the digest from address `a`. The buffer is its length in bytes followed by
+/// the bytes packed in u32 words, little endian. The digest is packed the same
+/// way.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Keccak {
+    pub a: ImmediateOrRegName,
+    pub ri: u8,
+}
+
+/// Run the host prophet `a` on the argument registers r1.., write its
+/// outputs to the next free words of the prophet memory region and set `ri`
+/// to the address of the first one. Nothing proves the outputs, the program
+/// has to check them.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Prophet {
+    pub ri: u8,
+    pub a: ImmediateOrRegName,
+}
+
+// Ids of the built-in prophets.
+/// Field inverse of r1.
+pub const PROPHET_INV: u64 = 1;
+/// u32 quotient and remainder of r1 by r2.
+pub const PROPHET_DIVMOD: u64 = 2;
+/// A field square root of r1.
+pub const PROPHET_SQRT: u64 = 3;
+/// Low u32 word and carry of the sum of u32 r1 and r2.
+pub const PROPHET_ADD_U32: u64 = 4;
+/// Low u32 word and borrow of u32 r1 minus r2.
+pub const PROPHET_SUB_U32: u64 = 5;
+/// Low and high u32 words of the product of u32 r1 and r2.
+pub const PROPHET_MUL_U32: u64 = 6;
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum Instruction {
     MOV(Mov),
@@ -164,10 +215,45 @@ pub enum Instruction {
     NOT(Not),
     NEQ(Neq),
     GTE(Gte),
+    POSEIDON(Poseidon),
+    ECDSA(Ecdsa),
+    KECCAK(Keccak),
+    PROPHET(Prophet),
     // todo: for test, delete next version
     SUB(Sub),
 }
 
+impl Instruction {
+    pub fn opcode(&self) -> Opcode {
+        match self {
+            Instruction::MOV(_) => Opcode::MOV,
+            Instruction::EQ(_) => Opcode::EQ,
+            Instruction::CJMP(_) => Opcode::CJMP,
+            Instruction::JMP(_) => Opcode::JMP,
+            Instruction::ADD(_) => Opcode::ADD,
+            Instruction::MUL(_) => Opcode::MUL,
+            Instruction::RET(_) => Opcode::RET,
+            Instruction::CALL(_) => Opcode::CALL,
+            Instruction::MSTORE(_) => Opcode::MSTORE,
+            Instruction::MLOAD(_) => Opcode::MLOAD,
+            Instruction::ASSERT(_) => Opcode::ASSERT,
+            Instruction::END(_) => Opcode::END,
+            Instruction::RANGE(_) => Opcode::RC,
+            Instruction::AND(_) => Opcode::AND,
+            Instruction::OR(_) => Opcode::OR,
+            Instruction::XOR(_) => Opcode::XOR,
+            Instruction::NOT(_) => Opcode::NOT,
+            Instruction::NEQ(_) => Opcode::NEQ,
+            Instruction::GTE(_) => Opcode::GTE,
+            Instruction::POSEIDON(_) => Opcode::PSDN,
+            Instruction::ECDSA(_) => Opcode::ECDSA,
+            Instruction::KECCAK(_) => Opcode::KECCAK,
+            Instruction::PROPHET(_) => Opcode::PROPHET,
+            Instruction::SUB(_) => Opcode::SUB,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, TryFromPrimitive)]
 #[repr(u8)]
 pub enum Opcode {
@@ -190,8 +276,14 @@ pub enum Opcode {
     NOT = 18,
     NEQ = 17,
     GTE = 16,
-    // todo: for test, delete next version
-    SUB = 15,
+    // builtins
+    PSDN = 15,
+    ECDSA = 14,
+    KECCAK = 13,
+    PROPHET = 12,
+    // todo: for test, delete next version, below the bits of the cpu
+    // selectors
+    SUB = 11,
 }
 
 impl fmt::Display for Opcode {
@@ -216,6 +308,10 @@ impl fmt::Display for Opcode {
             Opcode::NOT => write!(f, "not"),
             Opcode::NEQ => write!(f, "neq"),
             Opcode::GTE => write!(f, "gte"),
+            Opcode::PSDN => write!(f, "poseidon"),
+            Opcode::ECDSA => write!(f, "ecdsa"),
+            Opcode::KECCAK => write!(f, "keccak"),
+            Opcode::PROPHET => write!(f, "prophet"),
             Opcode::SUB => write!(f, "sub"),
         }
     }
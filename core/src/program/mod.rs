@@ -1,11 +1,27 @@
 use crate::trace::trace::Trace;
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::Field64;
 use serde::{Deserialize, Serialize};
 
+pub mod binary;
+pub mod decoder;
 pub mod instruction;
 
 /// fixme: use 9 registers
 pub const REGISTER_NUM: usize = 9;
 
+pub const REGION_SPAN: u64 = (1 << 32) - 1;
+// The prophet region is the top of the address space, prophets write it from
+// its first word up.
+pub const PROPHET_REGION_START: u64 = GoldilocksField::ORDER - 1 - REGION_SPAN;
+
+/// Address following `len` static data words loaded at `addr`, if they all
+/// lie below the prophet region.
+pub fn static_data_end(addr: u64, len: u64) -> Option<u64> {
+    addr.checked_add(len)
+        .filter(|end| *end <= PROPHET_REGION_START)
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
 pub struct Program {
     pub instructions: Vec<String>,
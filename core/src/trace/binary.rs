@@ -0,0 +1,588 @@
+/* OlaVM binary trace layout, all integers little endian:
++--------------------------------------------------------------+
+| magic "OLAT" | version u16 | flags u16                       |
++--------------------------------------------------------------+
+| body, zstd compressed when flags has BINARY_TRACE_ZSTD set:  |
+|   code_len u64 | input_len u64 | static_len u64              |
+|   step_len u64 | memory_len u64 | rangecheck_len u64         |
+|   bitwise_len u64 | cmp_len u64 | poseidon_len u64           |
+|   ecdsa_len u64 | keccak_len u64 | gas_used u64              |
+|   code: code_len u64 words                                   |
+|   inputs: input_len field elements                           |
+|   static data: (addr u64, value u64) * static_len            |
+|   steps: STEP_WORDS u64 * step_len                           |
+|   memory rows: MEMORY_ROW_WORDS u64 * memory_len             |
+|   rangecheck rows: RANGECHECK_ROW_WORDS u64 * rangecheck_len |
+|   bitwise rows: BITWISE_ROW_WORDS u64 * bitwise_len          |
+|   cmp rows: CMP_ROW_WORDS u64 * cmp_len                      |
+|   poseidon rows: POSEIDON_ROW_WORDS u64 * poseidon_len       |
+|   ecdsa rows: ECDSA_ROW_WORDS u64 * ecdsa_len                |
+|   keccak rows: KECCAK_ROW_WORDS u64 and the input words,     |
+|     one per 4 bytes of len, * keccak_len                     |
++--------------------------------------------------------------+
+Field elements are written in canonical form. The text instructions of
+`Trace.instructions` and `Trace.raw_instructions` are only used for debugging
+and are not kept, export the trace as JSON to inspect them.
+*/
+
+use crate::program::REGISTER_NUM;
+use crate::trace::trace::{
+    BitwiseCombinedRow, CmpRow, EcdsaRow, KeccakRow, MemoryTraceCell, PoseidonRow, RangeCheckRow,
+    RegisterSelector, Step, StepSink, Trace, ECDSA_INPUT_NUM, ECDSA_WORD_NUM, KECCAK_OUTPUT_NUM,
+    POSEIDON_INPUT_NUM, POSEIDON_OUTPUT_NUM,
+};
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::{Field64, PrimeField64};
+use std::io::{self, Read, Write};
+
+pub const BINARY_TRACE_MAGIC: [u8; 4] = *b"OLAT";
+pub const BINARY_TRACE_VERSION: u16 = 4;
+/// The body is zstd compressed.
+pub const BINARY_TRACE_ZSTD: u16 = 1;
+
+const STEP_WORDS: usize = 12 + 4 * REGISTER_NUM;
+const MEMORY_ROW_WORDS: usize = 19;
+const RANGECHECK_ROW_WORDS: usize = 6;
+const BITWISE_ROW_WORDS: usize = 16;
+const CMP_ROW_WORDS: usize = 6;
+const POSEIDON_ROW_WORDS: usize = 3 + POSEIDON_INPUT_NUM + POSEIDON_OUTPUT_NUM;
+const ECDSA_ROW_WORDS: usize = 3 + ECDSA_INPUT_NUM;
+const KECCAK_ROW_WORDS: usize = 4 + KECCAK_OUTPUT_NUM;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum BinaryTraceError {
+    /// file does not start with the binary trace magic
+    BadMagic,
+    /// written by an incompatible format version
+    UnsupportedVersion(u16),
+    /// unknown flags, or zstd body without the `zstd` feature
+    UnsupportedFlags(u16),
+    /// file ends inside a section
+    Truncated,
+    /// field element not in canonical form, or out of range value
+    InvalidValue(u64),
+    /// the compressed body can't be decoded
+    Compression(String),
+}
+
+pub fn is_binary_trace(bytes: &[u8]) -> bool {
+    bytes.starts_with(&BINARY_TRACE_MAGIC)
+}
+
+// Serialize `trace` in the layout above, compressing the body with zstd if
+// `compress` is set.
+pub fn write_binary_trace<W: Write>(trace: &Trace, compress: bool, out: &mut W) -> io::Result<()> {
+    out.write_all(&BINARY_TRACE_MAGIC)?;
+    out.write_all(&BINARY_TRACE_VERSION.to_le_bytes())?;
+    if compress {
+        out.write_all(&BINARY_TRACE_ZSTD.to_le_bytes())?;
+        write_compressed_body(trace, out)
+    } else {
+        out.write_all(&0u16.to_le_bytes())?;
+        write_body(trace, out)
+    }
+}
+
+#[cfg(feature = "zstd")]
+fn write_compressed_body<W: Write>(trace: &Trace, out: &mut W) -> io::Result<()> {
+    let mut encoder = zstd::Encoder::new(out, 0)?;
+    write_body(trace, &mut encoder)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "zstd"))]
+fn write_compressed_body<W: Write>(_trace: &Trace, _out: &mut W) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "zstd compression needs the `zstd` feature",
+    ))
+}
+
+// Read a trace written by `write_binary_trace`. Its steps are handed to
+// `steps` as they are read instead of being kept in `Trace.exec`, e.g. to
+// write them straight into the cpu trace columns.
+pub fn read_binary_trace<R: Read>(
+    mut input: R,
+    steps: &mut dyn StepSink,
+) -> Result<Trace, BinaryTraceError> {
+    let mut magic = [0; 4];
+    input
+        .read_exact(&mut magic)
+        .map_err(|_| BinaryTraceError::BadMagic)?;
+    if magic != BINARY_TRACE_MAGIC {
+        return Err(BinaryTraceError::BadMagic);
+    }
+    let mut reader = WordReader(input);
+    let version = reader.read_u16()?;
+    if version != BINARY_TRACE_VERSION {
+        return Err(BinaryTraceError::UnsupportedVersion(version));
+    }
+    match reader.read_u16()? {
+        0 => read_body(&mut reader, steps),
+        BINARY_TRACE_ZSTD => read_compressed_body(reader.0, steps),
+        flags => Err(BinaryTraceError::UnsupportedFlags(flags)),
+    }
+}
+
+#[cfg(feature = "zstd")]
+fn read_compressed_body<R: Read>(
+    input: R,
+    steps: &mut dyn StepSink,
+) -> Result<Trace, BinaryTraceError> {
+    let decoder =
+        zstd::Decoder::new(input).map_err(|e| BinaryTraceError::Compression(e.to_string()))?;
+    read_body(&mut WordReader(decoder), steps)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn read_compressed_body<R: Read>(
+    _input: R,
+    _steps: &mut dyn StepSink,
+) -> Result<Trace, BinaryTraceError> {
+    Err(BinaryTraceError::UnsupportedFlags(BINARY_TRACE_ZSTD))
+}
+
+fn write_body<W: Write>(trace: &Trace, out: &mut W) -> io::Result<()> {
+    let code = trace
+        .raw_binary_instructions
+        .iter()
+        .map(|word| {
+            u64::from_str_radix(word.trim().trim_start_matches("0x"), 16)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, word.clone()))
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let mut words = vec![
+        code.len() as u64,
+        trace.inputs.len() as u64,
+        trace.static_memory.len() as u64,
+        trace.exec.len() as u64,
+        trace.memory.len() as u64,
+        trace.builtin_rangecheck.len() as u64,
+        trace.builtin_bitwise_combined.len() as u64,
+        trace.builtin_cmp.len() as u64,
+        trace.builtin_poseidon.len() as u64,
+        trace.builtin_ecdsa.len() as u64,
+        trace.builtin_keccak.len() as u64,
+        trace.gas_used,
+    ];
+    words.extend(code);
+    words.extend(trace.inputs.iter().map(|e| e.to_canonical_u64()));
+    words.extend(
+        trace
+            .static_memory
+            .iter()
+            .flat_map(|(addr, value)| [*addr, *value]),
+    );
+    write_words(out, &words)?;
+
+    // Rows are written one by one, the trace may be large.
+    for step in &trace.exec {
+        write_words(out, &step_words(step))?;
+    }
+    for cell in &trace.memory {
+        write_words(out, &memory_row_words(cell))?;
+    }
+    for row in &trace.builtin_rangecheck {
+        write_words(out, &rangecheck_row_words(row))?;
+    }
+    for row in &trace.builtin_bitwise_combined {
+        write_words(out, &bitwise_row_words(row))?;
+    }
+    for row in &trace.builtin_cmp {
+        write_words(out, &cmp_row_words(row))?;
+    }
+    for row in &trace.builtin_poseidon {
+        write_words(out, &poseidon_row_words(row))?;
+    }
+    for row in &trace.builtin_ecdsa {
+        write_words(out, &ecdsa_row_words(row))?;
+    }
+    for row in &trace.builtin_keccak {
+        write_words(out, &keccak_row_words(row))?;
+    }
+    Ok(())
+}
+
+fn read_body<R: Read>(
+    reader: &mut WordReader<R>,
+    steps: &mut dyn StepSink,
+) -> Result<Trace, BinaryTraceError> {
+    let [code_len, input_len, static_len, step_len, memory_len, rangecheck_len, bitwise_len, cmp_len, poseidon_len, ecdsa_len, keccak_len, gas_used] =
+        reader.read_words::<12>()?;
+
+    let mut trace = Trace {
+        gas_used,
+        ..Default::default()
+    };
+    for _ in 0..code_len {
+        let word = reader.read_u64()?;
+        trace
+            .raw_binary_instructions
+            .push(format!("0x{word:0>16x}"));
+    }
+    for _ in 0..input_len {
+        trace.inputs.push(reader.read_field()?);
+    }
+    for _ in 0..static_len {
+        let [addr, value] = reader.read_words()?;
+        trace.static_memory.push((addr, value));
+    }
+    for _ in 0..step_len {
+        steps.push_step(step_from_words(reader.read_fields()?)?);
+    }
+    for _ in 0..memory_len {
+        trace
+            .memory
+            .push(memory_row_from_words(reader.read_fields()?));
+    }
+    for _ in 0..rangecheck_len {
+        trace
+            .builtin_rangecheck
+            .push(rangecheck_row_from_words(reader.read_fields()?));
+    }
+    for _ in 0..bitwise_len {
+        trace
+            .builtin_bitwise_combined
+            .push(bitwise_row_from_words(reader.read_fields()?)?);
+    }
+    for _ in 0..cmp_len {
+        trace
+            .builtin_cmp
+            .push(cmp_row_from_words(reader.read_fields()?));
+    }
+    for _ in 0..poseidon_len {
+        trace
+            .builtin_poseidon
+            .push(poseidon_row_from_words(reader.read_fields()?));
+    }
+    for _ in 0..ecdsa_len {
+        trace
+            .builtin_ecdsa
+            .push(ecdsa_row_from_words(reader.read_fields()?)?);
+    }
+    for _ in 0..keccak_len {
+        let row = keccak_row_from_words(reader)?;
+        trace.builtin_keccak.push(row);
+    }
+    Ok(trace)
+}
+
+fn write_words<W: Write>(out: &mut W, words: &[u64]) -> io::Result<()> {
+    for word in words {
+        out.write_all(&word.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+struct WordReader<R: Read>(R);
+
+impl<R: Read> WordReader<R> {
+    fn read_bytes<const N: usize>(&mut self) -> Result<[u8; N], BinaryTraceError> {
+        let mut buf = [0; N];
+        self.0.read_exact(&mut buf).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => BinaryTraceError::Truncated,
+            _ => BinaryTraceError::Compression(e.to_string()),
+        })?;
+        Ok(buf)
+    }
+
+    fn read_u16(&mut self) -> Result<u16, BinaryTraceError> {
+        Ok(u16::from_le_bytes(self.read_bytes()?))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, BinaryTraceError> {
+        Ok(u64::from_le_bytes(self.read_bytes()?))
+    }
+
+    fn read_words<const N: usize>(&mut self) -> Result<[u64; N], BinaryTraceError> {
+        let mut words = [0; N];
+        for word in words.iter_mut() {
+            *word = self.read_u64()?;
+        }
+        Ok(words)
+    }
+
+    fn read_field(&mut self) -> Result<GoldilocksField, BinaryTraceError> {
+        let word = self.read_u64()?;
+        if word >= GoldilocksField::ORDER {
+            return Err(BinaryTraceError::InvalidValue(word));
+        }
+        Ok(GoldilocksField(word))
+    }
+
+    fn read_fields<const N: usize>(&mut self) -> Result<[GoldilocksField; N], BinaryTraceError> {
+        let mut fields = [GoldilocksField(0); N];
+        for field in fields.iter_mut() {
+            *field = self.read_field()?;
+        }
+        Ok(fields)
+    }
+}
+
+fn canonical<const N: usize>(fields: [GoldilocksField; N]) -> [u64; N] {
+    fields.map(|e| e.to_canonical_u64())
+}
+
+fn step_words(step: &Step) -> Vec<u64> {
+    let selector = &step.register_selector;
+    let mut words = vec![step.clk as u64, step.pc, step.flag as u64];
+    words.extend(canonical([
+        step.instruction,
+        step.immediate_data,
+        step.opcode,
+        step.op1_imm,
+        selector.op0,
+        selector.op1,
+        selector.dst,
+        selector.aux0,
+        selector.aux1,
+    ]));
+    words.extend(canonical(step.regs));
+    words.extend(canonical(selector.op0_reg_sel));
+    words.extend(canonical(selector.op1_reg_sel));
+    words.extend(canonical(selector.dst_reg_sel));
+    debug_assert_eq!(words.len(), STEP_WORDS);
+    words
+}
+
+fn step_from_words(words: [GoldilocksField; STEP_WORDS]) -> Result<Step, BinaryTraceError> {
+    let word = |i: usize| words[i].0;
+    let regs = |i: usize| -> [GoldilocksField; REGISTER_NUM] {
+        words[i..i + REGISTER_NUM].try_into().unwrap()
+    };
+    let clk = u32::try_from(word(0)).map_err(|_| BinaryTraceError::InvalidValue(word(0)))?;
+    let flag = match word(2) {
+        0 => false,
+        1 => true,
+        flag => return Err(BinaryTraceError::InvalidValue(flag)),
+    };
+    Ok(Step {
+        clk,
+        pc: word(1),
+        flag,
+        instruction: words[3],
+        immediate_data: words[4],
+        opcode: words[5],
+        op1_imm: words[6],
+        register_selector: RegisterSelector {
+            op0: words[7],
+            op1: words[8],
+            dst: words[9],
+            aux0: words[10],
+            aux1: words[11],
+            op0_reg_sel: regs(12 + REGISTER_NUM),
+            op1_reg_sel: regs(12 + 2 * REGISTER_NUM),
+            dst_reg_sel: regs(12 + 3 * REGISTER_NUM),
+        },
+        regs: regs(12),
+    })
+}
+
+fn memory_row_words(cell: &MemoryTraceCell) -> [u64; MEMORY_ROW_WORDS] {
+    canonical([
+        cell.addr,
+        cell.clk,
+        cell.is_rw,
+        cell.op,
+        cell.is_write,
+        cell.diff_addr,
+        cell.diff_addr_inv,
+        cell.diff_clk,
+        cell.diff_addr_cond,
+        cell.filter_looked_for_main,
+        cell.rw_addr_unchanged,
+        cell.region_prophet,
+        cell.region_poseidon,
+        cell.region_ecdsa,
+        cell.value,
+        cell.filter_looking_rc,
+        cell.rc_value,
+        cell.filter_init,
+        cell.filter_final,
+    ])
+}
+
+fn memory_row_from_words(words: [GoldilocksField; MEMORY_ROW_WORDS]) -> MemoryTraceCell {
+    let [addr, clk, is_rw, op, is_write, diff_addr, diff_addr_inv, diff_clk, diff_addr_cond, filter_looked_for_main, rw_addr_unchanged, region_prophet, region_poseidon, region_ecdsa, value, filter_looking_rc, rc_value, filter_init, filter_final] =
+        words;
+    MemoryTraceCell {
+        addr,
+        clk,
+        is_rw,
+        op,
+        is_write,
+        diff_addr,
+        diff_addr_inv,
+        diff_clk,
+        diff_addr_cond,
+        filter_looked_for_main,
+        rw_addr_unchanged,
+        region_prophet,
+        region_poseidon,
+        region_ecdsa,
+        value,
+        filter_looking_rc,
+        rc_value,
+        filter_init,
+        filter_final,
+    }
+}
+
+fn rangecheck_row_words(row: &RangeCheckRow) -> [u64; RANGECHECK_ROW_WORDS] {
+    canonical([
+        row.val,
+        row.limb_lo,
+        row.limb_hi,
+        row.filter_looked_for_memory,
+        row.filter_looked_for_cpu,
+        row.filter_looked_for_comparison,
+    ])
+}
+
+fn rangecheck_row_from_words(words: [GoldilocksField; RANGECHECK_ROW_WORDS]) -> RangeCheckRow {
+    let [val, limb_lo, limb_hi, filter_looked_for_memory, filter_looked_for_cpu, filter_looked_for_comparison] =
+        words;
+    RangeCheckRow {
+        val,
+        limb_lo,
+        limb_hi,
+        filter_looked_for_memory,
+        filter_looked_for_cpu,
+        filter_looked_for_comparison,
+    }
+}
+
+fn bitwise_row_words(row: &BitwiseCombinedRow) -> [u64; BITWISE_ROW_WORDS] {
+    let mut words = [0; BITWISE_ROW_WORDS];
+    words[0] = row.bitwise_tag as u64;
+    words[1..].copy_from_slice(&canonical([
+        row.op0, row.op1, row.res, row.op0_0, row.op0_1, row.op0_2, row.op0_3, row.op1_0,
+        row.op1_1, row.op1_2, row.op1_3, row.res_0, row.res_1, row.res_2, row.res_3,
+    ]));
+    words
+}
+
+fn bitwise_row_from_words(
+    words: [GoldilocksField; BITWISE_ROW_WORDS],
+) -> Result<BitwiseCombinedRow, BinaryTraceError> {
+    let [bitwise_tag, op0, op1, res, op0_0, op0_1, op0_2, op0_3, op1_0, op1_1, op1_2, op1_3, res_0, res_1, res_2, res_3] =
+        words;
+    Ok(BitwiseCombinedRow {
+        bitwise_tag: u32::try_from(bitwise_tag.0)
+            .map_err(|_| BinaryTraceError::InvalidValue(bitwise_tag.0))?,
+        op0,
+        op1,
+        res,
+        op0_0,
+        op0_1,
+        op0_2,
+        op0_3,
+        op1_0,
+        op1_1,
+        op1_2,
+        op1_3,
+        res_0,
+        res_1,
+        res_2,
+        res_3,
+    })
+}
+
+fn cmp_row_words(row: &CmpRow) -> [u64; CMP_ROW_WORDS] {
+    canonical([
+        row.op0,
+        row.op1,
+        row.diff,
+        row.diff_limb_lo,
+        row.diff_limb_hi,
+        row.filter_looked_for_range_check,
+    ])
+}
+
+fn cmp_row_from_words(words: [GoldilocksField; CMP_ROW_WORDS]) -> CmpRow {
+    let [op0, op1, diff, diff_limb_lo, diff_limb_hi, filter_looked_for_range_check] = words;
+    CmpRow {
+        op0,
+        op1,
+        diff,
+        diff_limb_lo,
+        diff_limb_hi,
+        filter_looked_for_range_check,
+    }
+}
+
+fn poseidon_row_words(row: &PoseidonRow) -> Vec<u64> {
+    let mut words = vec![row.clk, row.input_addr, row.output_addr];
+    words.extend(row.input);
+    words.extend(row.output);
+    words.iter().map(|e| e.to_canonical_u64()).collect()
+}
+
+fn poseidon_row_from_words(words: [GoldilocksField; POSEIDON_ROW_WORDS]) -> PoseidonRow {
+    let output_start = 3 + POSEIDON_INPUT_NUM;
+    PoseidonRow {
+        clk: words[0],
+        input_addr: words[1],
+        output_addr: words[2],
+        input: words[3..output_start].try_into().unwrap(),
+        output: words[output_start..].try_into().unwrap(),
+    }
+}
+
+fn ecdsa_row_words(row: &EcdsaRow) -> Vec<u64> {
+    let mut words = vec![row.clk, row.addr];
+    words.extend(row.input());
+    words.push(row.flag);
+    words.iter().map(|e| e.to_canonical_u64()).collect()
+}
+
+fn ecdsa_row_from_words(
+    words: [GoldilocksField; ECDSA_ROW_WORDS],
+) -> Result<EcdsaRow, BinaryTraceError> {
+    let flag = words[ECDSA_ROW_WORDS - 1];
+    if flag.0 > 1 {
+        return Err(BinaryTraceError::InvalidValue(flag.0));
+    }
+    let input = |i: usize| {
+        let start = 2 + i * ECDSA_WORD_NUM;
+        words[start..start + ECDSA_WORD_NUM].try_into().unwrap()
+    };
+    Ok(EcdsaRow {
+        clk: words[0],
+        addr: words[1],
+        z: input(0),
+        qx: input(1),
+        qy: input(2),
+        r: input(3),
+        s: input(4),
+        flag,
+    })
+}
+
+fn keccak_row_words(row: &KeccakRow) -> Vec<u64> {
+    let mut words = vec![row.clk, row.input_addr, row.output_addr, row.len];
+    words.extend(row.output);
+    words.extend(&row.input);
+    words.iter().map(|e| e.to_canonical_u64()).collect()
+}
+
+fn keccak_row_from_words<R: Read>(
+    reader: &mut WordReader<R>,
+) -> Result<KeccakRow, BinaryTraceError> {
+    let words: [GoldilocksField; KECCAK_ROW_WORDS] = reader.read_fields()?;
+    let len = words[3];
+    let byte_len = u32::try_from(len.0).map_err(|_| BinaryTraceError::InvalidValue(len.0))?;
+    let input = (0..(byte_len as u64).div_ceil(4))
+        .map(|_| reader.read_field())
+        .collect::<Result<_, _>>()?;
+    Ok(KeccakRow {
+        clk: words[0],
+        input_addr: words[1],
+        output_addr: words[2],
+        len,
+        input,
+        output: words[4..].try_into().unwrap(),
+    })
+}
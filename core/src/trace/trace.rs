@@ -3,6 +3,7 @@ use crate::program::REGISTER_NUM;
 use crate::utils::split_limbs_from_field;
 use crate::utils::split_u16_limbs_from_field;
 use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::Field;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -71,6 +72,9 @@ pub struct MemoryCell {
     pub region_poseidon: GoldilocksField,
     pub region_ecdsa: GoldilocksField,
     pub value: GoldilocksField,
+    // static data cell written before the first step
+    #[serde(default)]
+    pub filter_init: GoldilocksField,
 }
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
@@ -92,6 +96,12 @@ pub struct MemoryTraceCell {
     pub value: GoldilocksField,
     pub filter_looking_rc: GoldilocksField,
     pub rc_value: GoldilocksField,
+    #[serde(default)]
+    pub filter_init: GoldilocksField,
+    // last cell of a read write address, its value is carried to the next
+    // segment
+    #[serde(default)]
+    pub filter_final: GoldilocksField,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -129,6 +139,18 @@ pub struct Step {
     pub register_selector: RegisterSelector,
 }
 
+/// Receives the steps in execution order, see `Process::execute_into`. The
+/// steps of `Trace.exec` are collected by `Vec<Step>`.
+pub trait StepSink {
+    fn push_step(&mut self, step: Step);
+}
+
+impl StepSink for Vec<Step> {
+    fn push_step(&mut self, step: Step) {
+        self.push(step);
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RangeCheckRow {
     //pub tag: u32,
@@ -179,21 +201,160 @@ pub struct CmpRow {
     pub filter_looked_for_range_check: GoldilocksField,
 }
 
+pub const POSEIDON_INPUT_NUM: usize = 8;
+pub const POSEIDON_OUTPUT_NUM: usize = 4;
+
+// One poseidon instruction: the digest of the words read from `input_addr` is
+// written from `output_addr`, both at `clk`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoseidonRow {
+    pub clk: GoldilocksField,
+    pub input_addr: GoldilocksField,
+    pub output_addr: GoldilocksField,
+    pub input: [GoldilocksField; POSEIDON_INPUT_NUM],
+    pub output: [GoldilocksField; POSEIDON_OUTPUT_NUM],
+}
+
+// u32 words of a 256 bit ecdsa input, least significant first.
+pub const ECDSA_WORD_NUM: usize = 8;
+// message hash, public key x and y, signature r and s
+pub const ECDSA_INPUT_NUM: usize = 5 * ECDSA_WORD_NUM;
+
+// One ecdsa instruction: the inputs are read from `addr` at `clk` and `flag`
+// is one for a valid signature.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EcdsaRow {
+    pub clk: GoldilocksField,
+    pub addr: GoldilocksField,
+    pub z: [GoldilocksField; ECDSA_WORD_NUM],
+    pub qx: [GoldilocksField; ECDSA_WORD_NUM],
+    pub qy: [GoldilocksField; ECDSA_WORD_NUM],
+    pub r: [GoldilocksField; ECDSA_WORD_NUM],
+    pub s: [GoldilocksField; ECDSA_WORD_NUM],
+    pub flag: GoldilocksField,
+}
+
+impl EcdsaRow {
+    // The inputs in memory order.
+    pub fn input(&self) -> [GoldilocksField; ECDSA_INPUT_NUM] {
+        let mut input = [GoldilocksField::ZERO; ECDSA_INPUT_NUM];
+        for (chunk, words) in input
+            .chunks_mut(ECDSA_WORD_NUM)
+            .zip([self.z, self.qx, self.qy, self.r, self.s])
+        {
+            chunk.copy_from_slice(&words);
+        }
+        input
+    }
+}
+
+// u32 words of a keccak digest
+pub const KECCAK_OUTPUT_NUM: usize = 8;
+
+// One keccak instruction: `len` and the `input` words are read from
+// `input_addr`, the digest is written from `output_addr`, all at `clk`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeccakRow {
+    pub clk: GoldilocksField,
+    pub input_addr: GoldilocksField,
+    pub output_addr: GoldilocksField,
+    // input length in bytes
+    pub len: GoldilocksField,
+    // the bytes packed in u32 words
+    pub input: Vec<GoldilocksField>,
+    pub output: [GoldilocksField; KECCAK_OUTPUT_NUM],
+}
+
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Trace {
     //(inst_asm_str, imm_flag, step, inst_encode, imm_val)
     pub instructions: HashMap<u64, (String, u8, u64, GoldilocksField, GoldilocksField)>,
     pub raw_instructions: HashMap<u64, Instruction>,
     pub raw_binary_instructions: Vec<String>,
-    // todo need limit the trace size
+    // program inputs, preloaded into r0..r(n-1) before the first step
+    #[serde(default)]
+    pub inputs: Vec<GoldilocksField>,
+    // (addr, value) of the program static data, loaded into memory before the
+    // first step
+    #[serde(default)]
+    pub static_memory: Vec<(u64, u64)>,
+    // bounded by the executor cycle limit
     pub exec: Vec<Step>,
+    #[serde(default)]
+    pub gas_used: u64,
     pub memory: Vec<MemoryTraceCell>,
     pub builtin_rangecheck: Vec<RangeCheckRow>,
     pub builtin_bitwise_combined: Vec<BitwiseCombinedRow>,
     pub builtin_cmp: Vec<CmpRow>,
+    #[serde(default)]
+    pub builtin_poseidon: Vec<PoseidonRow>,
+    #[serde(default)]
+    pub builtin_ecdsa: Vec<EcdsaRow>,
+    #[serde(default)]
+    pub builtin_keccak: Vec<KeccakRow>,
 }
 
 impl Trace {
+    pub fn insert_poseidon(
+        &mut self,
+        clk: u32,
+        input_addr: GoldilocksField,
+        output_addr: GoldilocksField,
+        input: [GoldilocksField; POSEIDON_INPUT_NUM],
+        output: [GoldilocksField; POSEIDON_OUTPUT_NUM],
+    ) {
+        self.builtin_poseidon.push(PoseidonRow {
+            clk: GoldilocksField::from_canonical_u64(clk as u64),
+            input_addr,
+            output_addr,
+            input,
+            output,
+        });
+    }
+
+    pub fn insert_keccak(
+        &mut self,
+        clk: u32,
+        input_addr: GoldilocksField,
+        output_addr: GoldilocksField,
+        len: GoldilocksField,
+        input: Vec<GoldilocksField>,
+        output: [GoldilocksField; KECCAK_OUTPUT_NUM],
+    ) {
+        self.builtin_keccak.push(KeccakRow {
+            clk: GoldilocksField::from_canonical_u64(clk as u64),
+            input_addr,
+            output_addr,
+            len,
+            input,
+            output,
+        });
+    }
+
+    pub fn insert_ecdsa(
+        &mut self,
+        clk: u32,
+        addr: GoldilocksField,
+        input: [GoldilocksField; ECDSA_INPUT_NUM],
+        flag: bool,
+    ) {
+        let words = |i: usize| {
+            input[i * ECDSA_WORD_NUM..(i + 1) * ECDSA_WORD_NUM]
+                .try_into()
+                .unwrap()
+        };
+        self.builtin_ecdsa.push(EcdsaRow {
+            clk: GoldilocksField::from_canonical_u64(clk as u64),
+            addr,
+            z: words(0),
+            qx: words(1),
+            qy: words(2),
+            r: words(3),
+            s: words(4),
+            flag: GoldilocksField::from_bool(flag),
+        });
+    }
+
     pub fn insert_cmp(
         &mut self,
         op0: GoldilocksField,
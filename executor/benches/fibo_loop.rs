@@ -66,7 +66,7 @@ pub(crate) fn bench_fibo_loop(inst_size: u64) {
 
     let mut process = Process::new();
     let start = Instant::now();
-    process.execute(&mut program);
+    process.execute(&mut program).unwrap();
     let exec_time = start.elapsed();
     info!(
         "exec_time: {}, exec steps: {}",
@@ -0,0 +1,311 @@
+use core::trace::trace::{ECDSA_INPUT_NUM, ECDSA_WORD_NUM};
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::ops::Square;
+use plonky2::field::secp256k1_base::Secp256K1Base;
+use plonky2::field::secp256k1_scalar::Secp256K1Scalar;
+use plonky2::field::types::{Field, PrimeField, PrimeField64};
+
+// y^2 = x^3 + 7
+pub const CURVE_B: u64 = 7;
+
+// The moduli of `Secp256K1Base` and `Secp256K1Scalar`, least significant
+// first.
+pub const BASE_ORDER: [u64; 4] = [
+    0xFFFFFFFEFFFFFC2F,
+    0xFFFFFFFFFFFFFFFF,
+    0xFFFFFFFFFFFFFFFF,
+    0xFFFFFFFFFFFFFFFF,
+];
+pub const SCALAR_ORDER: [u64; 4] = [
+    0xBFD25E8CD0364141,
+    0xBAAEDCE6AF48A03B,
+    0xFFFFFFFFFFFFFFFE,
+    0xFFFFFFFFFFFFFFFF,
+];
+
+pub const GENERATOR: AffinePoint = AffinePoint {
+    x: Secp256K1Base([
+        0x59F2815B16F81798,
+        0x029BFCDB2DCE28D9,
+        0x55A06295CE870B07,
+        0x79BE667EF9DCBBAC,
+    ]),
+    y: Secp256K1Base([
+        0x9C47D08FFB10D4B8,
+        0xFD17B448A6855419,
+        0x5DA4FBFC0E1108A8,
+        0x483ADA7726A3C465,
+    ]),
+};
+
+// The point of x = 1, the smallest x on the curve. Nobody knows its discrete
+// log, the ladder starts from it so that no partial sum is the point at
+// infinity or a point of the table it adds.
+pub const LADDER_OFFSET: AffinePoint = AffinePoint {
+    x: Secp256K1Base([1, 0, 0, 0]),
+    y: Secp256K1Base([
+        0xbc750d587e76a7ee,
+        0x264ca8d2587fdd6f,
+        0x63db68605822fb14,
+        0x4218f20ae6c646b3,
+    ]),
+};
+
+// GENERATOR + LADDER_OFFSET
+pub const GENERATOR_PLUS_OFFSET: AffinePoint = AffinePoint {
+    x: Secp256K1Base([
+        0x442db1307f9146a8,
+        0xebca4ebce1b5be71,
+        0x67a8aa7ad9b75a66,
+        0x57d783579d03d9ab,
+    ]),
+    y: Secp256K1Base([
+        0xbf26ea094de1c600,
+        0xc143dbf34b10ec35,
+        0xf01c75ece82067f4,
+        0xcb8c57e165f15f29,
+    ]),
+};
+
+// -(2^257 - 1) * LADDER_OFFSET, the ladder adds the offset once at the start
+// and once per step.
+pub const LADDER_CORRECTION: AffinePoint = AffinePoint {
+    x: Secp256K1Base([
+        0x8ce6ace3f7dbbda3,
+        0x8692a9ecf56d4efd,
+        0x1e324fd2231d8983,
+        0x80f0f50c9d325e0e,
+    ]),
+    y: Secp256K1Base([
+        0xd6d4c6050c3dd622,
+        0x7eb474d2ace78512,
+        0x0e811b9faa1997a8,
+        0xe2a7f8cd3987202e,
+    ]),
+};
+
+pub const LADDER_STEPS: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AffinePoint {
+    pub x: Secp256K1Base,
+    pub y: Secp256K1Base,
+}
+
+impl AffinePoint {
+    pub fn is_on_curve(&self) -> bool {
+        self.y.square() == self.x.cube() + Secp256K1Base::from_canonical_u64(CURVE_B)
+    }
+
+    pub fn neg(&self) -> Self {
+        Self {
+            x: self.x,
+            y: -self.y,
+        }
+    }
+
+    // There is no point of order 2 on the curve, y is never zero.
+    pub fn double(&self) -> Self {
+        let lambda = self.x.square() * Secp256K1Base::from_canonical_u64(3) / self.y.double();
+        self.with_slope(lambda, self)
+    }
+
+    // `None` when both points have the same x, they are either equal or
+    // opposite.
+    pub fn add(&self, rhs: &Self) -> Option<Self> {
+        let lambda = (rhs.y - self.y) * (rhs.x - self.x).try_inverse()?;
+        Some(self.with_slope(lambda, rhs))
+    }
+
+    fn with_slope(&self, lambda: Secp256K1Base, rhs: &Self) -> Self {
+        let x = lambda.square() - self.x - rhs.x;
+        let y = lambda * (self.x - x) - self.y;
+        Self { x, y }
+    }
+
+    // `scalar * self`, `None` for the point at infinity.
+    pub fn mul(&self, scalar: &Secp256K1Scalar) -> Option<Self> {
+        let scalar = canonical_words(scalar);
+        let mut acc: Option<Self> = None;
+        for i in (0..LADDER_STEPS).rev() {
+            acc = acc.map(|p| p.double());
+            if bit(&scalar, i) {
+                acc = match acc {
+                    None => Some(*self),
+                    Some(p) if p.x == self.x => (p.y == self.y).then(|| p.double()),
+                    Some(p) => p.add(self),
+                };
+            }
+        }
+        acc
+    }
+}
+
+// The canonical value of `f`, least significant word first.
+pub fn canonical_words<F: PrimeField>(f: &F) -> [u64; 4] {
+    let mut words = [0; 4];
+    for (word, digit) in words
+        .iter_mut()
+        .zip(f.to_canonical_biguint().to_u64_digits())
+    {
+        *word = digit;
+    }
+    words
+}
+
+// Bit `i` of a 256 bit value.
+pub fn bit(value: &[u64; 4], i: usize) -> bool {
+    (value[i / 64] >> (i % 64)) & 1 == 1
+}
+
+fn less_than(a: &[u64; 4], b: &[u64; 4]) -> bool {
+    a.iter().rev().lt(b.iter().rev())
+}
+
+// A 256 bit value from `ECDSA_WORD_NUM` u32 words, `None` if a word is over
+// u32.
+fn u256_from_words(words: &[GoldilocksField]) -> Option<[u64; 4]> {
+    let mut value = [0; 4];
+    for (i, word) in words.iter().enumerate() {
+        let word = u32::try_from(word.to_canonical_u64()).ok()?;
+        value[i / 2] |= (word as u64) << (32 * (i % 2));
+    }
+    Some(value)
+}
+
+// The operands of the ecdsa instruction.
+#[derive(Debug, Clone, Copy)]
+pub struct EcdsaInput {
+    // message hash, any 256 bit value
+    pub z: [u64; 4],
+    pub q: AffinePoint,
+    pub r: Secp256K1Scalar,
+    pub s: Secp256K1Scalar,
+}
+
+impl EcdsaInput {
+    // `None` unless every word is u32, the public key is a curve point in
+    // canonical form and r and s are in [1, n).
+    pub fn from_words(input: &[GoldilocksField; ECDSA_INPUT_NUM]) -> Option<Self> {
+        let values: Vec<[u64; 4]> = input
+            .chunks(ECDSA_WORD_NUM)
+            .map(u256_from_words)
+            .collect::<Option<_>>()?;
+        let [z, qx, qy, r, s]: [[u64; 4]; 5] = values.try_into().unwrap();
+        if !less_than(&qx, &BASE_ORDER) || !less_than(&qy, &BASE_ORDER) {
+            return None;
+        }
+        let q = AffinePoint {
+            x: Secp256K1Base(qx),
+            y: Secp256K1Base(qy),
+        };
+        let in_range = |v: &[u64; 4]| *v != [0; 4] && less_than(v, &SCALAR_ORDER);
+        if !q.is_on_curve() || !in_range(&r) || !in_range(&s) {
+            return None;
+        }
+        Some(Self {
+            z,
+            q,
+            r: Secp256K1Scalar(r),
+            s: Secp256K1Scalar(s),
+        })
+    }
+
+    // Sign the message hash `z` with the private key `d` and the nonce `k`,
+    // `None` if r or s is zero.
+    pub fn sign(z: [u64; 4], d: &Secp256K1Scalar, k: &Secp256K1Scalar) -> Option<Self> {
+        let q = GENERATOR.mul(d)?;
+        let r =
+            Secp256K1Scalar::from_noncanonical_biguint(GENERATOR.mul(k)?.x.to_canonical_biguint());
+        let s = (Secp256K1Scalar(z) + r * *d) / *k;
+        (!r.is_zero() && !s.is_zero()).then_some(Self { z, q, r, s })
+    }
+
+    // The u32 words of the input in memory order, as read by `from_words`.
+    pub fn to_words(&self) -> [GoldilocksField; ECDSA_INPUT_NUM] {
+        let values = [
+            self.z,
+            canonical_words(&self.q.x),
+            canonical_words(&self.q.y),
+            canonical_words(&self.r),
+            canonical_words(&self.s),
+        ];
+        let mut input = [GoldilocksField::ZERO; ECDSA_INPUT_NUM];
+        for (i, word) in input.iter_mut().enumerate() {
+            let value = values[i / ECDSA_WORD_NUM];
+            let j = i % ECDSA_WORD_NUM;
+            *word =
+                GoldilocksField::from_canonical_u64((value[j / 2] >> (32 * (j % 2))) & 0xffff_ffff);
+        }
+        input
+    }
+
+    // (u1, u2) = (z / s, r / s) mod n, the signature is valid when the x of
+    // u1 * G + u2 * Q is r mod n.
+    pub fn ladder_scalars(&self) -> (Secp256K1Scalar, Secp256K1Scalar) {
+        // z < 2^256 < 2n, the scalar field reduces it.
+        let w = self.s.inverse();
+        (Secp256K1Scalar(self.z) * w, self.r * w)
+    }
+
+    // The points added by a ladder step, indexed by the bits of u1 and u2 as
+    // `b1 + 2 * b2`. `None` if Q + K or Q + G + K doubles a point.
+    pub fn ladder_table(&self) -> Option<[AffinePoint; 4]> {
+        let qk = LADDER_OFFSET.add(&self.q)?;
+        let gqk = GENERATOR_PLUS_OFFSET.add(&self.q)?;
+        Some([LADDER_OFFSET, GENERATOR_PLUS_OFFSET, qk, gqk])
+    }
+
+    // Compute u1 * G + u2 * Q with a double and add ladder from the offset
+    // point, as the ecdsa table does. `None` if an addition of the ladder
+    // doubles a point, which an honest input only hits with negligible
+    // probability.
+    pub fn verify(&self) -> Option<bool> {
+        let (u1, u2) = self.ladder_scalars();
+        let (u1, u2) = (canonical_words(&u1), canonical_words(&u2));
+        let table = self.ladder_table()?;
+        let mut acc = LADDER_OFFSET;
+        for i in (0..LADDER_STEPS).rev() {
+            let index = bit(&u1, i) as usize + 2 * bit(&u2, i) as usize;
+            acc = acc.double().add(&table[index])?;
+        }
+        let acc = acc.add(&LADDER_CORRECTION)?;
+        // x < p < 2n
+        Some(Secp256K1Scalar(canonical_words(&acc.x)) == self.r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ladder_constants() {
+        for point in [
+            GENERATOR,
+            LADDER_OFFSET,
+            GENERATOR_PLUS_OFFSET,
+            LADDER_CORRECTION,
+        ] {
+            assert!(point.is_on_curve());
+        }
+        assert_eq!(Secp256K1Base::order().to_u64_digits(), BASE_ORDER);
+        assert_eq!(Secp256K1Scalar::order().to_u64_digits(), SCALAR_ORDER);
+        assert_eq!(
+            GENERATOR.mul(&Secp256K1Scalar::NEG_ONE),
+            Some(GENERATOR.neg())
+        );
+        assert_eq!(GENERATOR.mul(&Secp256K1Scalar::ZERO), None);
+
+        let x = (1..)
+            .map(Secp256K1Base::from_canonical_u64)
+            .find(|x| {
+                (x.cube() + Secp256K1Base::from_canonical_u64(CURVE_B)).is_quadratic_residue()
+            })
+            .unwrap();
+        assert_eq!(x, LADDER_OFFSET.x);
+        assert_eq!(GENERATOR.add(&LADDER_OFFSET), Some(GENERATOR_PLUS_OFFSET));
+        let steps = Secp256K1Scalar::TWO.exp_u64(LADDER_STEPS as u64 + 1) - Secp256K1Scalar::ONE;
+        assert_eq!(LADDER_OFFSET.mul(&-steps), Some(LADDER_CORRECTION));
+    }
+}
@@ -0,0 +1,257 @@
+use core::trace::trace::KECCAK_OUTPUT_NUM;
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::PrimeField64;
+
+pub const ROUNDS: usize = 24;
+// u32 words of the keccak state, lane `x + 5 * y` is the words `2 * lane` and
+// `2 * lane + 1`, least significant first.
+pub const STATE_WORDS: usize = 50;
+// Keccak-256 absorbs 136 bytes per block.
+pub const RATE_WORDS: usize = 34;
+pub const RATE_BYTES: usize = 4 * RATE_WORDS;
+// Longest input of one keccak instruction, it bounds the memory reads and
+// permutations of a step.
+pub const MAX_INPUT_BYTES: usize = 1 << 16;
+
+pub const ROUND_CONSTANTS: [u64; ROUNDS] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808A,
+    0x8000000080008000,
+    0x000000000000808B,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008A,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000A,
+    0x000000008000808B,
+    0x800000000000008B,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800A,
+    0x800000008000000A,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+// Rotation of lane (x, y) by rho, indexed `[x][y]`.
+pub const ROTATIONS: [[u32; 5]; 5] = [
+    [0, 36, 3, 41, 18],
+    [1, 44, 10, 45, 2],
+    [62, 6, 43, 15, 61],
+    [28, 55, 25, 21, 56],
+    [27, 20, 39, 8, 14],
+];
+
+// One round of Keccak-f[1600] on the lanes `x + 5 * y`.
+pub fn keccak_round(state: &mut [u64; 25], round: usize) {
+    // theta
+    let mut c = [0; 5];
+    for (x, c) in c.iter_mut().enumerate() {
+        *c = (0..5).fold(0, |acc, y| acc ^ state[x + 5 * y]);
+    }
+    for x in 0..5 {
+        let d = c[(x + 4) % 5] ^ c[(x + 1) % 5].rotate_left(1);
+        for y in 0..5 {
+            state[x + 5 * y] ^= d;
+        }
+    }
+    // rho and pi
+    let mut b = [0; 25];
+    for x in 0..5 {
+        for y in 0..5 {
+            b[y + 5 * ((2 * x + 3 * y) % 5)] = state[x + 5 * y].rotate_left(ROTATIONS[x][y]);
+        }
+    }
+    // chi
+    for x in 0..5 {
+        for y in 0..5 {
+            state[x + 5 * y] = b[x + 5 * y] ^ (!b[(x + 1) % 5 + 5 * y] & b[(x + 2) % 5 + 5 * y]);
+        }
+    }
+    // iota
+    state[0] ^= ROUND_CONSTANTS[round];
+}
+
+pub fn keccak_f(state: &mut [u64; 25]) {
+    for round in 0..ROUNDS {
+        keccak_round(state, round);
+    }
+}
+
+pub fn words_to_lanes(words: &[u32; STATE_WORDS]) -> [u64; 25] {
+    let mut lanes = [0; 25];
+    for (i, lane) in lanes.iter_mut().enumerate() {
+        *lane = words[2 * i] as u64 | (words[2 * i + 1] as u64) << 32;
+    }
+    lanes
+}
+
+pub fn lanes_to_words(lanes: &[u64; 25]) -> [u32; STATE_WORDS] {
+    let mut words = [0; STATE_WORDS];
+    for (i, word) in words.iter_mut().enumerate() {
+        *word = (lanes[i / 2] >> (32 * (i % 2))) as u32;
+    }
+    words
+}
+
+// Bytes packed in u32 words, little endian, the last word is zero padded.
+pub fn bytes_to_words(bytes: &[u8]) -> Vec<u32> {
+    bytes
+        .chunks(4)
+        .map(|chunk| {
+            let mut word = [0; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            u32::from_le_bytes(word)
+        })
+        .collect()
+}
+
+// The words of a `len` bytes input as read from memory, `None` if a word is
+// over u32 or has a non zero byte past the input.
+pub fn input_words(len: usize, words: &[GoldilocksField]) -> Option<Vec<u32>> {
+    debug_assert_eq!(words.len(), len.div_ceil(4));
+    let words = words
+        .iter()
+        .map(|word| u32::try_from(word.to_canonical_u64()).ok())
+        .collect::<Option<Vec<_>>>()?;
+    (tail_word(len, &words) & !tail_mask(len) == 0).then_some(words)
+}
+
+// The mask of the input bytes of the last word when the input does not end on
+// a word, 0 otherwise.
+pub fn tail_mask(len: usize) -> u32 {
+    ((1_u64 << (8 * (len % 4))) - 1) as u32
+}
+
+// The last word of the input when the input does not end on a word, 0
+// otherwise.
+pub fn tail_word(len: usize, words: &[u32]) -> u32 {
+    if len % 4 == 0 {
+        0
+    } else {
+        words[len / 4]
+    }
+}
+
+// One block absorbed by the sponge: the input words read from memory, zero
+// past the input, the words with the keccak padding, and the state before
+// and after the permutation.
+#[derive(Debug, Clone, Copy)]
+pub struct SpongeBlock {
+    pub input: [u32; RATE_WORDS],
+    pub padded: [u32; RATE_WORDS],
+    pub state: [u32; STATE_WORDS],
+    pub updated: [u32; STATE_WORDS],
+}
+
+impl SpongeBlock {
+    // The input of the permutation, the padded words xored into the rate.
+    pub fn xored(&self) -> [u32; STATE_WORDS] {
+        let mut xored = self.state;
+        for (word, padded) in xored.iter_mut().zip(self.padded) {
+            *word ^= padded;
+        }
+        xored
+    }
+}
+
+// Absorb a `len` bytes input, one block per 136 bytes and a last block with
+// the padding, which is the only block for an input shorter than 136 bytes.
+pub fn absorb(len: usize, words: &[u32]) -> Vec<SpongeBlock> {
+    let num_blocks = len / RATE_BYTES + 1;
+    let mut state = [0; STATE_WORDS];
+    (0..num_blocks)
+        .map(|i| {
+            let mut input = [0; RATE_WORDS];
+            let start = i * RATE_WORDS;
+            let end = words.len().min(start + RATE_WORDS);
+            input[..end - start].copy_from_slice(&words[start..end]);
+            let mut padded = input;
+            if i + 1 == num_blocks {
+                let final_len = len % RATE_BYTES;
+                padded[final_len / 4] += 1 << (8 * (final_len % 4));
+                padded[RATE_WORDS - 1] += 0x80 << 24;
+            }
+            let mut block = SpongeBlock {
+                input,
+                padded,
+                state,
+                updated: [0; STATE_WORDS],
+            };
+            let mut lanes = words_to_lanes(&block.xored());
+            keccak_f(&mut lanes);
+            block.updated = lanes_to_words(&lanes);
+            state = block.updated;
+            block
+        })
+        .collect()
+}
+
+// The digest of the last block.
+pub fn digest(blocks: &[SpongeBlock]) -> [u32; KECCAK_OUTPUT_NUM] {
+    blocks.last().unwrap().updated[..KECCAK_OUTPUT_NUM]
+        .try_into()
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::field::types::Field;
+
+    fn keccak256(bytes: &[u8]) -> Vec<u8> {
+        let blocks = absorb(bytes.len(), &bytes_to_words(bytes));
+        digest(&blocks)
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn keccak256_digests() {
+        let hex = |s: &str| {
+            (0..s.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            keccak256(b""),
+            hex("c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470")
+        );
+        assert_eq!(
+            keccak256(b"abc"),
+            hex("4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45")
+        );
+        // The padding fills a whole block after a 136 bytes input.
+        assert_eq!(absorb(RATE_BYTES, &[0; RATE_WORDS]).len(), 2);
+        assert_eq!(absorb(RATE_BYTES - 1, &[0; RATE_WORDS]).len(), 1);
+        assert_eq!(
+            absorb(RATE_BYTES - 1, &[0; RATE_WORDS])[0].padded[33],
+            0x81 << 24
+        );
+    }
+
+    #[test]
+    fn input_tail() {
+        let word = GoldilocksField::from_canonical_u64;
+        assert_eq!(
+            input_words(3, &[word(0x00ff_ffff)]),
+            Some(vec![0x00ff_ffff])
+        );
+        assert_eq!(input_words(3, &[word(0x0100_0000)]), None);
+        assert_eq!(
+            input_words(4, &[word(0xffff_ffff)]),
+            Some(vec![0xffff_ffff])
+        );
+        assert_eq!(input_words(4, &[word(1 << 32)]), None);
+        assert_eq!(input_words(0, &[]), Some(vec![]));
+    }
+}
@@ -1,2 +1,4 @@
+pub mod ecdsa;
 pub mod hash;
+pub mod keccak;
 pub mod poseidon_field;
@@ -1,5 +1,5 @@
 use crate::error::ProcessorError;
-use core::program::instruction::{Opcode, *};
+use core::program::decoder::{decode_instruction_word, DecodeError};
 use log::debug;
 
 fn parse_hex_str(hex_str: &str) -> Result<u64, ProcessorError> {
@@ -10,121 +10,19 @@ fn parse_hex_str(hex_str: &str) -> Result<u64, ProcessorError> {
     }
 }
 
-fn get_index(data: u64) -> u8 {
-    let mut i: i128 = 63;
-    while i >= 0 {
-        if ((data >> i) & 1) != 0 {
-            return i as u8;
-        }
-        i -= 1;
-    }
-    0xff
-}
 pub fn decode_raw_instruction(
     raw_inst_str: &str,
     imm_str: &str,
 ) -> Result<(String, u64), ProcessorError> {
-    let mut step = NO_IMM_INSTRUCTION_LEN;
     let raw_inst = parse_hex_str(raw_inst_str.trim_start_matches("0x"))?;
-    let opcode_index = get_index(raw_inst & OPCODE_FIELD_BITS_MASK);
     debug!("raw_inst:{}", raw_inst);
+    let imm = parse_hex_str(imm_str.trim_start_matches("0x")).ok();
 
-    if let Ok(op_code) = Opcode::try_from(opcode_index) {
-        debug!("op_code:{:?}", op_code);
-        let imm_flag = raw_inst >> IMM_FLAG_FIELD_BIT_POSITION & IMM_FLAG_FIELD_BITS_MASK;
-        debug!("imm_flag:{}", imm_flag);
-        let reg0 = get_index(raw_inst >> REG0_FIELD_BIT_POSITION & REG_FIELD_BITS_MASK);
-        debug!("reg0:{}", reg0);
-        let reg2 = get_index(raw_inst >> REG1_FIELD_BIT_POSITION & REG_FIELD_BITS_MASK);
-        debug!("op1:{}", reg2);
-        let reg1 = get_index(raw_inst >> REG2_FIELD_BIT_POSITION & REG_FIELD_BITS_MASK);
-        debug!("op0:{}", reg1);
-
-        let mut instruction = "".to_string();
-        match op_code {
-            Opcode::ADD | Opcode::MUL | Opcode::SUB | Opcode::AND | Opcode::OR | Opcode::XOR => {
-                instruction += &op_code.to_string();
-                instruction += " ";
-                let reg0_name = format!("r{}", reg0);
-                instruction += &reg0_name;
-                instruction += " ";
-                let reg1_name = format!("r{}", reg1);
-                instruction += &reg1_name;
-                instruction += " ";
-                if imm_flag == 1 {
-                    let imm = parse_hex_str(imm_str.trim_start_matches("0x"))?;
-                    instruction += &imm.to_string();
-                    step = IMM_INSTRUCTION_LEN;
-                } else {
-                    let reg2_name = format!("r{}", reg2);
-                    instruction += &reg2_name;
-                }
-            }
-            Opcode::EQ | Opcode::ASSERT | Opcode::NEQ | Opcode::GTE => {
-                instruction += &op_code.to_string();
-                instruction += " ";
-                let reg1_name = format!("r{}", reg1);
-                instruction += &reg1_name;
-                instruction += " ";
-                if imm_flag == 1 {
-                    let imm = parse_hex_str(imm_str.trim_start_matches("0x"))?;
-                    instruction += &imm.to_string();
-                    step = IMM_INSTRUCTION_LEN;
-                } else {
-                    let reg2_name = format!("r{}", reg2);
-                    instruction += &reg2_name;
-                }
-            }
-            Opcode::MOV | Opcode::MLOAD | Opcode::NOT => {
-                instruction += &op_code.to_string();
-                instruction += " ";
-                let reg0_name = format!("r{}", reg0);
-                instruction += &reg0_name;
-                instruction += " ";
-                if imm_flag == 1 {
-                    let imm = parse_hex_str(imm_str.trim_start_matches("0x"))?;
-                    instruction += &imm.to_string();
-                    step = IMM_INSTRUCTION_LEN;
-                } else {
-                    let reg2_name = format!("r{}", reg2);
-                    instruction += &reg2_name;
-                }
-            }
-            Opcode::MSTORE => {
-                instruction += &op_code.to_string();
-                instruction += " ";
-                if imm_flag == 1 {
-                    let imm = parse_hex_str(imm_str.trim_start_matches("0x"))?;
-                    instruction += &imm.to_string();
-                    step = IMM_INSTRUCTION_LEN;
-                } else {
-                    let reg2_name = format!("r{}", reg2);
-                    instruction += &reg2_name;
-                }
-                instruction += " ";
-                let reg1_name = format!("r{}", reg1);
-                instruction += &reg1_name;
-            }
-            Opcode::JMP | Opcode::CJMP | Opcode::CALL | Opcode::RC => {
-                instruction += &op_code.to_string();
-                instruction += " ";
-                if imm_flag == 1 {
-                    let imm = parse_hex_str(imm_str.trim_start_matches("0x"))?;
-                    instruction += &imm.to_string();
-                    step = IMM_INSTRUCTION_LEN;
-                } else {
-                    let reg2_name = format!("r{}", reg2);
-                    instruction += &reg2_name;
-                }
-            }
-            Opcode::RET | Opcode::END => {
-                instruction += &op_code.to_string();
-            }
-        };
-        return Ok((instruction, step));
-    }
-
-    Err(ProcessorError::ParseOpcodeError)
+    decode_instruction_word(raw_inst, imm).map_err(|err| match err {
+        DecodeError::UnknownOpcode(_) => ProcessorError::ParseOpcodeError,
+        DecodeError::MissingImmediate(_) => ProcessorError::MalformedImmediate(imm_str.to_string()),
+        DecodeError::DataAddressOverflow(addr) => ProcessorError::MalformedData(addr),
+    })
 }
 
 #[test]
@@ -1,7 +1,54 @@
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum ProcessorError {
     /// parse string to integer fail
     ParseIntError,
     /// parse integer to opcode fail
     ParseOpcodeError,
+    /// more program inputs than general purpose registers
+    TooManyInputs,
+    /// program input not an integer or not a field element
+    MalformedInput(String),
+    /// instruction with a wrong number of operands
+    InvalidOperands(String),
+    /// immediate missing or not a field element
+    MalformedImmediate(String),
+    /// static data overlapping the prophet memory region or not a field element
+    MalformedData(u64),
+    /// decoded program with a different number of words than its binary
+    InstructionCountMismatch { raw: usize, decoded: usize },
+    /// register name not in r0..r8
+    RegisterIndexOutOfRange(String),
+    /// assert instruction with different operands
+    AssertFail {
+        pc: u64,
+        clk: u32,
+        left: u64,
+        right: u64,
+    },
+    /// range instruction with an operand over u32
+    RangeCheckFail { pc: u64, clk: u32, value: u64 },
+    /// jump, call or ret to an address which is not an instruction
+    InvalidJumpTarget { pc: u64, target: u64 },
+    /// pc runs past the program without reaching end
+    PcOutOfBounds(u64),
+    /// clk reaches the cycle limit before end
+    StepLimitExceeded(u32),
+    /// ecdsa input with a word over u32, a public key off the curve, r or s
+    /// out of [1, n), or a ladder addition doubling a point
+    InvalidEcdsaInput { pc: u64, clk: u32 },
+    /// keccak input length over `keccak::MAX_INPUT_BYTES`, or an input word
+    /// over u32 or with a non zero byte past the input
+    InvalidKeccakInput { pc: u64, clk: u32 },
+    /// no prophet answers the id for the argument registers, or its outputs
+    /// run past the prophet memory region
+    ProphetFailed { pc: u64, clk: u32, id: u64 },
+    /// mstore, call, poseidon or keccak write to the prophet memory region,
+    /// only prophets write it
+    ProphetRegionWrite { pc: u64, addr: u64 },
+    /// mload of a prophet memory region word no prophet has written
+    ProphetRegionUnwritten { pc: u64, addr: u64 },
+    /// next instruction costs more gas than left
+    OutOfGas { pc: u64, gas_used: u64 },
+    /// segment length not a power of two or shorter than the program
+    InvalidSegmentLength(usize),
 }
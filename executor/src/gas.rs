@@ -0,0 +1,44 @@
+use crate::coprocessor::keccak::RATE_BYTES;
+use core::program::instruction::Opcode;
+
+// A keccak permutation of 24 rounds.
+pub const KECCAK_BLOCK_GAS: u64 = 256;
+
+// Gas charged for each executed instruction. Builtins cost more than the
+// field arithmetic proved by the cpu table alone since every call also adds
+// rows to the builtin and memory tables.
+pub fn opcode_gas(opcode: Opcode) -> u64 {
+    match opcode {
+        Opcode::ADD
+        | Opcode::MUL
+        | Opcode::SUB
+        | Opcode::MOV
+        | Opcode::NOT
+        | Opcode::EQ
+        | Opcode::NEQ
+        | Opcode::ASSERT
+        | Opcode::JMP
+        | Opcode::CJMP
+        | Opcode::END => 1,
+        Opcode::MLOAD | Opcode::MSTORE => 2,
+        Opcode::CALL | Opcode::RET => 3,
+        Opcode::RC | Opcode::AND | Opcode::OR | Opcode::XOR | Opcode::GTE => 4,
+        // 12 memory accesses and a permutation
+        Opcode::PSDN => 32,
+        // 40 memory reads and a ladder of 256 doublings and additions
+        Opcode::ECDSA => 2048,
+        // the length read and 8 output writes, the input is charged by
+        // `keccak_input_gas` once its length is known
+        Opcode::KECCAK => 18,
+        // a memory write for each output of the host prophet
+        Opcode::PROPHET => 4,
+    }
+}
+
+// Gas of a `byte_len` bytes keccak input: a memory read for each input word
+// and a permutation for each absorbed block, the padding included.
+pub fn keccak_input_gas(byte_len: usize) -> u64 {
+    let words = byte_len.div_ceil(4) as u64;
+    let blocks = (byte_len / RATE_BYTES + 1) as u64;
+    words * opcode_gas(Opcode::MLOAD) + blocks * KECCAK_BLOCK_GAS
+}
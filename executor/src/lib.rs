@@ -1,15 +1,21 @@
+use crate::coprocessor::ecdsa::EcdsaInput;
+use crate::coprocessor::hash::PoseidonHash;
+use crate::coprocessor::keccak;
 use crate::decode::decode_raw_instruction;
 use crate::error::ProcessorError;
+use crate::gas::{keccak_input_gas, opcode_gas};
 use crate::memory::MemoryTree;
+use crate::prophet::{BuiltinProphets, Prophet as _};
 //use core::program::instruction::ImmediateOrRegName::Immediate;
 use core::program::instruction::IMM_INSTRUCTION_LEN;
 use core::program::instruction::{
-    Add, And, Assert, CJmp, Call, End, Equal, Gte, ImmediateOrRegName, Instruction, Jmp, Mload,
-    Mov, Mstore, Mul, Neq, Not, Opcode, Or, Range, Ret, Sub, Xor,
+    Add, And, Assert, CJmp, Call, Ecdsa, End, Equal, Gte, ImmediateOrRegName, Instruction, Jmp,
+    Keccak, Mload, Mov, Mstore, Mul, Neq, Not, Opcode, Or, Poseidon, Prophet, Range, Ret, Sub, Xor,
 };
-use core::program::{Program, REGISTER_NUM};
+use core::program::{static_data_end, Program, REGION_SPAN, REGISTER_NUM};
 use core::trace::trace::{
-    BitwiseOperation, ComparisonOperation, MemoryTraceCell, RegisterSelector,
+    BitwiseOperation, MemoryTraceCell, RegisterSelector, Step, StepSink, Trace, ECDSA_INPUT_NUM,
+    POSEIDON_INPUT_NUM, POSEIDON_OUTPUT_NUM,
 };
 use core::trace::trace::{FilterLockForMain, MemoryOperation, MemoryType};
 use log::debug;
@@ -18,17 +24,38 @@ use plonky2::field::types::{Field, Field64, PrimeField64};
 use std::collections::BTreeMap;
 use std::time::Instant;
 
-mod coprocessor;
+pub mod coprocessor;
 mod decode;
 pub mod error;
+pub mod gas;
 mod memory;
+pub mod prophet;
 
 #[cfg(test)]
 mod tests;
 
+fn check_ops_len(ops: &[&str], len: usize) -> Result<(), ProcessorError> {
+    if ops.len() != len {
+        return Err(ProcessorError::InvalidOperands(ops.join(" ")));
+    }
+    Ok(())
+}
+
 // r15 use as fp for procedure
 const FP_REG_INDEX: usize = 8;
-const REGION_SPAN: u64 = 2 ^ 32 - 1;
+pub use core::program::PROPHET_REGION_START;
+// default bound on the executed steps, keeps the trace of a runaway program
+// in memory
+pub const DEFAULT_MAX_CYCLES: u32 = 1 << 22;
+
+#[derive(Debug, Clone, Copy)]
+struct DecodedInstruction {
+    instruction: Instruction,
+    step: u64,
+    op1_imm: GoldilocksField,
+    encode: GoldilocksField,
+    immediate_data: GoldilocksField,
+}
 
 #[derive(Debug, Default)]
 pub struct Process {
@@ -42,6 +69,13 @@ pub struct Process {
     pub opcode: GoldilocksField,
     pub op1_imm: GoldilocksField,
     pub memory: MemoryTree,
+    // `None` only stops at clk overflow
+    pub max_cycles: Option<u32>,
+    // `None` counts gas without limit
+    pub gas_limit: Option<u64>,
+    pub gas_used: u64,
+    // asked before the built-in prophets
+    pub prophet: Option<Box<dyn prophet::Prophet>>,
 }
 
 impl Process {
@@ -59,45 +93,104 @@ impl Process {
             memory: MemoryTree {
                 trace: BTreeMap::new(),
             },
+            max_cycles: Some(DEFAULT_MAX_CYCLES),
+            gas_limit: None,
+            gas_used: 0,
+            prophet: None,
         }
     }
 
-    pub fn get_reg_index(&self, reg_str: &str) -> usize {
-        let first = reg_str.chars().nth(0).unwrap();
-        assert!(first == 'r', "wrong reg name");
-        reg_str[1..].parse().unwrap()
+    pub fn get_reg_index(&self, reg_str: &str) -> Result<usize, ProcessorError> {
+        reg_str
+            .strip_prefix('r')
+            .and_then(|index| index.parse().ok())
+            .filter(|index| *index < REGISTER_NUM)
+            .ok_or_else(|| ProcessorError::RegisterIndexOutOfRange(reg_str.to_string()))
     }
 
-    pub fn get_index_value(&self, op_str: &str) -> (GoldilocksField, ImmediateOrRegName) {
-        let src = op_str.parse();
-        let value;
-        if src.is_ok() {
-            let data: u64 = src.unwrap();
-            return (
-                GoldilocksField::from_canonical_u64(data),
-                ImmediateOrRegName::Immediate(GoldilocksField::from_canonical_u64(data)),
-            );
-        } else {
-            let src_index = self.get_reg_index(op_str);
-            value = self.registers[src_index];
-            return (value, ImmediateOrRegName::RegName(src_index));
+    pub fn get_index_value(
+        &self,
+        op_str: &str,
+    ) -> Result<(GoldilocksField, ImmediateOrRegName), ProcessorError> {
+        if let Ok(data) = op_str.parse::<u64>() {
+            if data >= GoldilocksField::ORDER {
+                return Err(ProcessorError::MalformedImmediate(op_str.to_string()));
+            }
+            let value = GoldilocksField::from_canonical_u64(data);
+            return Ok((value, ImmediateOrRegName::Immediate(value)));
+        }
+        let src_index = self.get_reg_index(op_str)?;
+        Ok((
+            self.registers[src_index],
+            ImmediateOrRegName::RegName(src_index),
+        ))
+    }
+
+    // Jumps, calls and rets must land on the first word of an instruction.
+    fn check_jump_target(
+        &self,
+        decoded_instructions: &[Option<DecodedInstruction>],
+        target: u64,
+    ) -> Result<u64, ProcessorError> {
+        match decoded_instructions.get(target as usize) {
+            Some(Some(_)) => Ok(target),
+            _ => Err(ProcessorError::InvalidJumpTarget {
+                pc: self.pc,
+                target,
+            }),
+        }
+    }
+
+    fn check_writable(&self, addr: u64) -> Result<u64, ProcessorError> {
+        if addr >= PROPHET_REGION_START {
+            return Err(ProcessorError::ProphetRegionWrite { pc: self.pc, addr });
         }
+        Ok(addr)
     }
 
-    pub fn decode_instruction(&self, raw_instruction: String) -> Instruction {
+    fn operand_value(&self, operand: ImmediateOrRegName) -> GoldilocksField {
+        match operand {
+            ImmediateOrRegName::Immediate(value) => value,
+            ImmediateOrRegName::RegName(index) => self.registers[index],
+        }
+    }
+
+    fn select_op0(&mut self, index: usize) -> GoldilocksField {
+        self.register_selector.op0 = self.registers[index];
+        self.register_selector.op0_reg_sel[index] = GoldilocksField::ONE;
+        self.register_selector.op0
+    }
+
+    fn select_op1(&mut self, operand: ImmediateOrRegName) -> GoldilocksField {
+        self.register_selector.op1 = self.operand_value(operand);
+        if let ImmediateOrRegName::RegName(index) = operand {
+            self.register_selector.op1_reg_sel[index] = GoldilocksField::ONE;
+        }
+        self.register_selector.op1
+    }
+
+    fn select_dst(&mut self, index: usize, value: GoldilocksField) {
+        self.registers[index] = value;
+        self.register_selector.dst = value;
+        self.register_selector.dst_reg_sel[index] = GoldilocksField::ONE;
+    }
+
+    pub fn decode_instruction(
+        &self,
+        raw_instruction: String,
+    ) -> Result<Instruction, ProcessorError> {
         let ops: Vec<_> = raw_instruction.split(' ').collect();
-        let opcode = ops.first().unwrap().to_lowercase();
+        let opcode = ops
+            .first()
+            .ok_or(ProcessorError::ParseOpcodeError)?
+            .to_lowercase();
 
         let instuction = match opcode.as_str() {
-            "mov" | "assert" | "eq" | "neq" | "not" | "gte" => {
+            "mov" | "assert" | "eq" | "neq" | "not" | "gte" | "prophet" => {
                 debug!("opcode: mov");
-                assert!(
-                    ops.len() == 3,
-                    "{}",
-                    format!("{} params len is 2", opcode.as_str())
-                );
-                let dst_index = self.get_reg_index(ops[1]);
-                let value = self.get_index_value(ops[2]);
+                check_ops_len(&ops, 3)?;
+                let dst_index = self.get_reg_index(ops[1])?;
+                let value = self.get_index_value(ops[2])?;
                 match opcode.as_str() {
                     "mov" => Instruction::MOV(Mov {
                         ri: dst_index as u8,
@@ -123,35 +216,34 @@ impl Process {
                         ri: dst_index as u8,
                         a: value.1,
                     }),
-                    _ => panic!("not match opcode:{}", opcode),
+                    "prophet" => Instruction::PROPHET(Prophet {
+                        ri: dst_index as u8,
+                        a: value.1,
+                    }),
+                    _ => return Err(ProcessorError::ParseOpcodeError),
                 }
             }
-            "cjmp" | "jmp" | "call" | "range" => {
+            "cjmp" | "jmp" | "call" | "range" | "ecdsa" => {
                 debug!("opcode: {}", opcode.as_str());
-                assert!(
-                    ops.len() == 2,
-                    "{}",
-                    format!("{} params len is 1", opcode.as_str())
-                );
-                let value = self.get_index_value(ops[1]);
+                check_ops_len(&ops, 2)?;
+                let value = self.get_index_value(ops[1])?;
                 match opcode.as_str() {
                     "cjmp" => Instruction::CJMP(CJmp { a: value.1 }),
                     "jmp" => Instruction::JMP(Jmp { a: value.1 }),
                     "call" => Instruction::CALL(Call { ri: value.1 }),
-                    "range" => Instruction::RANGE(Range { ri: value.1 }),
-                    _ => panic!("not match opcode:{}", opcode),
+                    "range" => Instruction::RANGE(Range {
+                        ri: ImmediateOrRegName::RegName(self.get_reg_index(ops[1])?),
+                    }),
+                    "ecdsa" => Instruction::ECDSA(Ecdsa { a: value.1 }),
+                    _ => return Err(ProcessorError::ParseOpcodeError),
                 }
             }
             "add" | "sub" | "mul" | "and" | "or" | "xor" => {
                 debug!("opcode: arithmatic");
-                assert!(
-                    ops.len() == 4,
-                    "{}",
-                    format!("{} params len is 3", opcode.as_str())
-                );
-                let dst_index = self.get_reg_index(ops[1]);
-                let op1_index = self.get_reg_index(ops[2]);
-                let op2_value = self.get_index_value(ops[3]);
+                check_ops_len(&ops, 4)?;
+                let dst_index = self.get_reg_index(ops[1])?;
+                let op1_index = self.get_reg_index(ops[2])?;
+                let op2_value = self.get_index_value(ops[3])?;
                 match opcode.as_str() {
                     "add" => Instruction::ADD(Add {
                         ri: dst_index as u8,
@@ -183,37 +275,49 @@ impl Process {
                         rj: op1_index as u8,
                         a: op2_value.1,
                     }),
-                    _ => panic!("not match opcode:{}", opcode),
+                    _ => return Err(ProcessorError::ParseOpcodeError),
                 }
             }
             "ret" => {
                 debug!("opcode: ret");
-                assert!(ops.len() == 1, "ret params len is 0");
+                check_ops_len(&ops, 1)?;
                 Instruction::RET(Ret {})
             }
             "mstore" => {
                 debug!("opcode: mstore");
-                assert!(
-                    ops.len() == 3,
-                    "{}",
-                    format!("{} params len is 2", opcode.as_str())
-                );
-                let op1_value = self.get_index_value(ops[1]);
-                let op2_index = self.get_reg_index(ops[2]);
+                check_ops_len(&ops, 3)?;
+                let op1_value = self.get_index_value(ops[1])?;
+                let op2_index = self.get_reg_index(ops[2])?;
                 Instruction::MSTORE(Mstore {
                     a: op1_value.1,
                     ri: op2_index as u8,
                 })
             }
+            "poseidon" => {
+                debug!("opcode: poseidon");
+                check_ops_len(&ops, 3)?;
+                let op1_value = self.get_index_value(ops[1])?;
+                let op2_index = self.get_reg_index(ops[2])?;
+                Instruction::POSEIDON(Poseidon {
+                    a: op1_value.1,
+                    ri: op2_index as u8,
+                })
+            }
+            "keccak" => {
+                debug!("opcode: keccak");
+                check_ops_len(&ops, 3)?;
+                let op1_value = self.get_index_value(ops[1])?;
+                let op2_index = self.get_reg_index(ops[2])?;
+                Instruction::KECCAK(Keccak {
+                    a: op1_value.1,
+                    ri: op2_index as u8,
+                })
+            }
             "mload" => {
                 debug!("opcode: mload");
-                assert!(
-                    ops.len() == 3,
-                    "{}",
-                    format!("{} params len is 2", opcode.as_str())
-                );
-                let op2_value = self.get_index_value(ops[2]);
-                let op1_index = self.get_reg_index(ops[1]);
+                check_ops_len(&ops, 3)?;
+                let op2_value = self.get_index_value(ops[2])?;
+                let op1_index = self.get_reg_index(ops[1])?;
                 Instruction::MLOAD(Mload {
                     ri: op1_index as u8,
                     rj: op2_value.1,
@@ -221,18 +325,122 @@ impl Process {
             }
             "end" => {
                 debug!("opcode: end");
-                assert!(ops.len() == 1, "end params len is 0");
+                check_ops_len(&ops, 1)?;
                 Instruction::END(End {})
             }
-            _ => panic!("not match opcode:{}", opcode),
+            _ => return Err(ProcessorError::ParseOpcodeError),
         };
-        instuction
+        Ok(instuction)
+    }
+
+    pub fn execute_with_inputs(
+        &mut self,
+        program: &mut Program,
+        inputs: &[GoldilocksField],
+    ) -> Result<(), ProcessorError> {
+        self.set_inputs(program, inputs)?;
+        self.execute(program)
+    }
+
+    // Inputs are passed in the general purpose registers r0..r(n-1), fp is
+    // reserved so at most `FP_REG_INDEX` inputs are accepted.
+    pub fn set_inputs(
+        &mut self,
+        program: &mut Program,
+        inputs: &[GoldilocksField],
+    ) -> Result<(), ProcessorError> {
+        if inputs.len() > FP_REG_INDEX {
+            return Err(ProcessorError::TooManyInputs);
+        }
+        self.registers[..inputs.len()].copy_from_slice(inputs);
+        program.trace.inputs = inputs.to_vec();
+        Ok(())
+    }
+
+    // Static data words are placed from `addr` before execution, they are
+    // recorded in the trace so the prover commits them with the program.
+    pub fn init_memory(
+        &mut self,
+        program: &mut Program,
+        addr: u64,
+        data: &[u64],
+    ) -> Result<(), ProcessorError> {
+        static_data_end(addr, data.len() as u64).ok_or(ProcessorError::MalformedData(addr))?;
+        for (offset, value) in data.iter().enumerate() {
+            let cell_addr = addr + offset as u64;
+            if *value >= GoldilocksField::ORDER {
+                return Err(ProcessorError::MalformedData(cell_addr));
+            }
+            self.memory
+                .init(cell_addr, GoldilocksField::from_canonical_u64(*value));
+            program.trace.static_memory.push((cell_addr, *value));
+        }
+        Ok(())
     }
 
     pub fn execute(&mut self, program: &mut Program) -> Result<(), ProcessorError> {
+        let mut exec = std::mem::take(&mut program.trace.exec);
+        let res = self.run(program, None, &mut exec);
+        program.trace.exec = exec;
+        res.map(|_| ())
+    }
+
+    // Like `execute`, but the steps are handed to `steps` as they are
+    // executed instead of being kept in `program.trace.exec`, e.g. to write
+    // them straight into the cpu trace columns.
+    pub fn execute_into(
+        &mut self,
+        program: &mut Program,
+        steps: &mut dyn StepSink,
+    ) -> Result<(), ProcessorError> {
+        self.run(program, None, steps).map(|_| ())
+    }
+
+    // Run at most `segment_len` steps into `program.trace` and return whether
+    // the program reached end. Call it again to get the next segment, it
+    // starts from the read write memory and prophet outputs left by the
+    // previous one, recorded as its static data. The last step of a segment is
+    // the first step of the next one, see `run`.
+    pub fn execute_segment(
+        &mut self,
+        program: &mut Program,
+        segment_len: usize,
+    ) -> Result<bool, ProcessorError> {
+        // Segments are not padded, and the raw program rows fit in the cpu
+        // trace of every segment.
+        if !segment_len.is_power_of_two() || segment_len < program.instructions.len().max(2) {
+            return Err(ProcessorError::InvalidSegmentLength(segment_len));
+        }
+        if !program.trace.exec.is_empty() {
+            program.trace = Trace {
+                static_memory: self
+                    .memory
+                    .image()
+                    .into_iter()
+                    .map(|(addr, value)| (addr, value.to_canonical_u64()))
+                    .collect(),
+                ..Default::default()
+            };
+        }
+        let mut exec = std::mem::take(&mut program.trace.exec);
+        let res = self.run(program, Some(segment_len), &mut exec);
+        program.trace.exec = exec;
+        res
+    }
+
+    fn run(
+        &mut self,
+        program: &mut Program,
+        segment_len: Option<usize>,
+        steps: &mut dyn StepSink,
+    ) -> Result<bool, ProcessorError> {
         let instrs_len = program.instructions.len() as u64;
 
         let start = Instant::now();
+        // Decode every instruction once, the execution loop dispatches on
+        // them by pc. Immediate words have no entry.
+        let mut decoded_instructions: Vec<Option<DecodedInstruction>> =
+            Vec::with_capacity(instrs_len as usize);
         let mut pc: u64 = 0;
         while pc < instrs_len {
             let instruct_line = program.instructions[pc as usize].trim();
@@ -244,7 +452,7 @@ impl Process {
 
             let mut immediate_data = GoldilocksField::ZERO;
 
-            let next_instr = if (instrs_len - 2) > pc {
+            let next_instr = if pc + 1 < instrs_len {
                 program.instructions[(pc + 1) as usize].trim()
             } else {
                 ""
@@ -255,8 +463,11 @@ impl Process {
 
             let imm_flag = if step == IMM_INSTRUCTION_LEN {
                 let imm_u64 = next_instr.trim_start_matches("0x");
-                immediate_data =
-                    GoldilocksField::from_canonical_u64(u64::from_str_radix(imm_u64, 16).unwrap());
+                immediate_data = u64::from_str_radix(imm_u64, 16)
+                    .ok()
+                    .filter(|imm| *imm < GoldilocksField::ORDER)
+                    .map(GoldilocksField::from_canonical_u64)
+                    .ok_or_else(|| ProcessorError::MalformedImmediate(next_instr.to_string()))?;
                 program
                     .trace
                     .raw_binary_instructions
@@ -266,231 +477,162 @@ impl Process {
                 0
             };
 
-            let instruction = self.decode_instruction(txt_instruction.clone());
+            let instruction = self.decode_instruction(txt_instruction.clone())?;
             let inst_u64 = instruct_line.trim_start_matches("0x");
-            let inst_encode =
-                GoldilocksField::from_canonical_u64(u64::from_str_radix(inst_u64, 16).unwrap());
+            let inst_encode = u64::from_str_radix(inst_u64, 16)
+                .ok()
+                .filter(|inst| *inst < GoldilocksField::ORDER)
+                .map(GoldilocksField::from_canonical_u64)
+                .ok_or(ProcessorError::ParseIntError)?;
             program.trace.instructions.insert(
                 pc,
                 (txt_instruction, imm_flag, step, inst_encode, immediate_data),
             );
             program.trace.raw_instructions.insert(pc, instruction);
+            decoded_instructions.push(Some(DecodedInstruction {
+                instruction,
+                step,
+                op1_imm: GoldilocksField::from_canonical_u8(imm_flag),
+                encode: inst_encode,
+                immediate_data,
+            }));
+            if step == IMM_INSTRUCTION_LEN {
+                decoded_instructions.push(None);
+            }
             pc += step;
         }
 
         let decode_time = start.elapsed();
         debug!("decode_time: {}", decode_time.as_secs());
 
-        assert_eq!(
-            program.trace.raw_binary_instructions.len(),
-            program.instructions.len()
-        );
+        if program.trace.raw_binary_instructions.len() != program.instructions.len() {
+            return Err(ProcessorError::InstructionCountMismatch {
+                raw: program.trace.raw_binary_instructions.len(),
+                decoded: program.instructions.len(),
+            });
+        }
 
         let mut start = Instant::now();
-        loop {
+        let mut num_steps = 0;
+        let reached_end = loop {
             self.register_selector = RegisterSelector::default();
             let registers_status = self.registers;
             let flag_status = self.flag;
             let pc_status = self.pc;
 
-            let instruction = program.trace.instructions.get(&self.pc).unwrap().clone();
-            let ops: Vec<&str> = instruction.0.split(' ').collect();
-            let opcode = ops.first().unwrap().to_lowercase();
-            self.op1_imm = GoldilocksField::from_canonical_u64(instruction.1 as u64);
-            let step = instruction.2;
-            self.instruction = instruction.3;
-            self.immediate_data = instruction.4;
-            match opcode.as_str() {
-                //todo: not need move to arithmatic library
-                "mov" | "not" => {
-                    debug!("opcode: mov or not");
-                    assert!(
-                        ops.len() == 3,
-                        "{}",
-                        format!("{} params len is 2", opcode.as_str())
-                    );
-                    let dst_index = self.get_reg_index(ops[1]);
-                    let value = self.get_index_value(ops[2]);
-                    self.register_selector.op1 = value.0;
-                    if let ImmediateOrRegName::RegName(op1_index) = value.1 {
-                        self.register_selector.op1_reg_sel[op1_index] =
-                            GoldilocksField::from_canonical_u64(1);
-                    }
+            let decoded_instruction = decoded_instructions
+                .get(self.pc as usize)
+                .copied()
+                .flatten()
+                .ok_or(ProcessorError::PcOutOfBounds(self.pc))?;
+            let opcode = decoded_instruction.instruction.opcode();
+            let step = decoded_instruction.step;
+            self.op1_imm = decoded_instruction.op1_imm;
+            self.instruction = decoded_instruction.encode;
+            self.immediate_data = decoded_instruction.immediate_data;
 
-                    match opcode.as_str() {
-                        "mov" => {
-                            self.registers[dst_index] = value.0;
-                            self.opcode =
-                                GoldilocksField::from_canonical_u64(1 << Opcode::MOV as u8);
-                        }
-                        "not" => {
-                            self.registers[dst_index] = GoldilocksField::NEG_ONE - value.0;
-                            self.opcode =
-                                GoldilocksField::from_canonical_u64(1 << Opcode::NOT as u8);
-                        }
-                        _ => panic!("not match opcode:{}", opcode),
-                    };
+            // The last step of a segment is the first step of the next one. It
+            // is not executed here, its row only has the state and the
+            // instruction, without selectors, so that none of its memory
+            // accesses, builtin rows or prophet calls is in this segment.
+            if !matches!(opcode, Opcode::END)
+                && segment_len.is_some_and(|segment_len| num_steps + 1 == segment_len)
+            {
+                self.opcode = GoldilocksField::ZERO;
+                self.op1_imm = GoldilocksField::ZERO;
+                steps.push_step(self.step(pc_status, registers_status, flag_status));
+                break false;
+            }
+            self.opcode = GoldilocksField::from_canonical_u64(1 << opcode as u8);
 
-                    self.register_selector.dst = self.registers[dst_index];
-                    self.register_selector.dst_reg_sel[dst_index] =
-                        GoldilocksField::from_canonical_u64(1);
+            let gas_used = self.gas_used + opcode_gas(opcode);
+            if self.gas_limit.is_some_and(|gas_limit| gas_used > gas_limit) {
+                return Err(ProcessorError::OutOfGas {
+                    pc: self.pc,
+                    gas_used: self.gas_used,
+                });
+            }
+            self.gas_used = gas_used;
+
+            match decoded_instruction.instruction {
+                //todo: not need move to arithmatic library
+                Instruction::MOV(Mov { ri, a }) | Instruction::NOT(Not { ri, a }) => {
+                    let value = self.select_op1(a);
+                    let res = match opcode {
+                        Opcode::MOV => value,
+                        _ => GoldilocksField::NEG_ONE - value,
+                    };
+                    self.select_dst(ri as usize, res);
 
                     self.pc += step;
                 }
-                "eq" | "neq" | "assert" => {
-                    debug!("opcode: eq or neq or assert");
-                    assert!(
-                        ops.len() == 3,
-                        "{}",
-                        format!("{} params len is 2", opcode.as_str())
-                    );
-                    let op0_index = self.get_reg_index(ops[1]);
-                    // let src_index = self.get_reg_index(&ops[2]);
-                    let value = self.get_index_value(ops[2]);
-
-                    self.register_selector.op0 = self.registers[op0_index];
-                    self.register_selector.op1 = value.0;
-                    self.register_selector.op0_reg_sel[op0_index] =
-                        GoldilocksField::from_canonical_u64(1);
-                    if let ImmediateOrRegName::RegName(op1_index) = value.1 {
-                        self.register_selector.op1_reg_sel[op1_index] =
-                            GoldilocksField::from_canonical_u64(1);
-                    }
+                Instruction::EQ(Equal { ri, a })
+                | Instruction::NEQ(Neq { ri, a })
+                | Instruction::ASSERT(Assert { ri, a }) => {
+                    let op0 = self.select_op0(ri as usize);
+                    let op1 = self.select_op1(a);
 
-                    let op_type = match opcode.as_str() {
-                        "eq" => {
-                            self.register_selector.aux0 =
-                                self.register_selector.op0 - self.register_selector.op1;
-                            if self.register_selector.aux0.is_nonzero() {
-                                self.register_selector.aux0 = self.register_selector.aux0.inverse();
+                    match opcode {
+                        Opcode::EQ | Opcode::NEQ => {
+                            let diff = op0 - op1;
+                            if diff.is_nonzero() {
+                                self.register_selector.aux0 = diff.inverse();
                             }
-                            self.flag = self.registers[op0_index] == value.0;
-                            Opcode::EQ
+                            self.flag = (op0 == op1) == matches!(opcode, Opcode::EQ);
                         }
-                        "neq" => {
-                            self.register_selector.aux0 =
-                                self.register_selector.op0 - self.register_selector.op1;
-                            if self.register_selector.aux0.is_nonzero() {
-                                self.register_selector.aux0 = self.register_selector.aux0.inverse();
+                        _ => {
+                            if op0 != op1 {
+                                return Err(ProcessorError::AssertFail {
+                                    pc: self.pc,
+                                    clk: self.clk,
+                                    left: op0.to_canonical_u64(),
+                                    right: op1.to_canonical_u64(),
+                                });
                             }
-                            self.flag = self.registers[op0_index] != value.0;
-                            Opcode::NEQ
                         }
-                        "assert" => {
-                            if self.registers[op0_index] != value.0 {
-                                panic!(
-                                    "assert fail: left: {}, right: {}",
-                                    self.registers[op0_index], value.0
-                                );
-                            }
-                            Opcode::ASSERT
-                        }
-                        _ => panic!("not match opcode:{}", opcode),
-                    };
-                    self.opcode = GoldilocksField::from_canonical_u64(1 << op_type as u8);
+                    }
 
                     self.pc += step;
                 }
-                "cjmp" => {
-                    debug!("opcode: cjmp");
-                    assert!(
-                        ops.len() == 2,
-                        "{}",
-                        format!("{} params len is 1", opcode.as_str())
-                    );
-                    let value = self.get_index_value(ops[1]);
+                Instruction::CJMP(CJmp { a }) => {
+                    let target = self.select_op1(a);
                     if self.flag {
                         // fixme: use flag need reset?
                         self.flag = false;
-                        self.pc = value.0 .0;
+                        self.pc = self.check_jump_target(&decoded_instructions, target.0)?;
                     } else {
                         self.pc += step;
                     }
-                    self.opcode = GoldilocksField::from_canonical_u64(1 << Opcode::CJMP as u8);
-                    self.register_selector.op1 = value.0;
-                    if let ImmediateOrRegName::RegName(op1_index) = value.1 {
-                        self.register_selector.op1_reg_sel[op1_index] =
-                            GoldilocksField::from_canonical_u64(1);
-                    }
                 }
-                "jmp" => {
-                    debug!("opcode: jmp");
-                    assert!(
-                        ops.len() == 2,
-                        "{}",
-                        format!("{} params len is 1", opcode.as_str())
-                    );
-                    let value = self.get_index_value(ops[1]);
-                    self.opcode = GoldilocksField::from_canonical_u64(1 << Opcode::JMP as u8);
-                    self.pc = value.0 .0;
-                    self.register_selector.op1 = value.0;
-                    if let ImmediateOrRegName::RegName(op1_index) = value.1 {
-                        self.register_selector.op1_reg_sel[op1_index] =
-                            GoldilocksField::from_canonical_u64(1);
-                    }
+                Instruction::JMP(Jmp { a }) => {
+                    let target = self.select_op1(a);
+                    self.pc = self.check_jump_target(&decoded_instructions, target.0)?;
                 }
-                "add" | "mul" | "sub" => {
-                    debug!("opcode: field arithmatic");
-                    assert!(
-                        ops.len() == 4,
-                        "{}",
-                        format!("{} params len is 3", opcode.as_str())
-                    );
-                    let dst_index = self.get_reg_index(ops[1]);
-                    let op0_index = self.get_reg_index(ops[2]);
-                    let op1_value = self.get_index_value(ops[3]);
-
-                    self.register_selector.op0 = self.registers[op0_index];
-                    self.register_selector.op1 = op1_value.0;
-
-                    self.register_selector.op0_reg_sel[op0_index] =
-                        GoldilocksField::from_canonical_u64(1);
-                    if let ImmediateOrRegName::RegName(op1_index) = op1_value.1 {
-                        self.register_selector.op1_reg_sel[op1_index] =
-                            GoldilocksField::from_canonical_u64(1);
-                    }
+                Instruction::ADD(Add { ri, rj, a })
+                | Instruction::MUL(Mul { ri, rj, a })
+                | Instruction::SUB(Sub { ri, rj, a }) => {
+                    let op0 = self.select_op0(rj as usize);
+                    let op1 = self.select_op1(a);
 
-                    match opcode.as_str() {
-                        "add" => {
-                            self.registers[dst_index] = GoldilocksField::from_canonical_u64(
-                                (self.registers[op0_index] + op1_value.0).to_canonical_u64(),
-                            );
-                            self.opcode =
-                                GoldilocksField::from_canonical_u64(1 << Opcode::ADD as u8);
-                        }
-                        "mul" => {
-                            self.registers[dst_index] = GoldilocksField::from_canonical_u64(
-                                (self.registers[op0_index] * op1_value.0).to_canonical_u64(),
-                            );
-                            self.opcode =
-                                GoldilocksField::from_canonical_u64(1 << Opcode::MUL as u8);
-                        }
-                        "sub" => {
-                            self.registers[dst_index] = GoldilocksField::from_canonical_u64(
-                                (self.registers[op0_index] - op1_value.0).to_canonical_u64(),
-                            );
-                            self.opcode =
-                                GoldilocksField::from_canonical_u64(1 << Opcode::SUB as u8);
-                        }
-                        _ => panic!("not match opcode:{}", opcode),
+                    let res = match opcode {
+                        Opcode::ADD => op0 + op1,
+                        Opcode::MUL => op0 * op1,
+                        _ => op0 - op1,
                     };
-
-                    self.register_selector.dst = self.registers[dst_index];
-                    self.register_selector.dst_reg_sel[dst_index] =
-                        GoldilocksField::from_canonical_u64(1);
+                    self.select_dst(
+                        ri as usize,
+                        GoldilocksField::from_canonical_u64(res.to_canonical_u64()),
+                    );
 
                     self.pc += step;
                 }
-                "call" => {
-                    debug!("opcode: call");
-                    assert!(
-                        ops.len() == 2,
-                        "{}",
-                        format!("{} params len is 1", opcode.as_str())
-                    );
-                    let call_addr = self.get_index_value(ops[1]);
+                Instruction::CALL(Call { ri }) => {
+                    let call_addr = self.operand_value(ri);
+                    let fp = self.registers[FP_REG_INDEX];
+                    let ret_addr =
+                        self.check_writable((fp - GoldilocksField::ONE).to_canonical_u64())?;
                     self.memory.write(
-                        self.registers[FP_REG_INDEX].0 - 1,
+                        ret_addr,
                         self.clk,
                         GoldilocksField::from_canonical_u64(1 << Opcode::CALL as u64),
                         GoldilocksField::from_canonical_u64(MemoryType::ReadWrite as u64),
@@ -501,17 +643,14 @@ impl Process {
                         GoldilocksField::from_canonical_u64(0_u64),
                         GoldilocksField::from_canonical_u64(self.pc + step),
                     );
-                    self.opcode = GoldilocksField::from_canonical_u64(1 << Opcode::CALL as u8);
-                    self.register_selector.op0 =
-                        self.registers[FP_REG_INDEX] - GoldilocksField::ONE;
+                    self.register_selector.op0 = fp - GoldilocksField::ONE;
                     self.register_selector.dst =
                         GoldilocksField::from_canonical_u64(self.pc + step);
-                    self.register_selector.op1 = call_addr.0;
+                    self.register_selector.op1 = call_addr;
                     // fixme: not need aux0 and aux1
-                    self.register_selector.aux0 =
-                        self.registers[FP_REG_INDEX] - GoldilocksField::TWO;
+                    self.register_selector.aux0 = fp - GoldilocksField::TWO;
                     self.register_selector.aux1 = self.memory.read(
-                        self.registers[FP_REG_INDEX].0 - 2,
+                        (fp - GoldilocksField::TWO).to_canonical_u64(),
                         self.clk,
                         GoldilocksField::from_canonical_u64(1 << Opcode::CALL as u64),
                         GoldilocksField::from_canonical_u64(MemoryType::ReadWrite as u64),
@@ -521,20 +660,16 @@ impl Process {
                         GoldilocksField::from_canonical_u64(0_u64),
                         GoldilocksField::from_canonical_u64(0_u64),
                     );
-                    self.pc = call_addr.0 .0;
+                    self.pc = self.check_jump_target(&decoded_instructions, call_addr.0)?;
                 }
-                "ret" => {
-                    debug!("opcode: ret");
-                    assert!(ops.len() == 1, "ret params len is 0");
-                    self.opcode = GoldilocksField::from_canonical_u64(1 << Opcode::RET as u8);
-                    self.register_selector.op0 =
-                        self.registers[FP_REG_INDEX] - GoldilocksField::ONE;
-                    self.register_selector.aux0 =
-                        self.registers[FP_REG_INDEX] - GoldilocksField::TWO;
-                    self.pc = self
+                Instruction::RET(_) => {
+                    let fp = self.registers[FP_REG_INDEX];
+                    self.register_selector.op0 = fp - GoldilocksField::ONE;
+                    self.register_selector.aux0 = fp - GoldilocksField::TWO;
+                    let ret_addr = self
                         .memory
                         .read(
-                            self.registers[FP_REG_INDEX].0 - 1,
+                            (fp - GoldilocksField::ONE).to_canonical_u64(),
                             self.clk,
                             GoldilocksField::from_canonical_u64(1 << Opcode::RET as u64),
                             GoldilocksField::from_canonical_u64(MemoryType::ReadWrite as u64),
@@ -545,8 +680,9 @@ impl Process {
                             GoldilocksField::from_canonical_u64(0_u64),
                         )
                         .0;
+                    self.pc = self.check_jump_target(&decoded_instructions, ret_addr)?;
                     self.registers[FP_REG_INDEX] = self.memory.read(
-                        self.registers[FP_REG_INDEX].0 - 2,
+                        (fp - GoldilocksField::TWO).to_canonical_u64(),
                         self.clk,
                         GoldilocksField::from_canonical_u64(1 << Opcode::RET as u64),
                         GoldilocksField::from_canonical_u64(MemoryType::ReadWrite as u64),
@@ -560,27 +696,13 @@ impl Process {
                     self.register_selector.dst = GoldilocksField::from_canonical_u64(self.pc);
                     self.register_selector.aux1 = self.registers[FP_REG_INDEX];
                 }
-                "mstore" => {
-                    debug!("opcode: mstore");
-                    assert!(
-                        ops.len() == 3,
-                        "{}",
-                        format!("{} params len is 2", opcode.as_str())
-                    );
-                    let op1_value = self.get_index_value(ops[1]);
-                    let op0_index = self.get_reg_index(ops[2]);
-                    self.register_selector.op0 = self.registers[op0_index];
-                    self.register_selector.op0_reg_sel[op0_index] =
-                        GoldilocksField::from_canonical_u64(1);
-
-                    self.register_selector.op1 = op1_value.0;
-                    if let ImmediateOrRegName::RegName(op1_index) = op1_value.1 {
-                        self.register_selector.op1_reg_sel[op1_index] =
-                            GoldilocksField::from_canonical_u64(1);
-                    }
+                Instruction::MSTORE(Mstore { a, ri }) => {
+                    let addr = self.select_op1(a);
+                    let value = self.select_op0(ri as usize);
+                    let addr = self.check_writable(addr.0)?;
 
                     self.memory.write(
-                        op1_value.0 .0,
+                        addr,
                         self.clk,
                         GoldilocksField::from_canonical_u64(1 << Opcode::MSTORE as u64),
                         GoldilocksField::from_canonical_u64(MemoryType::ReadWrite as u64),
@@ -589,60 +711,88 @@ impl Process {
                         GoldilocksField::from_canonical_u64(0_u64),
                         GoldilocksField::from_canonical_u64(0_u64),
                         GoldilocksField::from_canonical_u64(0_u64),
-                        self.registers[op0_index],
+                        value,
                     );
-                    self.opcode = GoldilocksField::from_canonical_u64(1 << Opcode::MSTORE as u8);
 
                     self.pc += step;
                 }
-                "mload" => {
-                    debug!("opcode: mload");
-                    assert!(
-                        ops.len() == 3,
-                        "{}",
-                        format!("{} params len is 2", opcode.as_str())
-                    );
-                    let dst_index = self.get_reg_index(ops[1]);
-                    let op1_value = self.get_index_value(ops[2]);
-                    self.register_selector.op1 = op1_value.0;
-                    if let ImmediateOrRegName::RegName(op1_index) = op1_value.1 {
-                        self.register_selector.op1_reg_sel[op1_index] =
-                            GoldilocksField::from_canonical_u64(1);
+                Instruction::MLOAD(Mload { ri, rj }) => {
+                    let addr = self.select_op1(rj);
+                    let in_prophet_region = addr.0 >= PROPHET_REGION_START;
+                    if in_prophet_region && !self.memory.trace.contains_key(&addr.0) {
+                        return Err(ProcessorError::ProphetRegionUnwritten {
+                            pc: self.pc,
+                            addr: addr.0,
+                        });
                     }
-
-                    self.registers[dst_index] = self.memory.read(
-                        op1_value.0 .0,
+                    let memory_type = if in_prophet_region {
+                        MemoryType::WriteOnce
+                    } else {
+                        MemoryType::ReadWrite
+                    };
+                    let value = self.memory.read(
+                        addr.0,
                         self.clk,
                         GoldilocksField::from_canonical_u64(1 << Opcode::MLOAD as u64),
-                        GoldilocksField::from_canonical_u64(MemoryType::ReadWrite as u64),
+                        GoldilocksField::from_canonical_u64(memory_type as u64),
                         GoldilocksField::from_canonical_u64(MemoryOperation::Read as u64),
                         GoldilocksField::from_canonical_u64(FilterLockForMain::True as u64),
-                        GoldilocksField::from_canonical_u64(0_u64),
+                        GoldilocksField::from_bool(in_prophet_region),
                         GoldilocksField::from_canonical_u64(0_u64),
                         GoldilocksField::from_canonical_u64(0_u64),
                     );
-                    self.opcode = GoldilocksField::from_canonical_u64(1 << Opcode::MLOAD as u8);
+                    self.select_dst(ri as usize, value);
 
-                    self.register_selector.dst = self.registers[dst_index];
-                    self.register_selector.dst_reg_sel[dst_index] =
-                        GoldilocksField::from_canonical_u64(1);
+                    self.pc += step;
+                }
+                Instruction::PROPHET(Prophet { ri, a }) => {
+                    let id = self.select_op1(a).to_canonical_u64();
+                    let inputs = &self.registers[1..FP_REG_INDEX];
+                    let outputs = self
+                        .prophet
+                        .as_mut()
+                        .and_then(|prophet| prophet.prophesy(id, inputs))
+                        .or_else(|| BuiltinProphets.prophesy(id, inputs));
+                    let addr = self.memory.next_prophet_addr();
+                    let outputs = outputs
+                        .filter(|outputs| GoldilocksField::ORDER - addr >= outputs.len() as u64)
+                        .ok_or(ProcessorError::ProphetFailed {
+                            pc: self.pc,
+                            clk: self.clk,
+                            id,
+                        })?;
+
+                    // The outputs are not looked up by the cpu, the memory
+                    // table only checks they are written once.
+                    for (offset, value) in outputs.into_iter().enumerate() {
+                        self.memory.write(
+                            addr + offset as u64,
+                            self.clk,
+                            GoldilocksField::ZERO,
+                            GoldilocksField::from_canonical_u64(MemoryType::WriteOnce as u64),
+                            GoldilocksField::from_canonical_u64(MemoryOperation::Write as u64),
+                            GoldilocksField::from_canonical_u64(FilterLockForMain::False as u64),
+                            GoldilocksField::ONE,
+                            GoldilocksField::from_canonical_u64(0_u64),
+                            GoldilocksField::from_canonical_u64(0_u64),
+                            value,
+                        );
+                    }
+                    self.select_dst(ri as usize, GoldilocksField::from_canonical_u64(addr));
 
                     self.pc += step;
                 }
-                "range" => {
-                    debug!("opcode: range");
-                    assert!(
-                        ops.len() == 2,
-                        "{}",
-                        format!("{} params len is 1", opcode.as_str())
-                    );
-                    let op1_index = self.get_reg_index(ops[1]);
-                    self.opcode = GoldilocksField::from_canonical_u64(1 << Opcode::RC as u8);
-                    self.register_selector.op1 = self.registers[op1_index];
-                    self.register_selector.op1_reg_sel[op1_index] =
-                        GoldilocksField::from_canonical_u64(1);
+                Instruction::RANGE(Range { ri }) => {
+                    let value = self.select_op1(ri);
+                    if value.to_canonical_u64() > u32::MAX as u64 {
+                        return Err(ProcessorError::RangeCheckFail {
+                            pc: self.pc,
+                            clk: self.clk,
+                            value: value.to_canonical_u64(),
+                        });
+                    }
                     program.trace.insert_rangecheck(
-                        self.registers[op1_index],
+                        value,
                         (
                             GoldilocksField::ZERO,
                             GoldilocksField::ONE,
@@ -652,97 +802,35 @@ impl Process {
 
                     self.pc += step;
                 }
-                "and" | "or" | "xor" => {
-                    debug!("opcode: bitwise");
-                    assert!(
-                        ops.len() == 4,
-                        "{}",
-                        format!("{} params len is 3", opcode.as_str())
-                    );
-                    let dst_index = self.get_reg_index(ops[1]);
-                    let op0_index = self.get_reg_index(ops[2]);
-                    let op1_value = self.get_index_value(ops[3]);
-
-                    self.register_selector.op0 = self.registers[op0_index];
-                    self.register_selector.op1 = op1_value.0;
-                    self.register_selector.op0_reg_sel[op0_index] =
-                        GoldilocksField::from_canonical_u64(1);
-                    if let ImmediateOrRegName::RegName(op1_index) = op1_value.1 {
-                        self.register_selector.op1_reg_sel[op1_index] =
-                            GoldilocksField::from_canonical_u64(1);
-                    }
+                Instruction::AND(And { ri, rj, a })
+                | Instruction::OR(Or { ri, rj, a })
+                | Instruction::XOR(Xor { ri, rj, a }) => {
+                    let op0 = self.select_op0(rj as usize);
+                    let op1 = self.select_op1(a);
 
-                    let op_type = match opcode.as_str() {
-                        "and" => {
-                            self.registers[dst_index] =
-                                GoldilocksField(self.registers[op0_index].0 & op1_value.0 .0);
-                            self.opcode =
-                                GoldilocksField::from_canonical_u64(1 << Opcode::AND as u8);
-                            BitwiseOperation::And
-                        }
-                        "or" => {
-                            self.registers[dst_index] =
-                                GoldilocksField(self.registers[op0_index].0 | op1_value.0 .0);
-                            self.opcode =
-                                GoldilocksField::from_canonical_u64(1 << Opcode::OR as u8);
-                            BitwiseOperation::Or
-                        }
-                        "xor" => {
-                            self.registers[dst_index] =
-                                GoldilocksField(self.registers[op0_index].0 ^ op1_value.0 .0);
-                            self.opcode =
-                                GoldilocksField::from_canonical_u64(1 << Opcode::XOR as u8);
-                            BitwiseOperation::Xor
-                        }
-                        _ => panic!("not match opcode:{}", opcode),
+                    let (res, op_type) = match opcode {
+                        Opcode::AND => (op0.0 & op1.0, BitwiseOperation::And),
+                        Opcode::OR => (op0.0 | op1.0, BitwiseOperation::Or),
+                        _ => (op0.0 ^ op1.0, BitwiseOperation::Xor),
                     };
+                    let res = GoldilocksField(res);
+                    self.select_dst(ri as usize, res);
 
-                    self.register_selector.dst = self.registers[dst_index];
-                    self.register_selector.dst_reg_sel[dst_index] =
-                        GoldilocksField::from_canonical_u64(1);
-
-                    program.trace.insert_bitwise_combined(
-                        op_type as u32,
-                        self.registers[op0_index],
-                        op1_value.0,
-                        self.registers[dst_index],
-                    );
+                    program
+                        .trace
+                        .insert_bitwise_combined(op_type as u32, op0, op1, res);
                     self.pc += step;
                 }
-                "gte" => {
-                    debug!("opcode: comparison");
-                    assert!(
-                        ops.len() == 3,
-                        "{}",
-                        format!("{} params len is 2", opcode.as_str())
-                    );
-                    let op0_index = self.get_reg_index(ops[1]);
-                    let value = self.get_index_value(ops[2]);
-
-                    self.register_selector.op0 = self.registers[op0_index];
-                    self.register_selector.op1 = value.0;
-                    self.register_selector.op0_reg_sel[op0_index] =
-                        GoldilocksField::from_canonical_u64(1);
-                    if let ImmediateOrRegName::RegName(op1_index) = value.1 {
-                        self.register_selector.op1_reg_sel[op1_index] =
-                            GoldilocksField::from_canonical_u64(1);
-                    }
-                    let diff = self.register_selector.op0 - self.register_selector.op1;
+                Instruction::GTE(Gte { ri, a }) => {
+                    let op0 = self.select_op0(ri as usize);
+                    let op1 = self.select_op1(a);
+                    let diff = op0 - op1;
                     if diff.is_nonzero() {
                         self.register_selector.aux0 = diff.inverse();
                     } else {
                         self.register_selector.aux0 = GoldilocksField::ZERO;
                     }
-
-                    match opcode.as_str() {
-                        "gte" => {
-                            self.flag = self.registers[op0_index].0 >= value.0 .0;
-                            self.opcode =
-                                GoldilocksField::from_canonical_u64(1 << Opcode::GTE as u8);
-                            ComparisonOperation::Gte
-                        }
-                        _ => panic!("not match opcode:{}", opcode),
-                    };
+                    self.flag = op0.0 >= op1.0;
 
                     program.trace.insert_rangecheck(
                         diff,
@@ -753,57 +841,219 @@ impl Process {
                         ),
                     );
 
-                    program.trace.insert_cmp(
-                        self.registers[op0_index],
-                        value.0,
-                        GoldilocksField::ONE,
-                    );
+                    program.trace.insert_cmp(op0, op1, GoldilocksField::ONE);
                     self.pc += step;
                 }
-                "end" => {
-                    self.opcode = GoldilocksField::from_canonical_u64(1 << Opcode::END as u8);
-                    program.trace.insert_step(
-                        self.clk,
-                        pc_status,
-                        self.instruction,
-                        self.immediate_data,
-                        self.op1_imm,
-                        self.opcode,
-                        registers_status,
-                        flag_status,
-                        self.register_selector.clone(),
-                    );
-                    break;
+                Instruction::POSEIDON(Poseidon { a, ri }) => {
+                    let output_addr = self.select_op1(a);
+                    let input_addr = self.select_op0(ri as usize);
+                    let op = GoldilocksField::from_canonical_u64(1 << Opcode::PSDN as u64);
+
+                    // The inputs fill the rate of the sponge, the capacity is zero.
+                    let mut state = [GoldilocksField::ZERO; 12];
+                    for (i, input) in state[..POSEIDON_INPUT_NUM].iter_mut().enumerate() {
+                        *input = self.memory.read(
+                            (input_addr + GoldilocksField::from_canonical_usize(i))
+                                .to_canonical_u64(),
+                            self.clk,
+                            op,
+                            GoldilocksField::from_canonical_u64(MemoryType::ReadWrite as u64),
+                            GoldilocksField::from_canonical_u64(MemoryOperation::Read as u64),
+                            GoldilocksField::from_canonical_u64(FilterLockForMain::True as u64),
+                            GoldilocksField::from_canonical_u64(0_u64),
+                            GoldilocksField::from_canonical_u64(0_u64),
+                            GoldilocksField::from_canonical_u64(0_u64),
+                        );
+                    }
+                    let input = state[..POSEIDON_INPUT_NUM].try_into().unwrap();
+                    let output: [GoldilocksField; POSEIDON_OUTPUT_NUM] =
+                        PoseidonHash::poseidon(state)[..POSEIDON_OUTPUT_NUM]
+                            .try_into()
+                            .unwrap();
+                    for (i, value) in output.iter().enumerate() {
+                        let addr = self.check_writable(
+                            (output_addr + GoldilocksField::from_canonical_usize(i))
+                                .to_canonical_u64(),
+                        )?;
+                        self.memory.write(
+                            addr,
+                            self.clk,
+                            op,
+                            GoldilocksField::from_canonical_u64(MemoryType::ReadWrite as u64),
+                            GoldilocksField::from_canonical_u64(MemoryOperation::Write as u64),
+                            GoldilocksField::from_canonical_u64(FilterLockForMain::True as u64),
+                            GoldilocksField::from_canonical_u64(0_u64),
+                            GoldilocksField::from_canonical_u64(0_u64),
+                            GoldilocksField::from_canonical_u64(0_u64),
+                            *value,
+                        );
+                    }
+                    program
+                        .trace
+                        .insert_poseidon(self.clk, input_addr, output_addr, input, output);
+
+                    self.pc += step;
                 }
-                _ => panic!("not match opcode:{}", opcode),
-            }
-            program.trace.insert_step(
-                self.clk,
-                pc_status,
-                self.instruction,
-                self.immediate_data,
-                self.op1_imm,
-                self.opcode,
-                registers_status,
-                flag_status,
-                self.register_selector.clone(),
-            );
+                Instruction::ECDSA(Ecdsa { a }) => {
+                    let addr = self.select_op1(a);
+                    let op = GoldilocksField::from_canonical_u64(1 << Opcode::ECDSA as u64);
 
-            if self.pc >= instrs_len {
-                break;
+                    let mut input = [GoldilocksField::ZERO; ECDSA_INPUT_NUM];
+                    for (i, word) in input.iter_mut().enumerate() {
+                        *word = self.memory.read(
+                            (addr + GoldilocksField::from_canonical_usize(i)).to_canonical_u64(),
+                            self.clk,
+                            op,
+                            GoldilocksField::from_canonical_u64(MemoryType::ReadWrite as u64),
+                            GoldilocksField::from_canonical_u64(MemoryOperation::Read as u64),
+                            GoldilocksField::from_canonical_u64(FilterLockForMain::True as u64),
+                            GoldilocksField::from_canonical_u64(0_u64),
+                            GoldilocksField::from_canonical_u64(0_u64),
+                            GoldilocksField::from_canonical_u64(0_u64),
+                        );
+                    }
+                    let flag = EcdsaInput::from_words(&input)
+                        .and_then(|input| input.verify())
+                        .ok_or(ProcessorError::InvalidEcdsaInput {
+                            pc: self.pc,
+                            clk: self.clk,
+                        })?;
+                    // The cpu table takes the flag from aux0.
+                    self.register_selector.aux0 = GoldilocksField::from_bool(flag);
+                    self.flag = flag;
+                    program.trace.insert_ecdsa(self.clk, addr, input, flag);
+
+                    self.pc += step;
+                }
+                Instruction::KECCAK(Keccak { a, ri }) => {
+                    let output_addr = self.select_op1(a);
+                    let input_addr = self.select_op0(ri as usize);
+                    let op = GoldilocksField::from_canonical_u64(1 << Opcode::KECCAK as u64);
+                    let clk = self.clk;
+                    let pc = self.pc;
+                    let invalid_input = || ProcessorError::InvalidKeccakInput { pc, clk };
+
+                    // The length in bytes is followed by the bytes packed in u32 words.
+                    let mut read = |i: usize| {
+                        self.memory.read(
+                            (input_addr + GoldilocksField::from_canonical_usize(i))
+                                .to_canonical_u64(),
+                            clk,
+                            op,
+                            GoldilocksField::from_canonical_u64(MemoryType::ReadWrite as u64),
+                            GoldilocksField::from_canonical_u64(MemoryOperation::Read as u64),
+                            GoldilocksField::from_canonical_u64(FilterLockForMain::True as u64),
+                            GoldilocksField::from_canonical_u64(0_u64),
+                            GoldilocksField::from_canonical_u64(0_u64),
+                            GoldilocksField::from_canonical_u64(0_u64),
+                        )
+                    };
+                    let len = read(0);
+                    let byte_len = usize::try_from(len.to_canonical_u64())
+                        .ok()
+                        .filter(|byte_len| *byte_len <= keccak::MAX_INPUT_BYTES)
+                        .ok_or_else(invalid_input)?;
+                    // The input is charged before it is read.
+                    let gas_used = self.gas_used + keccak_input_gas(byte_len);
+                    if self.gas_limit.is_some_and(|gas_limit| gas_used > gas_limit) {
+                        return Err(ProcessorError::OutOfGas {
+                            pc,
+                            gas_used: self.gas_used,
+                        });
+                    }
+                    self.gas_used = gas_used;
+                    let input: Vec<_> = (0..byte_len.div_ceil(4)).map(|i| read(i + 1)).collect();
+                    let words = keccak::input_words(byte_len, &input).ok_or_else(invalid_input)?;
+
+                    // The sponge xors every padded block into the state and masks the
+                    // last word of the input with the bitwise builtin.
+                    let blocks = keccak::absorb(byte_len, &words);
+                    for block in &blocks {
+                        for (state, padded) in block.state.iter().zip(block.padded) {
+                            program.trace.insert_bitwise_combined(
+                                BitwiseOperation::Xor as u32,
+                                GoldilocksField::from_canonical_u32(*state),
+                                GoldilocksField::from_canonical_u32(padded),
+                                GoldilocksField::from_canonical_u32(state ^ padded),
+                            );
+                        }
+                    }
+                    let tail =
+                        GoldilocksField::from_canonical_u32(keccak::tail_word(byte_len, &words));
+                    program.trace.insert_bitwise_combined(
+                        BitwiseOperation::And as u32,
+                        tail,
+                        GoldilocksField::from_canonical_u32(keccak::tail_mask(byte_len)),
+                        tail,
+                    );
+
+                    let output = keccak::digest(&blocks).map(GoldilocksField::from_canonical_u32);
+                    for (i, value) in output.iter().enumerate() {
+                        let addr = self.check_writable(
+                            (output_addr + GoldilocksField::from_canonical_usize(i))
+                                .to_canonical_u64(),
+                        )?;
+                        self.memory.write(
+                            addr,
+                            clk,
+                            op,
+                            GoldilocksField::from_canonical_u64(MemoryType::ReadWrite as u64),
+                            GoldilocksField::from_canonical_u64(MemoryOperation::Write as u64),
+                            GoldilocksField::from_canonical_u64(FilterLockForMain::True as u64),
+                            GoldilocksField::from_canonical_u64(0_u64),
+                            GoldilocksField::from_canonical_u64(0_u64),
+                            GoldilocksField::from_canonical_u64(0_u64),
+                            *value,
+                        );
+                    }
+                    program
+                        .trace
+                        .insert_keccak(clk, input_addr, output_addr, len, input, output);
+
+                    self.pc += step;
+                }
+                Instruction::END(_) => {
+                    steps.push_step(self.step(pc_status, registers_status, flag_status));
+                    break true;
+                }
             }
+            steps.push_step(self.step(pc_status, registers_status, flag_status));
+            num_steps += 1;
 
-            self.clk += 1;
+            self.clk = self
+                .clk
+                .checked_add(1)
+                .filter(|clk| self.max_cycles.is_none_or(|max_cycles| *clk < max_cycles))
+                .ok_or(ProcessorError::StepLimitExceeded(self.clk))?;
             if self.clk % 1000000 == 0 {
                 let decode_time = start.elapsed();
                 debug!("100000_step_time: {}", decode_time.as_millis());
                 start = Instant::now();
             }
-        }
+        };
 
+        program.trace.gas_used = self.gas_used;
         self.gen_memory_table(program);
+        if !reached_end {
+            self.memory.start_segment();
+        }
 
-        Ok(())
+        Ok(reached_end)
+    }
+
+    // The step executed from `pc`, `registers` and `flag`.
+    fn step(&self, pc: u64, registers: [GoldilocksField; REGISTER_NUM], flag: bool) -> Step {
+        Step {
+            clk: self.clk,
+            pc,
+            instruction: self.instruction,
+            immediate_data: self.immediate_data,
+            opcode: self.opcode,
+            op1_imm: self.op1_imm,
+            regs: registers,
+            flag,
+            register_selector: self.register_selector.clone(),
+        }
     }
 
     pub fn gen_memory_table(&mut self, program: &mut Program) {
@@ -817,17 +1067,23 @@ impl Process {
 
         for (addr, cells) in self.memory.trace.iter() {
             let mut new_addr_flag = true;
-            for cell in cells {
+            for (index, cell) in cells.iter().enumerate() {
+                let filter_final = if index + 1 == cells.len() {
+                    cell.is_rw + cell.region_prophet
+                } else {
+                    GoldilocksField::ZERO
+                };
                 debug!("addr:{}, cell:{:?}", addr, cell);
+                // The regions end at p = -1, p - addr is range checked.
                 if cell.region_prophet.is_one() {
-                    diff_addr_cond = GoldilocksField::from_canonical_u64(GoldilocksField::ORDER)
-                        - GoldilocksField::from_canonical_u64(*addr);
+                    diff_addr_cond =
+                        GoldilocksField::NEG_ONE - GoldilocksField::from_canonical_u64(*addr);
                 } else if cell.region_poseidon.is_one() {
-                    diff_addr_cond = GoldilocksField::from_canonical_u64(GoldilocksField::ORDER)
+                    diff_addr_cond = GoldilocksField::NEG_ONE
                         - GoldilocksField::from_canonical_u64(REGION_SPAN)
                         - GoldilocksField::from_canonical_u64(*addr);
                 } else if cell.region_ecdsa.is_one() {
-                    diff_addr_cond = GoldilocksField::from_canonical_u64(GoldilocksField::ORDER)
+                    diff_addr_cond = GoldilocksField::NEG_ONE
                         - GoldilocksField::from_canonical_u64(2 * REGION_SPAN)
                         - GoldilocksField::from_canonical_u64(*addr);
                 } else {
@@ -851,7 +1107,13 @@ impl Process {
                         region_ecdsa: cell.region_ecdsa,
                         value: cell.value,
                         filter_looking_rc: GoldilocksField::ONE,
-                        rc_value: GoldilocksField::ZERO,
+                        rc_value: if cell.is_rw.is_one() {
+                            GoldilocksField::ZERO
+                        } else {
+                            diff_addr_cond
+                        },
+                        filter_init: cell.filter_init,
+                        filter_final,
                     };
                     program.trace.memory.push(trace_cell);
                     first_row_flag = false;
@@ -877,7 +1139,13 @@ impl Process {
                         region_ecdsa: cell.region_ecdsa,
                         value: cell.value,
                         filter_looking_rc: GoldilocksField::ONE,
-                        rc_value: diff_addr,
+                        rc_value: if cell.is_rw.is_one() {
+                            diff_addr
+                        } else {
+                            diff_addr_cond
+                        },
+                        filter_init: cell.filter_init,
+                        filter_final,
                     };
                     program.trace.memory.push(trace_cell);
                     new_addr_flag = false;
@@ -911,6 +1179,8 @@ impl Process {
                         value: cell.value,
                         filter_looking_rc: GoldilocksField::ONE,
                         rc_value,
+                        filter_init: cell.filter_init,
+                        filter_final,
                     };
                     program.trace.memory.push(trace_cell);
                 }
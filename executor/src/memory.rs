@@ -1,4 +1,4 @@
-use crate::GoldilocksField;
+use crate::{GoldilocksField, PROPHET_REGION_START};
 use core::trace::trace::MemoryCell;
 use plonky2::field::types::Field;
 use std::collections::BTreeMap;
@@ -13,6 +13,58 @@ pub struct MemoryTree {
 }
 
 impl MemoryTree {
+    // Static data is written before the first step, it is the first cell of
+    // its address and is checked against the program instead of the cpu.
+    // Prophet outputs carried from the previous segment are static data of
+    // the prophet region.
+    pub fn init(&mut self, addr: u64, value: GoldilocksField) {
+        let in_prophet_region = addr >= PROPHET_REGION_START;
+        let init_cell = MemoryCell {
+            is_rw: GoldilocksField::from_bool(!in_prophet_region),
+            clk: 0,
+            op: GoldilocksField::ZERO,
+            is_write: GoldilocksField::ONE,
+            filter_looked_for_main: GoldilocksField::ZERO,
+            region_prophet: GoldilocksField::from_bool(in_prophet_region),
+            region_poseidon: GoldilocksField::ZERO,
+            region_ecdsa: GoldilocksField::ZERO,
+            value,
+            filter_init: GoldilocksField::ONE,
+        };
+        self.trace.insert(addr, vec![init_cell]);
+    }
+
+    // (addr, value) of the read write memory and the prophet outputs, the
+    // builtin write once regions are not carried to the next segment.
+    pub fn image(&self) -> Vec<(u64, GoldilocksField)> {
+        self.trace
+            .iter()
+            .filter_map(|(addr, cells)| {
+                let last = cells.last().expect("empty address trace");
+                (last.is_rw.is_one() || last.region_prophet.is_one()).then_some((*addr, last.value))
+            })
+            .collect()
+    }
+
+    // The next segment starts from the memory image, each address gets a
+    // static data cell. Prophets go on writing after the carried outputs.
+    pub fn start_segment(&mut self) {
+        let image = self.image();
+        self.trace.clear();
+        for (addr, value) in image {
+            self.init(addr, value);
+        }
+    }
+
+    // The prophet region is written from its start without gaps, the memory
+    // table checks its addresses follow each other.
+    pub fn next_prophet_addr(&self) -> u64 {
+        match self.trace.range(PROPHET_REGION_START..).next_back() {
+            Some((addr, _)) => addr + 1,
+            None => PROPHET_REGION_START,
+        }
+    }
+
     pub fn read(
         &mut self,
         addr: u64,
@@ -43,6 +95,7 @@ impl MemoryTree {
                     region_poseidon,
                     region_ecdsa,
                     value: last_value,
+                    filter_init: GoldilocksField::ZERO,
                 };
                 addr_trace.push(new_value);
             })
@@ -57,6 +110,7 @@ impl MemoryTree {
                     region_poseidon,
                     region_ecdsa,
                     value: GoldilocksField::from_canonical_u64(INIT_MEMORY_DATA),
+                    filter_init: GoldilocksField::ZERO,
                 };
                 vec![new_value]
             })
@@ -90,6 +144,7 @@ impl MemoryTree {
             region_poseidon,
             region_ecdsa,
             value,
+            filter_init: GoldilocksField::ZERO,
         };
         self.trace
             .entry(addr)
@@ -0,0 +1,61 @@
+use crate::GoldilocksField;
+use core::program::instruction::{
+    PROPHET_ADD_U32, PROPHET_DIVMOD, PROPHET_INV, PROPHET_MUL_U32, PROPHET_SQRT, PROPHET_SUB_U32,
+};
+use plonky2::field::types::{Field, PrimeField, PrimeField64};
+use std::fmt::Debug;
+
+/// A helper run by the host for the `prophet` instruction. Its outputs are
+/// written to the write once prophet memory region without any proof, the
+/// program reads them with `mload` and checks them.
+pub trait Prophet: Debug {
+    /// Outputs of prophet `id` for the argument registers r1.. in `inputs`,
+    /// `None` if `id` is unknown or has no answer for them.
+    fn prophesy(&mut self, id: u64, inputs: &[GoldilocksField]) -> Option<Vec<GoldilocksField>>;
+}
+
+/// Field inverse, field square root and the u32 arithmetic of
+/// `core::program::instruction`, used for the ids a host prophet does not
+/// answer.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BuiltinProphets;
+
+impl Prophet for BuiltinProphets {
+    fn prophesy(&mut self, id: u64, inputs: &[GoldilocksField]) -> Option<Vec<GoldilocksField>> {
+        let x = *inputs.first()?;
+        let words = |(lo, hi): (u32, u32)| {
+            Some(vec![
+                GoldilocksField::from_canonical_u32(lo),
+                GoldilocksField::from_canonical_u32(hi),
+            ])
+        };
+        match id {
+            PROPHET_INV => Some(vec![x.try_inverse()?]),
+            PROPHET_SQRT => Some(vec![x.sqrt()?]),
+            PROPHET_DIVMOD | PROPHET_ADD_U32 | PROPHET_SUB_U32 | PROPHET_MUL_U32 => {
+                let a = to_u32(x)?;
+                let b = to_u32(*inputs.get(1)?)?;
+                match id {
+                    PROPHET_DIVMOD => words((a.checked_div(b)?, a % b)),
+                    PROPHET_ADD_U32 => {
+                        let (sum, carry) = a.overflowing_add(b);
+                        words((sum, carry as u32))
+                    }
+                    PROPHET_SUB_U32 => {
+                        let (diff, borrow) = a.overflowing_sub(b);
+                        words((diff, borrow as u32))
+                    }
+                    _ => {
+                        let product = a as u64 * b as u64;
+                        words((product as u32, (product >> 32) as u32))
+                    }
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+fn to_u32(x: GoldilocksField) -> Option<u32> {
+    u32::try_from(x.to_canonical_u64()).ok()
+}
@@ -1,6 +1,25 @@
-use crate::Process;
+use crate::coprocessor::ecdsa::EcdsaInput;
+use crate::coprocessor::keccak::{MAX_INPUT_BYTES, RATE_BYTES};
+use crate::error::ProcessorError;
+use crate::gas::{keccak_input_gas, opcode_gas, KECCAK_BLOCK_GAS};
+use crate::prophet::Prophet;
+use crate::{Process, PROPHET_REGION_START};
+use assembler::fixtures::{assemble, store_words, u32_program};
+use core::program::instruction::{
+    Opcode, PROPHET_ADD_U32, PROPHET_DIVMOD, PROPHET_INV, PROPHET_MUL_U32, PROPHET_SQRT,
+    PROPHET_SUB_U32,
+};
 use core::program::Program;
+use core::trace::binary::{
+    is_binary_trace, read_binary_trace, write_binary_trace, BinaryTraceError, BINARY_TRACE_VERSION,
+};
+use core::trace::trace::{ECDSA_INPUT_NUM, ECDSA_WORD_NUM};
 use log::debug;
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::secp256k1_scalar::Secp256K1Scalar;
+use plonky2::field::types::{Field, Field64, PrimeField64};
+use plonky2::hash::poseidon::PoseidonHash;
+use plonky2::plonk::config::Hasher;
 use std::fs::File;
 use std::io::Write;
 use std::time::Instant;
@@ -97,6 +116,43 @@ fn add_mul_decode() {
     file.write_all(trace_json_format.as_ref()).unwrap();
 }
 
+#[test]
+fn add_mul_with_inputs() {
+    //add r3 r0 r1
+    //mul r4 r3 r2
+    //end
+    let program_src = "0x0020204400000000
+                             0x0100408200000000
+                             0x0000000000800000";
+
+    let instructions = program_src.split('\n');
+    let mut program: Program = Program {
+        instructions: Vec::new(),
+        trace: Default::default(),
+    };
+
+    for inst in instructions.into_iter() {
+        program.instructions.push(inst.to_string());
+    }
+
+    let inputs = [8, 2, 3].map(GoldilocksField::from_canonical_u64);
+    let mut process = Process::new();
+    process.execute_with_inputs(&mut program, &inputs).unwrap();
+
+    assert_eq!(program.trace.inputs, inputs.to_vec());
+    assert_eq!(program.trace.exec[0].regs[..3], inputs);
+    assert_eq!(
+        process.registers[4],
+        GoldilocksField::from_canonical_u64(30)
+    );
+
+    let too_many_inputs = [GoldilocksField::ONE; 9];
+    let mut process = Process::new();
+    assert!(process
+        .execute_with_inputs(&mut program, &too_many_inputs)
+        .is_err());
+}
+
 #[test]
 fn fibo_use_loop_decode() {
     // mov r0 8
@@ -785,4 +841,787 @@ fn fibo_recursive() {
 
     let mut file = File::create("fibo_recursive.txt").unwrap();
     file.write_all(trace_json_format.as_ref()).unwrap();
-}
\ No newline at end of file
+}
+
+#[test]
+fn processor_errors() {
+    let execute = |program_src: &str| {
+        let mut program: Program = Program {
+            instructions: program_src.split('\n').map(|s| s.to_string()).collect(),
+            trace: Default::default(),
+        };
+        let mut process = Process::new();
+        process.execute(&mut program)
+    };
+
+    // assert r0 5
+    // end
+    assert_eq!(
+        execute("0x4020000080000000\n0x5\n0x0000000000800000"),
+        Err(ProcessorError::AssertFail {
+            pc: 0,
+            clk: 0,
+            left: 0,
+            right: 5
+        })
+    );
+
+    // jmp 1
+    // end
+    assert_eq!(
+        execute("0x4000000020000000\n0x1\n0x0000000000800000"),
+        Err(ProcessorError::InvalidJumpTarget { pc: 0, target: 1 })
+    );
+
+    // mov r0 8
+    assert_eq!(
+        execute("0x4000000840000000\n0x8"),
+        Err(ProcessorError::PcOutOfBounds(2))
+    );
+
+    // mov r0 <bad immediate>
+    assert_eq!(
+        execute("0x4000000840000000\n0xzz\n0x0000000000800000"),
+        Err(ProcessorError::MalformedImmediate("0xzz".to_string()))
+    );
+
+    let process = Process::new();
+    assert!(matches!(
+        process.decode_instruction("mov r9 1".to_string()),
+        Err(ProcessorError::RegisterIndexOutOfRange(_))
+    ));
+    assert!(matches!(
+        process.decode_instruction("add r0 r1".to_string()),
+        Err(ProcessorError::InvalidOperands(_))
+    ));
+    assert!(matches!(
+        process.decode_instruction("div r0 r1 r2".to_string()),
+        Err(ProcessorError::ParseOpcodeError)
+    ));
+}
+
+#[test]
+fn cycle_and_gas_limits() {
+    // mov r0 8
+    // mov r1 2
+    // mov r2 3
+    // add r3 r0 r1
+    // mul r4 r3 r2
+    // end
+    let program_src = "0x4000000840000000
+        0x8
+        0x4000001040000000
+        0x2
+        0x4000002040000000
+        0x3
+        0x0020204400000000
+        0x0100408200000000
+        0x0000000000800000";
+    let new_program = || Program {
+        instructions: program_src.split('\n').map(|s| s.to_string()).collect(),
+        trace: Default::default(),
+    };
+
+    let mut program = new_program();
+    let mut process = Process::new();
+    process.execute(&mut program).unwrap();
+    assert_eq!(program.trace.gas_used, 6);
+
+    let mut program = new_program();
+    let mut process = Process::new();
+    process.max_cycles = Some(3);
+    assert_eq!(
+        process.execute(&mut program),
+        Err(ProcessorError::StepLimitExceeded(2))
+    );
+
+    let mut program = new_program();
+    let mut process = Process::new();
+    process.gas_limit = Some(5);
+    assert_eq!(
+        process.execute(&mut program),
+        Err(ProcessorError::OutOfGas { pc: 8, gas_used: 5 })
+    );
+}
+
+#[test]
+fn static_data() {
+    // .data
+    // values:
+    //   7 0x9
+    // last:
+    //   11
+    // .text
+    // mov r1 values
+    // mload r0 r1
+    // add r1 r1 1
+    // mload r2 r1
+    // mload r4 last
+    // add r3 r0 r2
+    // end
+    let program_src = "0x4000001040000000
+        0x0000000010000000
+        0x0000200802000000
+        0x4040001400000000
+        0x0000000000000001
+        0x0000202002000000
+        0x4000008002000000
+        0x0000000010000002
+        0x0020404400000000
+        0x0000000000800000";
+    let mut program = Program {
+        instructions: program_src.split('\n').map(|s| s.to_string()).collect(),
+        trace: Default::default(),
+    };
+
+    let mut process = Process::new();
+    process
+        .init_memory(&mut program, 0x10000000, &[7, 9, 11])
+        .unwrap();
+    process.execute(&mut program).unwrap();
+    assert_eq!(process.registers[0].0, 7);
+    assert_eq!(process.registers[2].0, 9);
+    assert_eq!(process.registers[3].0, 16);
+    assert_eq!(process.registers[4].0, 11);
+    assert_eq!(
+        program.trace.static_memory,
+        vec![(0x10000000, 7), (0x10000001, 9), (0x10000002, 11)]
+    );
+
+    let mut process = Process::new();
+    assert_eq!(
+        process.init_memory(&mut program, 0x10000000, &[1, GoldilocksField::ORDER]),
+        Err(ProcessorError::MalformedData(0x10000001))
+    );
+    assert_eq!(
+        process.init_memory(&mut program, PROPHET_REGION_START - 1, &[1, 2]),
+        Err(ProcessorError::MalformedData(PROPHET_REGION_START - 1))
+    );
+}
+
+#[test]
+fn execute_segments() {
+    // fibo_use_loop_memory_decode with 10 iterations
+    let program_src = format!(
+        "0x4000000840000000
+        0x1
+        0x4000002040000000
+        0x1
+        0x4020000001000000
+        0x80
+        0x4020000001000000
+        0x87
+        0x4000000840000000
+        {:#x}
+        0x4000004040000000
+        0x0
+        0x0020800100000000
+        0x4000000010000000
+        0x1e
+        0x4000001002000000
+        0x80
+        0x0040400080000000
+        0x4000002002000000
+        0x87
+        0x0040408400000000
+        0x4080000001000000
+        0x80
+        0x4200000001000000
+        0x87
+        0x4000008040000000
+        0x1
+        0x0101004400000000
+        0x4000000020000000
+        0xc
+        0x0000800000400000
+        0x0000000000800000",
+        10
+    );
+    let new_program = || Program {
+        instructions: program_src
+            .split('\n')
+            .map(|s| s.trim().to_string())
+            .collect(),
+        trace: Default::default(),
+    };
+
+    let mut program = new_program();
+    let mut process = Process::new();
+    process.execute(&mut program).unwrap();
+    let steps = program.trace.exec.len();
+    let registers = process.registers;
+
+    let mut program = new_program();
+    let mut process = Process::new();
+    assert_eq!(
+        process.execute_segment(&mut program, 16),
+        Err(ProcessorError::InvalidSegmentLength(16))
+    );
+    assert_eq!(
+        process.execute_segment(&mut program, 48),
+        Err(ProcessorError::InvalidSegmentLength(48))
+    );
+
+    let mut segments = 0;
+    let mut segment_steps = 0;
+    while !process.execute_segment(&mut program, 32).unwrap() {
+        assert_eq!(program.trace.exec.len(), 32);
+        // The boundary step is not executed in this segment.
+        let boundary = program.trace.exec.last().unwrap();
+        assert_eq!(boundary.opcode, GoldilocksField::ZERO);
+        let boundary_clk = GoldilocksField::from_canonical_u32(boundary.clk);
+        assert!(program
+            .trace
+            .memory
+            .iter()
+            .all(|cell| cell.clk != boundary_clk));
+        segments += 1;
+        // The boundary step is in both segments.
+        segment_steps += 31;
+    }
+    segment_steps += program.trace.exec.len();
+    assert!(segments > 1);
+    assert_eq!(segment_steps, steps);
+    assert_eq!(process.registers, registers);
+    // The loop state is carried through memory.
+    let static_addrs: Vec<u64> = program
+        .trace
+        .static_memory
+        .iter()
+        .map(|(addr, _)| *addr)
+        .collect();
+    assert_eq!(static_addrs, vec![0x80, 0x87]);
+}
+
+#[test]
+fn prophet_segments() {
+    let asm_codes = "main:
+         mov r1 3
+         prophet r6 1
+         mov r7 r6
+         mov r0 20
+         mov r3 0
+         mov r4 1
+         .L0:
+         eq r0 r3
+         cjmp .L1
+         add r3 r3 r4
+         jmp .L0
+         .L1:
+         mload r5 r7
+         prophet r6 1
+         end";
+    let new_program = || Program {
+        instructions: assemble(asm_codes),
+        trace: Default::default(),
+    };
+    let inverse = GoldilocksField::from_canonical_u64(3).inverse();
+
+    let mut program = new_program();
+    let mut process = Process::new();
+    process.execute(&mut program).unwrap();
+    let registers = process.registers;
+    assert_eq!(registers[5], inverse);
+
+    // The prophet output is read after the boundaries, it is carried to the
+    // next segments as static data of the prophet region, and the next
+    // prophet writes after it.
+    let mut program = new_program();
+    let mut process = Process::new();
+    let mut segments = 1;
+    while !process.execute_segment(&mut program, 32).unwrap() {
+        segments += 1;
+    }
+    assert!(segments > 2);
+    assert_eq!(process.registers, registers);
+    assert_eq!(
+        [6, 7].map(|i| process.registers[i].0),
+        [PROPHET_REGION_START + 1, PROPHET_REGION_START]
+    );
+    assert_eq!(
+        program.trace.static_memory,
+        vec![(PROPHET_REGION_START, inverse.0)]
+    );
+    let carried: Vec<_> = program
+        .trace
+        .memory
+        .iter()
+        .filter(|row| row.addr.0 == PROPHET_REGION_START)
+        .collect();
+    assert!(carried[0].filter_init.is_one() && carried[0].region_prophet.is_one());
+    assert!(carried.last().unwrap().filter_final.is_one());
+}
+
+#[test]
+fn binary_trace_round_trip() {
+    let asm_codes = "main:
+         mov r0 8
+         mov r1 2
+         mov r2 3
+         add r3 r0 r1
+         mul r4 r3 r2
+         and r5 r4 r3
+         or r6 r1 r4
+         xor r7 r5 r2
+         or r3 r2 r3
+         and r4 r4 r3
+         end";
+    let mut program = Program {
+        instructions: assemble(asm_codes),
+        trace: Default::default(),
+    };
+    let mut process = Process::new();
+    process
+        .init_memory(&mut program, 0x10000000, &[7, 9])
+        .unwrap();
+    process
+        .execute_with_inputs(&mut program, &[GoldilocksField::ONE])
+        .unwrap();
+    let mut trace = program.trace;
+    // The text instructions are not kept.
+    trace.instructions.clear();
+    trace.raw_instructions.clear();
+    // The instruction words are read back zero padded.
+    for word in trace.raw_binary_instructions.iter_mut() {
+        *word = format!("0x{:0>16x}", u64::from_str_radix(&word[2..], 16).unwrap());
+    }
+    let json = serde_json::to_string(&trace).unwrap();
+
+    for compress in [false, true] {
+        let mut bytes = Vec::new();
+        write_binary_trace(&trace, compress, &mut bytes).unwrap();
+        assert!(is_binary_trace(&bytes));
+        let mut exec = Vec::new();
+        let mut read_trace = read_binary_trace(&bytes[..], &mut exec).unwrap();
+        assert!(read_trace.exec.is_empty());
+        read_trace.exec = exec;
+        assert_eq!(serde_json::to_string(&read_trace).unwrap(), json);
+        // A truncated zstd frame may fail in the decoder.
+        let truncated = read_binary_trace(&bytes[..bytes.len() - 1], &mut Vec::new());
+        if compress {
+            assert!(truncated.is_err());
+        } else {
+            assert_eq!(truncated.err(), Some(BinaryTraceError::Truncated));
+        }
+    }
+
+    let mut bytes = Vec::new();
+    write_binary_trace(&trace, false, &mut bytes).unwrap();
+    let version = BINARY_TRACE_VERSION + 1;
+    bytes[4..6].copy_from_slice(&version.to_le_bytes());
+    assert_eq!(
+        read_binary_trace(&bytes[..], &mut Vec::new()).err(),
+        Some(BinaryTraceError::UnsupportedVersion(version))
+    );
+    assert_eq!(
+        read_binary_trace(&json.as_bytes()[..], &mut Vec::new()).err(),
+        Some(BinaryTraceError::BadMagic)
+    );
+}
+
+#[test]
+fn poseidon_test() {
+    let asm_codes = format!(
+        "main:
+         {}mov r0 0x100
+         poseidon 0x200 r0
+         mload r2 0x200
+         end",
+        store_words(0x100, &[1, 2, 3, 4, 5, 6, 7, 8])
+    );
+    let mut program = Program {
+        instructions: assemble(&asm_codes),
+        trace: Default::default(),
+    };
+    let mut process = Process::new();
+    process.execute(&mut program).unwrap();
+
+    let inputs: Vec<_> = (1..=8).map(GoldilocksField::from_canonical_u64).collect();
+    let digest = PoseidonHash::hash_no_pad(&inputs);
+    let rows = &program.trace.builtin_poseidon;
+    assert_eq!(rows.len(), 1);
+    assert_eq!(
+        rows[0].input_addr,
+        GoldilocksField::from_canonical_u64(0x100)
+    );
+    assert_eq!(
+        rows[0].output_addr,
+        GoldilocksField::from_canonical_u64(0x200)
+    );
+    assert_eq!(rows[0].input.to_vec(), inputs);
+    assert_eq!(rows[0].output, digest.elements);
+    assert_eq!(process.registers[2], digest.elements[0]);
+
+    let mut bytes = Vec::new();
+    write_binary_trace(&program.trace, true, &mut bytes).unwrap();
+    let read_trace = read_binary_trace(&bytes[..], &mut Vec::new()).unwrap();
+    assert_eq!(
+        serde_json::to_string(&read_trace.builtin_poseidon).unwrap(),
+        serde_json::to_string(rows).unwrap()
+    );
+}
+
+#[test]
+fn ecdsa_test() {
+    let execute = |input: [GoldilocksField; ECDSA_INPUT_NUM]| {
+        let asm_codes = format!(
+            "main:
+             {}ecdsa 0x100
+             end",
+            store_words(0x100, &input.map(|word| word.to_canonical_u64()))
+        );
+        let mut program = Program {
+            instructions: assemble(&asm_codes),
+            trace: Default::default(),
+        };
+        let mut process = Process::new();
+        process.execute(&mut program).map(|_| (process, program))
+    };
+
+    let z = [0x1234, 0x5678, 0x9abc, 0xdef0];
+    let d = Secp256K1Scalar::from_canonical_u64(0xdead_beef);
+    let k = Secp256K1Scalar::from_canonical_u64(0x1337);
+    let signed = EcdsaInput::sign(z, &d, &k).unwrap();
+    let (process, program) = execute(signed.to_words()).unwrap();
+    assert!(process.flag);
+    let rows = &program.trace.builtin_ecdsa;
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].addr, GoldilocksField::from_canonical_u64(0x100));
+    assert_eq!(rows[0].input(), signed.to_words());
+    assert_eq!(rows[0].flag, GoldilocksField::ONE);
+
+    let mut bytes = Vec::new();
+    write_binary_trace(&program.trace, true, &mut bytes).unwrap();
+    let read_trace = read_binary_trace(&bytes[..], &mut Vec::new()).unwrap();
+    assert_eq!(
+        serde_json::to_string(&read_trace.builtin_ecdsa).unwrap(),
+        serde_json::to_string(rows).unwrap()
+    );
+
+    let mut tampered = signed;
+    tampered.z[0] ^= 1;
+    let (process, program) = execute(tampered.to_words()).unwrap();
+    assert!(!process.flag);
+    assert_eq!(program.trace.builtin_ecdsa[0].flag, GoldilocksField::ZERO);
+
+    // r = 0
+    let mut input = signed.to_words();
+    input[3 * ECDSA_WORD_NUM..4 * ECDSA_WORD_NUM].fill(GoldilocksField::ZERO);
+    assert!(matches!(
+        execute(input),
+        Err(ProcessorError::InvalidEcdsaInput { pc: 160, clk: 80 })
+    ));
+}
+
+#[test]
+fn keccak_test() {
+    // The byte length and the words of the input are stored at 0x100.
+    let execute = |buffer: &[u64], gas_limit: Option<u64>| {
+        let asm_codes = format!(
+            "main:
+             {}mov r0 0x100
+             keccak 0x200 r0
+             mload r2 0x200
+             end",
+            store_words(0x100, buffer)
+        );
+        let mut program = Program {
+            instructions: assemble(&asm_codes),
+            trace: Default::default(),
+        };
+        let mut process = Process::new();
+        process.gas_limit = gas_limit;
+        process.execute(&mut program).map(|_| (process, program))
+    };
+
+    // keccak256("abc")
+    let digest = [
+        0x7a65034e, 0x4fa945ea, 0xa87bd4c7, 0x67d6c826, 0xe3e6d1c0, 0x36a0643a, 0x8ff544ec,
+        0x456c2da1,
+    ]
+    .map(GoldilocksField::from_canonical_u64);
+    let (process, program) = execute(&[3, 0x636261], None).unwrap();
+    let rows = &program.trace.builtin_keccak;
+    assert_eq!(rows.len(), 1);
+    assert_eq!(
+        rows[0].input_addr,
+        GoldilocksField::from_canonical_u64(0x100)
+    );
+    assert_eq!(
+        rows[0].output_addr,
+        GoldilocksField::from_canonical_u64(0x200)
+    );
+    assert_eq!(rows[0].len, GoldilocksField::from_canonical_u64(3));
+    assert_eq!(rows[0].output, digest);
+    assert_eq!(process.registers[2], digest[0]);
+    // A block xor per word of the rate and the mask of the tail word.
+    assert_eq!(program.trace.builtin_bitwise_combined.len(), 35);
+
+    let mut bytes = Vec::new();
+    write_binary_trace(&program.trace, true, &mut bytes).unwrap();
+    let read_trace = read_binary_trace(&bytes[..], &mut Vec::new()).unwrap();
+    assert_eq!(
+        serde_json::to_string(&read_trace.builtin_keccak).unwrap(),
+        serde_json::to_string(rows).unwrap()
+    );
+
+    // The input words and blocks are charged.
+    assert_eq!(
+        program.trace.gas_used,
+        2 * (opcode_gas(Opcode::MOV) + opcode_gas(Opcode::MSTORE))
+            + opcode_gas(Opcode::MOV)
+            + opcode_gas(Opcode::KECCAK)
+            + keccak_input_gas(3)
+            + opcode_gas(Opcode::MLOAD)
+            + opcode_gas(Opcode::END)
+    );
+    // Before the input of a second block is read.
+    let gas_used =
+        2 * opcode_gas(Opcode::MOV) + opcode_gas(Opcode::MSTORE) + opcode_gas(Opcode::KECCAK);
+    assert_eq!(
+        execute(&[RATE_BYTES as u64], Some(gas_used + KECCAK_BLOCK_GAS)).err(),
+        Some(ProcessorError::OutOfGas { pc: 6, gas_used })
+    );
+
+    // A byte past the input, a word over u32 and a too long input.
+    let too_long = MAX_INPUT_BYTES as u64 + 1;
+    for buffer in [&[3, 0x0163_6261][..], &[4, 1 << 32], &[too_long]] {
+        let (pc, clk) = (4 * buffer.len() as u64 + 2, 2 * buffer.len() as u32 + 1);
+        assert!(matches!(
+            execute(buffer, None),
+            Err(ProcessorError::InvalidKeccakInput { pc: p, clk: c }) if p == pc && c == clk
+        ));
+    }
+}
+
+#[test]
+fn prophet_test() {
+    // The square root of square and the u32 division by divisor.
+    let prophet_program = |square: u64, divisor: u64| {
+        assemble(&format!(
+            "main:
+             mov r1 3
+             inv r0
+             mov r1 {square}
+             sqrt r3
+             mov r1 100
+             mov r2 {divisor}
+             divmod r4 r5
+             end"
+        ))
+    };
+    let code = prophet_program(9, 7);
+    let execute = |code: &[String], prophet: Option<Box<dyn Prophet>>| {
+        let mut program = Program {
+            instructions: code.to_vec(),
+            trace: Default::default(),
+        };
+        let mut process = Process::new();
+        process.prophet = prophet;
+        process.execute(&mut program).map(|_| (process, program))
+    };
+
+    let (process, program) = execute(&code, None).unwrap();
+    let word = GoldilocksField::from_canonical_u64;
+    assert_eq!(process.registers[0], word(3).inverse());
+    assert_eq!(process.registers[3] * process.registers[3], word(9));
+    assert_eq!(process.registers[4], word(14));
+    assert_eq!(process.registers[5], word(2));
+    // The outputs are written once from the start of the prophet region and
+    // read by the cpu.
+    let prophet_rows: Vec<_> = program
+        .trace
+        .memory
+        .iter()
+        .filter(|row| row.region_prophet.is_one())
+        .collect();
+    let writes: Vec<_> = prophet_rows
+        .iter()
+        .filter(|row| row.is_write.is_one())
+        .map(|row| (row.addr.0 - PROPHET_REGION_START, row.value))
+        .collect();
+    assert_eq!(
+        writes,
+        [
+            (0, process.registers[0]),
+            (1, process.registers[3]),
+            (2, word(14)),
+            (3, word(2))
+        ]
+    );
+    assert!(prophet_rows.iter().all(|row| row.is_rw.is_zero()
+        && row.rc_value == GoldilocksField::NEG_ONE - row.addr
+        && row.op.is_zero() == row.is_write.is_one()));
+
+    // A host prophet is asked first, a wrong inverse fails its check.
+    #[derive(Debug)]
+    struct HalfProphet;
+    impl Prophet for HalfProphet {
+        fn prophesy(&mut self, id: u64, _: &[GoldilocksField]) -> Option<Vec<GoldilocksField>> {
+            (id == PROPHET_INV).then(|| vec![GoldilocksField::TWO.inverse()])
+        }
+    }
+    assert!(matches!(
+        execute(&code, Some(Box::new(HalfProphet))),
+        Err(ProcessorError::AssertFail { pc: 6, clk: 4, .. })
+    ));
+
+    // 7 has no square root and u32 division by 0 has no answer.
+    assert_eq!(
+        execute(&prophet_program(7, 7), None).unwrap_err(),
+        ProcessorError::ProphetFailed {
+            pc: 11,
+            clk: 7,
+            id: PROPHET_SQRT
+        }
+    );
+    assert_eq!(
+        execute(&prophet_program(9, 0), None).unwrap_err(),
+        ProcessorError::ProphetFailed {
+            pc: 21,
+            clk: 14,
+            id: PROPHET_DIVMOD
+        }
+    );
+
+    let addr = PROPHET_REGION_START;
+    for (inst, err) in [
+        (
+            format!("mstore {addr:#x} r1"),
+            ProcessorError::ProphetRegionWrite { pc: 0, addr },
+        ),
+        (
+            format!("poseidon {addr:#x} r0"),
+            ProcessorError::ProphetRegionWrite { pc: 0, addr },
+        ),
+        (
+            format!("keccak {addr:#x} r0"),
+            ProcessorError::ProphetRegionWrite { pc: 0, addr },
+        ),
+        (
+            format!("mload r0 {addr:#x}"),
+            ProcessorError::ProphetRegionUnwritten { pc: 0, addr },
+        ),
+    ] {
+        let code = assemble(&format!("main:\n{inst}\nend"));
+        assert_eq!(execute(&code, None).unwrap_err(), err);
+    }
+}
+
+#[test]
+fn u32_test() {
+    let execute = |a: u64, b: u64, prophet: Option<Box<dyn Prophet>>| {
+        let mut program = Program {
+            instructions: u32_program(a, b),
+            trace: Default::default(),
+        };
+        let mut process = Process::new();
+        process.prophet = prophet;
+        process.execute(&mut program).map(|_| (process, program))
+    };
+
+    for (a, b) in [
+        (u32::MAX, 1),
+        (u32::MAX, u32::MAX),
+        (7, u32::MAX),
+        (100, 7),
+        (0, 1),
+        (0x12345678, 0x9abc),
+    ] {
+        let (process, program) = execute(a as u64, b as u64, None).unwrap();
+        let (sum, carry) = a.overflowing_add(b);
+        let (diff, borrow) = a.overflowing_sub(b);
+        let product = a as u64 * b as u64;
+        let stored: Vec<_> = program
+            .trace
+            .memory
+            .iter()
+            .filter(|row| row.is_write.is_one() && (0x100..0x104).contains(&row.addr.0))
+            .map(|row| row.value.0)
+            .collect();
+        assert_eq!(
+            stored,
+            [sum as u64, carry as u64, diff as u64, borrow as u64]
+        );
+        assert_eq!(
+            [3, 4, 5, 0].map(|i| process.registers[i].0),
+            [
+                product & 0xffffffff,
+                product >> 32,
+                (a / b) as u64,
+                (a % b) as u64
+            ]
+        );
+    }
+
+    // The inputs are u32 and the prophets have no answer for a zero divisor.
+    assert_eq!(
+        execute(1 << 32, 1, None).unwrap_err(),
+        ProcessorError::ProphetFailed {
+            pc: 4,
+            clk: 2,
+            id: PROPHET_ADD_U32
+        }
+    );
+    assert!(matches!(
+        execute(1, 0, None),
+        Err(ProcessorError::ProphetFailed {
+            id: PROPHET_DIVMOD,
+            ..
+        })
+    ));
+
+    // Wrong answers of a host prophet which fit the equations fail the other
+    // checks of the macros: 1 + 1 as 3 - 2^-32 * 2^32, 1 - 1 as 1 + 2^-32 * 2^32
+    // and 1 * 1 as 2 + (2^32 - 1) * 2^32, which is 1 + p.
+    #[derive(Debug)]
+    struct WrongProphet(u64);
+    impl Prophet for WrongProphet {
+        fn prophesy(&mut self, id: u64, _: &[GoldilocksField]) -> Option<Vec<GoldilocksField>> {
+            let word = GoldilocksField::from_canonical_u64;
+            let carry = word(1 << 32).inverse();
+            match id {
+                _ if id != self.0 => None,
+                PROPHET_ADD_U32 => Some(vec![word(3), -carry]),
+                PROPHET_SUB_U32 => Some(vec![word(1), carry]),
+                _ => Some(vec![word(2), word(u32::MAX as u64)]),
+            }
+        }
+    }
+    // The carry and the borrow are not 0 or 1, the high word is not below
+    // 2^32 - 1.
+    let carry = GoldilocksField::from_canonical_u64(1 << 32).inverse();
+    for (id, err) in [
+        (
+            PROPHET_ADD_U32,
+            ProcessorError::AssertFail {
+                pc: 25,
+                clk: 18,
+                left: (carry * carry).to_canonical_u64(),
+                right: (-carry).to_canonical_u64(),
+            },
+        ),
+        (
+            PROPHET_SUB_U32,
+            ProcessorError::AssertFail {
+                pc: 48,
+                clk: 36,
+                left: (carry * carry).to_canonical_u64(),
+                right: carry.to_canonical_u64(),
+            },
+        ),
+        (
+            PROPHET_MUL_U32,
+            ProcessorError::RangeCheckFail {
+                pc: 72,
+                clk: 53,
+                value: GoldilocksField::ORDER - 1,
+            },
+        ),
+    ] {
+        assert_eq!(
+            execute(1, 1, Some(Box::new(WrongProphet(id)))).unwrap_err(),
+            err
+        );
+    }
+}
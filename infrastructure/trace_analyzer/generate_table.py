@@ -216,7 +216,9 @@ def main():
 
     args = parser.parse_args()
 
-    trace_input = open(args.input, 'r').read()
+    trace_input = open(args.input, 'rb').read()
+    if trace_input.startswith(b'OLAT'):
+        sys.exit("{0} is a binary trace, run OlaVM with `--json` to export a JSON trace".format(args.input))
     trace_json = json.loads(trace_input)
 
     # print(args.format)
@@ -7,7 +7,6 @@
 #![allow(clippy::return_self_not_must_use)]
 #![feature(generic_const_exprs)]
 #![feature(specialization)]
-#![feature(stdsimd)]
 
 pub(crate) mod arch;
 pub mod batch_util;